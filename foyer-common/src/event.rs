@@ -25,6 +25,8 @@ pub enum Event {
     Remove,
     /// Cache clear.
     Clear,
+    /// Cache drain of the coldest entries on demand.
+    Drained,
 }
 
 /// Trait for the customized event listener.
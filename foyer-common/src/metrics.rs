@@ -12,9 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{borrow::Cow, fmt::Debug};
-
-use mixtrics::metrics::{BoxedCounter, BoxedGauge, BoxedHistogram, BoxedRegistry, Buckets};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    sync::LazyLock,
+};
+
+use mixtrics::metrics::{
+    BoxedCounter, BoxedCounterVec, BoxedGauge, BoxedGaugeVec, BoxedHistogram, BoxedHistogramVec, BoxedRegistry, Buckets,
+};
+use parking_lot::Mutex;
 
 #[expect(missing_docs)]
 pub struct Metrics {
@@ -60,6 +68,10 @@ pub struct Metrics {
     pub storage_disk_read_duration: BoxedHistogram,
     pub storage_disk_flush_duration: BoxedHistogram,
 
+    pub storage_disk_io_inflight: BoxedGauge,
+    pub storage_disk_io_queued: BoxedGauge,
+    pub storage_disk_retry: BoxedCounter,
+
     pub storage_region_total: BoxedGauge,
     pub storage_region_clean: BoxedGauge,
     pub storage_region_evictable: BoxedGauge,
@@ -71,9 +83,14 @@ pub struct Metrics {
 
     pub storage_lodc_indexer_conflict: BoxedCounter,
     pub storage_lodc_enqueue_skip: BoxedCounter,
+    pub storage_lodc_enqueue_reserved: BoxedCounter,
     pub storage_lodc_buffer_efficiency: BoxedHistogram,
     pub storage_lodc_recover_duration: BoxedHistogram,
 
+    pub storage_sodc_bloom_filter_skip: BoxedCounter,
+    pub storage_sodc_serialize_buffer_usage: BoxedGauge,
+    pub storage_sodc_serialize_buffer_wait_duration: BoxedHistogram,
+
     /* hybrid cache metrics */
     pub hybrid_insert: BoxedCounter,
     pub hybrid_hit: BoxedCounter,
@@ -97,8 +114,28 @@ impl Debug for Metrics {
 
 impl Metrics {
     /// Create a new metric with the given name.
+    ///
+    /// All metrics are registered to `registry` on creation with the `name` label, so there is no need to register
+    /// them afterward. To export the metrics to Prometheus, use `PrometheusMetricsRegistry` from `mixtrics` (with the
+    /// `prometheus` feature enabled) as the registry.
     pub fn new(name: impl Into<Cow<'static, str>>, registry: &BoxedRegistry) -> Self {
+        Self::with_labels(name, vec![], registry)
+    }
+
+    /// Create a new metric with the given name and extra static labels.
+    ///
+    /// The `labels` are attached to all metrics of the instance besides the `name` label, e.g.
+    /// `{cache="user-sessions", tier="ssd"}`, to distinguish multiple foyer instances in one process.
+    ///
+    /// Note: Instances that share a registry must use the same label keys, because a metric is registered with a fixed
+    /// set of label names.
+    pub fn with_labels(
+        name: impl Into<Cow<'static, str>>,
+        labels: Vec<(String, String)>,
+        registry: &BoxedRegistry,
+    ) -> Self {
         let name = name.into();
+        let registry = LabeledRegistry::new(registry, labels);
 
         /* in-memory cache metrics */
 
@@ -172,6 +209,12 @@ impl Metrics {
             Buckets::exponential(0.000_001, 2.0, 23),
         );
 
+        let foyer_storage_disk_io_concurrency = registry.register_gauge_vec(
+            "foyer_storage_disk_io_concurrency".into(),
+            "foyer disk cache disk io concurrency".into(),
+            &["name", "type"],
+        );
+
         let foyer_storage_region = registry.register_gauge_vec(
             "foyer_storage_region".into(),
             "foyer disk cache regions".into(),
@@ -213,6 +256,18 @@ impl Metrics {
             Buckets::exponential(0.001, 2.0, 21),
         );
 
+        let foyer_storage_sodc_op_total = registry.register_counter_vec(
+            "foyer_storage_sodc_op_total".into(),
+            "foyer small object disk cache operations".into(),
+            &["name", "op"],
+        );
+
+        let foyer_storage_sodc_serialize_buffer_usage_bytes = registry.register_gauge_vec(
+            "foyer_storage_sodc_serialize_buffer_usage_bytes".into(),
+            "foyer small object disk cache serialize buffer usage".into(),
+            &["name"],
+        );
+
         let storage_enqueue = foyer_storage_op_total.counter(&[name.clone(), "enqueue".into()]);
         let storage_hit = foyer_storage_op_total.counter(&[name.clone(), "hit".into()]);
         let storage_miss = foyer_storage_op_total.counter(&[name.clone(), "miss".into()]);
@@ -244,6 +299,10 @@ impl Metrics {
         let storage_disk_read_duration = foyer_storage_disk_io_duration.histogram(&[name.clone(), "read".into()]);
         let storage_disk_flush_duration = foyer_storage_disk_io_duration.histogram(&[name.clone(), "flush".into()]);
 
+        let storage_disk_io_inflight = foyer_storage_disk_io_concurrency.gauge(&[name.clone(), "inflight".into()]);
+        let storage_disk_io_queued = foyer_storage_disk_io_concurrency.gauge(&[name.clone(), "queued".into()]);
+        let storage_disk_retry = foyer_storage_inner_op_total.counter(&[name.clone(), "disk_retry".into()]);
+
         let storage_region_total = foyer_storage_region.gauge(&[name.clone(), "total".into()]);
         let storage_region_clean = foyer_storage_region.gauge(&[name.clone(), "clean".into()]);
         let storage_region_evictable = foyer_storage_region.gauge(&[name.clone(), "evictable".into()]);
@@ -258,9 +317,18 @@ impl Metrics {
         let storage_lodc_indexer_conflict =
             foyer_storage_lodc_op_total.counter(&[name.clone(), "indexer_conflict".into()]);
         let storage_lodc_enqueue_skip = foyer_storage_lodc_op_total.counter(&[name.clone(), "enqueue_skip".into()]);
+        let storage_lodc_enqueue_reserved =
+            foyer_storage_lodc_op_total.counter(&[name.clone(), "enqueue_reserved".into()]);
         let storage_lodc_buffer_efficiency = foyer_storage_lodc_buffer_efficiency.histogram(&[name.clone()]);
         let storage_lodc_recover_duration = foyer_storage_lodc_recover_duration.histogram(&[name.clone()]);
 
+        let storage_sodc_bloom_filter_skip =
+            foyer_storage_sodc_op_total.counter(&[name.clone(), "bloom_filter_skip".into()]);
+        let storage_sodc_serialize_buffer_usage =
+            foyer_storage_sodc_serialize_buffer_usage_bytes.gauge(&[name.clone()]);
+        let storage_sodc_serialize_buffer_wait_duration =
+            foyer_storage_inner_op_duration.histogram(&[name.clone(), "serialize_buffer_wait".into()]);
+
         /* hybrid cache metrics */
 
         let foyer_hybrid_op_total = registry.register_counter_vec(
@@ -321,6 +389,9 @@ impl Metrics {
             storage_disk_write_duration,
             storage_disk_read_duration,
             storage_disk_flush_duration,
+            storage_disk_io_inflight,
+            storage_disk_io_queued,
+            storage_disk_retry,
             storage_region_total,
             storage_region_clean,
             storage_region_evictable,
@@ -329,8 +400,12 @@ impl Metrics {
             storage_entry_deserialize_duration,
             storage_lodc_indexer_conflict,
             storage_lodc_enqueue_skip,
+            storage_lodc_enqueue_reserved,
             storage_lodc_buffer_efficiency,
             storage_lodc_recover_duration,
+            storage_sodc_bloom_filter_skip,
+            storage_sodc_serialize_buffer_usage,
+            storage_sodc_serialize_buffer_wait_duration,
 
             hybrid_insert,
             hybrid_hit,
@@ -356,6 +431,146 @@ impl Metrics {
     }
 }
 
+/// Label names interned for the whole process.
+///
+/// Label names must be `'static` to register a metric. The names of the extra labels are leaked once per distinct
+/// name and label set, instead of once per metrics instance.
+static LABEL_NAMES: LazyLock<Mutex<LabelNames>> = LazyLock::new(Default::default);
+
+#[derive(Default)]
+struct LabelNames {
+    names: HashSet<&'static str>,
+    sets: HashMap<Vec<&'static str>, &'static [&'static str]>,
+}
+
+impl LabelNames {
+    fn name(&mut self, name: &str) -> &'static str {
+        if let Some(name) = self.names.get(name) {
+            return name;
+        }
+        let name = &*Box::leak(name.into());
+        self.names.insert(name);
+        name
+    }
+
+    fn set(&mut self, names: Vec<&'static str>) -> &'static [&'static str] {
+        self.sets
+            .entry(names)
+            .or_insert_with_key(|names| Box::leak(names.clone().into_boxed_slice()))
+    }
+}
+
+/// A registry view that appends the extra static labels to the label names and values of all registered metrics.
+struct LabeledRegistry<'a> {
+    registry: &'a BoxedRegistry,
+    keys: Vec<&'static str>,
+    values: Vec<Cow<'static, str>>,
+}
+
+impl<'a> LabeledRegistry<'a> {
+    fn new(registry: &'a BoxedRegistry, labels: Vec<(String, String)>) -> Self {
+        let mut names = LABEL_NAMES.lock();
+        let (keys, values) = labels
+            .into_iter()
+            .map(|(key, value)| (names.name(&key), Cow::Owned(value)))
+            .unzip();
+        Self { registry, keys, values }
+    }
+
+    fn label_names(&self, label_names: &'static [&'static str]) -> &'static [&'static str] {
+        if self.keys.is_empty() {
+            return label_names;
+        }
+        let names = label_names.iter().chain(self.keys.iter()).copied().collect::<Vec<_>>();
+        LABEL_NAMES.lock().set(names)
+    }
+
+    fn register_counter_vec(
+        &self,
+        name: Cow<'static, str>,
+        desc: Cow<'static, str>,
+        label_names: &'static [&'static str],
+    ) -> LabeledVec<BoxedCounterVec> {
+        let vec = self
+            .registry
+            .register_counter_vec(name, desc, self.label_names(label_names));
+        LabeledVec::new(vec, &self.values)
+    }
+
+    fn register_gauge_vec(
+        &self,
+        name: Cow<'static, str>,
+        desc: Cow<'static, str>,
+        label_names: &'static [&'static str],
+    ) -> LabeledVec<BoxedGaugeVec> {
+        let vec = self
+            .registry
+            .register_gauge_vec(name, desc, self.label_names(label_names));
+        LabeledVec::new(vec, &self.values)
+    }
+
+    fn register_histogram_vec(
+        &self,
+        name: Cow<'static, str>,
+        desc: Cow<'static, str>,
+        label_names: &'static [&'static str],
+    ) -> LabeledVec<BoxedHistogramVec> {
+        let vec = self
+            .registry
+            .register_histogram_vec(name, desc, self.label_names(label_names));
+        LabeledVec::new(vec, &self.values)
+    }
+
+    fn register_histogram_vec_with_buckets(
+        &self,
+        name: Cow<'static, str>,
+        desc: Cow<'static, str>,
+        label_names: &'static [&'static str],
+        buckets: Vec<f64>,
+    ) -> LabeledVec<BoxedHistogramVec> {
+        let vec = self
+            .registry
+            .register_histogram_vec_with_buckets(name, desc, self.label_names(label_names), buckets);
+        LabeledVec::new(vec, &self.values)
+    }
+}
+
+struct LabeledVec<T> {
+    vec: T,
+    values: Vec<Cow<'static, str>>,
+}
+
+impl<T> LabeledVec<T> {
+    fn new(vec: T, values: &[Cow<'static, str>]) -> Self {
+        Self {
+            vec,
+            values: values.to_vec(),
+        }
+    }
+
+    fn labels(&self, labels: &[Cow<'static, str>]) -> Vec<Cow<'static, str>> {
+        labels.iter().chain(self.values.iter()).cloned().collect()
+    }
+}
+
+impl LabeledVec<BoxedCounterVec> {
+    fn counter(&self, labels: &[Cow<'static, str>]) -> BoxedCounter {
+        self.vec.counter(&self.labels(labels))
+    }
+}
+
+impl LabeledVec<BoxedGaugeVec> {
+    fn gauge(&self, labels: &[Cow<'static, str>]) -> BoxedGauge {
+        self.vec.gauge(&self.labels(labels))
+    }
+}
+
+impl LabeledVec<BoxedHistogramVec> {
+    fn histogram(&self, labels: &[Cow<'static, str>]) -> BoxedHistogram {
+        self.vec.histogram(&self.labels(labels))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use mixtrics::metrics::BoxedRegistry;
@@ -367,4 +582,58 @@ mod tests {
     }
 
     mixtrics::test! { test_fn }
+
+    mod labels {
+        use mixtrics::{
+            metrics::BoxedRegistry,
+            registry::prometheus_0_14::PrometheusMetricsRegistry,
+            test_utils::prometheus_0_14::{Encoder, Registry, TextEncoder},
+        };
+
+        use super::super::{LabeledRegistry, Metrics};
+
+        fn labels(tier: &str) -> Vec<(String, String)> {
+            vec![
+                ("cache".to_string(), "user-sessions".to_string()),
+                ("tier".to_string(), tier.to_string()),
+            ]
+        }
+
+        fn test_fn(registry: &BoxedRegistry) {
+            Metrics::with_labels("test", labels("ssd"), registry);
+        }
+
+        mixtrics::test! { test_fn }
+
+        #[test]
+        fn test_labels_exported() {
+            let prometheus = Registry::new();
+            let registry: BoxedRegistry = Box::new(PrometheusMetricsRegistry::new(prometheus.clone()));
+            let ssd = Metrics::with_labels("test", labels("ssd"), &registry);
+            let hdd = Metrics::with_labels("test", labels("hdd"), &registry);
+            ssd.memory_insert.increase(1);
+            hdd.memory_insert.increase(2);
+
+            let mut buf = vec![];
+            TextEncoder::new().encode(&prometheus.gather(), &mut buf).unwrap();
+            let text = String::from_utf8(buf).unwrap();
+            for (tier, count) in [("ssd", 1), ("hdd", 2)] {
+                let line = format!(
+                    r#"foyer_memory_op_total{{cache="user-sessions",name="test",op="insert",tier="{tier}"}} {count}"#
+                );
+                assert!(text.lines().any(|l| l == line), "{line} not found in:\n{text}");
+            }
+        }
+
+        #[test]
+        fn test_label_names_interned() {
+            let registry: BoxedRegistry = Box::new(mixtrics::registry::noop::NoopMetricsRegistry);
+            let ssd = LabeledRegistry::new(&registry, labels("ssd"));
+            let hdd = LabeledRegistry::new(&registry, labels("hdd"));
+            assert!(std::ptr::eq(ssd.keys[0], hdd.keys[0]));
+            let names = ssd.label_names(&["name", "op"]);
+            assert_eq!(names, &["name", "op", "cache", "tier"]);
+            assert!(std::ptr::eq(names, hdd.label_names(&["name", "op"])));
+        }
+    }
 }
@@ -0,0 +1,145 @@
+// Copyright 2025 foyer Project Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! micro benchmark for foyer in-memory cache object hit ratio and byte hit ratio with heterogeneous entry sizes
+
+use foyer_memory::{Cache, CacheBuilder, GdsfConfig, LruConfig};
+use rand::{distr::Distribution, rngs::StdRng, Rng, SeedableRng};
+
+/// Entry size in bytes.
+type CacheValue = usize;
+
+const ITEMS: usize = 10_000;
+const ITERATIONS: usize = 5_000_000;
+
+const SHARDS: usize = 1;
+
+/// Entry sizes are log-uniform in `[1, MAX_SIZE]`.
+const MAX_SIZE: usize = 4096;
+
+/*
+cargo bench --bench bench_byte_hit_ratio
+
+zif_exp, cache_size           lru (object)    lru (byte)      gdsf (object)   gdsf (byte)
+  0.90,  0.005                18.22%          17.20%          52.36%          16.90%
+  0.90,   0.01                25.12%          25.39%          60.02%          20.24%
+  0.90,   0.05                44.97%          48.93%          78.54%          50.79%
+  0.90,    0.1                55.29%          58.49%          85.14%          60.07%
+  0.90,   0.25                70.91%          72.65%          92.36%          74.90%
+  1.00,  0.005                29.43%          27.32%          60.70%          24.55%
+  1.00,   0.01                37.62%          37.81%          67.54%          30.93%
+  1.00,   0.05                58.13%          61.89%          84.05%          64.05%
+  1.00,    0.1                67.26%          69.97%          89.11%          71.21%
+  1.00,   0.25                79.73%          81.14%          94.60%          82.81%
+  1.10,  0.005                42.31%          38.86%          69.12%          33.53%
+  1.10,   0.01                51.13%          51.11%          75.49%          47.42%
+  1.10,   0.05                70.46%          73.38%          88.92%          74.72%
+  1.10,    0.1                77.80%          79.68%          92.56%          80.60%
+  1.10,   0.25                86.92%          87.86%          96.42%          89.09%
+  1.50,  0.005                82.77%          78.42%          91.99%          74.78%
+  1.50,   0.01                88.59%          88.31%          95.36%          88.47%
+  1.50,   0.05                95.96%          96.18%          98.51%          96.51%
+  1.50,    0.1                97.48%          97.53%          99.07%          97.71%
+  1.50,   0.25                98.79%          98.83%          99.54%          98.93%
+
+GDSF trades a little byte hit ratio with small caches for a much higher object hit ratio.
+*/
+#[derive(Debug, Default)]
+struct HitRatio {
+    hit: usize,
+    hit_bytes: usize,
+    bytes: usize,
+}
+
+impl HitRatio {
+    fn object(&self) -> f64 {
+        self.hit as f64 / ITERATIONS as f64
+    }
+
+    fn byte(&self) -> f64 {
+        self.hit_bytes as f64 / self.bytes as f64
+    }
+}
+
+fn cache_hit(cache: Cache<usize, CacheValue>, keys: &[usize], sizes: &[usize]) -> HitRatio {
+    let mut ratio = HitRatio::default();
+    for &key in keys {
+        let size = sizes[key];
+        ratio.bytes += size;
+        if cache.get(&key).is_some() {
+            ratio.hit += 1;
+            ratio.hit_bytes += size;
+        } else {
+            cache.insert(key, size);
+        }
+    }
+    ratio
+}
+
+fn new_lru_cache(capacity: usize) -> Cache<usize, CacheValue> {
+    CacheBuilder::new(capacity)
+        .with_shards(SHARDS)
+        .with_weighter(|_, size: &usize| *size)
+        .with_eviction_config(LruConfig {
+            high_priority_pool_ratio: 0.1,
+        })
+        .build()
+}
+
+fn new_gdsf_cache(capacity: usize) -> Cache<usize, CacheValue> {
+    CacheBuilder::new(capacity)
+        .with_shards(SHARDS)
+        .with_weighter(|_, size: &usize| *size)
+        .with_eviction_config(GdsfConfig::default())
+        .build()
+}
+
+fn bench_one(zif_exp: f64, cache_size_percent: f64, sizes: &[usize]) {
+    print!("{zif_exp:6.2}, {cache_size_percent:6}{:6}", "");
+
+    let mut rng = StdRng::seed_from_u64(42);
+    let zipf = rand_distr::Zipf::new(ITEMS as f64, zif_exp).unwrap();
+    let keys = (0..ITERATIONS)
+        .map(|_| zipf.sample(&mut rng) as usize - 1)
+        .collect::<Vec<_>>();
+
+    let cache_size = (sizes.iter().sum::<usize>() as f64 * cache_size_percent) as usize;
+    let lru = cache_hit(new_lru_cache(cache_size), &keys, sizes);
+    let gdsf = cache_hit(new_gdsf_cache(cache_size), &keys, sizes);
+
+    print!("{:15.2}%", lru.object() * 100.0);
+    print!("{:15.2}%", lru.byte() * 100.0);
+    print!("{:15.2}%", gdsf.object() * 100.0);
+    print!("{:15.2}%", gdsf.byte() * 100.0);
+    println!();
+}
+
+fn main() {
+    // The sizes are independent of the popularity of the keys.
+    let mut rng = StdRng::seed_from_u64(0);
+    let sizes = (0..ITEMS)
+        .map(|_| 2f64.powf(rng.random_range(0.0..(MAX_SIZE as f64).log2())).round() as usize)
+        .collect::<Vec<_>>();
+
+    println!();
+    println!(
+        "{:30}{:16}{:16}{:16}{:16}",
+        "zif_exp, cache_size", "lru (object)", "lru (byte)", "gdsf (object)", "gdsf (byte)"
+    );
+    for zif_exp in [0.9, 1.0, 1.1, 1.5] {
+        for cache_capacity in [0.005, 0.01, 0.05, 0.1, 0.25] {
+            bench_one(zif_exp, cache_capacity, &sizes);
+        }
+    }
+}
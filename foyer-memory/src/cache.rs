@@ -28,16 +28,22 @@ use pin_project::pin_project;
 use serde::{Deserialize, Serialize};
 use tokio::sync::oneshot;
 
+#[cfg(any(test, feature = "debug"))]
+use crate::eviction::EvictionDebug;
 use crate::{
     eviction::{
         fifo::{Fifo, FifoConfig},
+        gdsf::{Gdsf, GdsfConfig},
         lfu::{Lfu, LfuConfig},
         lru::{Lru, LruConfig},
         s3fifo::{S3Fifo, S3FifoConfig},
         sieve::{Sieve, SieveConfig},
     },
-    raw::{FetchContext, FetchState, RawCache, RawCacheConfig, RawCacheEntry, RawFetch, Weighter},
-    Piece, Pipe, Result,
+    raw::{
+        FetchContext, FetchState, RawCache, RawCacheConfig, RawCacheEntry, RawFetch, RawWeakCacheEntry, ShardStats,
+        Weighter,
+    },
+    Error, Piece, Pipe, Result,
 };
 
 /// Entry properties for in-memory only cache.
@@ -128,6 +134,33 @@ pub type SieveCache<K, V, S = DefaultHasher, P = CacheProperties> = RawCache<Sie
 pub type SieveCacheEntry<K, V, S = DefaultHasher, P = CacheProperties> = RawCacheEntry<Sieve<K, V, P>, S>;
 pub type SieveFetch<K, V, ER, S = DefaultHasher, P = CacheProperties> = RawFetch<Sieve<K, V, P>, ER, S>;
 
+pub type GdsfCache<K, V, S = DefaultHasher, P = CacheProperties> = RawCache<Gdsf<K, V, P>, S>;
+pub type GdsfCacheEntry<K, V, S = DefaultHasher, P = CacheProperties> = RawCacheEntry<Gdsf<K, V, P>, S>;
+pub type GdsfFetch<K, V, ER, S = DefaultHasher, P = CacheProperties> = RawFetch<Gdsf<K, V, P>, ER, S>;
+
+/// Metadata of a cached entry, without the value, see [`Cache::entry_metas`].
+///
+/// With the `serde` feature enabled, the metadata can be persisted to snapshot the keys and the recency order of the
+/// in-memory cache, and the values can be fetched lazily after restart.
+///
+/// The in-memory cache doesn't track the hits or the access time of an entry, the recency is given by
+/// [`CacheEntryMeta::rank`] instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CacheEntryMeta<K> {
+    /// Key of the cached entry.
+    pub key: K,
+    /// Key hash of the cached entry.
+    pub hash: u64,
+    /// Weight of the cached entry.
+    pub weight: usize,
+    /// Rank of the cached entry in [`Cache::iter_eviction_order`] when the metadata is taken, from the coldest `0`.
+    ///
+    /// Inserting the entries back in the ascending order of the rank rebuilds the recency order of the eviction
+    /// algorithms ordered by the insertion and access time, e.g. FIFO and LRU.
+    pub rank: usize,
+}
+
 /// A cached entry holder of the in-memory cache.
 #[derive(Debug)]
 pub enum CacheEntry<K, V, S = DefaultHasher, P = CacheProperties>
@@ -147,6 +180,8 @@ where
     Lfu(LfuCacheEntry<K, V, S, P>),
     /// A cached entry holder of the in-memory Sieve cache.
     Sieve(SieveCacheEntry<K, V, S, P>),
+    /// A cached entry holder of the in-memory GDSF cache.
+    Gdsf(GdsfCacheEntry<K, V, S, P>),
 }
 
 impl<K, V, S, P> Clone for CacheEntry<K, V, S, P>
@@ -163,6 +198,7 @@ where
             Self::Lfu(entry) => Self::Lfu(entry.clone()),
             Self::S3Fifo(entry) => Self::S3Fifo(entry.clone()),
             Self::Sieve(entry) => Self::Sieve(entry.clone()),
+            Self::Gdsf(entry) => Self::Gdsf(entry.clone()),
         }
     }
 }
@@ -183,6 +219,7 @@ where
             CacheEntry::Lfu(entry) => entry.deref(),
             CacheEntry::S3Fifo(entry) => entry.deref(),
             CacheEntry::Sieve(entry) => entry.deref(),
+            CacheEntry::Gdsf(entry) => entry.deref(),
         }
     }
 }
@@ -247,6 +284,18 @@ where
     }
 }
 
+impl<K, V, S, P> From<GdsfCacheEntry<K, V, S, P>> for CacheEntry<K, V, S, P>
+where
+    K: Key,
+    V: Value,
+    S: HashBuilder,
+    P: Properties,
+{
+    fn from(entry: GdsfCacheEntry<K, V, S, P>) -> Self {
+        Self::Gdsf(entry)
+    }
+}
+
 impl<K, V, S, P> CacheEntry<K, V, S, P>
 where
     K: Key,
@@ -262,6 +311,7 @@ where
             CacheEntry::Lfu(entry) => entry.hash(),
             CacheEntry::S3Fifo(entry) => entry.hash(),
             CacheEntry::Sieve(entry) => entry.hash(),
+            CacheEntry::Gdsf(entry) => entry.hash(),
         }
     }
 
@@ -273,6 +323,7 @@ where
             CacheEntry::Lfu(entry) => entry.key(),
             CacheEntry::S3Fifo(entry) => entry.key(),
             CacheEntry::Sieve(entry) => entry.key(),
+            CacheEntry::Gdsf(entry) => entry.key(),
         }
     }
 
@@ -284,6 +335,25 @@ where
             CacheEntry::Lfu(entry) => entry.value(),
             CacheEntry::S3Fifo(entry) => entry.value(),
             CacheEntry::Sieve(entry) => entry.value(),
+            CacheEntry::Gdsf(entry) => entry.value(),
+        }
+    }
+
+    /// Shared ownership of the value of the cached entry.
+    ///
+    /// The value is stored in an [`Arc`], so it can be shared beyond the lifetime of the entry without cloning, e.g. a
+    /// large `Vec<u8>`.
+    ///
+    /// Note: the memory of the value is released after all the [`Arc`]s are dropped, not when the entry is evicted,
+    /// and it is no longer counted in the cache usage after the entry is evicted.
+    pub fn value_arc(&self) -> Arc<V> {
+        match self {
+            CacheEntry::Fifo(entry) => entry.value_arc(),
+            CacheEntry::Lru(entry) => entry.value_arc(),
+            CacheEntry::Lfu(entry) => entry.value_arc(),
+            CacheEntry::S3Fifo(entry) => entry.value_arc(),
+            CacheEntry::Sieve(entry) => entry.value_arc(),
+            CacheEntry::Gdsf(entry) => entry.value_arc(),
         }
     }
 
@@ -295,6 +365,7 @@ where
             CacheEntry::Lfu(entry) => entry.properties(),
             CacheEntry::S3Fifo(entry) => entry.properties(),
             CacheEntry::Sieve(entry) => entry.properties(),
+            CacheEntry::Gdsf(entry) => entry.properties(),
         }
     }
 
@@ -306,6 +377,7 @@ where
             CacheEntry::Lfu(entry) => entry.weight(),
             CacheEntry::S3Fifo(entry) => entry.weight(),
             CacheEntry::Sieve(entry) => entry.weight(),
+            CacheEntry::Gdsf(entry) => entry.weight(),
         }
     }
 
@@ -317,6 +389,7 @@ where
             CacheEntry::Lfu(entry) => entry.refs(),
             CacheEntry::S3Fifo(entry) => entry.refs(),
             CacheEntry::Sieve(entry) => entry.refs(),
+            CacheEntry::Gdsf(entry) => entry.refs(),
         }
     }
 
@@ -328,6 +401,7 @@ where
             CacheEntry::Lfu(entry) => entry.is_outdated(),
             CacheEntry::S3Fifo(entry) => entry.is_outdated(),
             CacheEntry::Sieve(entry) => entry.is_outdated(),
+            CacheEntry::Gdsf(entry) => entry.is_outdated(),
         }
     }
 
@@ -339,6 +413,88 @@ where
             CacheEntry::Lfu(entry) => entry.piece(),
             CacheEntry::S3Fifo(entry) => entry.piece(),
             CacheEntry::Sieve(entry) => entry.piece(),
+            CacheEntry::Gdsf(entry) => entry.piece(),
+        }
+    }
+
+    /// Create a weak handle of the cached entry.
+    ///
+    /// The weak handle doesn't keep the entry from being evicted, see [`WeakCacheEntry::upgrade`].
+    pub fn downgrade(&self) -> WeakCacheEntry<K, V, S, P> {
+        match self {
+            CacheEntry::Fifo(entry) => WeakCacheEntry::Fifo(entry.downgrade()),
+            CacheEntry::Lru(entry) => WeakCacheEntry::Lru(entry.downgrade()),
+            CacheEntry::Lfu(entry) => WeakCacheEntry::Lfu(entry.downgrade()),
+            CacheEntry::S3Fifo(entry) => WeakCacheEntry::S3Fifo(entry.downgrade()),
+            CacheEntry::Sieve(entry) => WeakCacheEntry::Sieve(entry.downgrade()),
+            CacheEntry::Gdsf(entry) => WeakCacheEntry::Gdsf(entry.downgrade()),
+        }
+    }
+}
+
+/// A weak handle of a cached entry of the in-memory cache, created by [`CacheEntry::downgrade`].
+///
+/// Unlike [`CacheEntry`], holding a weak handle doesn't keep the entry in memory.
+#[derive(Debug)]
+pub enum WeakCacheEntry<K, V, S = DefaultHasher, P = CacheProperties>
+where
+    K: Key,
+    V: Value,
+    S: HashBuilder,
+    P: Properties,
+{
+    /// A weak handle of the in-memory FIFO cache entry.
+    Fifo(RawWeakCacheEntry<Fifo<K, V, P>, S>),
+    /// A weak handle of the in-memory S3FIFO cache entry.
+    S3Fifo(RawWeakCacheEntry<S3Fifo<K, V, P>, S>),
+    /// A weak handle of the in-memory LRU cache entry.
+    Lru(RawWeakCacheEntry<Lru<K, V, P>, S>),
+    /// A weak handle of the in-memory LFU cache entry.
+    Lfu(RawWeakCacheEntry<Lfu<K, V, P>, S>),
+    /// A weak handle of the in-memory Sieve cache entry.
+    Sieve(RawWeakCacheEntry<Sieve<K, V, P>, S>),
+    /// A weak handle of the in-memory GDSF cache entry.
+    Gdsf(RawWeakCacheEntry<Gdsf<K, V, P>, S>),
+}
+
+impl<K, V, S, P> Clone for WeakCacheEntry<K, V, S, P>
+where
+    K: Key,
+    V: Value,
+    S: HashBuilder,
+    P: Properties,
+{
+    fn clone(&self) -> Self {
+        match self {
+            Self::Fifo(entry) => Self::Fifo(entry.clone()),
+            Self::Lru(entry) => Self::Lru(entry.clone()),
+            Self::Lfu(entry) => Self::Lfu(entry.clone()),
+            Self::S3Fifo(entry) => Self::S3Fifo(entry.clone()),
+            Self::Sieve(entry) => Self::Sieve(entry.clone()),
+            Self::Gdsf(entry) => Self::Gdsf(entry.clone()),
+        }
+    }
+}
+
+impl<K, V, S, P> WeakCacheEntry<K, V, S, P>
+where
+    K: Key,
+    V: Value,
+    S: HashBuilder,
+    P: Properties,
+{
+    /// Get the cached entry if it is still in the cache.
+    ///
+    /// Returns `None` if the entry has been evicted, removed or replaced, or the cache has been dropped. Upgrading
+    /// doesn't count as an access of the entry for the eviction algorithm.
+    pub fn upgrade(&self) -> Option<CacheEntry<K, V, S, P>> {
+        match self {
+            WeakCacheEntry::Fifo(entry) => entry.upgrade().map(CacheEntry::from),
+            WeakCacheEntry::Lru(entry) => entry.upgrade().map(CacheEntry::from),
+            WeakCacheEntry::Lfu(entry) => entry.upgrade().map(CacheEntry::from),
+            WeakCacheEntry::S3Fifo(entry) => entry.upgrade().map(CacheEntry::from),
+            WeakCacheEntry::Sieve(entry) => entry.upgrade().map(CacheEntry::from),
+            WeakCacheEntry::Gdsf(entry) => entry.upgrade().map(CacheEntry::from),
         }
     }
 }
@@ -356,6 +512,8 @@ pub enum EvictionConfig {
     Lfu(LfuConfig),
     /// Sieve eviction algorithm config.
     Sieve(SieveConfig),
+    /// GDSF eviction algorithm config.
+    Gdsf(GdsfConfig),
 }
 
 impl From<FifoConfig> for EvictionConfig {
@@ -388,6 +546,12 @@ impl From<SieveConfig> for EvictionConfig {
     }
 }
 
+impl From<GdsfConfig> for EvictionConfig {
+    fn from(value: GdsfConfig) -> EvictionConfig {
+        EvictionConfig::Gdsf(value)
+    }
+}
+
 /// In-memory cache builder.
 pub struct CacheBuilder<K, V, S>
 where
@@ -400,6 +564,7 @@ where
     capacity: usize,
     shards: usize,
     eviction_config: EvictionConfig,
+    eviction_batch: usize,
 
     hash_builder: S,
     weighter: Arc<dyn Weighter<K, V>>,
@@ -407,6 +572,7 @@ where
     event_listener: Option<Arc<dyn EventListener<Key = K, Value = V>>>,
 
     registry: BoxedRegistry,
+    metrics_labels: Vec<(String, String)>,
     metrics: Option<Arc<Metrics>>,
 }
 
@@ -423,12 +589,14 @@ where
             capacity,
             shards: 8,
             eviction_config: LruConfig::default().into(),
+            eviction_batch: 1,
 
             hash_builder: Default::default(),
             weighter: Arc::new(|_, _| 1),
             event_listener: None,
 
             registry: Box::new(NoopMetricsRegistry),
+            metrics_labels: vec![],
             metrics: None,
         }
     }
@@ -465,6 +633,19 @@ where
         self
     }
 
+    /// Set how many entries to evict at least when the in-memory cache runs out of capacity.
+    ///
+    /// Evicting entries in batches amortizes the eviction overhead of inserts under sustained pressure, at the cost of
+    /// keeping the usage slightly below the capacity. The cache refills naturally with later inserts.
+    ///
+    /// `0` is treated as `1`.
+    ///
+    /// Default: `1`.
+    pub fn with_eviction_batch(mut self, eviction_batch: usize) -> Self {
+        self.eviction_batch = eviction_batch;
+        self
+    }
+
     /// Set in-memory cache hash builder.
     pub fn with_hash_builder<OS>(self, hash_builder: OS) -> CacheBuilder<K, V, OS>
     where
@@ -475,10 +656,12 @@ where
             capacity: self.capacity,
             shards: self.shards,
             eviction_config: self.eviction_config,
+            eviction_batch: self.eviction_batch,
             hash_builder,
             weighter: self.weighter,
             event_listener: self.event_listener,
             registry: self.registry,
+            metrics_labels: self.metrics_labels,
             metrics: self.metrics,
         }
     }
@@ -503,6 +686,16 @@ where
         self
     }
 
+    /// Set extra static labels attached to all metrics of the in-memory cache, besides the `name` label.
+    ///
+    /// Caches that share a metrics registry must use the same label keys.
+    ///
+    /// Default: No extra labels.
+    pub fn with_metrics_labels(mut self, labels: Vec<(String, String)>) -> Self {
+        self.metrics_labels = labels;
+        self
+    }
+
     /// Set metrics.
     ///
     /// Note: `with_metrics` is only supposed to be called by other foyer components.
@@ -527,13 +720,14 @@ where
 
         let metrics = self
             .metrics
-            .unwrap_or_else(|| Arc::new(Metrics::new(self.name, &self.registry)));
+            .unwrap_or_else(|| Arc::new(Metrics::with_labels(self.name, self.metrics_labels, &self.registry)));
 
         match self.eviction_config {
             EvictionConfig::Fifo(eviction_config) => Cache::Fifo(Arc::new(RawCache::new(RawCacheConfig {
                 capacity: self.capacity,
                 shards: self.shards,
                 eviction_config,
+                eviction_batch: self.eviction_batch,
                 hash_builder: self.hash_builder,
                 weighter: self.weighter,
                 event_listener: self.event_listener,
@@ -543,6 +737,7 @@ where
                 capacity: self.capacity,
                 shards: self.shards,
                 eviction_config,
+                eviction_batch: self.eviction_batch,
                 hash_builder: self.hash_builder,
                 weighter: self.weighter,
                 event_listener: self.event_listener,
@@ -552,6 +747,7 @@ where
                 capacity: self.capacity,
                 shards: self.shards,
                 eviction_config,
+                eviction_batch: self.eviction_batch,
                 hash_builder: self.hash_builder,
                 weighter: self.weighter,
                 event_listener: self.event_listener,
@@ -561,6 +757,7 @@ where
                 capacity: self.capacity,
                 shards: self.shards,
                 eviction_config,
+                eviction_batch: self.eviction_batch,
                 hash_builder: self.hash_builder,
                 weighter: self.weighter,
                 event_listener: self.event_listener,
@@ -570,6 +767,17 @@ where
                 capacity: self.capacity,
                 shards: self.shards,
                 eviction_config,
+                eviction_batch: self.eviction_batch,
+                hash_builder: self.hash_builder,
+                weighter: self.weighter,
+                event_listener: self.event_listener,
+                metrics,
+            }))),
+            EvictionConfig::Gdsf(eviction_config) => Cache::Gdsf(Arc::new(RawCache::new(RawCacheConfig {
+                capacity: self.capacity,
+                shards: self.shards,
+                eviction_config,
+                eviction_batch: self.eviction_batch,
                 hash_builder: self.hash_builder,
                 weighter: self.weighter,
                 event_listener: self.event_listener,
@@ -597,6 +805,8 @@ where
     S3Fifo(Arc<S3FifoCache<K, V, S, P>>),
     /// In-memory Sieve cache.
     Sieve(Arc<SieveCache<K, V, S, P>>),
+    /// In-memory GDSF cache.
+    Gdsf(Arc<GdsfCache<K, V, S, P>>),
 }
 
 impl<K, V, S, P> Debug for Cache<K, V, S, P>
@@ -613,6 +823,7 @@ where
             Self::Lru(_) => f.debug_tuple("Cache::LruCache").finish(),
             Self::Lfu(_) => f.debug_tuple("Cache::LfuCache").finish(),
             Self::Sieve(_) => f.debug_tuple("Cache::SieveCache").finish(),
+            Self::Gdsf(_) => f.debug_tuple("Cache::GdsfCache").finish(),
         }
     }
 }
@@ -631,6 +842,7 @@ where
             Self::Lru(cache) => Self::Lru(cache.clone()),
             Self::Lfu(cache) => Self::Lfu(cache.clone()),
             Self::Sieve(cache) => Self::Sieve(cache.clone()),
+            Self::Gdsf(cache) => Self::Gdsf(cache.clone()),
         }
     }
 }
@@ -662,10 +874,37 @@ where
             Cache::Lru(cache) => cache.resize(capacity),
             Cache::Lfu(cache) => cache.resize(capacity),
             Cache::Sieve(cache) => cache.resize(capacity),
+            Cache::Gdsf(cache) => cache.resize(capacity),
+        }
+    }
+
+    /// Replace all entries of the in-memory cache with the entries of `other`, shard by shard.
+    ///
+    /// Useful to swap in a cache that is warmed up in the background. `other` must be built with the same eviction
+    /// algorithm, shard count and hash builder. Each shard is swapped atomically, so no entry is lost or duplicated.
+    ///
+    /// The displaced entries leave the cache with [`foyer_common::event::Event::Clear`]. Entries of `other` that are
+    /// still referenced by [`CacheEntry`]s are not moved.
+    pub fn swap_contents(&self, other: Cache<K, V, S, P>) -> Result<()> {
+        match (self, &other) {
+            (Cache::Fifo(cache), Cache::Fifo(other)) => cache.swap_contents(other),
+            (Cache::S3Fifo(cache), Cache::S3Fifo(other)) => cache.swap_contents(other),
+            (Cache::Lru(cache), Cache::Lru(other)) => cache.swap_contents(other),
+            (Cache::Lfu(cache), Cache::Lfu(other)) => cache.swap_contents(other),
+            (Cache::Sieve(cache), Cache::Sieve(other)) => cache.swap_contents(other),
+            (Cache::Gdsf(cache), Cache::Gdsf(other)) => cache.swap_contents(other),
+            _ => Err(Error::ConfigError(
+                "cannot swap contents of caches with different eviction algorithms".to_string(),
+            )),
         }
     }
 
     /// Insert cache entry to the in-memory cache.
+    ///
+    /// Entries evicted by the insertion are handed to the pipe with [`Pipe::send`] without waiting. If the disk cache
+    /// cannot keep up, it is up to the disk cache to drop them.
+    ///
+    /// Use [`Cache::insert_async`] to apply backpressure on the caller instead.
     #[cfg_attr(feature = "tracing", fastrace::trace(name = "foyer::memory::cache::insert"))]
     pub fn insert(&self, key: K, value: V) -> CacheEntry<K, V, S, P> {
         match self {
@@ -674,6 +913,44 @@ where
             Cache::Lru(cache) => cache.insert(key, value).into(),
             Cache::Lfu(cache) => cache.insert(key, value).into(),
             Cache::Sieve(cache) => cache.insert(key, value).into(),
+            Cache::Gdsf(cache) => cache.insert(key, value).into(),
+        }
+    }
+
+    /// Insert cache entry to the in-memory cache, and return the value of the replaced entry if the key existed.
+    ///
+    /// The replaced entry is taken out under the same shard lock as the insertion, so "update and get old" can be
+    /// implemented without racing with other insertions.
+    #[cfg_attr(feature = "tracing", fastrace::trace(name = "foyer::memory::cache::insert_replace"))]
+    pub fn insert_replace(&self, key: K, value: V) -> (CacheEntry<K, V, S, P>, Option<V>)
+    where
+        V: Clone,
+    {
+        match self {
+            Cache::Fifo(cache) => {
+                let (entry, old) = cache.insert_replace(key, value);
+                (entry.into(), old)
+            }
+            Cache::S3Fifo(cache) => {
+                let (entry, old) = cache.insert_replace(key, value);
+                (entry.into(), old)
+            }
+            Cache::Lru(cache) => {
+                let (entry, old) = cache.insert_replace(key, value);
+                (entry.into(), old)
+            }
+            Cache::Lfu(cache) => {
+                let (entry, old) = cache.insert_replace(key, value);
+                (entry.into(), old)
+            }
+            Cache::Sieve(cache) => {
+                let (entry, old) = cache.insert_replace(key, value);
+                (entry.into(), old)
+            }
+            Cache::Gdsf(cache) => {
+                let (entry, old) = cache.insert_replace(key, value);
+                (entry.into(), old)
+            }
         }
     }
 
@@ -689,6 +966,39 @@ where
             Cache::Lru(cache) => cache.insert_with_properties(key, value, properties).into(),
             Cache::Lfu(cache) => cache.insert_with_properties(key, value, properties).into(),
             Cache::Sieve(cache) => cache.insert_with_properties(key, value, properties).into(),
+            Cache::Gdsf(cache) => cache.insert_with_properties(key, value, properties).into(),
+        }
+    }
+
+    /// Insert cache entry to the in-memory cache, and wait until the evicted entries are accepted by the pipe.
+    ///
+    /// Entries evicted by the insertion are handed to the pipe with [`Pipe::send_async`]. With the hybrid cache, the
+    /// evicted entries are paced by the write io throttle of the disk cache, and the returned future completes only
+    /// after all of them are enqueued, so the producer is slowed down by the throttle. The disk cache may still drop an
+    /// enqueued entry, e.g. if its write queue is full, the same as with [`Cache::insert`].
+    ///
+    /// Use [`Cache::insert`] if the caller must not wait.
+    #[cfg_attr(feature = "tracing", fastrace::trace(name = "foyer::memory::cache::insert_async"))]
+    pub async fn insert_async(&self, key: K, value: V) -> CacheEntry<K, V, S, P> {
+        self.insert_with_properties_async(key, value, P::default()).await
+    }
+
+    /// Insert cache entry to the in-memory cache with properties, and wait until the evicted entries are accepted by
+    /// the pipe.
+    ///
+    /// See [`Cache::insert_async`] for the difference from [`Cache::insert_with_properties`].
+    #[cfg_attr(
+        feature = "tracing",
+        fastrace::trace(name = "foyer::memory::cache::insert_with_properties_async")
+    )]
+    pub async fn insert_with_properties_async(&self, key: K, value: V, properties: P) -> CacheEntry<K, V, S, P> {
+        match self {
+            Cache::Fifo(cache) => cache.insert_with_properties_async(key, value, properties).await.into(),
+            Cache::S3Fifo(cache) => cache.insert_with_properties_async(key, value, properties).await.into(),
+            Cache::Lru(cache) => cache.insert_with_properties_async(key, value, properties).await.into(),
+            Cache::Lfu(cache) => cache.insert_with_properties_async(key, value, properties).await.into(),
+            Cache::Sieve(cache) => cache.insert_with_properties_async(key, value, properties).await.into(),
+            Cache::Gdsf(cache) => cache.insert_with_properties_async(key, value, properties).await.into(),
         }
     }
 
@@ -704,6 +1014,7 @@ where
             Cache::Lru(cache) => cache.remove(key).map(CacheEntry::from),
             Cache::Lfu(cache) => cache.remove(key).map(CacheEntry::from),
             Cache::Sieve(cache) => cache.remove(key).map(CacheEntry::from),
+            Cache::Gdsf(cache) => cache.remove(key).map(CacheEntry::from),
         }
     }
 
@@ -719,6 +1030,7 @@ where
             Cache::Lru(cache) => cache.get(key).map(CacheEntry::from),
             Cache::Lfu(cache) => cache.get(key).map(CacheEntry::from),
             Cache::Sieve(cache) => cache.get(key).map(CacheEntry::from),
+            Cache::Gdsf(cache) => cache.get(key).map(CacheEntry::from),
         }
     }
 
@@ -734,6 +1046,7 @@ where
             Cache::Lru(cache) => cache.contains(key),
             Cache::Lfu(cache) => cache.contains(key),
             Cache::Sieve(cache) => cache.contains(key),
+            Cache::Gdsf(cache) => cache.contains(key),
         }
     }
 
@@ -751,6 +1064,7 @@ where
             Cache::Lru(cache) => cache.touch(key),
             Cache::Lfu(cache) => cache.touch(key),
             Cache::Sieve(cache) => cache.touch(key),
+            Cache::Gdsf(cache) => cache.touch(key),
         }
     }
 
@@ -763,6 +1077,7 @@ where
             Cache::Lru(cache) => cache.clear(),
             Cache::Lfu(cache) => cache.clear(),
             Cache::Sieve(cache) => cache.clear(),
+            Cache::Gdsf(cache) => cache.clear(),
         }
     }
 
@@ -774,6 +1089,7 @@ where
             Cache::Lru(cache) => cache.capacity(),
             Cache::Lfu(cache) => cache.capacity(),
             Cache::Sieve(cache) => cache.capacity(),
+            Cache::Gdsf(cache) => cache.capacity(),
         }
     }
 
@@ -785,6 +1101,7 @@ where
             Cache::Lru(cache) => cache.usage(),
             Cache::Lfu(cache) => cache.usage(),
             Cache::Sieve(cache) => cache.usage(),
+            Cache::Gdsf(cache) => cache.usage(),
         }
     }
 
@@ -804,6 +1121,7 @@ where
             Cache::Lru(cache) => cache.hash_builder(),
             Cache::Lfu(cache) => cache.hash_builder(),
             Cache::Sieve(cache) => cache.hash_builder(),
+            Cache::Gdsf(cache) => cache.hash_builder(),
         }
     }
 
@@ -815,6 +1133,98 @@ where
             Cache::Lru(cache) => cache.shards(),
             Cache::Lfu(cache) => cache.shards(),
             Cache::Sieve(cache) => cache.shards(),
+            Cache::Gdsf(cache) => cache.shards(),
+        }
+    }
+
+    /// Iterate the cached entries in the order that the eviction algorithm would evict them, from the coldest.
+    ///
+    /// The order is predicted from a snapshot of the current state, shards are interleaved round-robin. See
+    /// [`RawCache::eviction_order`] for details.
+    ///
+    /// The returned entries are held until the iterator is dropped, which keeps their memory usage.
+    pub fn iter_eviction_order(&self) -> impl Iterator<Item = CacheEntry<K, V, S, P>> {
+        let entries: Vec<_> = match self {
+            Cache::Fifo(cache) => cache.eviction_order().into_iter().map(CacheEntry::from).collect(),
+            Cache::S3Fifo(cache) => cache.eviction_order().into_iter().map(CacheEntry::from).collect(),
+            Cache::Lru(cache) => cache.eviction_order().into_iter().map(CacheEntry::from).collect(),
+            Cache::Lfu(cache) => cache.eviction_order().into_iter().map(CacheEntry::from).collect(),
+            Cache::Sieve(cache) => cache.eviction_order().into_iter().map(CacheEntry::from).collect(),
+            Cache::Gdsf(cache) => cache.eviction_order().into_iter().map(CacheEntry::from).collect(),
+        };
+        entries.into_iter()
+    }
+
+    /// Snapshot the metadata of the cached entries in the order of [`Cache::iter_eviction_order`], from the coldest.
+    ///
+    /// Unlike [`Cache::iter_eviction_order`], no entry is held after returning.
+    pub fn entry_metas(&self) -> Vec<CacheEntryMeta<K>>
+    where
+        K: Clone,
+    {
+        self.iter_eviction_order()
+            .enumerate()
+            .map(|(rank, entry)| CacheEntryMeta {
+                key: entry.key().clone(),
+                hash: entry.hash(),
+                weight: entry.weight(),
+                rank,
+            })
+            .collect()
+    }
+
+    /// Evict the `n` coldest entries of the in-memory cache per the eviction algorithm and return their keys and
+    /// values, e.g. to write them back to the disk cache in a batch.
+    ///
+    /// The entries are drained round-robin across shards, so the chosen entries are the first `n` entries of
+    /// [`Cache::iter_eviction_order`] without concurrent operations. An entry is not necessarily colder than an entry
+    /// left in another shard.
+    ///
+    /// The drained entries leave the cache with [`foyer_common::event::Event::Drained`] and are not sent to the pipe.
+    pub fn drain_cold(&self, n: usize) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        match self {
+            Cache::Fifo(cache) => cache.drain_cold(n),
+            Cache::S3Fifo(cache) => cache.drain_cold(n),
+            Cache::Lru(cache) => cache.drain_cold(n),
+            Cache::Lfu(cache) => cache.drain_cold(n),
+            Cache::Sieve(cache) => cache.drain_cold(n),
+            Cache::Gdsf(cache) => cache.drain_cold(n),
+        }
+    }
+
+    /// Snapshot the statistics of each shard of the in-memory cache.
+    ///
+    /// Each shard is snapshotted under its own lock, so the snapshots of different shards may not be taken at the
+    /// same time.
+    pub fn shard_stats(&self) -> Vec<ShardStats> {
+        match self {
+            Cache::Fifo(cache) => cache.shard_stats(),
+            Cache::S3Fifo(cache) => cache.shard_stats(),
+            Cache::Lru(cache) => cache.shard_stats(),
+            Cache::Lfu(cache) => cache.shard_stats(),
+            Cache::Sieve(cache) => cache.shard_stats(),
+            Cache::Gdsf(cache) => cache.shard_stats(),
+        }
+    }
+
+    /// Get the index of the shard that the key is routed to.
+    ///
+    /// The index is in range `[0, shards)`, it is useful to diagnose the hot shards.
+    pub fn shard_of<Q>(&self, key: &Q) -> usize
+    where
+        Q: Hash + ?Sized,
+    {
+        match self {
+            Cache::Fifo(cache) => cache.shard_of(key),
+            Cache::S3Fifo(cache) => cache.shard_of(key),
+            Cache::Lru(cache) => cache.shard_of(key),
+            Cache::Lfu(cache) => cache.shard_of(key),
+            Cache::Sieve(cache) => cache.shard_of(key),
+            Cache::Gdsf(cache) => cache.shard_of(key),
         }
     }
 
@@ -827,6 +1237,7 @@ where
             Cache::Lru(cache) => cache.set_pipe(pipe),
             Cache::Lfu(cache) => cache.set_pipe(pipe),
             Cache::Sieve(cache) => cache.set_pipe(pipe),
+            Cache::Gdsf(cache) => cache.set_pipe(pipe),
         }
     }
 
@@ -841,6 +1252,7 @@ where
             Cache::Lru(cache) => cache.evict_all(),
             Cache::Lfu(cache) => cache.evict_all(),
             Cache::Sieve(cache) => cache.evict_all(),
+            Cache::Gdsf(cache) => cache.evict_all(),
         }
     }
 
@@ -856,6 +1268,7 @@ where
             Cache::Lru(cache) => cache.flush().await,
             Cache::Lfu(cache) => cache.flush().await,
             Cache::Sieve(cache) => cache.flush().await,
+            Cache::Gdsf(cache) => cache.flush().await,
         }
     }
 }
@@ -879,6 +1292,8 @@ where
     Lfu(#[pin] LfuFetch<K, V, ER, S, P>),
     /// A future that is used to get entry value from the remote storage for the in-memory sieve cache.
     Sieve(#[pin] SieveFetch<K, V, ER, S, P>),
+    /// A future that is used to get entry value from the remote storage for the in-memory GDSF cache.
+    Gdsf(#[pin] GdsfFetch<K, V, ER, S, P>),
 }
 
 impl<K, V, ER, S, P> From<FifoFetch<K, V, ER, S, P>> for Fetch<K, V, ER, S, P>
@@ -941,6 +1356,18 @@ where
     }
 }
 
+impl<K, V, ER, S, P> From<GdsfFetch<K, V, ER, S, P>> for Fetch<K, V, ER, S, P>
+where
+    K: Key,
+    V: Value,
+    S: HashBuilder,
+    P: Properties,
+{
+    fn from(entry: GdsfFetch<K, V, ER, S, P>) -> Self {
+        Self::Gdsf(entry)
+    }
+}
+
 impl<K, V, ER, S, P> Future for Fetch<K, V, ER, S, P>
 where
     K: Key,
@@ -958,6 +1385,7 @@ where
             FetchProj::Lru(entry) => entry.poll(cx).map(|res| res.map(CacheEntry::from)),
             FetchProj::Lfu(entry) => entry.poll(cx).map(|res| res.map(CacheEntry::from)),
             FetchProj::Sieve(entry) => entry.poll(cx).map(|res| res.map(CacheEntry::from)),
+            FetchProj::Gdsf(entry) => entry.poll(cx).map(|res| res.map(CacheEntry::from)),
         }
     }
 }
@@ -977,6 +1405,7 @@ where
             Fetch::Lru(fetch) => fetch.state(),
             Fetch::Lfu(fetch) => fetch.state(),
             Fetch::Sieve(fetch) => fetch.state(),
+            Fetch::Gdsf(fetch) => fetch.state(),
         }
     }
 
@@ -989,6 +1418,7 @@ where
             Fetch::Lru(fetch) => fetch.store(),
             Fetch::Lfu(fetch) => fetch.store(),
             Fetch::Sieve(fetch) => fetch.store(),
+            Fetch::Gdsf(fetch) => fetch.store(),
         }
     }
 }
@@ -1000,6 +1430,23 @@ where
     S: HashBuilder,
     P: Properties,
 {
+    /// Take a snapshot of the internal state of the eviction algorithm of each shard, in shard index order.
+    ///
+    /// The snapshot is specific to the configured eviction algorithm, see [`EvictionDebug`]. Taking it doesn't count
+    /// as accesses, so the state is not affected. For testing and debugging only, each shard is locked while its
+    /// state is copied.
+    #[cfg(any(test, feature = "debug"))]
+    pub fn eviction_debug(&self) -> Vec<EvictionDebug<K>> {
+        match self {
+            Cache::Fifo(cache) => cache.eviction_debug(),
+            Cache::S3Fifo(cache) => cache.eviction_debug(),
+            Cache::Lru(cache) => cache.eviction_debug(),
+            Cache::Lfu(cache) => cache.eviction_debug(),
+            Cache::Sieve(cache) => cache.eviction_debug(),
+            Cache::Gdsf(cache) => cache.eviction_debug(),
+        }
+    }
+
     /// Get the cached entry with the given key from the in-memory cache.
     ///
     /// Use `fetch` to fetch the cache value from the remote storage on cache miss.
@@ -1018,6 +1465,7 @@ where
             Cache::Lru(cache) => Fetch::from(cache.fetch(key, fetch)),
             Cache::Lfu(cache) => Fetch::from(cache.fetch(key, fetch)),
             Cache::Sieve(cache) => Fetch::from(cache.fetch(key, fetch)),
+            Cache::Gdsf(cache) => Fetch::from(cache.fetch(key, fetch)),
         }
     }
 
@@ -1039,6 +1487,7 @@ where
             Cache::Lru(cache) => Fetch::from(cache.fetch_with_properties(key, properties, fetch)),
             Cache::Lfu(cache) => Fetch::from(cache.fetch_with_properties(key, properties, fetch)),
             Cache::Sieve(cache) => Fetch::from(cache.fetch_with_properties(key, properties, fetch)),
+            Cache::Gdsf(cache) => Fetch::from(cache.fetch_with_properties(key, properties, fetch)),
         }
     }
 
@@ -1069,6 +1518,7 @@ where
             Cache::Lfu(cache) => Fetch::from(cache.fetch_inner(key, properties, fetch, runtime)),
             Cache::S3Fifo(cache) => Fetch::from(cache.fetch_inner(key, properties, fetch, runtime)),
             Cache::Sieve(cache) => Fetch::from(cache.fetch_inner(key, properties, fetch, runtime)),
+            Cache::Gdsf(cache) => Fetch::from(cache.fetch_inner(key, properties, fetch, runtime)),
         }
     }
 }
@@ -1136,6 +1586,13 @@ mod tests {
             .build()
     }
 
+    fn gdsf() -> Cache<u64, u64> {
+        CacheBuilder::new(CAPACITY)
+            .with_shards(SHARDS)
+            .with_eviction_config(GdsfConfig::default())
+            .build()
+    }
+
     fn init_cache(cache: &Cache<u64, u64>, rng: &mut StdRng) {
         let mut v = RANGE.collect_vec();
         v.shuffle(rng);
@@ -1220,4 +1677,149 @@ mod tests {
     async fn test_sieve_cache() {
         case(sieve()).await
     }
+
+    #[tokio::test]
+    async fn test_gdsf_cache() {
+        case(gdsf()).await
+    }
+
+    #[test]
+    fn test_entry_metas() {
+        let cache: Cache<u64, Vec<u8>> = CacheBuilder::new(CAPACITY)
+            .with_shards(1)
+            .with_eviction_config(LruConfig {
+                high_priority_pool_ratio: 0.0,
+            })
+            .with_weighter(|_, v: &Vec<u8>| v.len())
+            .build();
+        (1..=3).for_each(|i| drop(cache.insert(i, vec![0; i as usize])));
+        drop(cache.get(&1));
+
+        let metas = cache.entry_metas();
+        assert_eq!(
+            metas,
+            [(2, 0), (3, 1), (1, 2)]
+                .into_iter()
+                .map(|(key, rank)| CacheEntryMeta {
+                    key,
+                    hash: cache.hash(&key),
+                    weight: key as usize,
+                    rank,
+                })
+                .collect_vec()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_entry_metas_serde() {
+        let lru = || -> Cache<u64, u64> {
+            CacheBuilder::new(CAPACITY)
+                .with_shards(1)
+                .with_eviction_config(LruConfig {
+                    high_priority_pool_ratio: 0.0,
+                })
+                .build()
+        };
+
+        let cache = lru();
+        (1..=3).for_each(|i| drop(cache.insert(i, i)));
+        drop(cache.get(&1));
+        let metas = cache.entry_metas();
+
+        let json = serde_json::to_string(&metas).unwrap();
+        let mut restored: Vec<CacheEntryMeta<u64>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, metas);
+
+        // Rebuild the recency order by inserting the entries from the coldest.
+        restored.sort_by_key(|meta| meta.rank);
+        let cache = lru();
+        restored.iter().for_each(|meta| drop(cache.insert(meta.key, meta.key)));
+        assert_eq!(cache.entry_metas(), metas);
+    }
+
+    #[test]
+    fn test_eviction_debug() {
+        let cache: Cache<u64, u64> = CacheBuilder::new(CAPACITY)
+            .with_shards(1)
+            .with_eviction_config(LruConfig {
+                high_priority_pool_ratio: 0.0,
+            })
+            .build();
+        (1..=3).for_each(|i| drop(cache.insert(i, i)));
+        drop(cache.get(&1));
+        let entry = cache.get(&2).unwrap();
+
+        // Released entries go to the back, held entries are pinned.
+        assert_eq!(
+            cache.eviction_debug(),
+            vec![EvictionDebug::Lru {
+                low_priority: vec![3, 1],
+                high_priority: vec![],
+                pinned: vec![2],
+            }]
+        );
+        drop(entry);
+
+        assert_eq!(
+            sieve().eviction_debug().len(),
+            SHARDS,
+            "a snapshot is taken for each shard"
+        );
+    }
+
+    #[test]
+    fn test_weak_entry() {
+        for cache in [fifo(), lru(), lfu(), s3fifo(), sieve(), gdsf()] {
+            let weak = cache.insert(1, 1).downgrade();
+            assert_eq!(weak.upgrade().map(|entry| *entry.value()), Some(1));
+
+            // Removed entries cannot be upgraded, even if they are still held.
+            let entry = weak.upgrade().unwrap();
+            cache.remove(&1);
+            assert!(weak.upgrade().is_none());
+            assert_eq!(entry.value(), &1);
+            drop(entry);
+
+            let weak = cache.insert(2, 2).downgrade();
+            cache.insert(2, 3);
+            assert!(weak.upgrade().is_none());
+
+            let weak = cache.insert(4, 4).downgrade();
+            drop(cache);
+            assert!(weak.upgrade().is_none());
+        }
+
+        // Weak handles don't keep the entries from being evicted.
+        let cache: Cache<u64, u64> = CacheBuilder::new(CAPACITY)
+            .with_shards(1)
+            .with_eviction_config(FifoConfig {})
+            .build();
+        let weak = cache.insert(0, 0).downgrade();
+        for i in 1..=CAPACITY as u64 {
+            cache.insert(i, i);
+            if i < CAPACITY as u64 {
+                assert!(weak.upgrade().is_some());
+            }
+        }
+        assert!(weak.upgrade().is_none());
+        assert!(cache.get(&0).is_none());
+    }
+
+    #[test]
+    fn test_value_arc() {
+        // The value doesn't need to be `Clone` to be shared.
+        #[derive(Debug, PartialEq)]
+        struct Large(Vec<u8>);
+
+        let cache: Cache<u64, Large> = CacheBuilder::new(1).with_shards(1).build();
+        let value = cache.insert(1, Large(vec![1; 1024])).value_arc();
+        assert!(Arc::ptr_eq(&value, &cache.get(&1).unwrap().value_arc()));
+
+        // The value outlives the entry.
+        cache.insert(2, Large(vec![2; 1024]));
+        assert!(cache.get(&1).is_none());
+        assert_eq!(*value, Large(vec![1; 1024]));
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
 }
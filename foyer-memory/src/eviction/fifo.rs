@@ -21,6 +21,8 @@ use foyer_common::{
 use intrusive_collections::{intrusive_adapter, LinkedList, LinkedListAtomicLink};
 use serde::{Deserialize, Serialize};
 
+#[cfg(any(test, feature = "debug"))]
+use super::EvictionDebug;
 use super::{Eviction, Op};
 use crate::{error::Result, record::Record};
 
@@ -84,6 +86,25 @@ where
         record.set_in_eviction(false);
     }
 
+    fn eviction_order(&self) -> Vec<Arc<Record<Self>>> {
+        let mut res = vec![];
+        let mut cursor = self.queue.front();
+        while let Some(record) = cursor.clone_pointer() {
+            res.push(record);
+            cursor.move_next();
+        }
+        res
+    }
+
+    #[cfg(any(test, feature = "debug"))]
+    fn debug(&self) -> EvictionDebug<K>
+    where
+        K: Clone,
+    {
+        let queue = self.queue.iter().map(|record| record.key().clone()).collect();
+        EvictionDebug::Fifo { queue }
+    }
+
     fn acquire() -> Op<Self> {
         Op::noop()
     }
@@ -100,7 +121,7 @@ pub mod tests {
 
     use super::*;
     use crate::{
-        eviction::test_utils::{assert_ptr_eq, assert_ptr_vec_eq, Dump, TestProperties},
+        eviction::test_utils::{assert_eviction_order, assert_ptr_eq, assert_ptr_vec_eq, Dump, TestProperties},
         record::Data,
     };
 
@@ -132,7 +153,7 @@ pub mod tests {
             .map(|i| {
                 Arc::new(Record::new(Data {
                     key: i,
-                    value: i,
+                    value: Arc::new(i),
                     properties: TestProperties::default(),
                     hash: i,
                     weight: 1,
@@ -171,4 +192,28 @@ pub mod tests {
 
         assert_ptr_vec_eq(fifo.dump(), vec![]);
     }
+
+    #[test]
+    fn test_fifo_eviction_order() {
+        let rs = (0..8)
+            .map(|i| {
+                Arc::new(Record::new(Data {
+                    key: i,
+                    value: Arc::new(i),
+                    properties: TestProperties::default(),
+                    hash: i,
+                    weight: 1,
+                }))
+            })
+            .collect_vec();
+        let r = |i: usize| rs[i].clone();
+
+        let mut fifo = TestFifo::new(100, &FifoConfig {});
+        (0..8).for_each(|i| fifo.push(r(i)));
+        fifo.pop().unwrap();
+        fifo.remove(&rs[3]);
+
+        assert_ptr_vec_eq(fifo.eviction_order(), vec![r(1), r(2), r(4), r(5), r(6), r(7)]);
+        assert_eviction_order(&mut fifo);
+    }
 }
@@ -0,0 +1,368 @@
+// Copyright 2025 foyer Project Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{mem::offset_of, sync::Arc};
+
+use foyer_common::{
+    code::{Key, Value},
+    properties::{Hint, Properties},
+    strict_assert,
+};
+use intrusive_collections::{intrusive_adapter, KeyAdapter, RBTree, RBTreeAtomicLink};
+use serde::{Deserialize, Serialize};
+
+#[cfg(any(test, feature = "debug"))]
+use super::EvictionDebug;
+use super::{Eviction, Op};
+use crate::{
+    error::{Error, Result},
+    record::Record,
+};
+
+/// Gdsf eviction algorithm config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GdsfConfig {
+    /// Max access frequency counted for a record.
+    ///
+    /// A record hot in the past keeps its priority until it is accessed again, the cap bounds how long it can
+    /// outlive the records accessed recently.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the value is `0`.
+    pub max_frequency: u32,
+}
+
+impl Default for GdsfConfig {
+    fn default() -> Self {
+        Self { max_frequency: 32 }
+    }
+}
+
+/// Gdsf eviction algorithm state.
+#[derive(Debug, Default)]
+pub struct GdsfState {
+    link: RBTreeAtomicLink,
+    priority: f64,
+    sequence: u64,
+    frequency: u32,
+}
+
+intrusive_adapter! { Adapter<K, V, P> = Arc<Record<Gdsf<K, V, P>>>: Record<Gdsf<K, V, P>> { ?offset = Record::<Gdsf<K, V, P>>::STATE_OFFSET + offset_of!(GdsfState, link) => RBTreeAtomicLink } where K: Key, V: Value, P: Properties }
+
+impl<'a, K, V, P> KeyAdapter<'a> for Adapter<K, V, P>
+where
+    K: Key,
+    V: Value,
+    P: Properties,
+{
+    type Key = (u64, u64);
+
+    fn get_key(&self, record: &'a Record<Gdsf<K, V, P>>) -> Self::Key {
+        let state = unsafe { &*record.state().get() };
+        // The priorities are non-negative, so the order of the bits matches the order of the values.
+        (state.priority.to_bits(), state.sequence)
+    }
+}
+
+/// GreedyDual-Size-Frequency eviction algorithm implementation based on the paper:
+/// "Improving Web Servers and Proxies Performance with GDSF Caching Policies"
+/// (https://www.hpl.hp.com/techreports/98/HPL-98-69R1.pdf).
+///
+/// Each record has the priority `L + frequency / weight`, and the record with the lowest priority is evicted first.
+/// `L` is the priority of the last evicted record, so the priorities of the records not accessed for a while fall
+/// behind. Compared to LRU, many small hot records are kept over a few large cold ones, which favors the object hit
+/// ratio over the byte hit ratio of a cache with heterogeneous record weights. Records with the same priority are
+/// evicted in the order of their last access.
+///
+/// The weights are given by the weighter of the cache, see [`crate::CacheBuilder::with_weighter`].
+///
+/// A record inserted with [`Hint::Low`] starts with frequency `0`, so it is evicted before the other records that are
+/// not accessed since the last eviction.
+pub struct Gdsf<K, V, P>
+where
+    K: Key,
+    V: Value,
+    P: Properties,
+{
+    tree: RBTree<Adapter<K, V, P>>,
+
+    /// Priority of the last evicted record.
+    inflation: f64,
+    sequence: u64,
+
+    config: GdsfConfig,
+}
+
+impl<K, V, P> Gdsf<K, V, P>
+where
+    K: Key,
+    V: Value,
+    P: Properties,
+{
+    fn insert(&mut self, record: Arc<Record<Self>>) {
+        let state = unsafe { &mut *record.state().get() };
+        strict_assert!(!state.link.is_linked());
+
+        state.priority = self.inflation + state.frequency as f64 / record.weight().max(1) as f64;
+        state.sequence = self.sequence;
+        self.sequence += 1;
+        self.tree.insert(record);
+    }
+}
+
+impl<K, V, P> Eviction for Gdsf<K, V, P>
+where
+    K: Key,
+    V: Value,
+    P: Properties,
+{
+    type Config = GdsfConfig;
+    type Key = K;
+    type Value = V;
+    type Properties = P;
+    type State = GdsfState;
+
+    fn new(_capacity: usize, config: &Self::Config) -> Self
+    where
+        Self: Sized,
+    {
+        assert!(
+            config.max_frequency > 0,
+            "max_frequency must be positive, given: {}",
+            config.max_frequency
+        );
+
+        Self {
+            tree: RBTree::new(Adapter::new()),
+            inflation: 0.0,
+            sequence: 0,
+            config: config.clone(),
+        }
+    }
+
+    fn update(&mut self, _: usize, config: Option<&Self::Config>) -> Result<()> {
+        if let Some(config) = config {
+            if config.max_frequency == 0 {
+                return Err(Error::ConfigError(format!(
+                    "[gdsf]: max_frequency must be positive, given: {}, new configuration ignored",
+                    config.max_frequency
+                )));
+            }
+            self.config = config.clone();
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, record: Arc<Record<Self>>) {
+        let state = unsafe { &mut *record.state().get() };
+
+        record.set_in_eviction(true);
+
+        state.frequency = match record.properties().hint().unwrap_or_default() {
+            Hint::Normal => 1,
+            Hint::Low => 0,
+        };
+        self.insert(record);
+    }
+
+    fn pop(&mut self) -> Option<Arc<Record<Self>>> {
+        let record = self.tree.front_mut().remove()?;
+
+        let state = unsafe { &*record.state().get() };
+        strict_assert!(!state.link.is_linked());
+        self.inflation = state.priority;
+
+        record.set_in_eviction(false);
+
+        Some(record)
+    }
+
+    fn remove(&mut self, record: &Arc<Record<Self>>) {
+        let state = unsafe { &*record.state().get() };
+        strict_assert!(state.link.is_linked());
+
+        unsafe { self.tree.cursor_mut_from_ptr(Arc::as_ptr(record)).remove() };
+
+        strict_assert!(!state.link.is_linked());
+
+        record.set_in_eviction(false);
+    }
+
+    fn clear(&mut self) {
+        while self.pop().is_some() {}
+        self.inflation = 0.0;
+    }
+
+    fn eviction_order(&self) -> Vec<Arc<Record<Self>>> {
+        // The priorities of the records don't change on evictions, only the priorities of the records accessed
+        // later do.
+        let mut res = vec![];
+        let mut cursor = self.tree.front();
+        while let Some(record) = cursor.clone_pointer() {
+            res.push(record);
+            cursor.move_next();
+        }
+        res
+    }
+
+    #[cfg(any(test, feature = "debug"))]
+    fn debug(&self) -> EvictionDebug<K>
+    where
+        K: Clone,
+    {
+        let queue = self
+            .tree
+            .iter()
+            .map(|record| {
+                let state = unsafe { &*record.state().get() };
+                (record.key().clone(), state.priority, state.frequency)
+            })
+            .collect();
+        EvictionDebug::Gdsf {
+            queue,
+            inflation: self.inflation,
+        }
+    }
+
+    fn acquire() -> Op<Self> {
+        Op::mutable(|this: &mut Self, record| {
+            if !record.is_in_eviction() {
+                return;
+            }
+
+            let state = unsafe { &mut *record.state().get() };
+            assert!(state.link.is_linked());
+
+            // Reinsert the record with the updated priority.
+            let record = unsafe { this.tree.cursor_mut_from_ptr(Arc::as_ptr(record)).remove() }.unwrap();
+            state.frequency = (state.frequency + 1).min(this.config.max_frequency);
+            this.insert(record);
+        })
+    }
+
+    fn release() -> Op<Self> {
+        Op::noop()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::{
+        eviction::test_utils::{assert_eviction_order, assert_ptr_eq, assert_ptr_vec_eq, Dump, OpExt, TestProperties},
+        record::Data,
+    };
+
+    impl<K, V> Dump for Gdsf<K, V, TestProperties>
+    where
+        K: Key + Clone,
+        V: Value + Clone,
+    {
+        type Output = Vec<Arc<Record<Self>>>;
+        fn dump(&self) -> Self::Output {
+            self.eviction_order()
+        }
+    }
+
+    type TestGdsf = Gdsf<u64, u64, TestProperties>;
+
+    fn records(weights: &[usize]) -> Vec<Arc<Record<TestGdsf>>> {
+        weights
+            .iter()
+            .enumerate()
+            .map(|(i, &weight)| {
+                Arc::new(Record::new(Data {
+                    key: i as u64,
+                    value: Arc::new(i as u64),
+                    properties: TestProperties::default(),
+                    hash: i as u64,
+                    weight,
+                }))
+            })
+            .collect_vec()
+    }
+
+    #[test]
+    fn test_gdsf_basic() {
+        let rs = records(&[4, 1, 2, 1, 8, 1]);
+        let r = |i: usize| rs[i].clone();
+        let mut gdsf = TestGdsf::new(100, &GdsfConfig::default());
+
+        // Priorities: 0.25, 1, 0.5, 1
+        gdsf.push(r(0));
+        gdsf.push(r(1));
+        gdsf.push(r(2));
+        gdsf.push(r(3));
+        assert_ptr_vec_eq(gdsf.dump(), vec![r(0), r(2), r(1), r(3)]);
+
+        // The large record is evicted first.
+        let r0 = gdsf.pop().unwrap();
+        assert_ptr_eq(&rs[0], &r0);
+
+        // Priorities: 0.25 + 2 / 2 = 1.25, the access makes 2 hotter than 1 and 3.
+        gdsf.acquire_mutable(&r(2));
+        assert_ptr_vec_eq(gdsf.dump(), vec![r(1), r(3), r(2)]);
+
+        // The records inserted later are aged by the evicted priority, 0.25 + 1 / 8.
+        gdsf.push(r(4));
+        assert_ptr_vec_eq(gdsf.dump(), vec![r(4), r(1), r(3), r(2)]);
+
+        // Records with the same priority are evicted in the order of their last access.
+        gdsf.remove(&r(4));
+        let r1 = gdsf.pop().unwrap();
+        assert_ptr_eq(&rs[1], &r1);
+        // Priority: 1 + 1 / 1 = 2.
+        gdsf.push(r(5));
+        assert_ptr_vec_eq(gdsf.dump(), vec![r(3), r(2), r(5)]);
+
+        gdsf.clear();
+        assert_ptr_vec_eq(gdsf.dump(), vec![]);
+        assert_eq!(gdsf.inflation, 0.0);
+    }
+
+    #[test]
+    fn test_gdsf_max_frequency() {
+        let rs = records(&[1, 1]);
+        let r = |i: usize| rs[i].clone();
+        let mut gdsf = TestGdsf::new(100, &GdsfConfig { max_frequency: 2 });
+
+        gdsf.push(r(0));
+        gdsf.push(r(1));
+        (0..10).for_each(|_| gdsf.acquire_mutable(&r(0)));
+        gdsf.acquire_mutable(&r(1));
+
+        // Both are capped at frequency 2, 0 is accessed earlier.
+        assert_ptr_vec_eq(gdsf.dump(), vec![r(0), r(1)]);
+
+        assert!(gdsf.update(100, Some(&GdsfConfig { max_frequency: 0 })).is_err());
+    }
+
+    #[test]
+    fn test_gdsf_eviction_order() {
+        let rs = records(&[1, 2, 3, 4, 1, 2, 3, 4]);
+        let r = |i: usize| rs[i].clone();
+        let mut gdsf = TestGdsf::new(100, &GdsfConfig::default());
+        (0..8).for_each(|i| gdsf.push(r(i)));
+
+        let r3 = gdsf.pop().unwrap();
+        assert_ptr_eq(&rs[3], &r3);
+        [0, 3, 6].into_iter().for_each(|i| gdsf.acquire_mutable(&rs[i]));
+        [6, 5].into_iter().for_each(|i| gdsf.acquire_mutable(&rs[i]));
+
+        assert_eviction_order(&mut gdsf);
+    }
+}
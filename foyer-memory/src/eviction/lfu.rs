@@ -23,6 +23,8 @@ use foyer_common::{
 use intrusive_collections::{intrusive_adapter, LinkedList, LinkedListAtomicLink};
 use serde::{Deserialize, Serialize};
 
+#[cfg(any(test, feature = "debug"))]
+use super::EvictionDebug;
 use super::{Eviction, Op};
 use crate::{
     error::{Error, Result},
@@ -352,6 +354,53 @@ where
         }
     }
 
+    fn eviction_order(&self) -> Vec<Arc<Record<Self>>> {
+        // Merge `window` and `probation` the same way as `pop`, then `protected`.
+        let mut res = vec![];
+        let mut cw = self.window.front();
+        let mut cp = self.probation.front();
+        loop {
+            let cursor = match (cw.get(), cp.get()) {
+                (None, None) => break,
+                (None, Some(_)) => &mut cp,
+                (Some(_), None) => &mut cw,
+                (Some(w), Some(p)) => {
+                    if self.frequencies.estimate(w.hash()) < self.frequencies.estimate(p.hash()) {
+                        &mut cw
+                    } else {
+                        &mut cp
+                    }
+                }
+            };
+            res.extend(cursor.clone_pointer());
+            cursor.move_next();
+        }
+        let mut cursor = self.protected.front();
+        while let Some(record) = cursor.clone_pointer() {
+            res.push(record);
+            cursor.move_next();
+        }
+        res
+    }
+
+    #[cfg(any(test, feature = "debug"))]
+    fn debug(&self) -> EvictionDebug<K>
+    where
+        K: Clone,
+    {
+        let dump = |queue: &LinkedList<Adapter<K, V, P>>| {
+            queue
+                .iter()
+                .map(|record| (record.key().clone(), self.frequencies.estimate(record.hash())))
+                .collect()
+        };
+        EvictionDebug::Lfu {
+            window: dump(&self.window),
+            probation: dump(&self.probation),
+            protected: dump(&self.protected),
+        }
+    }
+
     fn acquire() -> Op<Self> {
         Op::mutable(|this: &mut Self, record| {
             // Update frequency by access.
@@ -412,7 +461,9 @@ mod tests {
 
     use super::*;
     use crate::{
-        eviction::test_utils::{assert_ptr_eq, assert_ptr_vec_vec_eq, Dump, OpExt, TestProperties},
+        eviction::test_utils::{
+            assert_eviction_order, assert_ptr_eq, assert_ptr_vec_vec_eq, Dump, OpExt, TestProperties,
+        },
         record::Data,
     };
 
@@ -466,7 +517,7 @@ mod tests {
             .map(|i| {
                 Arc::new(Record::new(Data {
                     key: i,
-                    value: i,
+                    value: Arc::new(i),
                     properties: TestProperties::default(),
                     hash: i,
                     weight: 1,
@@ -648,4 +699,40 @@ mod tests {
         lfu.clear();
         assert_ptr_vec_vec_eq(lfu.dump(), vec![vec![], vec![], vec![]]);
     }
+
+    #[test]
+    fn test_lfu_eviction_order() {
+        let rs = (0..16)
+            .map(|i| {
+                Arc::new(Record::new(Data {
+                    key: i,
+                    value: Arc::new(i),
+                    properties: TestProperties::default(),
+                    hash: i,
+                    weight: 1,
+                }))
+            })
+            .collect_vec();
+        let r = |i: usize| rs[i].clone();
+
+        // window: 2, probation: 2, protected: 6
+        let config = LfuConfig {
+            window_capacity_ratio: 0.2,
+            protected_capacity_ratio: 0.6,
+            cmsketch_eps: 0.01,
+            cmsketch_confidence: 0.95,
+        };
+        let mut lfu = TestLfu::new(10, &config);
+
+        (0..10).for_each(|i| lfu.push(r(i)));
+        // Promote some records to the protected queue and raise the frequencies of the others.
+        [1, 3, 3, 5, 8, 9, 9, 9]
+            .into_iter()
+            .for_each(|i| lfu.acquire_mutable(&rs[i]));
+        lfu.pop().unwrap();
+        (10..12).for_each(|i| lfu.push(r(i)));
+        [8, 10, 10].into_iter().for_each(|i| lfu.acquire_mutable(&rs[i]));
+
+        assert_eviction_order(&mut lfu);
+    }
 }
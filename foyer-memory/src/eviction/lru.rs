@@ -22,6 +22,8 @@ use foyer_common::{
 use intrusive_collections::{intrusive_adapter, LinkedList, LinkedListAtomicLink};
 use serde::{Deserialize, Serialize};
 
+#[cfg(any(test, feature = "debug"))]
+use super::EvictionDebug;
 use super::{Eviction, Op};
 use crate::{
     error::{Error, Result},
@@ -240,6 +242,32 @@ where
         assert_eq!(self.high_priority_weight, 0);
     }
 
+    fn eviction_order(&self) -> Vec<Arc<Record<Self>>> {
+        // Pinned records are never evicted.
+        let mut res = vec![];
+        for list in [&self.list, &self.high_priority_list] {
+            let mut cursor = list.front();
+            while let Some(record) = cursor.clone_pointer() {
+                res.push(record);
+                cursor.move_next();
+            }
+        }
+        res
+    }
+
+    #[cfg(any(test, feature = "debug"))]
+    fn debug(&self) -> EvictionDebug<K>
+    where
+        K: Clone,
+    {
+        let dump = |list: &LinkedList<Adapter<K, V, P>>| list.iter().map(|record| record.key().clone()).collect();
+        EvictionDebug::Lru {
+            low_priority: dump(&self.list),
+            high_priority: dump(&self.high_priority_list),
+            pinned: dump(&self.pin_list),
+        }
+    }
+
     fn acquire() -> Op<Self> {
         Op::mutable(|this: &mut Self, record| {
             if !record.is_in_eviction() {
@@ -302,7 +330,9 @@ pub mod tests {
 
     use super::*;
     use crate::{
-        eviction::test_utils::{assert_ptr_eq, assert_ptr_vec_vec_eq, Dump, OpExt, TestProperties},
+        eviction::test_utils::{
+            assert_eviction_order, assert_ptr_eq, assert_ptr_vec_eq, assert_ptr_vec_vec_eq, Dump, OpExt, TestProperties,
+        },
         record::Data,
     };
 
@@ -356,7 +386,7 @@ pub mod tests {
             .map(|i| {
                 Arc::new(Record::new(Data {
                     key: i,
-                    value: i,
+                    value: Arc::new(i),
                     properties: if i < 10 {
                         TestProperties::default().with_hint(Hint::Normal)
                     } else {
@@ -432,7 +462,7 @@ pub mod tests {
             .map(|i| {
                 Arc::new(Record::new(Data {
                     key: i,
-                    value: i,
+                    value: Arc::new(i),
                     properties: if i < 10 {
                         TestProperties::default().with_hint(Hint::Normal)
                     } else {
@@ -519,4 +549,43 @@ pub mod tests {
         lru.clear();
         assert_ptr_vec_vec_eq(lru.dump(), vec![vec![], vec![], vec![]]);
     }
+
+    #[test]
+    fn test_lru_eviction_order() {
+        let rs = (0..8)
+            .map(|i| {
+                Arc::new(Record::new(Data {
+                    key: i,
+                    value: Arc::new(i),
+                    properties: if i % 2 == 0 {
+                        TestProperties::default().with_hint(Hint::Normal)
+                    } else {
+                        TestProperties::default().with_hint(Hint::Low)
+                    },
+                    hash: i,
+                    weight: 1,
+                }))
+            })
+            .collect_vec();
+        let r = |i: usize| rs[i].clone();
+
+        let config = LruConfig {
+            high_priority_pool_ratio: 0.5,
+        };
+        let mut lru = TestLru::new(8, &config);
+
+        (0..8).for_each(|i| lru.push(r(i)));
+        // Pinned records are not evicted.
+        lru.acquire_mutable(&rs[1]);
+        lru.acquire_mutable(&rs[2]);
+        assert_ptr_vec_eq(lru.eviction_order(), vec![r(3), r(5), r(7), r(0), r(4), r(6)]);
+
+        lru.release_mutable(&rs[1]);
+        lru.release_mutable(&rs[2]);
+        assert_ptr_vec_eq(
+            lru.eviction_order(),
+            vec![r(3), r(5), r(7), r(1), r(0), r(4), r(6), r(2)],
+        );
+        assert_eviction_order(&mut lru);
+    }
 }
@@ -128,6 +128,21 @@ pub trait Eviction: Send + Sync + 'static + Sized {
         while self.pop().is_some() {}
     }
 
+    /// Get the records in the order that the cache eviction algorithm instance would evict them, starting from the
+    /// next record to evict.
+    ///
+    /// The order is predicted from the current state, assuming no further operations. It MUST NOT modify the
+    /// cache eviction algorithm instance or the per-record state.
+    fn eviction_order(&self) -> Vec<Arc<Record<Self>>>;
+
+    /// Take a snapshot of the internal state of the cache eviction algorithm instance, for testing and debugging.
+    ///
+    /// It MUST NOT modify the cache eviction algorithm instance or the per-record state.
+    #[cfg(any(test, feature = "debug"))]
+    fn debug(&self) -> EvictionDebug<Self::Key>
+    where
+        Self::Key: Clone;
+
     /// `acquire` is called when an external caller acquire a cache entry from the cache.
     ///
     /// The entry can be EITHER in the cache eviction algorithm instance or not.
@@ -139,7 +154,63 @@ pub trait Eviction: Send + Sync + 'static + Sized {
     fn release() -> Op<Self>;
 }
 
+/// Snapshot of the internal state of a cache eviction algorithm instance, see [`Eviction::debug`].
+///
+/// The records of each queue are listed by their keys from the front of the queue, i.e. the next record to leave the
+/// queue.
+#[cfg(any(test, feature = "debug"))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvictionDebug<K> {
+    /// State of [`fifo::Fifo`].
+    Fifo {
+        /// The FIFO queue.
+        queue: Vec<K>,
+    },
+    /// State of [`s3fifo::S3Fifo`].
+    S3Fifo {
+        /// The small queue, with the frequency of each record.
+        small: Vec<(K, u8)>,
+        /// The main queue, with the frequency of each record.
+        main: Vec<(K, u8)>,
+        /// Count of the hashes in the ghost queue.
+        ghost: usize,
+    },
+    /// State of [`lru::Lru`].
+    Lru {
+        /// The low priority list, evicted first.
+        low_priority: Vec<K>,
+        /// The high priority list.
+        high_priority: Vec<K>,
+        /// The records pinned by the external holders, never evicted.
+        pinned: Vec<K>,
+    },
+    /// State of [`lfu::Lfu`].
+    Lfu {
+        /// The window queue, with the estimated frequency of each record.
+        window: Vec<(K, u16)>,
+        /// The probation queue, with the estimated frequency of each record.
+        probation: Vec<(K, u16)>,
+        /// The protected queue, with the estimated frequency of each record.
+        protected: Vec<(K, u16)>,
+    },
+    /// State of [`sieve::Sieve`].
+    Sieve {
+        /// The queue, with the visited bit of each record.
+        queue: Vec<(K, bool)>,
+        /// The record the hand points to.
+        hand: Option<K>,
+    },
+    /// State of [`gdsf::Gdsf`].
+    Gdsf {
+        /// The records in the order of priority, with the priority and the frequency of each record.
+        queue: Vec<(K, f64, u32)>,
+        /// Priority of the last evicted record.
+        inflation: f64,
+    },
+}
+
 pub mod fifo;
+pub mod gdsf;
 pub mod lfu;
 pub mod lru;
 pub mod s3fifo;
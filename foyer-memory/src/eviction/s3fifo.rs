@@ -29,6 +29,8 @@ use foyer_common::{
 use intrusive_collections::{intrusive_adapter, LinkedList, LinkedListAtomicLink};
 use serde::{Deserialize, Serialize};
 
+#[cfg(any(test, feature = "debug"))]
+use super::EvictionDebug;
 use super::{Eviction, Op};
 use crate::{
     error::{Error, Result},
@@ -308,6 +310,81 @@ where
         }
     }
 
+    fn eviction_order(&self) -> Vec<Arc<Record<Self>>> {
+        // Replay `evict` on a copy of the queues and the frequencies.
+        let copy = |queue: &LinkedList<Adapter<K, V, P>>| {
+            let mut res = VecDeque::new();
+            let mut cursor = queue.front();
+            while let Some(record) = cursor.clone_pointer() {
+                let frequency = unsafe { &*record.state().get() }.frequency();
+                res.push_back((record, frequency));
+                cursor.move_next();
+            }
+            res
+        };
+        let mut small = copy(&self.small_queue);
+        let mut main = copy(&self.main_queue);
+        let mut small_weight = self.small_weight;
+
+        let mut res = Vec::with_capacity(small.len() + main.len());
+        loop {
+            let mut evicted = None;
+            // `evict_small`
+            if small_weight > self.small_weight_capacity {
+                while let Some((record, frequency)) = small.pop_front() {
+                    small_weight -= record.weight();
+                    if frequency >= self.small_to_main_freq_threshold {
+                        main.push_back((record, frequency));
+                    } else {
+                        evicted = Some(record);
+                        break;
+                    }
+                }
+            }
+            // `evict_main`
+            if evicted.is_none() {
+                while let Some((record, frequency)) = main.pop_front() {
+                    if frequency > 0 {
+                        main.push_back((record, frequency - 1));
+                    } else {
+                        evicted = Some(record);
+                        break;
+                    }
+                }
+            }
+            // `evict_small_force`
+            if evicted.is_none() {
+                evicted = small.pop_front().map(|(record, _)| {
+                    small_weight -= record.weight();
+                    record
+                });
+            }
+            match evicted {
+                Some(record) => res.push(record),
+                None => break,
+            }
+        }
+        res
+    }
+
+    #[cfg(any(test, feature = "debug"))]
+    fn debug(&self) -> EvictionDebug<K>
+    where
+        K: Clone,
+    {
+        let dump = |queue: &LinkedList<Adapter<K, V, P>>| {
+            queue
+                .iter()
+                .map(|record| (record.key().clone(), unsafe { &*record.state().get() }.frequency()))
+                .collect()
+        };
+        EvictionDebug::S3Fifo {
+            small: dump(&self.small_queue),
+            main: dump(&self.main_queue),
+            ghost: self.ghost_queue.queue.len(),
+        }
+    }
+
     fn acquire() -> Op<Self> {
         Op::immutable(|_: &Self, record| {
             let state = unsafe { &mut *record.state().get() };
@@ -380,7 +457,9 @@ mod tests {
 
     use super::*;
     use crate::{
-        eviction::test_utils::{assert_ptr_eq, assert_ptr_vec_vec_eq, Dump, OpExt, TestProperties},
+        eviction::test_utils::{
+            assert_eviction_order, assert_ptr_eq, assert_ptr_vec_vec_eq, Dump, OpExt, TestProperties,
+        },
         record::Data,
     };
 
@@ -431,7 +510,7 @@ mod tests {
             .map(|i| {
                 Arc::new(Record::new(Data {
                     key: i,
-                    value: i,
+                    value: Arc::new(i),
                     properties: TestProperties::default(),
                     hash: i,
                     weight: 1,
@@ -484,4 +563,39 @@ mod tests {
         s3fifo.clear();
         assert_ptr_vec_vec_eq(s3fifo.dump(), vec![vec![], vec![]]);
     }
+
+    #[test]
+    fn test_s3fifo_eviction_order() {
+        let rs = (0..16)
+            .map(|i| {
+                Arc::new(Record::new(Data {
+                    key: i,
+                    value: Arc::new(i),
+                    properties: TestProperties::default(),
+                    hash: i,
+                    weight: 1,
+                }))
+            })
+            .collect_vec();
+        let r = |i: usize| rs[i].clone();
+
+        // capacity: 8, small: 2, ghost: 80
+        let config = S3FifoConfig {
+            small_queue_capacity_ratio: 0.25,
+            ghost_queue_capacity_ratio: 10.0,
+            small_to_main_freq_threshold: 2,
+        };
+        let mut s3fifo = TestS3Fifo::new(8, &config);
+
+        (0..8).for_each(|i| s3fifo.push(r(i)));
+        (0..8).for_each(|i| (0..i % 4).for_each(|_| s3fifo.acquire_immutable(&rs[i])));
+
+        // Move some records to the main queue.
+        s3fifo.pop().unwrap();
+        s3fifo.pop().unwrap();
+        (8..12).for_each(|i| s3fifo.push(r(i)));
+        [2, 9, 9, 10].into_iter().for_each(|i| s3fifo.acquire_immutable(&rs[i]));
+
+        assert_eviction_order(&mut s3fifo);
+    }
 }
@@ -27,6 +27,8 @@ use foyer_common::{
 use intrusive_collections::{intrusive_adapter, LinkedList, LinkedListAtomicLink};
 use serde::{Deserialize, Serialize};
 
+#[cfg(any(test, feature = "debug"))]
+use super::EvictionDebug;
 use super::{Eviction, Op};
 use crate::{error::Result, record::Record};
 
@@ -154,6 +156,41 @@ where
         record.set_in_eviction(false);
     }
 
+    fn eviction_order(&self) -> Vec<Arc<Record<Self>>> {
+        let mut records = vec![];
+        let mut cursor = self.queue.front();
+        while let Some(record) = cursor.clone_pointer() {
+            records.push(record);
+            cursor.move_next();
+        }
+
+        // The hand scans the queue cyclically, evicting the unvisited records and clearing the visited ones in the
+        // first round, so the visited records are evicted in the same cyclic order after all the unvisited ones.
+        if let Some(hand) = self.hand.as_ref() {
+            if let Some(index) = records.iter().position(|record| Arc::ptr_eq(record, hand)) {
+                records.rotate_left(index);
+            }
+        }
+        let (unvisited, visited): (Vec<_>, Vec<_>) = records
+            .into_iter()
+            .partition(|record| !unsafe { &*record.state().get() }.is_visited());
+        unvisited.into_iter().chain(visited).collect()
+    }
+
+    #[cfg(any(test, feature = "debug"))]
+    fn debug(&self) -> EvictionDebug<K>
+    where
+        K: Clone,
+    {
+        let queue = self
+            .queue
+            .iter()
+            .map(|record| (record.key().clone(), unsafe { &*record.state().get() }.is_visited()))
+            .collect();
+        let hand = self.hand.as_ref().map(|record| record.key().clone());
+        EvictionDebug::Sieve { queue, hand }
+    }
+
     fn acquire() -> Op<Self> {
         Op::immutable(|_: &Self, record| {
             let state = unsafe { &*record.state().get() };
@@ -172,7 +209,7 @@ pub mod tests {
 
     use super::*;
     use crate::{
-        eviction::test_utils::{assert_ptr_eq, assert_ptr_vec_eq, Dump, OpExt, TestProperties},
+        eviction::test_utils::{assert_eviction_order, assert_ptr_eq, assert_ptr_vec_eq, Dump, OpExt, TestProperties},
         record::Data,
     };
 
@@ -204,7 +241,7 @@ pub mod tests {
             .map(|i| {
                 Arc::new(Record::new(Data {
                     key: i,
-                    value: i,
+                    value: Arc::new(i),
                     properties: TestProperties::default(),
                     hash: i,
                     weight: 1,
@@ -252,4 +289,32 @@ pub mod tests {
         sieve.clear();
         assert_ptr_vec_eq(sieve.dump(), vec![]);
     }
+
+    #[test]
+    fn test_sieve_eviction_order() {
+        let rs = (0..8)
+            .map(|i| {
+                Arc::new(Record::new(Data {
+                    key: i,
+                    value: Arc::new(i),
+                    properties: TestProperties::default(),
+                    hash: i,
+                    weight: 1,
+                }))
+            })
+            .collect_vec();
+        let r = |i: usize| rs[i].clone();
+        let mut sieve = TestSieve::new(100, &SieveConfig {});
+        (0..8).for_each(|i| sieve.push(r(i)));
+
+        // Move the hand to 2.
+        sieve.acquire_immutable(&rs[0]);
+        let r1 = sieve.pop().unwrap();
+        assert_ptr_eq(&rs[1], &r1);
+
+        [0, 3, 6].into_iter().for_each(|i| sieve.acquire_immutable(&rs[i]));
+
+        assert_ptr_vec_eq(sieve.eviction_order(), vec![r(2), r(4), r(5), r(7), r(3), r(6), r(0)]);
+        assert_eviction_order(&mut sieve);
+    }
 }
@@ -78,6 +78,15 @@ pub fn assert_ptr_vec_vec_eq<T>(vva: Vec<Vec<Arc<T>>>, vvb: Vec<Vec<Arc<T>>>) {
     assert_eq!(trans(vva), trans(vvb));
 }
 
+/// Assert that [`Eviction::eviction_order`] predicts the records popped one by one, which empties the eviction
+/// container.
+#[cfg_attr(not(test), expect(dead_code))]
+pub fn assert_eviction_order<E: Eviction>(eviction: &mut E) {
+    let order = eviction.eviction_order().iter().map(Arc::as_ptr).collect_vec();
+    let popped = std::iter::from_fn(|| eviction.pop()).collect_vec();
+    assert_eq!(order, popped.iter().map(Arc::as_ptr).collect_vec());
+}
+
 /// Properties for test, support all properties.
 #[derive(Debug, Clone, Default)]
 pub struct TestProperties {
@@ -85,4 +85,8 @@ where
     fn drain(&mut self) -> impl Iterator<Item = Arc<Record<Self::Eviction>>> {
         self.table.drain()
     }
+
+    fn len(&self) -> usize {
+        self.table.len()
+    }
 }
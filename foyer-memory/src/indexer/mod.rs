@@ -29,6 +29,7 @@ pub trait Indexer: Send + Sync + 'static + Default {
     where
         Q: Hash + Equivalent<<Self::Eviction as Eviction>::Key> + ?Sized;
     fn drain(&mut self) -> impl Iterator<Item = Arc<Record<Self::Eviction>>>;
+    fn len(&self) -> usize;
 }
 
 pub mod hash_table;
@@ -77,4 +77,8 @@ where
             r.set_in_indexer(false)
         })
     }
+
+    fn len(&self) -> usize {
+        self.indexer.len()
+    }
 }
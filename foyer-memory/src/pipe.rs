@@ -88,6 +88,28 @@ impl<K, V, P> Piece<K, V, P> {
         }
     }
 
+    /// Create a piece that is not backed by any in-memory cache record.
+    ///
+    /// It is useful to push an entry to the disk cache without inserting it into the in-memory cache.
+    pub fn detached(key: K, value: V, hash: u64, properties: P) -> Self {
+        let raw = Box::into_raw(Box::new((key, value, properties)));
+        let record = raw as *const ();
+        let key = unsafe { &(*raw).0 } as *const _;
+        let value = unsafe { &(*raw).1 } as *const _;
+        let properties = unsafe { &(*raw).2 } as *const _;
+        let drop_fn = |ptr| unsafe {
+            let _ = Box::from_raw(ptr as *mut (K, V, P));
+        };
+        Self {
+            record,
+            key,
+            value,
+            hash,
+            properties,
+            drop_fn,
+        }
+    }
+
     /// Get the key of the record.
     pub fn key(&self) -> &K {
         unsafe { &*self.key }
@@ -103,6 +125,14 @@ impl<K, V, P> Piece<K, V, P> {
         self.hash
     }
 
+    /// Replace the hash of the piece, e.g. with the hash calculated by another hasher.
+    ///
+    /// The hash of the backing in-memory cache record is not changed.
+    pub fn with_hash(mut self, hash: u64) -> Self {
+        self.hash = hash;
+        self
+    }
+
     /// Get the properties of the record.
     pub fn properties(&self) -> &P {
         unsafe { &*self.properties }
@@ -132,6 +162,23 @@ pub trait Pipe: Send + Sync + 'static + Debug {
         &self,
         pieces: Vec<Piece<Self::Key, Self::Value, Self::Properties>>,
     ) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+
+    /// Send the evicted pieces to the disk cache in a asynchronous manner.
+    ///
+    /// This function is called when entries are evicted by [`crate::Cache::insert_async`].
+    /// It is expected to skip the same pieces as [`Pipe::send`] and to obey the io throttle of the disk cache, so the
+    /// inserting caller is slowed down by the throttle.
+    ///
+    /// The default implementation sends the pieces with [`Pipe::send`] without waiting.
+    fn send_async(
+        &self,
+        pieces: Vec<Piece<Self::Key, Self::Value, Self::Properties>>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        for piece in pieces {
+            self.send(piece);
+        }
+        Box::pin(async {})
+    }
 }
 
 /// An no-op pipe that is never enabled.
@@ -185,7 +232,7 @@ mod tests {
     fn test_piece() {
         let r1 = Arc::new(Record::new(Data::<Fifo<Arc<Vec<u8>>, Arc<Vec<u8>>, TestProperties>> {
             key: Arc::new(vec![b'k'; 4096]),
-            value: Arc::new(vec![b'k'; 16384]),
+            value: Arc::new(Arc::new(vec![b'k'; 16384])),
             properties: TestProperties::default(),
             hash: 1,
             weight: 1,
@@ -202,4 +249,20 @@ mod tests {
         drop(r1);
         drop(k1);
     }
+
+    #[test]
+    fn test_detached_piece() {
+        let key = Arc::new(vec![b'k'; 4096]);
+        let value = Arc::new(vec![b'v'; 16384]);
+        let piece = Piece::detached(key.clone(), value.clone(), 1, TestProperties::default());
+
+        assert_eq!(piece.key(), &key);
+        assert_eq!(piece.value(), &value);
+        assert_eq!(piece.hash(), 1);
+        assert_eq!(Arc::strong_count(&key), 2);
+
+        drop(piece);
+        assert_eq!(Arc::strong_count(&key), 1);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
 }
@@ -14,10 +14,14 @@
 
 #[cfg(any(test, feature = "test_utils"))]
 pub use crate::eviction::test_utils::TestProperties;
+#[cfg(any(test, feature = "debug"))]
+pub use crate::eviction::EvictionDebug;
 pub use crate::{
-    cache::{Cache, CacheBuilder, CacheEntry, CacheProperties, EvictionConfig, Fetch},
+    cache::{Cache, CacheBuilder, CacheEntry, CacheEntryMeta, CacheProperties, EvictionConfig, Fetch, WeakCacheEntry},
     error::{Error, Result},
-    eviction::{fifo::FifoConfig, lfu::LfuConfig, lru::LruConfig, s3fifo::S3FifoConfig, Eviction, Op},
+    eviction::{
+        fifo::FifoConfig, gdsf::GdsfConfig, lfu::LfuConfig, lru::LruConfig, s3fifo::S3FifoConfig, Eviction, Op,
+    },
     pipe::{Piece, Pipe},
-    raw::{FetchContext, FetchState, Weighter},
+    raw::{FetchContext, FetchState, ShardStats, Weighter},
 };
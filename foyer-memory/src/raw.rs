@@ -19,7 +19,10 @@ use std::{
     hash::Hash,
     ops::Deref,
     pin::Pin,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
     task::{Context, Poll},
 };
 
@@ -45,6 +48,8 @@ use parking_lot::{Mutex, RwLock};
 use pin_project::pin_project;
 use tokio::{sync::oneshot, task::JoinHandle};
 
+#[cfg(any(test, feature = "debug"))]
+use crate::eviction::EvictionDebug;
 use crate::{
     error::{Error, Result},
     eviction::{Eviction, Op},
@@ -68,12 +73,26 @@ where
     pub capacity: usize,
     pub shards: usize,
     pub eviction_config: E::Config,
+    pub eviction_batch: usize,
     pub hash_builder: S,
     pub weighter: Arc<dyn Weighter<E::Key, E::Value>>,
     pub event_listener: Option<Arc<dyn EventListener<Key = E::Key, Value = E::Value>>>,
     pub metrics: Arc<Metrics>,
 }
 
+/// Statistics of a shard of the in-memory cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShardStats {
+    /// Count of the entries in the shard.
+    pub entries: usize,
+    /// Total weight of the entries in the shard.
+    pub usage: usize,
+    /// Count of the lookups that hit the shard.
+    pub hits: usize,
+    /// Count of the lookups that miss the shard.
+    pub misses: usize,
+}
+
 struct RawCacheShard<E, S, I>
 where
     E: Eviction,
@@ -85,6 +104,10 @@ where
 
     usage: usize,
     capacity: usize,
+    eviction_batch: usize,
+
+    hits: AtomicUsize,
+    misses: AtomicUsize,
 
     #[expect(clippy::type_complexity)]
     waiters: Mutex<HashMap<E::Key, Vec<oneshot::Sender<RawCacheEntry<E, S, I>>>>>,
@@ -100,25 +123,39 @@ where
     I: Indexer<Eviction = E>,
 {
     /// Evict entries to fit the target usage.
-    fn evict(&mut self, target: usize, garbages: &mut Vec<(Event, Arc<Record<E>>)>) {
+    ///
+    /// If any entry needs to be evicted, at least `batch` entries are evicted, even if the usage drops below the
+    /// target.
+    fn evict(&mut self, target: usize, batch: usize, garbages: &mut Vec<(Event, Arc<Record<E>>)>) {
+        let mut count = 0;
         // Evict overflow records.
-        while self.usage > target {
-            let evicted = match self.eviction.pop() {
+        while self.usage > target || (count > 0 && count < batch) {
+            let evicted = match self.pop_evicted() {
                 Some(evicted) => evicted,
                 None => break,
             };
             self.metrics.memory_evict.increase(1);
+            count += 1;
 
-            let e = self.indexer.remove(evicted.hash(), evicted.key()).unwrap();
-            assert_eq!(Arc::as_ptr(&evicted), Arc::as_ptr(&e));
+            garbages.push((Event::Evict, evicted));
+        }
+    }
 
-            strict_assert!(!evicted.as_ref().is_in_indexer());
-            strict_assert!(!evicted.as_ref().is_in_eviction());
+    /// Pop the next record to evict from the eviction container and remove it from the indexer.
+    ///
+    /// The caller is in charge of the metrics of the popped record, so drained entries are not counted as evictions.
+    fn pop_evicted(&mut self) -> Option<Arc<Record<E>>> {
+        let evicted = self.eviction.pop()?;
 
-            self.usage -= evicted.weight();
+        let e = self.indexer.remove(evicted.hash(), evicted.key()).unwrap();
+        assert_eq!(Arc::as_ptr(&evicted), Arc::as_ptr(&e));
 
-            garbages.push((Event::Evict, evicted));
-        }
+        strict_assert!(!evicted.as_ref().is_in_indexer());
+        strict_assert!(!evicted.as_ref().is_in_eviction());
+
+        self.usage -= evicted.weight();
+
+        Some(evicted)
     }
 
     fn emplace(
@@ -135,7 +172,7 @@ where
         let record = Arc::new(Record::new(data));
 
         // Evict overflow records.
-        self.evict(self.capacity.saturating_sub(weight), garbages);
+        self.evict(self.capacity.saturating_sub(weight), self.eviction_batch, garbages);
 
         // Insert new record
         if let Some(old) = self.indexer.insert(record.clone()) {
@@ -241,10 +278,12 @@ where
         let record = match self.indexer.get(hash, key).cloned() {
             Some(record) => {
                 self.metrics.memory_hit.increase(1);
+                self.hits.fetch_add(1, Ordering::Relaxed);
                 record
             }
             None => {
                 self.metrics.memory_miss.increase(1);
+                self.misses.fetch_add(1, Ordering::Relaxed);
                 return None;
             }
         };
@@ -276,6 +315,33 @@ where
         self.metrics.memory_remove.increase(count);
     }
 
+    /// Take the data of all records that are not referenced outside of the shard, for moving them to another cache.
+    ///
+    /// Referenced records are kept in the shard.
+    fn take(&mut self) -> Vec<Data<E>> {
+        let records = self.indexer.drain().collect_vec();
+        self.eviction.clear();
+        self.metrics.memory_usage.decrease(self.usage as _);
+        self.usage = 0;
+
+        let mut taken = Vec::with_capacity(records.len());
+        for record in records {
+            match Arc::try_unwrap(record) {
+                Ok(record) => taken.push(record.into_data()),
+                Err(record) => {
+                    if !record.is_ephemeral() {
+                        self.eviction.push(record.clone());
+                    }
+                    self.usage += record.weight();
+                    self.metrics.memory_usage.increase(record.weight() as _);
+                    self.indexer.insert(record);
+                }
+            }
+        }
+        self.metrics.memory_remove.increase(taken.len() as _);
+        taken
+    }
+
     #[cfg_attr(
         feature = "tracing",
         fastrace::trace(name = "foyer::memory::raw::shard::acquire_immutable")
@@ -430,6 +496,11 @@ where
             }
         }
     }
+
+    /// Route the hash to the index of the shard.
+    fn shard(&self, hash: u64) -> usize {
+        hash as usize % self.shards.len()
+    }
 }
 
 pub struct RawCache<E, S, I = HashTableIndexer<E>>
@@ -480,6 +551,9 @@ where
                 indexer: Sentry::default(),
                 usage: 0,
                 capacity: shard_capacity,
+                eviction_batch: config.eviction_batch.max(1),
+                hits: AtomicUsize::new(0),
+                misses: AtomicUsize::new(0),
                 waiters: Mutex::default(),
                 metrics: config.metrics.clone(),
                 _event_listener: config.event_listener.clone(),
@@ -516,7 +590,7 @@ where
                     let res = inner.shards[i].write().with(|mut shard| {
                         shard.eviction.update(shard_capacity, None).inspect(|_| {
                             shard.capacity = shard_capacity;
-                            shard.evict(shard_capacity, &mut garbages)
+                            shard.evict(shard_capacity, 1, &mut garbages)
                         })
                     });
                     // Deallocate data out of the lock critical section.
@@ -555,6 +629,11 @@ where
         self.insert_with_properties(key, value, Default::default())
     }
 
+    #[cfg_attr(feature = "tracing", fastrace::trace(name = "foyer::memory::raw::insert_async"))]
+    pub async fn insert_async(&self, key: E::Key, value: E::Value) -> RawCacheEntry<E, S, I> {
+        self.insert_with_properties_async(key, value, Default::default()).await
+    }
+
     #[cfg_attr(
         feature = "tracing",
         fastrace::trace(name = "foyer::memory::raw::insert_with_properties")
@@ -565,6 +644,116 @@ where
         value: E::Value,
         properties: E::Properties,
     ) -> RawCacheEntry<E, S, I> {
+        let (record, garbages) = self.emplace(key, value, properties);
+
+        // Deallocate data out of the lock critical section.
+        self.dispose(garbages);
+
+        RawCacheEntry {
+            record,
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Insert cached entry, and return the value of the replaced entry with the same key if there is one.
+    ///
+    /// The replaced entry is taken out of the cache in the same lock critical section as the insertion, so no other
+    /// insertion can replace it in between, which a `get` followed by an `insert` cannot guarantee. The value is
+    /// cloned only if the replaced entry or its value is still referenced outside of the cache.
+    #[cfg_attr(feature = "tracing", fastrace::trace(name = "foyer::memory::raw::insert_replace"))]
+    pub fn insert_replace(&self, key: E::Key, value: E::Value) -> (RawCacheEntry<E, S, I>, Option<E::Value>)
+    where
+        E::Value: Clone,
+    {
+        let (record, mut garbages) = self.emplace(key, value, Default::default());
+        let replaced = garbages
+            .iter()
+            .position(|(event, _)| *event == Event::Replace)
+            .map(|index| garbages.remove(index).1);
+
+        // Deallocate data out of the lock critical section.
+        self.dispose(garbages);
+        let replaced = replaced.map(|record| {
+            if let Some(listener) = self.inner.event_listener.as_ref() {
+                listener.on_leave(Event::Replace, record.key(), record.value());
+            }
+            let value = match Arc::try_unwrap(record) {
+                Ok(record) => record.into_data().value,
+                Err(record) => record.value_arc(),
+            };
+            Arc::unwrap_or_clone(value)
+        });
+
+        (
+            RawCacheEntry {
+                record,
+                inner: self.inner.clone(),
+            },
+            replaced,
+        )
+    }
+
+    /// Notify the listener of the records that left the cache, and send the evicted ones to the pipe.
+    fn dispose(&self, garbages: Vec<(Event, Arc<Record<E>>)>) {
+        let pipe = self.inner.pipe.load();
+        self.dispose_with(garbages, pipe.is_enabled(), |piece| pipe.send(piece));
+    }
+
+    /// Notify the listener of the records that left the cache, and hand the evicted ones to `offload` if `piped`.
+    fn dispose_with<F>(&self, garbages: Vec<(Event, Arc<Record<E>>)>, piped: bool, mut offload: F)
+    where
+        F: FnMut(Piece<E::Key, E::Value, E::Properties>),
+    {
+        if self.inner.event_listener.is_some() || piped {
+            for (event, record) in garbages {
+                if let Some(listener) = self.inner.event_listener.as_ref() {
+                    listener.on_leave(event, record.key(), record.value())
+                }
+                if piped && event == Event::Evict {
+                    offload(Piece::new(record));
+                }
+            }
+        }
+    }
+
+    /// Insert cached entry with properties, and offload the evicted entries with backpressure.
+    ///
+    /// Unlike [`RawCache::insert_with_properties`], which hands evicted entries to [`Pipe::send`] and returns
+    /// immediately, the returned future hands them to [`Pipe::send_async`] and only completes after the pipe accepted
+    /// them.
+    #[cfg_attr(
+        feature = "tracing",
+        fastrace::trace(name = "foyer::memory::raw::insert_with_properties_async")
+    )]
+    pub async fn insert_with_properties_async(
+        &self,
+        key: E::Key,
+        value: E::Value,
+        properties: E::Properties,
+    ) -> RawCacheEntry<E, S, I> {
+        let (record, garbages) = self.emplace(key, value, properties);
+
+        // Deallocate data out of the lock critical section.
+        let pipe = self.inner.pipe.load();
+        let mut pieces = vec![];
+        self.dispose_with(garbages, pipe.is_enabled(), |piece| pieces.push(piece));
+        if !pieces.is_empty() {
+            pipe.send_async(pieces).await;
+        }
+
+        RawCacheEntry {
+            record,
+            inner: self.inner.clone(),
+        }
+    }
+
+    #[expect(clippy::type_complexity)]
+    fn emplace(
+        &self,
+        key: E::Key,
+        value: E::Value,
+        properties: E::Properties,
+    ) -> (Arc<Record<E>>, Vec<(Event, Arc<Record<E>>)>) {
         let hash = self.inner.hash_builder.hash_one(&key);
         let weight = (self.inner.weighter)(&key, &value);
 
@@ -575,7 +764,7 @@ where
             shard.emplace(
                 Data {
                     key,
-                    value,
+                    value: Arc::new(value),
                     properties,
                     hash,
                     weight,
@@ -593,24 +782,58 @@ where
             });
         }
 
-        // Deallocate data out of the lock critical section.
-        let pipe = self.inner.pipe.load();
-        let piped = pipe.is_enabled();
-        if self.inner.event_listener.is_some() || piped {
-            for (event, record) in garbages {
-                if let Some(listener) = self.inner.event_listener.as_ref() {
-                    listener.on_leave(event, record.key(), record.value())
+        (record, garbages)
+    }
+
+    /// Evict the `n` coldest entries per the eviction algorithm and return their keys and values.
+    ///
+    /// The entries are drained from the shards round-robin, one entry from each shard that still has entries to evict
+    /// per round, so the chosen entries are the first `n` of [`RawCache::eviction_order`] if there are no concurrent
+    /// operations. Shards are not weighted by their usage.
+    ///
+    /// The drained entries leave the cache with [`Event::Drained`], they are neither sent to the pipe nor counted as
+    /// evictions. The key and value are cloned only if the entry or its value is still referenced outside of the cache.
+    #[cfg_attr(feature = "tracing", fastrace::trace(name = "foyer::memory::raw::drain_cold"))]
+    pub fn drain_cold(&self, n: usize) -> Vec<(E::Key, E::Value)>
+    where
+        E::Key: Clone,
+        E::Value: Clone,
+    {
+        let mut records = Vec::with_capacity(n);
+        let mut shards = (0..self.inner.shards.len()).collect_vec();
+        while records.len() < n && !shards.is_empty() {
+            shards.retain(|&index| {
+                if records.len() >= n {
+                    return true;
                 }
-                if piped && event == Event::Evict {
-                    pipe.send(Piece::new(record));
+                let mut shard = self.inner.shards[index].write();
+                match shard.pop_evicted() {
+                    Some(record) => {
+                        shard.metrics.memory_usage.decrease(record.weight() as _);
+                        records.push(record);
+                        true
+                    }
+                    None => false,
                 }
-            }
+            });
         }
 
-        RawCacheEntry {
-            record,
-            inner: self.inner.clone(),
-        }
+        // Deallocate data out of the lock critical section.
+        records
+            .into_iter()
+            .map(|record| {
+                if let Some(listener) = self.inner.event_listener.as_ref() {
+                    listener.on_leave(Event::Drained, record.key(), record.value());
+                }
+                match Arc::try_unwrap(record) {
+                    Ok(record) => {
+                        let data = record.into_data();
+                        (data.key, Arc::unwrap_or_clone(data.value))
+                    }
+                    Err(record) => (record.key().clone(), record.value().clone()),
+                }
+            })
+            .collect()
     }
 
     /// Evict all entries in the cache and offload them into the disk cache via the pipe if needed.
@@ -618,7 +841,7 @@ where
     pub fn evict_all(&self) {
         let mut garbages = vec![];
         for shard in self.inner.shards.iter() {
-            shard.write().evict(0, &mut garbages);
+            shard.write().evict(0, 1, &mut garbages);
         }
 
         // Deallocate data out of the lock critical section.
@@ -644,7 +867,7 @@ where
     pub async fn flush(&self) {
         let mut garbages = vec![];
         for shard in self.inner.shards.iter() {
-            shard.write().evict(0, &mut garbages);
+            shard.write().evict(0, 1, &mut garbages);
         }
 
         // Deallocate data out of the lock critical section.
@@ -744,6 +967,89 @@ where
         self.inner.clear();
     }
 
+    /// Replace all entries of the cache with the entries of `other`, shard by shard.
+    ///
+    /// `other` must be built with the same shard count and hash builder. Each shard is swapped atomically, so no entry
+    /// is lost or duplicated, but lookups of different shards may observe the old and the new entries during the swap.
+    ///
+    /// The displaced entries leave the cache with [`Event::Clear`]. Entries of `other` that are still referenced are
+    /// kept in `other`.
+    #[cfg_attr(feature = "tracing", fastrace::trace(name = "foyer::memory::raw::swap_contents"))]
+    pub fn swap_contents(&self, other: &Self) -> Result<()> {
+        if Arc::ptr_eq(&self.inner, &other.inner) {
+            return Ok(());
+        }
+        if self.inner.shards.len() != other.inner.shards.len() {
+            return Err(Error::ConfigError(format!(
+                "cannot swap contents of caches with different shards: {} and {}",
+                self.inner.shards.len(),
+                other.inner.shards.len()
+            )));
+        }
+
+        let mut displaced = vec![];
+        let mut garbages = vec![];
+        let mut records = vec![];
+
+        for (shard, other_shard) in self.inner.shards.iter().zip_eq(other.inner.shards.iter()) {
+            // Lock the shards in address order to avoid deadlocks with a concurrent swap in the opposite direction.
+            let (mut shard, mut other_shard) = if std::ptr::from_ref(shard) < std::ptr::from_ref(other_shard) {
+                let shard = shard.write();
+                (shard, other_shard.write())
+            } else {
+                let other_shard = other_shard.write();
+                (shard.write(), other_shard)
+            };
+
+            let taken = other_shard.take();
+            drop(other_shard);
+
+            shard.clear(&mut displaced);
+            self.inner.metrics.memory_usage.decrease(shard.usage as _);
+            shard.usage = 0;
+
+            for data in taken {
+                let mut waiters = vec![];
+                let record = shard.emplace(data, &mut garbages, &mut waiters);
+                records.push((record, waiters));
+            }
+        }
+
+        // Notify waiters and deallocate data out of the lock critical section.
+        for (record, waiters) in records {
+            for waiter in waiters {
+                let _ = waiter.send(RawCacheEntry {
+                    record: record.clone(),
+                    inner: self.inner.clone(),
+                });
+            }
+            drop(RawCacheEntry {
+                record,
+                inner: self.inner.clone(),
+            });
+        }
+
+        let pipe = self.inner.pipe.load();
+        let piped = pipe.is_enabled();
+        if let Some(listener) = self.inner.event_listener.as_ref() {
+            for record in displaced.iter() {
+                listener.on_leave(Event::Clear, record.key(), record.value());
+            }
+        }
+        if self.inner.event_listener.is_some() || piped {
+            for (event, record) in garbages {
+                if let Some(listener) = self.inner.event_listener.as_ref() {
+                    listener.on_leave(event, record.key(), record.value())
+                }
+                if piped && event == Event::Evict {
+                    pipe.send(Piece::new(record));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn capacity(&self) -> usize {
         self.inner.capacity
     }
@@ -768,8 +1074,72 @@ where
         self.inner.pipe.store(Arc::new(pipe));
     }
 
+    /// Get the entries in the order that the eviction algorithm would evict them, starting from the next entry to evict.
+    ///
+    /// Each shard is evicted independently, so the per-shard orders are interleaved round-robin: the first entry of
+    /// each shard in shard index order, then the second of each shard, and so on. Each shard is snapshotted under its
+    /// own lock, and getting the entries doesn't count as accesses, so the eviction order is not affected.
+    pub fn eviction_order(&self) -> Vec<RawCacheEntry<E, S, I>> {
+        let mut shards = self
+            .inner
+            .shards
+            .iter()
+            .map(|shard| {
+                let shard = shard.read();
+                shard
+                    .eviction
+                    .eviction_order()
+                    .into_iter()
+                    .map(|record| {
+                        record.inc_refs(1);
+                        RawCacheEntry {
+                            inner: self.inner.clone(),
+                            record,
+                        }
+                    })
+                    .collect_vec()
+                    .into_iter()
+            })
+            .collect_vec();
+
+        let mut entries = vec![];
+        loop {
+            let len = entries.len();
+            entries.extend(shards.iter_mut().filter_map(|shard| shard.next()));
+            if entries.len() == len {
+                break;
+            }
+        }
+        entries
+    }
+
+    /// Snapshot the statistics of each shard under its own lock.
+    pub fn shard_stats(&self) -> Vec<ShardStats> {
+        self.inner
+            .shards
+            .iter()
+            .map(|shard| {
+                let shard = shard.read();
+                ShardStats {
+                    entries: shard.indexer.len(),
+                    usage: shard.usage,
+                    hits: shard.hits.load(Ordering::Relaxed),
+                    misses: shard.misses.load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+
+    /// Get the index of the shard that the key is routed to.
+    pub fn shard_of<Q>(&self, key: &Q) -> usize
+    where
+        Q: Hash + ?Sized,
+    {
+        self.shard(self.inner.hash_builder.hash_one(key))
+    }
+
     fn shard(&self, hash: u64) -> usize {
-        hash as usize % self.inner.shards.len()
+        self.inner.shard(hash)
     }
 }
 
@@ -802,7 +1172,7 @@ where
 {
     fn drop(&mut self) {
         let hash = self.record.hash();
-        let shard = &self.inner.shards[hash as usize % self.inner.shards.len()];
+        let shard = &self.inner.shards[self.inner.shard(hash)];
 
         if self.record.dec_refs(1) == 0 {
             match E::release() {
@@ -896,6 +1266,10 @@ where
         self.record.value()
     }
 
+    pub fn value_arc(&self) -> Arc<E::Value> {
+        self.record.value_arc()
+    }
+
     pub fn properties(&self) -> &E::Properties {
         self.record.properties()
     }
@@ -915,6 +1289,92 @@ where
     pub fn piece(&self) -> Piece<E::Key, E::Value, E::Properties> {
         Piece::new(self.record.clone())
     }
+
+    pub fn downgrade(&self) -> RawWeakCacheEntry<E, S, I> {
+        RawWeakCacheEntry {
+            inner: Arc::downgrade(&self.inner),
+            record: Arc::downgrade(&self.record),
+        }
+    }
+}
+
+/// A weak handle of a [`RawCacheEntry`] that doesn't keep the entry from being evicted.
+pub struct RawWeakCacheEntry<E, S, I = HashTableIndexer<E>>
+where
+    E: Eviction,
+    S: HashBuilder,
+    I: Indexer<Eviction = E>,
+{
+    inner: Weak<RawCacheInner<E, S, I>>,
+    record: Weak<Record<E>>,
+}
+
+impl<E, S, I> Debug for RawWeakCacheEntry<E, S, I>
+where
+    E: Eviction,
+    S: HashBuilder,
+    I: Indexer<Eviction = E>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RawWeakCacheEntry").finish_non_exhaustive()
+    }
+}
+
+impl<E, S, I> Clone for RawWeakCacheEntry<E, S, I>
+where
+    E: Eviction,
+    S: HashBuilder,
+    I: Indexer<Eviction = E>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            record: self.record.clone(),
+        }
+    }
+}
+
+unsafe impl<E, S, I> Send for RawWeakCacheEntry<E, S, I>
+where
+    E: Eviction,
+    S: HashBuilder,
+    I: Indexer<Eviction = E>,
+{
+}
+
+unsafe impl<E, S, I> Sync for RawWeakCacheEntry<E, S, I>
+where
+    E: Eviction,
+    S: HashBuilder,
+    I: Indexer<Eviction = E>,
+{
+}
+
+impl<E, S, I> RawWeakCacheEntry<E, S, I>
+where
+    E: Eviction,
+    S: HashBuilder,
+    I: Indexer<Eviction = E>,
+{
+    /// Get the entry if it is still in the cache.
+    ///
+    /// Returns `None` if the entry is evicted, removed or replaced, even if it is still referenced by other holders.
+    /// Upgrading doesn't count as an access of the entry for the eviction algorithm.
+    pub fn upgrade(&self) -> Option<RawCacheEntry<E, S, I>> {
+        let inner = self.inner.upgrade()?;
+        let record = self.record.upgrade()?;
+
+        // Records only leave the indexer with the shard write lock held, check and increase the reference count within
+        // the read lock section so the record cannot be evicted in between.
+        let shard = inner.shards[inner.shard(record.hash())].read();
+        if !record.is_in_indexer() {
+            return None;
+        }
+        record.inc_refs(1);
+        drop(shard);
+
+        Some(RawCacheEntry { inner, record })
+    }
 }
 
 /// The state of `fetch`.
@@ -1010,6 +1470,17 @@ where
     I: Indexer<Eviction = E>,
     E::Key: Clone,
 {
+    /// Take a snapshot of the internal state of the eviction algorithm of each shard, in shard index order, see
+    /// [`Eviction::debug`].
+    #[cfg(any(test, feature = "debug"))]
+    pub fn eviction_debug(&self) -> Vec<EvictionDebug<E::Key>> {
+        self.inner
+            .shards
+            .iter()
+            .map(|shard| shard.read().eviction.debug())
+            .collect()
+    }
+
     #[cfg_attr(feature = "tracing", fastrace::trace(name = "foyer::memory::raw::fetch"))]
     pub fn fetch<F, FU, ER>(&self, key: E::Key, fetch: F) -> RawFetch<E, ER, S, I>
     where
@@ -1135,6 +1606,7 @@ mod tests {
     use crate::{
         eviction::{
             fifo::{Fifo, FifoConfig},
+            gdsf::{Gdsf, GdsfConfig},
             lfu::{Lfu, LfuConfig},
             lru::{Lru, LruConfig},
             s3fifo::{S3Fifo, S3FifoConfig},
@@ -1153,6 +1625,7 @@ mod tests {
         is_send_sync_static::<RawCache<Lfu<(), (), TestProperties>, ModHasher>>();
         is_send_sync_static::<RawCache<Lru<(), (), TestProperties>, ModHasher>>();
         is_send_sync_static::<RawCache<Sieve<(), (), TestProperties>, ModHasher>>();
+        is_send_sync_static::<RawCache<Gdsf<(), (), TestProperties>, ModHasher>>();
     }
 
     #[expect(clippy::type_complexity)]
@@ -1162,6 +1635,7 @@ mod tests {
             capacity: 256,
             shards: 4,
             eviction_config: FifoConfig::default(),
+            eviction_batch: 1,
             hash_builder: Default::default(),
             weighter: Arc::new(|_, _| 1),
             event_listener: None,
@@ -1176,6 +1650,7 @@ mod tests {
             capacity: 256,
             shards: 4,
             eviction_config: S3FifoConfig::default(),
+            eviction_batch: 1,
             hash_builder: Default::default(),
             weighter: Arc::new(|_, _| 1),
             event_listener: None,
@@ -1190,6 +1665,7 @@ mod tests {
             capacity: 256,
             shards: 4,
             eviction_config: LruConfig::default(),
+            eviction_batch: 1,
             hash_builder: Default::default(),
             weighter: Arc::new(|_, _| 1),
             event_listener: None,
@@ -1204,6 +1680,7 @@ mod tests {
             capacity: 256,
             shards: 4,
             eviction_config: LfuConfig::default(),
+            eviction_batch: 1,
             hash_builder: Default::default(),
             weighter: Arc::new(|_, _| 1),
             event_listener: None,
@@ -1218,6 +1695,7 @@ mod tests {
             capacity: 256,
             shards: 4,
             eviction_config: SieveConfig {},
+            eviction_batch: 1,
             hash_builder: Default::default(),
             weighter: Arc::new(|_, _| 1),
             event_listener: None,
@@ -1265,12 +1743,258 @@ mod tests {
         assert_eq!(pieces, expected);
     }
 
+    #[test]
+    fn test_swap_contents() {
+        let cache = fifo_cache_for_test();
+        let other = fifo_cache_for_test();
+        for i in 0..16 {
+            cache.insert(i, i);
+            other.insert(i + 100, i + 100);
+        }
+        let held = other.get(&100).unwrap();
+
+        cache.swap_contents(&other).unwrap();
+
+        assert_eq!(cache.usage(), 15);
+        assert_eq!(other.usage(), 1);
+        for i in 0..16 {
+            assert!(!cache.contains(&i));
+        }
+        for i in 101..116 {
+            assert_eq!(cache.get(&i).unwrap().value(), &i);
+            assert!(!other.contains(&i));
+        }
+        assert!(!cache.contains(&100));
+        assert!(other.contains(&100));
+        drop(held);
+
+        let sharded = RawCache::new(RawCacheConfig {
+            capacity: 256,
+            shards: 2,
+            eviction_config: FifoConfig::default(),
+            eviction_batch: 1,
+            hash_builder: ModHasher::default(),
+            weighter: Arc::new(|_, _| 1),
+            event_listener: None,
+            metrics: Arc::new(Metrics::noop()),
+        });
+        assert!(cache.swap_contents(&sharded).is_err());
+    }
+
+    #[test]
+    fn test_eviction_order() {
+        let fifo = fifo_cache_for_test();
+        for i in [0, 4, 1, 5, 2, 3, 6] {
+            fifo.insert(i, i);
+        }
+        let keys = fifo.eviction_order().iter().map(|e| *e.key()).collect_vec();
+        assert_eq!(keys, vec![0, 1, 2, 3, 4, 5, 6]);
+
+        // Getting the eviction order doesn't mark the entries as visited.
+        let sieve = sieve_cache_for_test();
+        for i in 0..8 {
+            sieve.insert(i, i);
+        }
+        sieve.get(&0).unwrap();
+        let keys = sieve.eviction_order().iter().map(|e| *e.key()).collect_vec();
+        assert_eq!(keys, vec![4, 1, 2, 3, 0, 5, 6, 7]);
+        let keys = sieve.eviction_order().iter().map(|e| *e.key()).collect_vec();
+        assert_eq!(keys, vec![4, 1, 2, 3, 0, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_drain_cold() {
+        #[derive(Debug, Default)]
+        struct DrainedListener(Mutex<Vec<u64>>);
+
+        impl EventListener for DrainedListener {
+            type Key = u64;
+            type Value = u64;
+
+            fn on_leave(&self, reason: Event, key: &u64, _: &u64) {
+                assert_eq!(reason, Event::Drained);
+                self.0.lock().push(*key);
+            }
+        }
+
+        let listener = Arc::new(DrainedListener::default());
+        let fifo: RawCache<Fifo<u64, u64, TestProperties>, ModHasher> = RawCache::new(RawCacheConfig {
+            capacity: 256,
+            shards: 4,
+            eviction_config: FifoConfig::default(),
+            eviction_batch: 1,
+            hash_builder: Default::default(),
+            weighter: Arc::new(|_, _| 1),
+            event_listener: Some(listener.clone()),
+            metrics: Arc::new(Metrics::noop()),
+        });
+        for i in [0, 4, 8, 1, 5, 2, 3] {
+            fifo.insert(i, i * 10);
+        }
+        let held = fifo.get(&1).unwrap();
+
+        // Round-robin across shards, skipping the drained shards.
+        let drained = fifo.drain_cold(6);
+        assert_eq!(drained, vec![(0, 0), (1, 10), (2, 20), (3, 30), (4, 40), (5, 50)]);
+        assert_eq!(*listener.0.lock(), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(fifo.usage(), 1);
+        assert!(fifo.contains(&8));
+        for i in [0, 1, 2, 3, 4, 5] {
+            assert!(!fifo.contains(&i));
+        }
+        assert_eq!(held.value(), &10);
+        drop(held);
+
+        assert_eq!(fifo.drain_cold(4), vec![(8, 80)]);
+        assert!(fifo.drain_cold(4).is_empty());
+        assert_eq!(fifo.usage(), 0);
+    }
+
+    #[test]
+    fn test_insert_replace() {
+        #[derive(Debug, Default)]
+        struct ReplaceListener(Mutex<Vec<(u64, u64)>>);
+
+        impl EventListener for ReplaceListener {
+            type Key = u64;
+            type Value = u64;
+
+            fn on_leave(&self, reason: Event, key: &u64, value: &u64) {
+                if reason == Event::Replace {
+                    self.0.lock().push((*key, *value));
+                }
+            }
+        }
+
+        let listener = Arc::new(ReplaceListener::default());
+        let fifo: RawCache<Fifo<u64, u64, TestProperties>, ModHasher> = RawCache::new(RawCacheConfig {
+            capacity: 256,
+            shards: 4,
+            eviction_config: FifoConfig::default(),
+            eviction_batch: 1,
+            hash_builder: Default::default(),
+            weighter: Arc::new(|_, _| 1),
+            event_listener: Some(listener.clone()),
+            metrics: Arc::new(Metrics::noop()),
+        });
+
+        let (entry, old) = fifo.insert_replace(1, 10);
+        assert_eq!(entry.value(), &10);
+        assert_eq!(old, None);
+        drop(entry);
+
+        let (entry, old) = fifo.insert_replace(1, 11);
+        assert_eq!(entry.value(), &11);
+        assert_eq!(old, Some(10));
+
+        // The replaced value is returned even if the replaced entry is still referenced.
+        let (_, old) = fifo.insert_replace(1, 12);
+        assert_eq!(old, Some(11));
+        assert_eq!(entry.value(), &11);
+        drop(entry);
+
+        assert_eq!(fifo.get(&1).unwrap().value(), &12);
+        assert_eq!(fifo.usage(), 1);
+        assert_eq!(*listener.0.lock(), vec![(1, 10), (1, 11)]);
+    }
+
+    #[tokio::test]
+    async fn test_insert_async_offloads_evicted() {
+        let pipe = Box::new(PiecePipe::default());
+
+        let fifo = fifo_cache_for_test();
+        fifo.set_pipe(pipe.clone());
+        for i in 0..fifo.capacity() as u64 * 2 {
+            fifo.insert_async(i, i).await;
+        }
+        assert_eq!(fifo.usage(), fifo.capacity());
+
+        let mut pieces = pipe.pieces().iter().map(|p| *p.key()).collect_vec();
+        pieces.sort();
+        let remains = (0..fifo.capacity() as u64 * 2)
+            .filter(|i| fifo.get(i).is_none())
+            .collect_vec();
+        assert_eq!(pieces.len(), fifo.capacity());
+        assert_eq!(pieces, remains);
+    }
+
+    #[test]
+    fn test_eviction_batch() {
+        let cache: RawCache<Fifo<u64, u64, TestProperties>, ModHasher> = RawCache::new(RawCacheConfig {
+            capacity: 64,
+            shards: 1,
+            eviction_config: FifoConfig::default(),
+            eviction_batch: 8,
+            hash_builder: Default::default(),
+            weighter: Arc::new(|_, _| 1),
+            event_listener: None,
+            metrics: Arc::new(Metrics::noop()),
+        });
+
+        for i in 0..64 {
+            cache.insert(i, i);
+        }
+        assert_eq!(cache.usage(), 64);
+
+        // Evict a batch of entries on the first overflow.
+        cache.insert(64, 64);
+        assert_eq!(cache.usage(), 64 - 8 + 1);
+        assert!((0..8).all(|i| cache.get(&i).is_none()));
+        assert!((8..65).all(|i| cache.get(&i).is_some()));
+
+        // Refill without eviction.
+        for i in 65..72 {
+            cache.insert(i, i);
+        }
+        assert_eq!(cache.usage(), 64);
+        assert!(cache.get(&8).is_some());
+    }
+
+    #[test]
+    fn test_shard_stats() {
+        let fifo = fifo_cache_for_test();
+        for key in 0..8 {
+            fifo.insert(key, key);
+        }
+        for key in 0..12 {
+            fifo.get(&key);
+        }
+
+        let stats = fifo.shard_stats();
+        assert_eq!(stats.len(), fifo.shards());
+        for stats in stats {
+            assert_eq!(
+                stats,
+                ShardStats {
+                    entries: 2,
+                    usage: 2,
+                    hits: 2,
+                    misses: 1,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_shard_of() {
+        let fifo = fifo_cache_for_test();
+        for key in [0, 1, 2, 3, 42, 4097, u64::MAX] {
+            let shard = fifo.shard_of(&key);
+            assert!(shard < fifo.shards());
+
+            fifo.insert(key, key);
+            assert_eq!(fifo.inner.shards[shard].read().usage, 1);
+            fifo.remove(&key);
+        }
+    }
+
     #[test]
     fn test_insert_size_over_capacity() {
         let cache: RawCache<Fifo<Vec<u8>, Vec<u8>, TestProperties>, ModHasher> = RawCache::new(RawCacheConfig {
             capacity: 4 * 1024, // 4KB
             shards: 1,
             eviction_config: FifoConfig::default(),
+            eviction_batch: 1,
             hash_builder: Default::default(),
             weighter: Arc::new(|k, v| k.len() + v.len()),
             event_listener: None,
@@ -1373,6 +2097,7 @@ mod tests {
                 capacity: 256,
                 shards: 4,
                 eviction_config: FifoConfig::default(),
+                eviction_batch: 1,
                 hash_builder: Default::default(),
                 weighter: Arc::new(|_, _| 1),
                 event_listener: None,
@@ -1388,6 +2113,7 @@ mod tests {
                 capacity: 256,
                 shards: 4,
                 eviction_config: S3FifoConfig::default(),
+                eviction_batch: 1,
                 hash_builder: Default::default(),
                 weighter: Arc::new(|_, _| 1),
                 event_listener: None,
@@ -1403,6 +2129,7 @@ mod tests {
                 capacity: 256,
                 shards: 4,
                 eviction_config: LruConfig::default(),
+                eviction_batch: 1,
                 hash_builder: Default::default(),
                 weighter: Arc::new(|_, _| 1),
                 event_listener: None,
@@ -1418,6 +2145,7 @@ mod tests {
                 capacity: 256,
                 shards: 4,
                 eviction_config: LfuConfig::default(),
+                eviction_batch: 1,
                 hash_builder: Default::default(),
                 weighter: Arc::new(|_, _| 1),
                 event_listener: None,
@@ -1433,6 +2161,7 @@ mod tests {
                 capacity: 256,
                 shards: 4,
                 eviction_config: SieveConfig {},
+                eviction_batch: 1,
                 hash_builder: Default::default(),
                 weighter: Arc::new(|_, _| 1),
                 event_listener: None,
@@ -1441,5 +2170,21 @@ mod tests {
             let hints = vec![Hint::Normal];
             fuzzy(cache, hints);
         }
+
+        #[test_log::test]
+        fn test_gdsf_cache_fuzzy() {
+            let cache: RawCache<Gdsf<u64, u64, TestProperties>, ModHasher> = RawCache::new(RawCacheConfig {
+                capacity: 256,
+                shards: 4,
+                eviction_config: GdsfConfig::default(),
+                eviction_batch: 1,
+                hash_builder: Default::default(),
+                weighter: Arc::new(|_, _| 1),
+                event_listener: None,
+                metrics: Arc::new(Metrics::noop()),
+            });
+            let hints = vec![Hint::Normal, Hint::Low];
+            fuzzy(cache, hints);
+        }
     }
 }
@@ -15,7 +15,10 @@
 use std::{
     cell::UnsafeCell,
     fmt::Debug,
-    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 use bitflags::bitflags;
@@ -36,7 +39,8 @@ where
     E: Eviction,
 {
     pub key: E::Key,
-    pub value: E::Value,
+    /// The value is shared, so it can outlive the record, see [`Record::value_arc`].
+    pub value: Arc<E::Value>,
     pub properties: E::Properties,
     pub hash: u64,
     pub weight: usize,
@@ -83,6 +87,11 @@ where
         }
     }
 
+    /// Take the data of the record.
+    pub fn into_data(self) -> Data<E> {
+        self.data
+    }
+
     /// Get the immutable reference of the record key.
     pub fn key(&self) -> &E::Key {
         &self.data.key
@@ -93,6 +102,11 @@ where
         &self.data.value
     }
 
+    /// Get the shared ownership of the record value.
+    pub fn value_arc(&self) -> Arc<E::Value> {
+        self.data.value.clone()
+    }
+
     /// Get the immutable reference of the record properties.
     pub fn properties(&self) -> &E::Properties {
         &self.data.properties
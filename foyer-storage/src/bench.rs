@@ -0,0 +1,314 @@
+// Copyright 2025 foyer Project Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmark helpers for the `foyer-storage` crate.
+//!
+//! The helpers measure the raw in-memory operations of the disk cache engines without any I/O, so the results can be
+//! reproduced on any hardware with the entry shapes of the workload.
+
+use std::{
+    collections::HashSet,
+    ops::RangeInclusive,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use foyer_common::metrics::Metrics;
+use rand::{rng, Rng};
+
+use crate::{
+    io::{buffer::IoBuffer, PAGE},
+    serde::EntrySerializer,
+    small::{
+        batch::Item,
+        serde::EntryHeader,
+        set::{SetLayout, SetStorage},
+    },
+    Compression,
+};
+
+/// A workload on a single set of the small object disk cache engine.
+///
+/// The entries are serialized before the measurement. They are applied to the set in batches, each batch also deletes
+/// a part of the live entries. Then every live entry is read back, and random missing hashes are probed to measure the
+/// bloom filter false positives and the miss latency.
+///
+/// The keys and values are `Vec<u8>` with random bytes, the sizes are the lengths of them before encoding.
+#[derive(Debug, Clone)]
+pub struct SetWorkload {
+    set_size: usize,
+    layout: SetLayout,
+    entries: usize,
+    batch: usize,
+    key_size: RangeInclusive<usize>,
+    value_size: RangeInclusive<usize>,
+    delete_ratio: f64,
+    probes: usize,
+}
+
+impl SetWorkload {
+    /// Create a workload on a set of `set_size` bytes.
+    ///
+    /// `set_size` must be a multiplier of 4 KiB.
+    pub fn new(set_size: usize) -> Self {
+        assert!(
+            set_size > SetStorage::SET_HEADER_SIZE && set_size % PAGE == 0,
+            "set size ({set_size}) must be a multiplier of PAGE ({PAGE})"
+        );
+        Self {
+            set_size,
+            layout: SetLayout::default(),
+            entries: 10000,
+            batch: 16,
+            key_size: 16..=16,
+            value_size: 64..=256,
+            delete_ratio: 0.0,
+            probes: 10000,
+        }
+    }
+
+    /// Set the entry layout of the set.
+    ///
+    /// Default: [`SetLayout::Interleaved`].
+    pub fn with_layout(mut self, layout: SetLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Set the total count of the entries to insert, the set wipes the oldest entries once it is full.
+    ///
+    /// Default: `10000`.
+    pub fn with_entries(mut self, entries: usize) -> Self {
+        self.entries = entries;
+        self
+    }
+
+    /// Set how many entries are inserted by each apply.
+    ///
+    /// Default: `16`.
+    pub fn with_batch(mut self, batch: usize) -> Self {
+        assert!(batch > 0, "batch must be positive");
+        self.batch = batch;
+        self
+    }
+
+    /// Set the range of the key sizes, the key size of each entry is picked uniformly from it.
+    ///
+    /// Default: `16..=16`.
+    pub fn with_key_size(mut self, key_size: RangeInclusive<usize>) -> Self {
+        assert!(!key_size.is_empty(), "key size range must not be empty");
+        self.key_size = key_size;
+        self
+    }
+
+    /// Set the range of the value sizes, the value size of each entry is picked uniformly from it.
+    ///
+    /// Default: `64..=256`.
+    pub fn with_value_size(mut self, value_size: RangeInclusive<usize>) -> Self {
+        assert!(!value_size.is_empty(), "value size range must not be empty");
+        self.value_size = value_size;
+        self
+    }
+
+    /// Set the ratio of the deletions to the insertions of each apply, range: [0 ~ 1].
+    ///
+    /// The deleted entries are picked randomly from the live entries.
+    ///
+    /// Default: `0.0`.
+    pub fn with_delete_ratio(mut self, delete_ratio: f64) -> Self {
+        assert!((0.0..=1.0).contains(&delete_ratio), "delete ratio must be in [0, 1]");
+        self.delete_ratio = delete_ratio;
+        self
+    }
+
+    /// Set how many random missing hashes are probed after the applies.
+    ///
+    /// Default: `10000`.
+    pub fn with_probes(mut self, probes: usize) -> Self {
+        self.probes = probes;
+        self
+    }
+
+    /// Run the workload and report the measurements.
+    pub fn run(&self) -> SetReport {
+        let mut rng = rng();
+
+        let mut buffer = IoBuffer::new(self.set_size);
+        buffer.fill(0);
+        let mut storage = SetStorage::load_with(buffer, 0, false, self.layout, Arc::new(Metrics::noop()));
+
+        // Serialize the entries ahead, so only the applies are measured.
+        let items = (0..self.entries)
+            .map(|_| {
+                let key = (0..rng.random_range(self.key_size.clone()))
+                    .map(|_| rng.random())
+                    .collect::<Vec<u8>>();
+                let value = (0..rng.random_range(self.value_size.clone()))
+                    .map(|_| rng.random())
+                    .collect::<Vec<u8>>();
+                let hash = rng.random();
+                (hash, serialize(&key, &value, hash))
+            })
+            .collect::<Vec<_>>();
+
+        let mut live = HashSet::new();
+        let mut report = SetReport::default();
+        let mut deleted = 0.0;
+        for batch in items.chunks(self.batch) {
+            deleted += self.delete_ratio * batch.len() as f64;
+            let deletions = live.iter().take(deleted as usize).copied().collect::<HashSet<u64>>();
+            deleted -= deletions.len() as f64;
+            let batch_items = batch
+                .iter()
+                .map(|(hash, slice)| Item {
+                    slice: slice.clone(),
+                    hash: *hash,
+                    inserted_at: None,
+                })
+                .collect::<Vec<_>>();
+
+            let now = Instant::now();
+            let wiped = storage.apply(&deletions, batch_items);
+            report.apply_duration += now.elapsed();
+
+            report.applies += 1;
+            report.inserted += batch.len();
+            report.inserted_bytes += batch.iter().map(|(_, slice)| slice.len()).sum::<usize>();
+            report.deleted += deletions.len();
+            report.wiped += wiped.len();
+            for hash in deletions.iter().chain(wiped.iter()) {
+                live.remove(hash);
+            }
+            live.extend(batch.iter().map(|(hash, _)| *hash));
+        }
+
+        for hash in live.iter() {
+            let now = Instant::now();
+            let res = storage.get::<Vec<u8>, Vec<u8>>(*hash);
+            report.hit_duration += now.elapsed();
+            assert!(matches!(res, Ok(Some(_))), "live entry {hash} is missing");
+            report.hits += 1;
+        }
+
+        for _ in 0..self.probes {
+            let hash = rng.random();
+            if live.contains(&hash) {
+                continue;
+            }
+            report.false_positives += storage.bloom_filter().lookup(hash) as usize;
+            let now = Instant::now();
+            let res = storage.get::<Vec<u8>, Vec<u8>>(hash);
+            report.miss_duration += now.elapsed();
+            assert!(matches!(res, Ok(None)), "missing entry {hash} is found");
+            report.misses += 1;
+        }
+
+        report.entries = storage.entry_count();
+        report.len = storage.len();
+        report
+    }
+}
+
+/// Measurements of a [`SetWorkload`] run.
+#[derive(Debug, Clone, Default)]
+pub struct SetReport {
+    /// Count of the applies.
+    pub applies: usize,
+    /// Count of the inserted entries.
+    pub inserted: usize,
+    /// Serialized bytes of the inserted entries.
+    pub inserted_bytes: usize,
+    /// Count of the deleted entries.
+    pub deleted: usize,
+    /// Count of the entries wiped to make room for the inserted ones.
+    pub wiped: usize,
+    /// Total duration of the applies.
+    pub apply_duration: Duration,
+    /// Count of the lookups of the live entries.
+    pub hits: usize,
+    /// Total duration of the lookups of the live entries.
+    pub hit_duration: Duration,
+    /// Count of the lookups of the missing hashes.
+    pub misses: usize,
+    /// Total duration of the lookups of the missing hashes.
+    pub miss_duration: Duration,
+    /// Count of the missing hashes that pass the bloom filter.
+    pub false_positives: usize,
+    /// Count of the entries in the set after the applies.
+    pub entries: usize,
+    /// Length of the set data after the applies.
+    pub len: usize,
+}
+
+impl SetReport {
+    /// Inserted entries per second of the applies.
+    pub fn apply_throughput(&self) -> f64 {
+        self.inserted as f64 / self.apply_duration.as_secs_f64()
+    }
+
+    /// Inserted bytes per second of the applies.
+    pub fn apply_bandwidth(&self) -> f64 {
+        self.inserted_bytes as f64 / self.apply_duration.as_secs_f64()
+    }
+
+    /// Mean latency of the lookups of the live entries.
+    pub fn hit_latency(&self) -> Duration {
+        self.hit_duration.checked_div(self.hits as u32).unwrap_or_default()
+    }
+
+    /// Mean latency of the lookups of the missing hashes.
+    pub fn miss_latency(&self) -> Duration {
+        self.miss_duration.checked_div(self.misses as u32).unwrap_or_default()
+    }
+
+    /// Ratio of the missing hashes that pass the bloom filter, i.e. the measured false positive probability.
+    pub fn bloom_fpp(&self) -> f64 {
+        if self.misses == 0 {
+            return 0.0;
+        }
+        self.false_positives as f64 / self.misses as f64
+    }
+}
+
+fn serialize(key: &Vec<u8>, value: &Vec<u8>, hash: u64) -> Bytes {
+    let mut buf = vec![0; EntryHeader::ENTRY_HEADER_SIZE];
+    let info = EntrySerializer::serialize(key, value, Compression::None, &mut buf).unwrap();
+    EntryHeader::new(hash, info.key_len, info.value_len).write(&mut buf[..EntryHeader::ENTRY_HEADER_SIZE]);
+    Bytes::from(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_set_workload() {
+        for layout in [SetLayout::Interleaved, SetLayout::Split] {
+            let report = SetWorkload::new(PAGE)
+                .with_layout(layout)
+                .with_entries(1000)
+                .with_value_size(16..=128)
+                .with_delete_ratio(0.25)
+                .with_probes(1000)
+                .run();
+            assert_eq!(report.applies, 1000usize.div_ceil(16));
+            assert_eq!(report.inserted, 1000);
+            assert_eq!(report.inserted, report.deleted + report.wiped + report.entries);
+            assert_eq!(report.hits, report.entries);
+            assert!(report.misses > 0);
+            assert!(report.bloom_fpp() <= 1.0);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright 2025 foyer Project Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checksums written by the disk cache, for verifying the device data out of process.
+//!
+//! All checksums are xxHash (not CRC) with seed `0`:
+//!
+//! - [`checksum32`]: 32-bit xxHash (XXH32), used by the sets of the small object disk cache engine.
+//! - [`checksum64`]: 64-bit xxHash (XXH64), used by the entries of the large object disk cache engine.
+//!
+//! The integers in the headers are written in big-endian.
+
+use std::hash::Hasher;
+
+use twox_hash::{XxHash32, XxHash64};
+
+/// Offset of the data part of a set, i.e. the set header size.
+const SET_HEADER_SIZE: usize = 69;
+
+/// 32-bit xxHash (XXH32) with seed `0` of `buf`.
+pub fn checksum32(buf: &[u8]) -> u32 {
+    checksum32_vectored(&[buf])
+}
+
+/// 32-bit xxHash (XXH32) with seed `0` of the concatenation of `bufs`.
+pub fn checksum32_vectored(bufs: &[&[u8]]) -> u32 {
+    let mut hasher = XxHash32::with_seed(0);
+    bufs.iter().for_each(|buf| hasher.write(buf));
+    hasher.finish_32()
+}
+
+/// 64-bit xxHash (XXH64) with seed `0` of `buf`.
+pub fn checksum64(buf: &[u8]) -> u64 {
+    XxHash64::oneshot(0, buf)
+}
+
+/// Calculate the checksum of a set of the small object disk cache engine read from the device.
+///
+/// `set` must be the whole set, i.e. `set size` bytes starting at the set offset. The checksum is stored in the first
+/// 4 bytes of the set, and is [`checksum32`] of the concatenation of:
+///
+/// - the set header after the checksum and the head region of the data: `set[4..69 + len - tail]`.
+/// - the tail region of the data: `set[set.len() - tail..]`.
+///
+/// `len` is the big-endian `u32` at `set[20..24]` and `tail` is the big-endian `u32` at `set[61..65]`.
+///
+/// Returns `None` if the lengths in the set header are out of range, such a set is never valid. A set written without
+/// a checksum stores `0`, which only matches by chance.
+pub fn set_checksum(set: &[u8]) -> Option<u32> {
+    if set.len() < SET_HEADER_SIZE {
+        return None;
+    }
+    let len = u32::from_be_bytes(set[20..24].try_into().unwrap()) as usize;
+    let tail = u32::from_be_bytes(set[61..65].try_into().unwrap()) as usize;
+    if SET_HEADER_SIZE + len > set.len() || tail > len {
+        return None;
+    }
+    Some(set_checksum_with(set, len - tail, tail))
+}
+
+/// Checksum of the set with the given head and tail region lengths, see [`set_checksum`].
+pub(crate) fn set_checksum_with(set: &[u8], head: usize, tail: usize) -> u32 {
+    checksum32_vectored(&[&set[4..SET_HEADER_SIZE + head], &set[set.len() - tail..]])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use foyer_common::metrics::Metrics;
+
+    use super::*;
+    use crate::{
+        io::{buffer::IoBuffer, PAGE},
+        serde::EntrySerializer,
+        small::{
+            batch::Item,
+            serde::EntryHeader,
+            set::{SetLayout, SetStorage},
+        },
+        Compression,
+    };
+
+    #[test]
+    fn test_set_checksum() {
+        assert_eq!(SET_HEADER_SIZE, SetStorage::SET_HEADER_SIZE);
+
+        for layout in [SetLayout::Interleaved, SetLayout::Split] {
+            let mut buffer = IoBuffer::new(PAGE);
+            buffer.fill(0);
+            let mut storage = SetStorage::load_with(buffer, 0, false, layout, Arc::new(Metrics::noop()));
+            let items = (0..4u64)
+                .map(|i| {
+                    let mut buf = vec![0; EntryHeader::ENTRY_HEADER_SIZE];
+                    let info =
+                        EntrySerializer::serialize(&i, &vec![i as u8; 100], Compression::None, &mut buf).unwrap();
+                    EntryHeader::new(i, info.key_len, info.value_len).write(&mut buf[..EntryHeader::ENTRY_HEADER_SIZE]);
+                    Item {
+                        slice: buf.into(),
+                        hash: i,
+                        inserted_at: None,
+                    }
+                })
+                .collect();
+            storage.apply(&HashSet::new(), items);
+            storage.update();
+
+            let checksum = storage.checksum();
+            let mut set = storage.into_io_buffer().to_vec();
+            assert_eq!(u32::from_be_bytes(set[0..4].try_into().unwrap()), checksum);
+            assert_eq!(set_checksum(&set), Some(checksum));
+
+            set[100] ^= 1;
+            assert_ne!(set_checksum(&set), Some(checksum));
+            set[20..24].copy_from_slice(&(PAGE as u32).to_be_bytes());
+            assert_eq!(set_checksum(&set), None);
+        }
+    }
+}
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use std::{
-    fs::{create_dir_all, File, OpenOptions},
+    fs::{create_dir_all, File},
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -21,7 +21,7 @@ use std::{
 use foyer_common::{asyncify::asyncify_with_runtime, bits};
 use fs4::free_space;
 
-use super::{Dev, RegionId, Throttle};
+use super::{open_file, Dev, RegionId, Throttle};
 use crate::{
     error::{Error, Result},
     io::{
@@ -38,9 +38,14 @@ pub struct DirectFileDeviceConfig {
     capacity: usize,
     region_size: usize,
     throttle: Throttle,
+    direct: bool,
 }
 
 impl DirectFileDeviceConfig {
+    pub(crate) fn set_direct_io(&mut self, direct: bool) {
+        self.direct = direct;
+    }
+
     fn verify(&self) -> Result<()> {
         if self.region_size == 0 || self.region_size % PAGE != 0 {
             return Err(anyhow::anyhow!(
@@ -156,7 +161,14 @@ impl DirectFileDevice {
             };
 
             if written != len {
-                return (buf, Err(anyhow::anyhow!("written {written}, expected: {len}").into()));
+                return (
+                    buf,
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::WriteZero,
+                        format!("written {written}, expected: {len}"),
+                    )
+                    .into()),
+                );
             }
 
             (buf, Ok(()))
@@ -203,7 +215,14 @@ impl DirectFileDevice {
             };
 
             if read != len {
-                return (buf, Err(anyhow::anyhow!("read {read}, expected: {len}").into()));
+                return (
+                    buf,
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        format!("read {read}, expected: {len}"),
+                    )
+                    .into()),
+                );
             }
 
             (buf, Ok(()))
@@ -243,17 +262,7 @@ impl Dev for DirectFileDevice {
             create_dir_all(dir)?;
         }
 
-        let mut opts = OpenOptions::new();
-
-        opts.create(true).write(true).read(true);
-
-        #[cfg(target_os = "linux")]
-        {
-            use std::os::unix::fs::OpenOptionsExt;
-            opts.custom_flags(libc::O_DIRECT | libc::O_NOATIME);
-        }
-
-        let file = opts.open(&options.path)?;
+        let file = open_file(&options.path, options.direct)?;
 
         if file.metadata().unwrap().is_file() {
             tracing::warn!(
@@ -331,6 +340,7 @@ pub struct DirectFileDeviceOptions {
     capacity: Option<usize>,
     region_size: Option<usize>,
     throttle: Throttle,
+    direct: bool,
 }
 
 impl DirectFileDeviceOptions {
@@ -343,6 +353,7 @@ impl DirectFileDeviceOptions {
             capacity: None,
             region_size: None,
             throttle: Throttle::default(),
+            direct: true,
         }
     }
 
@@ -371,6 +382,16 @@ impl DirectFileDeviceOptions {
         self.throttle = throttle;
         self
     }
+
+    /// Enable/disable direct I/O (`O_DIRECT`) on Linux.
+    ///
+    /// Direct I/O bypasses the page cache. If the file system doesn't support it, buffered I/O is used instead.
+    ///
+    /// Default: `true`.
+    pub fn with_direct_io(mut self, direct: bool) -> Self {
+        self.direct = direct;
+        self
+    }
 }
 
 impl From<DirectFileDeviceOptions> for DirectFileDeviceConfig {
@@ -395,12 +416,14 @@ impl From<DirectFileDeviceOptions> for DirectFileDeviceConfig {
 
         let capacity = align_v(capacity, region_size);
         let throttle = options.throttle;
+        let direct = options.direct;
 
         DirectFileDeviceConfig {
             path,
             capacity,
             region_size,
             throttle,
+            direct,
         }
     }
 }
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use std::{
-    fs::{create_dir_all, File, OpenOptions},
+    fs::{create_dir_all, File},
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -23,7 +23,7 @@ use fs4::free_space;
 use futures_util::future::try_join_all;
 use itertools::Itertools;
 
-use super::{Dev, DevExt, RegionId, Throttle};
+use super::{open_file, Dev, DevExt, RegionId, Throttle};
 use crate::{
     error::{Error, Result},
     io::{
@@ -40,9 +40,14 @@ pub struct DirectFsDeviceConfig {
     capacity: usize,
     file_size: usize,
     throttle: Throttle,
+    direct: bool,
 }
 
 impl DirectFsDeviceConfig {
+    pub(crate) fn set_direct_io(&mut self, direct: bool) {
+        self.direct = direct;
+    }
+
     fn verify(&self) -> Result<()> {
         if self.file_size == 0 || self.file_size % PAGE != 0 {
             return Err(anyhow::anyhow!(
@@ -147,17 +152,7 @@ impl Dev for DirectFsDevice {
             .map(|i| {
                 let path = options.dir.clone().join(Self::filename(i as RegionId));
                 async {
-                    let mut opts = OpenOptions::new();
-
-                    opts.create(true).write(true).read(true);
-
-                    #[cfg(target_os = "linux")]
-                    {
-                        use std::os::unix::fs::OpenOptionsExt;
-                        opts.custom_flags(libc::O_DIRECT | libc::O_NOATIME);
-                    }
-
-                    let file = opts.open(path)?;
+                    let file = open_file(path, options.direct)?;
                     file.set_len(options.file_size as _)?;
                     let file = Arc::new(file);
 
@@ -212,7 +207,14 @@ impl Dev for DirectFsDevice {
             };
 
             if written != len {
-                return (buf, Err(anyhow::anyhow!("written {written}, expected: {len}").into()));
+                return (
+                    buf,
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::WriteZero,
+                        format!("written {written}, expected: {len}"),
+                    )
+                    .into()),
+                );
             }
 
             (buf, Ok(()))
@@ -254,7 +256,14 @@ impl Dev for DirectFsDevice {
             };
 
             if read != len {
-                return (buf, Err(anyhow::anyhow!("read {read}, expected: {len}").into()));
+                return (
+                    buf,
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        format!("read {read}, expected: {len}"),
+                    )
+                    .into()),
+                );
             }
 
             (buf, Ok(()))
@@ -293,6 +302,7 @@ pub struct DirectFsDeviceOptions {
     capacity: Option<usize>,
     file_size: Option<usize>,
     throttle: Throttle,
+    direct: bool,
 }
 
 impl DirectFsDeviceOptions {
@@ -305,6 +315,7 @@ impl DirectFsDeviceOptions {
             capacity: None,
             file_size: None,
             throttle: Throttle::default(),
+            direct: true,
         }
     }
 
@@ -333,6 +344,16 @@ impl DirectFsDeviceOptions {
         self.throttle = throttle;
         self
     }
+
+    /// Enable/disable direct I/O (`O_DIRECT`) on Linux.
+    ///
+    /// Direct I/O bypasses the page cache. If the file system doesn't support it, buffered I/O is used instead.
+    ///
+    /// Default: `true`.
+    pub fn with_direct_io(mut self, direct: bool) -> Self {
+        self.direct = direct;
+        self
+    }
 }
 
 impl From<DirectFsDeviceOptions> for DirectFsDeviceConfig {
@@ -357,12 +378,14 @@ impl From<DirectFsDeviceOptions> for DirectFsDeviceConfig {
         let capacity = align_v(capacity, file_size);
 
         let throttle = options.throttle;
+        let direct = options.direct;
 
         DirectFsDeviceConfig {
             dir,
             capacity,
             file_size,
             throttle,
+            direct,
         }
     }
 }
@@ -0,0 +1,298 @@
+// Copyright 2025 foyer Project Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use foyer_common::bits;
+use parking_lot::RwLock;
+
+use super::{Dev, RegionId, Throttle};
+use crate::{
+    error::{Error, Result},
+    io::{
+        buffer::{IoBuf, IoBufMut},
+        PAGE,
+    },
+    Runtime,
+};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryDeviceConfig {
+    capacity: usize,
+    region_size: usize,
+    throttle: Throttle,
+}
+
+impl MemoryDeviceConfig {
+    fn verify(&self) -> Result<()> {
+        if self.region_size == 0 || self.region_size % PAGE != 0 {
+            return Err(anyhow::anyhow!(
+                "region size ({region_size}) must be a multiplier of PAGE ({PAGE})",
+                region_size = self.region_size,
+            )
+            .into());
+        }
+
+        if self.capacity == 0 || self.capacity % self.region_size != 0 {
+            return Err(anyhow::anyhow!(
+                "capacity ({capacity}) must be a multiplier of region size ({region_size})",
+                capacity = self.capacity,
+                region_size = self.region_size,
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// A device that keeps all data in memory.
+///
+/// The data is lost once the device is dropped. The memory of a region is allocated on its first write, reading a
+/// region that has never been written returns zeros.
+///
+/// # Safety
+///
+/// Reads and writes MUST be 4K-aligned.
+#[derive(Debug, Clone)]
+pub struct MemoryDevice {
+    regions: Arc<Vec<RwLock<Vec<u8>>>>,
+
+    capacity: usize,
+    region_size: usize,
+
+    throttle: Throttle,
+}
+
+impl MemoryDevice {
+    fn check_io_range(&self, region: RegionId, offset: u64, len: usize) -> Result<()> {
+        // Assert alignment.
+        bits::assert_aligned(PAGE, offset as _);
+        bits::assert_aligned(PAGE, len);
+
+        if region as usize >= self.regions.len() || offset as usize + len > self.region_size {
+            let start = region as usize * self.region_size + offset as usize;
+            let e = Error::InvalidIoRange {
+                range: start..start + len,
+                region_size: self.region_size,
+                capacity: self.capacity,
+            };
+            tracing::error!(?e, "[memory]: io range out of bound");
+            return Err(e);
+        }
+
+        Ok(())
+    }
+}
+
+impl Dev for MemoryDevice {
+    type Config = MemoryDeviceConfig;
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn region_size(&self) -> usize {
+        self.region_size
+    }
+
+    fn throttle(&self) -> &Throttle {
+        &self.throttle
+    }
+
+    async fn open(options: Self::Config, _: Runtime) -> Result<Self> {
+        options.verify()?;
+
+        let regions = (0..options.capacity / options.region_size)
+            .map(|_| RwLock::new(vec![]))
+            .collect();
+
+        Ok(Self {
+            regions: Arc::new(regions),
+            capacity: options.capacity,
+            region_size: options.region_size,
+            throttle: options.throttle,
+        })
+    }
+
+    async fn write<B>(&self, buf: B, region: RegionId, offset: u64) -> (B, Result<()>)
+    where
+        B: IoBuf,
+    {
+        if let Err(e) = self.check_io_range(region, offset, buf.len()) {
+            return (buf, Err(e));
+        }
+
+        let mut data = self.regions[region as usize].write();
+        if data.is_empty() {
+            data.resize(self.region_size, 0);
+        }
+        data[offset as usize..offset as usize + buf.len()].copy_from_slice(&buf);
+        drop(data);
+
+        (buf, Ok(()))
+    }
+
+    async fn read<B>(&self, mut buf: B, region: RegionId, offset: u64) -> (B, Result<()>)
+    where
+        B: IoBufMut,
+    {
+        if let Err(e) = self.check_io_range(region, offset, buf.len()) {
+            return (buf, Err(e));
+        }
+
+        let data = self.regions[region as usize].read();
+        if data.is_empty() {
+            buf.fill(0);
+        } else {
+            let len = buf.len();
+            buf.copy_from_slice(&data[offset as usize..offset as usize + len]);
+        }
+        drop(data);
+
+        (buf, Ok(()))
+    }
+
+    async fn flush(&self, _: Option<RegionId>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// [`MemoryDeviceOptions`] is used to build the options for the memory device.
+///
+/// The memory device keeps the data of the disk cache in memory with the same format as the other devices, for
+/// testing or for environments without a disk.
+#[derive(Debug)]
+pub struct MemoryDeviceOptions {
+    capacity: usize,
+    region_size: Option<usize>,
+    throttle: Throttle,
+}
+
+impl MemoryDeviceOptions {
+    const DEFAULT_REGION_SIZE: usize = 64 * 1024 * 1024;
+
+    /// Use the given capacity in bytes as the memory device capacity.
+    ///
+    /// The given capacity may be modified on build for alignment.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            region_size: None,
+            throttle: Throttle::default(),
+        }
+    }
+
+    /// Set the region size of the memory device.
+    ///
+    /// The given region size may be modified on build for alignment.
+    ///
+    /// The serialized entry size (with extra metadata) must be equal to or smaller than the region size.
+    pub fn with_region_size(mut self, region_size: usize) -> Self {
+        self.region_size = Some(region_size);
+        self
+    }
+
+    /// Set the throttle of the memory device.
+    pub fn with_throttle(mut self, throttle: Throttle) -> Self {
+        self.throttle = throttle;
+        self
+    }
+}
+
+impl From<MemoryDeviceOptions> for MemoryDeviceConfig {
+    fn from(options: MemoryDeviceOptions) -> Self {
+        let align_v = |value: usize, align: usize| value - value % align;
+
+        let capacity = align_v(options.capacity, PAGE);
+
+        let region_size = options
+            .region_size
+            .unwrap_or(MemoryDeviceOptions::DEFAULT_REGION_SIZE)
+            .min(capacity);
+        let region_size = align_v(region_size, PAGE);
+
+        let capacity = if region_size == 0 {
+            capacity
+        } else {
+            align_v(capacity, region_size)
+        };
+
+        MemoryDeviceConfig {
+            capacity,
+            region_size,
+            throttle: options.throttle,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BufMut;
+
+    use super::*;
+    use crate::io::buffer::IoBuffer;
+
+    #[test_log::test]
+    fn test_options_builder() {
+        let config: MemoryDeviceConfig = MemoryDeviceOptions::new(4 * 1024 * 1024 + 100)
+            .with_region_size(1024 * 1024 + 100)
+            .into();
+        assert_eq!(config.capacity, 4 * 1024 * 1024);
+        assert_eq!(config.region_size, 1024 * 1024);
+        config.verify().unwrap();
+
+        let config: MemoryDeviceConfig = MemoryDeviceOptions::new(100).into();
+        assert!(config.verify().is_err());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_memory_device_io() {
+        let config: MemoryDeviceConfig = MemoryDeviceOptions::new(4 * 1024 * 1024)
+            .with_region_size(1024 * 1024)
+            .into();
+        let device = MemoryDevice::open(config, Runtime::current()).await.unwrap();
+        assert_eq!(device.capacity(), 4 * 1024 * 1024);
+        assert_eq!(device.region_size(), 1024 * 1024);
+
+        let mut buf = IoBuffer::new(64 * 1024);
+        (&mut buf[..]).put_bytes(b'x', 64 * 1024);
+        let buf = buf.into_shared_io_slice();
+
+        let (_, res) = device.write(buf.clone(), 1, 4096).await;
+        res.unwrap();
+
+        let b = IoBuffer::new(64 * 1024);
+        let (b, res) = device.read(b, 1, 4096).await;
+        res.unwrap();
+        assert_eq!(&buf[..], &b[..]);
+
+        // Unwritten ranges read as zeros.
+        let b = IoBuffer::new(4096);
+        let (b, res) = device.read(b, 1, 0).await;
+        res.unwrap();
+        assert!(b.iter().all(|&b| b == 0));
+        let b = IoBuffer::new(4096);
+        let (b, res) = device.read(b, 2, 4096).await;
+        res.unwrap();
+        assert!(b.iter().all(|&b| b == 0));
+
+        // Out of range.
+        let (_, res) = device.write(buf.clone(), 4, 0).await;
+        assert!(matches!(res, Err(Error::InvalidIoRange { .. })));
+        let (_, res) = device.write(buf, 0, 1024 * 1024 - 4096).await;
+        assert!(matches!(res, Err(Error::InvalidIoRange { .. })));
+    }
+}
@@ -14,26 +14,32 @@
 
 pub mod direct_file;
 pub mod direct_fs;
+pub mod memory;
 pub mod monitor;
 
 use std::{
     fmt::{Debug, Display},
+    fs::{File, OpenOptions},
     future::Future,
     num::NonZeroUsize,
+    path::Path,
     str::FromStr,
+    time::Duration,
 };
 
 use direct_file::DirectFileDeviceConfig;
 use direct_fs::DirectFsDeviceConfig;
+use memory::MemoryDeviceConfig;
 use monitor::Monitored;
 
 use crate::{
-    error::Result,
+    error::{Error, Result},
     io::{
         buffer::{IoBuf, IoBufMut},
         PAGE,
     },
-    DirectFileDevice, DirectFileDeviceOptions, DirectFsDevice, DirectFsDeviceOptions, Runtime,
+    DirectFileDevice, DirectFileDeviceOptions, DirectFsDevice, DirectFsDeviceOptions, MemoryDevice,
+    MemoryDeviceOptions, Runtime,
 };
 
 #[cfg(test)]
@@ -43,6 +49,42 @@ use test_utils::NoopDevice;
 
 pub type RegionId = u32;
 
+/// Open the file of a device, create it if it doesn't exist.
+///
+/// With `direct`, the file is opened with `O_DIRECT` on Linux. If the file system rejects `O_DIRECT`, the file is
+/// opened with buffered I/O instead and a warning is logged.
+fn open_file(path: impl AsRef<Path>, direct: bool) -> std::io::Result<File> {
+    let path = path.as_ref();
+    let open = |direct: bool| {
+        let mut opts = OpenOptions::new();
+
+        opts.create(true).write(true).read(true);
+
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            let flags = if direct { libc::O_DIRECT } else { 0 };
+            opts.custom_flags(flags | libc::O_NOATIME);
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = direct;
+
+        opts.open(path)
+    };
+
+    match open(direct) {
+        #[cfg(target_os = "linux")]
+        Err(e) if direct && e.raw_os_error() == Some(libc::EINVAL) => {
+            tracing::warn!(
+                ?path,
+                "[device]: The file system doesn't support direct I/O, fall back to buffered I/O."
+            );
+            open(false)
+        }
+        res => res,
+    }
+}
+
 /// Config for the device.
 pub trait DevConfig: Send + Sync + 'static + Debug {}
 impl<T: Send + Sync + 'static + Debug> DevConfig for T {}
@@ -175,6 +217,58 @@ impl Throttle {
     }
 }
 
+/// Retry policy of the device operations on transient errors.
+///
+/// A failed read, write or flush is retried after a backoff if its error is retryable, see
+/// [`RetryPolicy::is_retryable`]. The backoff starts from `backoff` and doubles on each retry. The error of the last
+/// attempt is returned once `max_attempts` attempts fail, a non-retryable error is returned right away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetryPolicy {
+    /// Max attempts of each operation, including the first one. `1` disables retrying.
+    pub max_attempts: usize,
+    /// Backoff before the first retry, doubled on each following retry.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// If the error of a device operation may succeed on retry.
+    ///
+    /// Only I/O errors are retryable, and only these kinds of them:
+    ///
+    /// - `EIO`, `EAGAIN` and `EBUSY` from the operating system, e.g. a transient failure of a network block device.
+    /// - [`std::io::ErrorKind::Interrupted`], [`std::io::ErrorKind::WouldBlock`] and
+    ///   [`std::io::ErrorKind::TimedOut`].
+    /// - [`std::io::ErrorKind::WriteZero`] and [`std::io::ErrorKind::UnexpectedEof`], i.e. short writes and short reads.
+    ///
+    /// All other errors fail right away, e.g. `ENOSPC`, permission errors and invalid I/O ranges, which fail again on
+    /// retry.
+    pub fn is_retryable(error: &Error) -> bool {
+        use std::io::ErrorKind;
+
+        let Error::Io(e) = error else {
+            return false;
+        };
+        #[cfg(target_family = "unix")]
+        if matches!(e.raw_os_error(), Some(libc::EIO | libc::EAGAIN | libc::EBUSY)) {
+            return true;
+        }
+        matches!(
+            e.kind(),
+            ErrorKind::Interrupted
+                | ErrorKind::WouldBlock
+                | ErrorKind::TimedOut
+                | ErrorKind::WriteZero
+                | ErrorKind::UnexpectedEof
+        )
+    }
+
+    /// Backoff before the `retry`-th retry, starting from `1`.
+    pub fn backoff(&self, retry: usize) -> Duration {
+        self.backoff.saturating_mul(1 << (retry.saturating_sub(1)).min(31))
+    }
+}
+
 /// [`Dev`] represents 4K aligned block device.
 ///
 /// Both i/o block and i/o buffer must be aligned to 4K.
@@ -231,10 +325,23 @@ impl<T> DevExt for T where T: Dev {}
 pub enum DeviceConfig {
     DirectFile(DirectFileDeviceConfig),
     DirectFs(DirectFsDeviceConfig),
+    Memory(MemoryDeviceConfig),
     #[cfg(test)]
     Noop,
 }
 
+impl DeviceConfig {
+    pub(crate) fn set_direct_io(&mut self, direct: bool) {
+        match self {
+            Self::DirectFile(config) => config.set_direct_io(direct),
+            Self::DirectFs(config) => config.set_direct_io(direct),
+            Self::Memory(_) => {}
+            #[cfg(test)]
+            Self::Noop => {}
+        }
+    }
+}
+
 impl From<DirectFileDeviceOptions> for DeviceConfig {
     fn from(options: DirectFileDeviceOptions) -> Self {
         Self::DirectFile(options.into())
@@ -247,6 +354,12 @@ impl From<DirectFsDeviceOptions> for DeviceConfig {
     }
 }
 
+impl From<MemoryDeviceOptions> for DeviceConfig {
+    fn from(options: MemoryDeviceOptions) -> Self {
+        Self::Memory(options.into())
+    }
+}
+
 #[cfg(test)]
 impl From<()> for DeviceConfig {
     fn from(_: ()) -> Self {
@@ -258,6 +371,7 @@ impl From<()> for DeviceConfig {
 pub enum Device {
     DirectFile(DirectFileDevice),
     DirectFs(DirectFsDevice),
+    Memory(MemoryDevice),
     #[cfg(test)]
     Noop(NoopDevice),
 }
@@ -269,6 +383,7 @@ impl Dev for Device {
         match self {
             Device::DirectFile(dev) => dev.capacity(),
             Device::DirectFs(dev) => dev.capacity(),
+            Device::Memory(dev) => dev.capacity(),
             #[cfg(test)]
             Device::Noop(dev) => dev.capacity(),
         }
@@ -278,6 +393,7 @@ impl Dev for Device {
         match self {
             Device::DirectFile(dev) => dev.region_size(),
             Device::DirectFs(dev) => dev.region_size(),
+            Device::Memory(dev) => dev.region_size(),
             #[cfg(test)]
             Device::Noop(dev) => dev.region_size(),
         }
@@ -287,6 +403,7 @@ impl Dev for Device {
         match options {
             DeviceConfig::DirectFile(opts) => Ok(Self::DirectFile(DirectFileDevice::open(opts, runtime).await?)),
             DeviceConfig::DirectFs(opts) => Ok(Self::DirectFs(DirectFsDevice::open(opts, runtime).await?)),
+            DeviceConfig::Memory(opts) => Ok(Self::Memory(MemoryDevice::open(opts, runtime).await?)),
             #[cfg(test)]
             DeviceConfig::Noop => Ok(Self::Noop(NoopDevice::open((), runtime).await?)),
         }
@@ -296,6 +413,7 @@ impl Dev for Device {
         match self {
             Device::DirectFile(dev) => dev.throttle(),
             Device::DirectFs(dev) => dev.throttle(),
+            Device::Memory(dev) => dev.throttle(),
             #[cfg(test)]
             Device::Noop(dev) => dev.throttle(),
         }
@@ -308,6 +426,7 @@ impl Dev for Device {
         match self {
             Device::DirectFile(dev) => dev.write(buf, region, offset).await,
             Device::DirectFs(dev) => dev.write(buf, region, offset).await,
+            Device::Memory(dev) => dev.write(buf, region, offset).await,
             #[cfg(test)]
             Device::Noop(dev) => dev.write(buf, region, offset).await,
         }
@@ -320,6 +439,7 @@ impl Dev for Device {
         match self {
             Device::DirectFile(dev) => dev.read(buf, region, offset).await,
             Device::DirectFs(dev) => dev.read(buf, region, offset).await,
+            Device::Memory(dev) => dev.read(buf, region, offset).await,
             #[cfg(test)]
             Device::Noop(dev) => dev.read(buf, region, offset).await,
         }
@@ -329,6 +449,7 @@ impl Dev for Device {
         match self {
             Device::DirectFile(dev) => dev.flush(region).await,
             Device::DirectFs(dev) => dev.flush(region).await,
+            Device::Memory(dev) => dev.flush(region).await,
             #[cfg(test)]
             Device::Noop(dev) => dev.flush(region).await,
         }
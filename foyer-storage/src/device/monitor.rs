@@ -12,11 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{fmt::Debug, sync::Arc, time::Instant};
+use std::{fmt::Debug, future::Future, sync::Arc, time::Instant};
 
 use foyer_common::metrics::Metrics;
+use tokio::sync::{OwnedRwLockWriteGuard, RwLock, Semaphore, SemaphorePermit};
 
-use super::{RegionId, Throttle};
+use super::{RegionId, RetryPolicy, Throttle};
 use crate::{
     error::Result,
     io::buffer::{IoBuf, IoBufMut},
@@ -30,6 +31,10 @@ where
 {
     pub config: D::Config,
     pub metrics: Arc<Metrics>,
+    /// Max count of the concurrent device operations, no limit if `None`.
+    pub io_concurrency: Option<usize>,
+    /// Retry policy of the device operations on transient errors, no retry if `None`.
+    pub io_retry: Option<RetryPolicy>,
 }
 
 impl<D> Debug for MonitoredConfig<D>
@@ -40,6 +45,8 @@ where
         f.debug_struct("MonitoredOptions")
             .field("options", &self.config)
             .field("metrics", &self.metrics)
+            .field("io_concurrency", &self.io_concurrency)
+            .field("io_retry", &self.io_retry)
             .finish()
     }
 }
@@ -52,6 +59,23 @@ where
     device: D,
     stats: Arc<Statistics>,
     metrics: Arc<Metrics>,
+    /// Writes hold the read lock, quiescing holds the write lock.
+    gate: Arc<RwLock<()>>,
+    /// Limits the concurrent device operations, reads, writes and flushes share the permits.
+    limiter: Option<Arc<Semaphore>>,
+    retry: Option<RetryPolicy>,
+}
+
+/// Tracks an ongoing device operation, holds the permit of the limiter if there is one.
+struct IoPermit<'a> {
+    _permit: Option<SemaphorePermit<'a>>,
+    metrics: &'a Metrics,
+}
+
+impl Drop for IoPermit<'_> {
+    fn drop(&mut self) {
+        self.metrics.storage_disk_io_inflight.decrease(1);
+    }
 }
 
 impl<D> Monitored<D>
@@ -65,9 +89,72 @@ where
             device,
             stats: Arc::new(Statistics::new(iops_counter)),
             metrics: options.metrics,
+            gate: Arc::new(RwLock::new(())),
+            limiter: options.io_concurrency.map(|n| Arc::new(Semaphore::new(n))),
+            retry: options.io_retry,
         })
     }
 
+    /// Run the device operation, and run it again on retryable errors as the retry policy says.
+    ///
+    /// Each attempt is a separate device operation, it waits for its own permit and is recorded on its own.
+    async fn retry<B, F, Fut>(&self, mut buf: B, mut f: F) -> (B, Result<()>)
+    where
+        F: FnMut(B) -> Fut,
+        Fut: Future<Output = (B, Result<()>)>,
+    {
+        let mut retries = 0;
+        loop {
+            let (b, res) = f(buf).await;
+            let Some(policy) = self.retry.as_ref() else {
+                return (b, res);
+            };
+            match res {
+                Err(e) if retries + 1 < policy.max_attempts && RetryPolicy::is_retryable(&e) => {
+                    retries += 1;
+                    let backoff = policy.backoff(retries);
+                    tracing::warn!(
+                        ?backoff,
+                        retries,
+                        "[device]: retry the device operation on transient error: {e}"
+                    );
+                    self.metrics.storage_disk_retry.increase(1);
+                    tokio::time::sleep(backoff).await;
+                    buf = b;
+                }
+                res => return (b, res),
+            }
+        }
+    }
+
+    /// Wait until the device operation can start without exceeding the concurrency limit.
+    async fn acquire(&self) -> IoPermit<'_> {
+        let permit = match self.limiter.as_ref() {
+            Some(limiter) => match limiter.try_acquire() {
+                Ok(permit) => Some(permit),
+                Err(_) => {
+                    self.metrics.storage_disk_io_queued.increase(1);
+                    let permit = limiter.acquire().await.unwrap();
+                    self.metrics.storage_disk_io_queued.decrease(1);
+                    Some(permit)
+                }
+            },
+            None => None,
+        };
+        self.metrics.storage_disk_io_inflight.increase(1);
+        IoPermit {
+            _permit: permit,
+            metrics: &self.metrics,
+        }
+    }
+
+    /// Block writes to the device until the returned guard is dropped.
+    ///
+    /// Waits for the ongoing writes to finish. Reads and flushes are not blocked.
+    pub async fn quiesce(&self) -> OwnedRwLockWriteGuard<()> {
+        self.gate.clone().write_owned().await
+    }
+
     #[cfg_attr(
         feature = "tracing",
         fastrace::trace(name = "foyer::storage::device::monitor::write")
@@ -76,21 +163,29 @@ where
     where
         B: IoBuf,
     {
-        let now = Instant::now();
+        self.retry(buf, |buf| async move {
+            let gate = self.gate.read().await;
+            let permit = self.acquire().await;
+            let now = Instant::now();
 
-        let bytes = buf.len();
+            let bytes = buf.len();
 
-        let res = self.device.write(buf, region, offset).await;
+            let res = self.device.write(buf, region, offset).await;
+            drop(permit);
+            drop(gate);
 
-        self.stats.record_disk_write(bytes);
+            self.stats.record_disk_write(bytes);
+            self.stats.record_disk_write_latency(now.elapsed());
 
-        self.metrics.storage_disk_write.increase(1);
-        self.metrics.storage_disk_write_bytes.increase(bytes as u64);
-        self.metrics
-            .storage_disk_write_duration
-            .record(now.elapsed().as_secs_f64());
+            self.metrics.storage_disk_write.increase(1);
+            self.metrics.storage_disk_write_bytes.increase(bytes as u64);
+            self.metrics
+                .storage_disk_write_duration
+                .record(now.elapsed().as_secs_f64());
 
-        res
+            res
+        })
+        .await
     }
 
     #[cfg_attr(feature = "tracing", fastrace::trace(name = "foyer::storage::device::monitor::read"))]
@@ -98,21 +193,27 @@ where
     where
         B: IoBufMut,
     {
-        let now = Instant::now();
+        self.retry(buf, |buf| async move {
+            let permit = self.acquire().await;
+            let now = Instant::now();
 
-        let bytes = buf.len();
+            let bytes = buf.len();
 
-        let res = self.device.read(buf, region, offset).await;
+            let res = self.device.read(buf, region, offset).await;
+            drop(permit);
 
-        self.stats.record_disk_read(bytes);
+            self.stats.record_disk_read(bytes);
+            self.stats.record_disk_read_latency(now.elapsed());
 
-        self.metrics.storage_disk_read.increase(1);
-        self.metrics.storage_disk_read_bytes.increase(bytes as u64);
-        self.metrics
-            .storage_disk_read_duration
-            .record(now.elapsed().as_secs_f64());
+            self.metrics.storage_disk_read.increase(1);
+            self.metrics.storage_disk_read_bytes.increase(bytes as u64);
+            self.metrics
+                .storage_disk_read_duration
+                .record(now.elapsed().as_secs_f64());
 
-        res
+            res
+        })
+        .await
     }
 
     #[cfg_attr(
@@ -120,18 +221,24 @@ where
         fastrace::trace(name = "foyer::storage::device::monitor::flush")
     )]
     async fn flush(&self, region: Option<RegionId>) -> Result<()> {
-        let now = Instant::now();
+        self.retry((), |()| async move {
+            let permit = self.acquire().await;
+            let now = Instant::now();
 
-        let res = self.device.flush(region).await;
+            let res = self.device.flush(region).await;
+            drop(permit);
 
-        self.stats.record_disk_flush();
+            self.stats.record_disk_flush();
 
-        self.metrics.storage_disk_flush.increase(1);
-        self.metrics
-            .storage_disk_flush_duration
-            .record(now.elapsed().as_secs_f64());
+            self.metrics.storage_disk_flush.increase(1);
+            self.metrics
+                .storage_disk_flush_duration
+                .record(now.elapsed().as_secs_f64());
 
-        res
+            ((), res)
+        })
+        .await
+        .1
     }
 }
 
@@ -186,21 +293,29 @@ impl Monitored<DirectFileDevice> {
     where
         B: IoBuf,
     {
-        let now = Instant::now();
+        self.retry(buf, |buf| async move {
+            let gate = self.gate.read().await;
+            let permit = self.acquire().await;
+            let now = Instant::now();
 
-        let bytes = buf.len();
+            let bytes = buf.len();
 
-        let res = self.device.pwrite(buf, offset).await;
+            let res = self.device.pwrite(buf, offset).await;
+            drop(permit);
+            drop(gate);
 
-        self.stats.record_disk_write(bytes);
+            self.stats.record_disk_write(bytes);
+            self.stats.record_disk_write_latency(now.elapsed());
 
-        self.metrics.storage_disk_write.increase(1);
-        self.metrics.storage_disk_write_bytes.increase(bytes as u64);
-        self.metrics
-            .storage_disk_write_duration
-            .record(now.elapsed().as_secs_f64());
+            self.metrics.storage_disk_write.increase(1);
+            self.metrics.storage_disk_write_bytes.increase(bytes as u64);
+            self.metrics
+                .storage_disk_write_duration
+                .record(now.elapsed().as_secs_f64());
 
-        res
+            res
+        })
+        .await
     }
 
     #[cfg_attr(
@@ -211,21 +326,27 @@ impl Monitored<DirectFileDevice> {
     where
         B: IoBufMut,
     {
-        let now = Instant::now();
+        self.retry(buf, |buf| async move {
+            let permit = self.acquire().await;
+            let now = Instant::now();
 
-        let bytes = buf.len();
+            let bytes = buf.len();
 
-        let res = self.device.pread(buf, offset).await;
+            let res = self.device.pread(buf, offset).await;
+            drop(permit);
 
-        self.stats.record_disk_read(bytes);
+            self.stats.record_disk_read(bytes);
+            self.stats.record_disk_read_latency(now.elapsed());
 
-        self.metrics.storage_disk_read.increase(1);
-        self.metrics.storage_disk_read_bytes.increase(bytes as u64);
-        self.metrics
-            .storage_disk_read_duration
-            .record(now.elapsed().as_secs_f64());
+            self.metrics.storage_disk_read.increase(1);
+            self.metrics.storage_disk_read_bytes.increase(bytes as u64);
+            self.metrics
+                .storage_disk_read_duration
+                .record(now.elapsed().as_secs_f64());
 
-        res
+            res
+        })
+        .await
     }
 }
 
@@ -249,6 +370,180 @@ where
             device,
             stats: Arc::new(Statistics::new(IopsCounter::PerIo)),
             metrics: Arc::new(Metrics::noop()),
+            gate: Arc::new(RwLock::new(())),
+            limiter: None,
+            retry: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{
+        device::memory::{MemoryDevice, MemoryDeviceOptions},
+        error::Error,
+        io::{buffer::IoBuffer, PAGE},
+    };
+
+    /// A device whose next `failures` operations fail with the os error `errno`.
+    #[derive(Debug, Clone)]
+    struct FlakyDevice {
+        failures: Arc<AtomicUsize>,
+        errno: i32,
+        throttle: Throttle,
+    }
+
+    impl FlakyDevice {
+        fn result(&self) -> Result<()> {
+            match self
+                .failures
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            {
+                Ok(_) => Err(std::io::Error::from_raw_os_error(self.errno).into()),
+                Err(_) => Ok(()),
+            }
+        }
+    }
+
+    impl Dev for FlakyDevice {
+        type Config = ();
+
+        fn capacity(&self) -> usize {
+            0
+        }
+
+        fn region_size(&self) -> usize {
+            0
+        }
+
+        fn throttle(&self) -> &Throttle {
+            &self.throttle
+        }
+
+        async fn open(_: Self::Config, _: Runtime) -> Result<Self> {
+            unreachable!()
         }
+
+        async fn write<B>(&self, buf: B, _: RegionId, _: u64) -> (B, Result<()>)
+        where
+            B: IoBuf,
+        {
+            (buf, self.result())
+        }
+
+        async fn read<B>(&self, buf: B, _: RegionId, _: u64) -> (B, Result<()>)
+        where
+            B: IoBufMut,
+        {
+            (buf, self.result())
+        }
+
+        async fn flush(&self, _: Option<RegionId>) -> Result<()> {
+            self.result()
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_io_retry() {
+        let flaky = |errno: i32, retry: Option<RetryPolicy>| {
+            let failures = Arc::new(AtomicUsize::new(0));
+            let mut device = Monitored::new_for_test(FlakyDevice {
+                failures: failures.clone(),
+                errno,
+                throttle: Throttle::default(),
+            });
+            device.retry = retry;
+            (device, failures)
+        };
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_millis(1),
+        };
+
+        let (device, failures) = flaky(libc::EIO, Some(policy));
+        // Transient errors are retried until the operation succeeds.
+        failures.store(2, Ordering::Relaxed);
+        let (_, res) = device.write(IoBuffer::new(PAGE).into_shared_io_slice(), 0, 0).await;
+        res.unwrap();
+        assert_eq!(failures.load(Ordering::Relaxed), 0);
+        failures.store(2, Ordering::Relaxed);
+        device.flush(None).await.unwrap();
+        // The last error is returned once the attempts are exhausted.
+        failures.store(4, Ordering::Relaxed);
+        let (_, res) = device.read(IoBuffer::new(PAGE), 0, 0).await;
+        assert!(res.is_err());
+        assert_eq!(failures.load(Ordering::Relaxed), 1);
+
+        // Non-retryable errors fail right away.
+        let (device, failures) = flaky(libc::ENOSPC, Some(policy));
+        failures.store(2, Ordering::Relaxed);
+        assert!(device.flush(None).await.is_err());
+        assert_eq!(failures.load(Ordering::Relaxed), 1);
+
+        // Nothing is retried without a policy.
+        let (device, failures) = flaky(libc::EIO, None);
+        failures.store(2, Ordering::Relaxed);
+        assert!(device.flush(None).await.is_err());
+        assert_eq!(failures.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let io = |kind: std::io::ErrorKind| Error::Io(kind.into());
+        assert!(RetryPolicy::is_retryable(&io(std::io::ErrorKind::WriteZero)));
+        assert!(RetryPolicy::is_retryable(&io(std::io::ErrorKind::UnexpectedEof)));
+        assert!(RetryPolicy::is_retryable(&io(std::io::ErrorKind::TimedOut)));
+        assert!(!RetryPolicy::is_retryable(&io(std::io::ErrorKind::PermissionDenied)));
+        assert!(!RetryPolicy::is_retryable(&Error::CompressionAlgorithmNotSupported(0)));
+
+        let policy = RetryPolicy {
+            max_attempts: 8,
+            backoff: Duration::from_millis(10),
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(10));
+        assert_eq!(policy.backoff(3), Duration::from_millis(40));
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_io_concurrency() {
+        let device = Monitored::<MemoryDevice>::open(
+            MonitoredConfig {
+                config: MemoryDeviceOptions::new(4 * 1024 * 1024)
+                    .with_region_size(1024 * 1024)
+                    .into(),
+                metrics: Arc::new(Metrics::noop()),
+                io_concurrency: Some(2),
+                io_retry: None,
+            },
+            Runtime::current(),
+        )
+        .await
+        .unwrap();
+
+        let p1 = device.acquire().await;
+        let p2 = device.acquire().await;
+
+        // Operations queue once the limit is reached.
+        let read = device.read(IoBuffer::new(PAGE), 0, 0);
+        let mut read = std::pin::pin!(read);
+        assert!(tokio::time::timeout(Duration::from_millis(50), &mut read)
+            .await
+            .is_err());
+
+        drop(p1);
+        let (_, res) = tokio::time::timeout(Duration::from_secs(1), read).await.unwrap();
+        res.unwrap();
+
+        drop(p2);
+        let (_, res) = device.write(IoBuffer::new(PAGE).into_shared_io_slice(), 0, 0).await;
+        res.unwrap();
+        device.flush(None).await.unwrap();
+        assert_eq!(device.limiter.as_ref().unwrap().available_permits(), 2);
     }
 }
@@ -15,6 +15,7 @@
 use std::{fmt::Debug, future::Future, marker::PhantomData, sync::Arc};
 
 use auto_enums::auto_enum;
+use bytes::Bytes;
 use foyer_common::{
     code::{StorageKey, StorageValue},
     properties::Properties,
@@ -80,7 +81,11 @@ where
     type Properties = P;
 
     fn select(&self, _piece: &Piece<Self::Key, Self::Value, Self::Properties>, estimated_size: usize) -> Selection {
-        if estimated_size < self.threshold {
+        self.select_raw(0, estimated_size)
+    }
+
+    fn select_raw(&self, _hash: u64, size: usize) -> Selection {
+        if size < self.threshold {
             Selection::Left
         } else {
             Selection::Right
@@ -88,7 +93,7 @@ where
     }
 }
 
-#[expect(clippy::type_complexity)]
+#[expect(clippy::type_complexity, clippy::large_enum_variant)]
 pub enum EngineConfig<K, V, P>
 where
     K: StorageKey,
@@ -204,6 +209,15 @@ where
         }
     }
 
+    fn enqueue_raw(&self, hash: u64, key: Bytes, value: Bytes) -> bool {
+        match self {
+            EngineEnum::Noop(storage) => storage.enqueue_raw(hash, key, value),
+            EngineEnum::Large(storage) => storage.enqueue_raw(hash, key, value),
+            EngineEnum::Small(storage) => storage.enqueue_raw(hash, key, value),
+            EngineEnum::Mixed(storage) => storage.enqueue_raw(hash, key, value),
+        }
+    }
+
     #[auto_enum(Future)]
     fn load(&self, hash: u64) -> impl Future<Output = Result<Load<Self::Key, Self::Value>>> + Send + 'static {
         match self {
@@ -214,6 +228,19 @@ where
         }
     }
 
+    #[auto_enum(Future)]
+    fn load_many(
+        &self,
+        hashes: &[u64],
+    ) -> impl Future<Output = Vec<Result<Load<Self::Key, Self::Value>>>> + Send + 'static {
+        match self {
+            EngineEnum::Noop(storage) => storage.load_many(hashes),
+            EngineEnum::Large(storage) => storage.load_many(hashes),
+            EngineEnum::Small(storage) => storage.load_many(hashes),
+            EngineEnum::Mixed(storage) => storage.load_many(hashes),
+        }
+    }
+
     fn delete(&self, hash: u64) {
         match self {
             EngineEnum::Noop(storage) => storage.delete(hash),
@@ -15,6 +15,7 @@
 use std::{
     fmt::{Debug, Display},
     ops::Range,
+    time::Duration,
 };
 
 use foyer_common::code::CodeError;
@@ -68,6 +69,12 @@ pub enum Error {
     /// Compression algorithm not supported.
     #[error("compression algorithm not supported: {0}")]
     CompressionAlgorithmNotSupported(u8),
+    /// Invalid configuration.
+    #[error("invalid config: {0}")]
+    InvalidConfig(String),
+    /// Operation timed out.
+    #[error("timed out after {0:?}")]
+    Timeout(Duration),
     /// Other error.
     #[error(transparent)]
     Other(#[from] anyhow::Error),
@@ -0,0 +1,131 @@
+// Copyright 2025 foyer Project Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use futures_core::Stream;
+use tokio::sync::broadcast;
+
+use crate::{device::RegionId, small::set::SetId};
+
+/// Discrete lifecycle events of the disk cache, see [`crate::Store::subscribe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StoreEvent {
+    /// The large object disk cache starts to compact a region picked for eviction, i.e. to reinsert the live entries
+    /// of the region picked by the reinsertion picker.
+    CompactionStarted {
+        /// The region being compacted.
+        region: RegionId,
+    },
+    /// The large object disk cache finishes compacting a region.
+    CompactionFinished {
+        /// The region being compacted.
+        region: RegionId,
+        /// Count of the entries picked by the reinsertion picker and reinserted.
+        reinserted: usize,
+        /// Count of the entries not picked and dropped.
+        dropped: usize,
+    },
+    /// A region of the large object disk cache is reclaimed, and is clean for writing again.
+    RegionReclaimed {
+        /// The reclaimed region.
+        region: RegionId,
+    },
+    /// A set of the small object disk cache is corrupted on load, i.e. the lengths in the set header are out of range
+    /// or the checksum mismatches, the set is loaded as an empty set.
+    SetCorrupted {
+        /// The corrupted set.
+        set: SetId,
+    },
+    /// The small object disk cache starts to resize the sets in use.
+    ResizeStarted {
+        /// Count of the sets in use before resizing.
+        from: usize,
+        /// Target count of the sets in use.
+        to: usize,
+    },
+    /// The small object disk cache finishes resizing the sets in use.
+    ResizeFinished {
+        /// Count of the sets in use after resizing, less than the target if the resizing fails.
+        sets: usize,
+    },
+    /// The subscriber falls behind, the oldest `missed` events are dropped for it.
+    Lagged {
+        /// Count of the dropped events.
+        missed: u64,
+    },
+}
+
+/// The channel of [`StoreEvent`]s shared by the disk cache engines.
+///
+/// Each subscriber buffers up to [`EventBus::CAPACITY`] events. A slow subscriber lags behind instead of backing up
+/// the disk cache, the dropped events are reported as [`StoreEvent::Lagged`].
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<StoreEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(Self::CAPACITY);
+        Self { sender }
+    }
+}
+
+impl EventBus {
+    /// Count of the events buffered for each subscriber.
+    pub const CAPACITY: usize = 1024;
+
+    /// Deliver the event to the subscribers, the event is dropped if there is no subscriber.
+    pub fn emit(&self, event: StoreEvent) {
+        tracing::trace!(?event, "[event bus]: emit");
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribe to the events emitted from now on.
+    pub fn subscribe(&self) -> impl Stream<Item = StoreEvent> + Send + 'static {
+        futures_util::stream::unfold(self.sender.subscribe(), |mut receiver| async move {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(missed)) => StoreEvent::Lagged { missed },
+                Err(broadcast::error::RecvError::Closed) => return None,
+            };
+            Some((event, receiver))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_event_bus_lagged() {
+        let bus = EventBus::default();
+        // Events without subscribers are dropped.
+        bus.emit(StoreEvent::SetCorrupted { set: 0 });
+
+        let events = bus.subscribe();
+        for set in 0..EventBus::CAPACITY as SetId + 2 {
+            bus.emit(StoreEvent::SetCorrupted { set });
+        }
+        drop(bus);
+
+        let events = events.collect::<Vec<_>>().await;
+        assert_eq!(events.len(), EventBus::CAPACITY + 1);
+        assert_eq!(events[0], StoreEvent::Lagged { missed: 2 });
+        assert_eq!(events[1], StoreEvent::SetCorrupted { set: 2 });
+    }
+}
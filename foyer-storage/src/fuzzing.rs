@@ -0,0 +1,209 @@
+// Copyright 2025 foyer Project Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fuzzing harnesses for the `foyer-storage` crate.
+//!
+//! The harnesses take arbitrary bytes, so they can be driven by any fuzzer. The `cargo fuzz` targets live in the
+//! `fuzz` directory of the crate.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Arc,
+};
+
+use bytes::Bytes;
+use foyer_common::metrics::Metrics;
+
+use crate::{
+    io::{buffer::IoBuffer, PAGE},
+    serde::EntrySerializer,
+    small::{
+        batch::Item,
+        serde::EntryHeader,
+        set::{SetDeleteMode, SetLayout, SetStorage},
+    },
+    Compression,
+};
+
+/// Byte reader of the fuzzer input, every read returns `None` once the input is exhausted.
+struct Input<'a> {
+    data: &'a [u8],
+}
+
+impl Input<'_> {
+    fn u8(&mut self) -> Option<u8> {
+        let (&b, rest) = self.data.split_first()?;
+        self.data = rest;
+        Some(b)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes([self.u8()?, self.u8()?]))
+    }
+}
+
+/// An entry expected to be in the set, the key is used as the hash.
+struct Entry {
+    key: u64,
+    value: Vec<u8>,
+    len: usize,
+}
+
+/// Apply the operations decoded from `data` to a [`SetStorage`] and check it against a model after each operation.
+///
+/// The first byte picks the [`SetLayout`] and the [`SetDeleteMode`]. Each following operation is one of:
+///
+/// - apply: delete up to 3 random keys and insert up to 3 entries with random keys and value lengths, the inserted
+///   keys are deleted first like the small engine batches do;
+/// - reload: write the set header and load the set from its buffer;
+/// - compact: reclaim the tombstones.
+///
+/// After each operation, [`SetStorage::check_invariants`] and [`SetStorage::validate_bloom_filter`] must pass, every
+/// entry that is neither deleted nor wiped by the model must be readable with its value, and every other key must be
+/// missing. The entries reported wiped by [`SetStorage::apply`] must be exactly the ones wiped by the model.
+///
+/// The model wipes the oldest entries when the live entries and the new ones don't fit, which is what
+/// [`SetStorage::apply`] promises regardless of the layout, the delete mode, and the pending tombstones.
+pub fn set_storage(data: &[u8]) {
+    let mut input = Input { data };
+    let Some(config) = input.u8() else {
+        return;
+    };
+    let layout = if config & 1 == 0 {
+        SetLayout::Interleaved
+    } else {
+        SetLayout::Split
+    };
+    let delete_mode = match (config >> 1) & 3 {
+        0 => SetDeleteMode::Compact,
+        r => SetDeleteMode::Tombstone { ratio: r as f64 / 4.0 },
+    };
+
+    let metrics = Arc::new(Metrics::noop());
+    let load = |buffer: IoBuffer| {
+        let mut storage = SetStorage::load_with(buffer, 0, true, layout, metrics.clone());
+        storage.set_delete_mode(delete_mode);
+        storage
+    };
+    let mut buffer = IoBuffer::new(PAGE);
+    buffer.fill(0);
+    let mut storage = load(buffer);
+    let capacity = PAGE - SetStorage::SET_HEADER_SIZE;
+
+    let mut model = VecDeque::<Entry>::new();
+    let mut seen = HashSet::new();
+
+    while let Some(op) = input.u8() {
+        match op % 8 {
+            0..=5 => {
+                let mut deletes = HashSet::new();
+                for _ in 0..op % 4 {
+                    let Some(key) = input.u8() else { break };
+                    deletes.insert(key as u64);
+                }
+                let mut entries = Vec::<Entry>::new();
+                for _ in 0..(op >> 2) % 4 {
+                    let (Some(key), Some(len), Some(fill)) = (input.u8(), input.u16(), input.u8()) else {
+                        break;
+                    };
+                    let key = key as u64;
+                    let value = vec![fill; len as usize % (capacity + 64)];
+                    entries.retain(|e| e.key != key);
+                    entries.push(Entry { key, value, len: 0 });
+                }
+
+                let items = entries
+                    .iter_mut()
+                    .map(|e| {
+                        deletes.insert(e.key);
+                        let slice = serialize(e.key, &e.value);
+                        e.len = slice.len();
+                        Item {
+                            slice,
+                            hash: e.key,
+                            inserted_at: None,
+                        }
+                    })
+                    .collect();
+                let wiped = storage.apply(&deletes, items);
+
+                // Only the newest entries that fit in the set are appended.
+                model.retain(|e| !deletes.contains(&e.key));
+                let mut size = 0;
+                let mut skip = entries.len();
+                for e in entries.iter().rev() {
+                    if size + e.len > capacity {
+                        break;
+                    }
+                    size += e.len;
+                    skip -= 1;
+                }
+                let mut live = model.iter().map(|e| e.len).sum::<usize>();
+                let mut evicted = vec![];
+                while capacity - live < size {
+                    let e = model.pop_front().unwrap();
+                    live -= e.len;
+                    evicted.push(e.key);
+                }
+                assert_eq!(wiped, evicted);
+                seen.extend(deletes);
+                model.extend(entries.into_iter().skip(skip));
+            }
+            6 => {
+                storage.update();
+                storage = load(storage.into_io_buffer());
+            }
+            _ => storage.compact(),
+        }
+
+        storage.check_invariants();
+        assert!(storage.validate_bloom_filter());
+        assert_eq!(storage.entry_count(), model.len());
+        for e in model.iter() {
+            let (key, value) = storage.get::<u64, Vec<u8>>(e.key).unwrap().unwrap();
+            assert_eq!(key, e.key);
+            assert_eq!(value, e.value);
+        }
+        for &key in seen.iter().filter(|&&key| model.iter().all(|e| e.key != key)) {
+            assert!(
+                storage.get::<u64, Vec<u8>>(key).unwrap().is_none(),
+                "key {key} is not deleted"
+            );
+        }
+    }
+}
+
+fn serialize(key: u64, value: &Vec<u8>) -> Bytes {
+    let mut buf = vec![0; EntryHeader::ENTRY_HEADER_SIZE];
+    let info = EntrySerializer::serialize(&key, value, Compression::None, &mut buf).unwrap();
+    EntryHeader::new(key, info.key_len, info.value_len).write(&mut buf[..EntryHeader::ENTRY_HEADER_SIZE]);
+    Bytes::from(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rng, Rng};
+
+    use super::*;
+
+    #[test_log::test]
+    fn test_set_storage_fuzzing_harness() {
+        let mut rng = rng();
+        for _ in 0..256 {
+            let len = rng.random_range(0..1024);
+            let data = (0..len).map(|_| rng.random()).collect::<Vec<u8>>();
+            set_storage(&data);
+        }
+    }
+}
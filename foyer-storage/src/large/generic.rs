@@ -24,6 +24,7 @@ use std::{
     time::Instant,
 };
 
+use bytes::Bytes;
 #[cfg(feature = "tracing")]
 use fastrace::prelude::*;
 use foyer_common::{
@@ -48,6 +49,7 @@ use crate::{
     compress::Compression,
     device::{Dev, DevExt, MonitoredDevice, RegionId},
     error::{Error, Result},
+    event::EventBus,
     io::{buffer::IoBuffer, PAGE},
     large::{
         reclaimer::RegionCleaner,
@@ -57,10 +59,10 @@ use crate::{
     picker::{EvictionPicker, ReinsertionPicker},
     region::RegionManager,
     runtime::Runtime,
-    serde::EntryDeserializer,
+    serde::{EntryDeserializer, RawValue},
     statistics::Statistics,
     storage::Storage,
-    Load, Throttle,
+    EntryLocation, Load, Throttle,
 };
 
 pub struct GenericLargeStorageConfig<K, V>
@@ -81,9 +83,11 @@ where
     pub blob_index_size: usize,
     pub submit_queue_size_threshold: usize,
     pub clean_region_threshold: usize,
+    pub reserved_regions: usize,
     pub eviction_pickers: Vec<Box<dyn EvictionPicker>>,
     pub reinsertion_picker: Arc<dyn ReinsertionPicker>,
     pub tombstone_log_config: Option<TombstoneLogConfig>,
+    pub events: EventBus,
     pub runtime: Runtime,
     pub marker: PhantomData<(K, V)>,
 }
@@ -107,9 +111,11 @@ where
             .field("blob_index_size", &self.blob_index_size)
             .field("submit_queue_size_threshold", &self.submit_queue_size_threshold)
             .field("clean_region_threshold", &self.clean_region_threshold)
+            .field("reserved_regions", &self.reserved_regions)
             .field("eviction_pickers", &self.eviction_pickers)
             .field("reinsertion_pickers", &self.reinsertion_picker)
             .field("tombstone_log_config", &self.tombstone_log_config)
+            .field("events", &self.events)
             .field("runtime", &self.runtime)
             .finish()
     }
@@ -151,6 +157,8 @@ where
     submit_queue_size: Arc<AtomicUsize>,
     submit_queue_size_threshold: usize,
 
+    reserved_regions: usize,
+
     flush: bool,
 
     sequence: AtomicSequence,
@@ -196,6 +204,13 @@ where
         let device = config.device.clone();
         let metrics = device.metrics().clone();
 
+        // The reserved regions are kept clean by the reclaimers along with the clean region threshold, but only
+        // reinsertions can use them.
+        config.clean_region_threshold += config.reserved_regions;
+        device
+            .statistics()
+            .set_reserved_space(config.reserved_regions * device.region_size());
+
         let mut tombstones = vec![];
         let tombstone_log = match &config.tombstone_log_config {
             None => None,
@@ -290,6 +305,7 @@ where
                 reclaimers,
                 submit_queue_size,
                 submit_queue_size_threshold: config.submit_queue_size_threshold,
+                reserved_regions: config.reserved_regions,
                 flush: config.flush,
                 sequence,
                 runtime: config.runtime,
@@ -348,6 +364,11 @@ where
             return;
         }
 
+        if self.inner.reserved_regions > 0 && self.inner.region_manager.clean_regions() <= self.inner.reserved_regions {
+            self.inner.metrics.storage_lodc_enqueue_reserved.increase(1);
+            return;
+        }
+
         let sequence = self.inner.sequence.fetch_add(1, Ordering::Relaxed);
 
         self.inner.flushers[sequence as usize % self.inner.flushers.len()].submit(Submission::CacheEntry {
@@ -357,7 +378,44 @@ where
         });
     }
 
+    /// Locate the region of the entry with the hash from the in-memory index, without reading the device.
+    pub fn locate(&self, hash: u64) -> EntryLocation {
+        match self.inner.indexer.get(hash) {
+            Some(addr) => EntryLocation::Large { region: addr.region },
+            None => EntryLocation::Absent,
+        }
+    }
+
     fn load(&self, hash: u64) -> impl Future<Output = Result<Load<K, V>>> + Send + 'static {
+        self.load_with(hash, |buf, header| {
+            EntryDeserializer::deserialize::<K, V>(
+                buf,
+                header.key_len as _,
+                header.value_len as _,
+                header.compression,
+                Some(header.checksum),
+            )
+        })
+    }
+
+    /// Load the entry with the hash the same as [`GenericLargeStorage::load`], but keep the value as it is written.
+    pub fn load_raw(&self, hash: u64) -> impl Future<Output = Result<Load<K, RawValue>>> + Send + 'static {
+        self.load_with(hash, |buf, header| {
+            EntryDeserializer::deserialize_raw::<K>(
+                buf,
+                header.key_len as _,
+                header.value_len as _,
+                header.compression,
+                Some(header.checksum),
+            )
+        })
+    }
+
+    fn load_with<T, F>(&self, hash: u64, deserialize: F) -> impl Future<Output = Result<Load<K, T>>> + Send + 'static
+    where
+        T: Send + 'static,
+        F: FnOnce(&[u8], &EntryHeader) -> Result<(K, T)> + Send + 'static,
+    {
         tracing::trace!(hash, "[lodc]: load");
 
         let now = Instant::now();
@@ -424,13 +482,7 @@ where
 
             let (key, value) = {
                 let now = Instant::now();
-                let res = match EntryDeserializer::deserialize::<K, V>(
-                    &buf[EntryHeader::serialized_len()..],
-                    header.key_len as _,
-                    header.value_len as _,
-                    header.compression,
-                    Some(header.checksum),
-                ) {
+                let res = match deserialize(&buf[EntryHeader::serialized_len()..], &header) {
                     Ok(res) => res,
                     Err(e @ Error::MagicMismatch { .. })
                     | Err(e @ Error::ChecksumMismatch { .. })
@@ -581,6 +633,11 @@ where
         self.enqueue(piece, estimated_size)
     }
 
+    fn enqueue_raw(&self, _hash: u64, _key: Bytes, _value: Bytes) -> bool {
+        // Entries of the large object disk cache are checksummed and may be compressed, raw entries are not supported.
+        false
+    }
+
     fn load(&self, hash: u64) -> impl Future<Output = Result<Load<Self::Key, Self::Value>>> + Send + 'static {
         self.load(hash)
     }
@@ -649,6 +706,8 @@ mod tests {
                     .with_file_size(ByteSize::kib(16).as_u64() as _)
                     .into(),
                 metrics: Arc::new(Metrics::noop()),
+                io_concurrency: None,
+                io_retry: None,
             },
             runtime,
         )
@@ -678,12 +737,14 @@ mod tests {
             flushers: 1,
             reclaimers: 1,
             clean_region_threshold: 1,
+            reserved_regions: 0,
             eviction_pickers: vec![Box::<FifoPicker>::default()],
             reinsertion_picker,
             tombstone_log_config: None,
             buffer_pool_size: 16 * 1024 * 1024,
             blob_index_size: 4 * 1024,
             submit_queue_size_threshold: 16 * 1024 * 1024 * 2,
+            events: EventBus::default(),
             runtime: Runtime::new(None, None, Handle::current()),
             marker: PhantomData,
         };
@@ -707,12 +768,14 @@ mod tests {
             flushers: 1,
             reclaimers: 1,
             clean_region_threshold: 1,
+            reserved_regions: 0,
             eviction_pickers: vec![Box::<FifoPicker>::default()],
             reinsertion_picker: Arc::<RejectAllPicker>::default(),
             tombstone_log_config: Some(TombstoneLogConfigBuilder::new(path).with_flush(true).build()),
             buffer_pool_size: 16 * 1024 * 1024,
             blob_index_size: 4 * 1024,
             submit_queue_size_threshold: 16 * 1024 * 1024 * 2,
+            events: EventBus::default(),
             runtime: Runtime::new(None, None, Handle::current()),
             marker: PhantomData,
         };
@@ -27,6 +27,7 @@ use tokio::sync::{mpsc, oneshot, Semaphore, SemaphorePermit};
 use crate::{
     device::MonitoredDevice,
     error::Result,
+    event::{EventBus, StoreEvent},
     io::{
         buffer::{IoBuffer, OwnedSlice},
         PAGE,
@@ -74,6 +75,7 @@ impl Reclaimer {
             blob_index_size: config.blob_index_size,
             flush: config.flush,
             _metrics: metrics,
+            events: config.events.clone(),
             wait_rx,
             runtime: config.runtime.clone(),
         };
@@ -114,6 +116,7 @@ where
     flush: bool,
 
     _metrics: Arc<Metrics>,
+    events: EventBus,
 
     wait_rx: mpsc::UnboundedReceiver<oneshot::Sender<()>>,
 
@@ -173,6 +176,7 @@ where
         let id = region.id();
 
         tracing::debug!("[reclaimer]: Start reclaiming region {id}.");
+        self.events.emit(StoreEvent::CompactionStarted { region: id });
 
         let mut scanner = RegionScanner::new(region.clone(), self.blob_index_size);
         let mut picked_count = 0;
@@ -229,6 +233,11 @@ where
         }
 
         let unpicked_count = unpicked.len();
+        self.events.emit(StoreEvent::CompactionFinished {
+            region: id,
+            reinserted: picked_count,
+            dropped: unpicked_count,
+        });
 
         let waits = self.flushers.iter().map(|flusher| flusher.wait()).collect_vec();
         self.runtime.write().spawn(async move {
@@ -247,6 +256,7 @@ where
         region.statistics().reset();
 
         self.region_manager.mark_clean(id).await;
+        self.events.emit(StoreEvent::RegionReclaimed { region: id });
         // These operations should be atomic:
         //
         // 1. Reclaim runner releases 1 permit on finish. (+1)
@@ -123,6 +123,8 @@ mod tests {
                     .with_file_size(file_size)
                     .into(),
                 metrics: Arc::new(Metrics::noop()),
+                io_concurrency: None,
+                io_retry: None,
             },
             runtime,
         )
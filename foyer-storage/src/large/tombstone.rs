@@ -152,6 +152,8 @@ impl TombstoneLog {
                     .with_capacity(capacity)
                     .into(),
                 metrics: metrics.clone(),
+                io_concurrency: None,
+                io_retry: None,
             },
             runtime,
         )
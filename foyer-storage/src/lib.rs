@@ -17,15 +17,19 @@
 #![cfg_attr(feature = "nightly", feature(allocator_api))]
 #![cfg_attr(feature = "nightly", feature(write_all_vectored))]
 
+pub mod checksum;
 mod compress;
 mod device;
 mod engine;
 mod error;
+mod event;
 mod io;
 mod large;
+mod loader;
 mod picker;
 mod region;
 mod runtime;
+mod scan;
 mod serde;
 mod small;
 mod statistics;
@@ -37,3 +41,9 @@ pub use prelude::*;
 
 #[cfg(any(test, feature = "test_utils"))]
 pub mod test_utils;
+
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzzing;
+
+#[cfg(any(test, feature = "bench"))]
+pub mod bench;
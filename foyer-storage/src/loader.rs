@@ -0,0 +1,27 @@
+// Copyright 2025 foyer Project Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Debug;
+
+use futures_core::future::BoxFuture;
+
+/// The loader of the disk cache, which loads the entry from the backing source on disk cache miss.
+///
+/// With a loader registered, the disk cache works as a read-through cache, see [`crate::Store::get`].
+pub trait Loader<K, V>: Send + Sync + 'static + Debug {
+    /// Load the value of the given key from the backing source.
+    ///
+    /// Returns `Ok(None)` if the backing source doesn't have the key either.
+    fn load(&self, key: &K) -> BoxFuture<'static, anyhow::Result<Option<V>>>;
+}
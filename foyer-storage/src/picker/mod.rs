@@ -55,6 +55,19 @@ pub trait AdmissionPicker: Send + Sync + 'static + Debug {
     fn pick(&self, stats: &Arc<Statistics>, hash: u64) -> Pick;
 }
 
+/// The admission picker for the disk cache that is aware of whether the entry is probably already resident.
+///
+/// The resident hint comes from the bloom filter of the target set for the small object disk cache, and from the
+/// indexer for the large object disk cache. The bloom filter may return false-positive results, the downside of which
+/// is only skipping a re-write.
+///
+/// The picker is only consulted for the entries populated from the disk cache. Entries from the outer system may be
+/// updates of existing keys, they are always let through to replace the stale values on disk.
+pub trait ResidentAdmissionPicker: Send + Sync + 'static + Debug {
+    /// Decide whether to pick an entry by hash with the hint if the entry is probably resident in the disk cache.
+    fn pick(&self, stats: &Arc<Statistics>, hash: u64, resident: bool) -> Pick;
+}
+
 /// The reinsertion picker for the disk cache.
 pub trait ReinsertionPicker: Send + Sync + 'static + Debug {
     /// Decide whether to pick an entry by hash.
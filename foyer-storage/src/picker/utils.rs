@@ -27,7 +27,7 @@ use std::{
 use foyer_common::strict_assert;
 use itertools::Itertools;
 
-use super::{AdmissionPicker, EvictionInfo, EvictionPicker, Pick, ReinsertionPicker};
+use super::{AdmissionPicker, EvictionInfo, EvictionPicker, Pick, ReinsertionPicker, ResidentAdmissionPicker};
 use crate::{device::RegionId, io::throttle::IoThrottler, statistics::Statistics};
 
 /// Only admit on all chained admission pickers pick.
@@ -91,6 +91,16 @@ impl ReinsertionPicker for AdmitAllPicker {
     }
 }
 
+/// A picker that rejects the entries that are probably already resident in the disk cache.
+#[derive(Debug, Default)]
+pub struct RejectResidentPicker;
+
+impl ResidentAdmissionPicker for RejectResidentPicker {
+    fn pick(&self, _: &Arc<Statistics>, _: u64, resident: bool) -> Pick {
+        (!resident).into()
+    }
+}
+
 /// A picker that always returns `false`.
 #[derive(Debug, Default)]
 pub struct RejectAllPicker;
@@ -269,6 +279,34 @@ impl EvictionPicker for FifoPicker {
     }
 }
 
+/// A picker that pick region to eviction with a LRU behavior.
+///
+/// The evictable region with the least recent read or write is picked. Compared with [`FifoPicker`], regions with hot
+/// entries are kept longer.
+#[derive(Debug, Default)]
+pub struct LruPicker;
+
+impl EvictionPicker for LruPicker {
+    fn pick(&mut self, info: EvictionInfo<'_>) -> Option<RegionId> {
+        let res = info.evictable.iter().copied().min_by_key(|rid| {
+            info.regions[*rid as usize]
+                .statistics()
+                .last_access
+                .load(Ordering::Relaxed)
+        });
+        tracing::trace!("[lru picker]: pick {res:?}");
+        res
+    }
+
+    fn on_region_evictable(&mut self, _: EvictionInfo<'_>, region: RegionId) {
+        tracing::trace!("[lru picker]: {region} is evictable");
+    }
+
+    fn on_region_evict(&mut self, _: EvictionInfo<'_>, region: RegionId) {
+        tracing::trace!("[lru picker]: {region} is evicted");
+    }
+}
+
 /// Evict the region with the largest invalid data ratio.
 ///
 /// If the largest invalid data ratio is less than the threshold, no region will be picked.
@@ -331,7 +369,7 @@ mod tests {
     use std::collections::HashSet;
 
     use super::*;
-    use crate::{device::test_utils::NoopDevice, Region};
+    use crate::{device::test_utils::NoopDevice, IopsCounter, Region};
 
     #[test_log::test]
     fn test_fifo_picker() {
@@ -449,4 +487,44 @@ mod tests {
         assert_eq!(picker.pick(info(&regions, &evictable)), None);
         assert_eq!(picker.pick(info(&regions, &evictable)), None);
     }
+
+    #[test]
+    fn test_lru_picker() {
+        let mut picker = LruPicker;
+
+        let regions = (0..4)
+            .map(|rid| Region::new_for_test(rid, NoopDevice::monitored()))
+            .collect_vec();
+        for (rid, last_access) in [(0, 3), (1, 1), (2, 4), (3, 2)] {
+            regions[rid]
+                .statistics()
+                .last_access
+                .store(last_access, Ordering::Relaxed);
+        }
+
+        fn info<'a>(regions: &'a [Region], evictable: &'a HashSet<RegionId>) -> EvictionInfo<'a> {
+            EvictionInfo {
+                regions,
+                evictable,
+                clean: 0,
+            }
+        }
+
+        let mut evictable = HashSet::from_iter([0, 1, 2, 3]);
+        assert_eq!(picker.pick(info(&regions, &evictable)), Some(1));
+        evictable.remove(&1);
+        assert_eq!(picker.pick(info(&regions, &evictable)), Some(3));
+        evictable.remove(&3);
+        assert_eq!(picker.pick(info(&regions, &evictable)), Some(0));
+        evictable.clear();
+        assert_eq!(picker.pick(info(&regions, &evictable)), None);
+    }
+
+    #[test]
+    fn test_reject_resident_picker() {
+        let stats = Arc::new(Statistics::new(IopsCounter::per_io()));
+        let picker = RejectResidentPicker;
+        assert!(picker.pick(&stats, 42, false).admitted());
+        assert!(picker.pick(&stats, 42, true).rejected());
+    }
 }
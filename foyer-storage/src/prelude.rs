@@ -17,9 +17,11 @@ pub use crate::{
     device::{
         direct_file::{DirectFileDevice, DirectFileDeviceOptions},
         direct_fs::{DirectFsDevice, DirectFsDeviceOptions},
-        Dev, DevConfig, DevExt, IopsCounter, Throttle,
+        memory::{MemoryDevice, MemoryDeviceOptions},
+        Dev, DevConfig, DevExt, IopsCounter, RetryPolicy, Throttle,
     },
     error::{Error, Result},
+    event::StoreEvent,
     io::{
         buffer::{IoBuf, IoBufMut, IoBuffer, OwnedIoSlice, OwnedSlice, SharedIoSlice},
         throttle::IoThrottler,
@@ -28,19 +30,27 @@ pub use crate::{
         recover::RecoverMode,
         tombstone::{TombstoneLogConfig, TombstoneLogConfigBuilder},
     },
+    loader::Loader,
     picker::{
         utils::{
             AdmitAllPicker, ChainedAdmissionPicker, ChainedAdmissionPickerBuilder, FifoPicker, InvalidRatioPicker,
-            IoThrottlerPicker, IoThrottlerTarget, RejectAllPicker,
+            IoThrottlerPicker, IoThrottlerTarget, LruPicker, RejectAllPicker, RejectResidentPicker,
         },
-        AdmissionPicker, EvictionInfo, EvictionPicker, Pick, ReinsertionPicker,
+        AdmissionPicker, EvictionInfo, EvictionPicker, Pick, ReinsertionPicker, ResidentAdmissionPicker,
     },
     region::{Region, RegionStatistics},
     runtime::Runtime,
-    statistics::Statistics,
+    scan::{ScanOptions, ScanProgress},
+    serde::RawValue,
+    small::{
+        footer::{IntegrityMode, RegionFooter},
+        set::{read_set_entries, SetAlignment, SetBloomFilterRebuild, SetDeleteMode, SetId, SetLayout, SetReadOptions},
+        set_manager::{region_offset, SetResizePolicy, SetRouter, SetSizeClass},
+    },
+    statistics::{Statistics, StatisticsSnapshot},
     storage::{either::Order, Storage},
     store::{
-        DeviceOptions, Engine, LargeEngineOptions, Load, RuntimeOptions, SmallEngineOptions, Store, StoreBuilder,
-        TokioRuntimeOptions,
+        DeviceOptions, Engine, EntryLocation, LargeEngineOptions, Load, QuiesceGuard, RuntimeOptions,
+        SmallEngineOptions, Store, StoreBuilder, TokioRuntimeOptions,
     },
 };
@@ -18,7 +18,7 @@ use std::{
     future::Future,
     pin::Pin,
     sync::{
-        atomic::{AtomicBool, AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
     task::{Context, Poll},
@@ -50,6 +50,10 @@ pub struct RegionStatistics {
     pub invalid: AtomicUsize,
     /// Access count of the region.
     pub access: AtomicUsize,
+    /// Logical timestamp of the last read or write of the region.
+    ///
+    /// The timestamps are only comparable among the regions of the same disk cache.
+    pub last_access: AtomicU64,
     /// Marked as `true` if the region is about to be evicted by some eviction picker.
     pub probation: AtomicBool,
 }
@@ -58,6 +62,7 @@ impl RegionStatistics {
     pub(crate) fn reset(&self) {
         self.invalid.store(0, Ordering::Relaxed);
         self.access.store(0, Ordering::Relaxed);
+        self.last_access.store(0, Ordering::Relaxed);
         self.probation.store(false, Ordering::Relaxed);
     }
 }
@@ -66,6 +71,8 @@ impl RegionStatistics {
 struct RegionInner {
     device: MonitoredDevice,
     statistics: Arc<RegionStatistics>,
+    /// Logical clock shared by all regions of the same disk cache.
+    clock: Arc<AtomicU64>,
 }
 
 /// A region is a logical partition of a device. It is used to manage the device's storage space.
@@ -95,6 +102,7 @@ impl Region {
     where
         B: IoBuf,
     {
+        self.touch();
         self.inner.device.write(buf, self.id, offset).await
     }
 
@@ -103,9 +111,15 @@ impl Region {
         B: IoBufMut,
     {
         self.inner.statistics.access.fetch_add(1, Ordering::Relaxed);
+        self.touch();
         self.inner.device.read(buf, self.id, offset).await
     }
 
+    fn touch(&self) {
+        let now = self.inner.clock.fetch_add(1, Ordering::Relaxed) + 1;
+        self.inner.statistics.last_access.store(now, Ordering::Relaxed);
+    }
+
     pub(crate) async fn flush(&self) -> Result<()> {
         self.inner.device.flush(Some(self.id)).await
     }
@@ -117,6 +131,7 @@ impl Region {
         let inner = RegionInner {
             device,
             statistics: Arc::<RegionStatistics>::default(),
+            clock: Arc::<AtomicU64>::default(),
         };
         let inner = Arc::new(inner);
         Self { id, inner }
@@ -160,12 +175,14 @@ impl RegionManager {
         reclaim_semaphore: Arc<Semaphore>,
         metrics: Arc<Metrics>,
     ) -> Self {
+        let clock = Arc::<AtomicU64>::default();
         let regions = (0..device.regions() as RegionId)
             .map(|id| Region {
                 id,
                 inner: Arc::new(RegionInner {
                     device: device.clone(),
                     statistics: Arc::<RegionStatistics>::default(),
+                    clock: clock.clone(),
                 }),
             })
             .collect_vec();
@@ -278,12 +295,12 @@ impl RegionManager {
     }
 
     pub async fn mark_clean(&self, region: RegionId) {
-        self.inner
-            .clean_region_tx
-            .send_async(self.region(region).clone())
-            .await
-            .unwrap();
+        let region = self.region(region).clone();
+        let statistics = region.inner.device.statistics().clone();
+        let bytes = region.size();
+        self.inner.clean_region_tx.send_async(region).await.unwrap();
         self.inner.metrics.storage_region_clean.increase(1);
+        statistics.record_region_clean(bytes);
     }
 
     pub fn get_clean_region(&self) -> GetCleanRegionHandle {
@@ -301,6 +318,7 @@ impl RegionManager {
                     reclaim_semaphore.add_permits(1);
                 }
                 metrics.storage_region_clean.decrease(1);
+                region.inner.device.statistics().record_region_taken(region.size());
                 region
             }
             .boxed(),
@@ -316,7 +334,6 @@ impl RegionManager {
         self.inner.eviction.lock().evictable.len()
     }
 
-    #[expect(dead_code)]
     pub fn clean_regions(&self) -> usize {
         self.inner.clean_region_rx.len()
     }
@@ -0,0 +1,86 @@
+// Copyright 2025 foyer Project Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{num::NonZeroUsize, time::SystemTime};
+
+/// Options of a full scan of the disk cache, see [`crate::Store::scan_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    pub(crate) start: usize,
+    pub(crate) entries: bool,
+    pub(crate) throughput: Option<NonZeroUsize>,
+    pub(crate) iops: Option<NonZeroUsize>,
+}
+
+impl ScanOptions {
+    /// Create the options of a full scan with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skip the first `sets` sets, e.g. to resume an interrupted scan from [`ScanProgress::sets_done`] of its last
+    /// progress.
+    ///
+    /// The sets are scanned in a stable order as long as the sets in use are not resized.
+    ///
+    /// Default: `0`.
+    pub fn with_start(mut self, sets: usize) -> Self {
+        self.start = sets;
+        self
+    }
+
+    /// Enable/disable yielding the entries of each scanned set with the progress.
+    ///
+    /// Without the entries, only the set headers are inspected and no entry is deserialized.
+    ///
+    /// Default: `false`.
+    pub fn with_entries(mut self, entries: bool) -> Self {
+        self.entries = entries;
+        self
+    }
+
+    /// Set the max bytes per second read by the scan.
+    ///
+    /// Default: `0`, unlimited.
+    pub fn with_throughput(mut self, throughput: usize) -> Self {
+        self.throughput = NonZeroUsize::new(throughput);
+        self
+    }
+
+    /// Set the max sets per second read by the scan, each set is read with one I/O.
+    ///
+    /// Default: `0`, unlimited.
+    pub fn with_iops(mut self, iops: usize) -> Self {
+        self.iops = NonZeroUsize::new(iops);
+        self
+    }
+}
+
+/// Progress of a full scan of the disk cache after scanning a set, see [`crate::Store::scan_with_progress`].
+#[derive(Debug)]
+pub struct ScanProgress<K, V> {
+    /// Count of the sets scanned so far, including the skipped ones, see [`ScanOptions::with_start`].
+    pub sets_done: usize,
+    /// Count of the sets to scan.
+    pub sets_total: usize,
+    /// Count of the entries seen by this scan so far.
+    pub entries_seen: usize,
+    /// Bytes of the set data seen by this scan so far.
+    pub bytes_seen: usize,
+    /// Entries of the set just scanned, empty unless [`ScanOptions::with_entries`] is set.
+    pub entries: Vec<(K, V)>,
+    /// Insertion times of [`ScanProgress::entries`] in the same order, `None` if not recorded, see
+    /// [`crate::SmallEngineOptions::with_entry_inserted_at`].
+    pub inserted_at: Vec<Option<SystemTime>>,
+}
@@ -12,12 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{fmt::Debug, io::Write};
+use std::{
+    fmt::Debug,
+    io::{Read, Write},
+};
 
 use foyer_common::code::{StorageKey, StorageValue};
-use twox_hash::{XxHash32, XxHash64};
 
 use crate::{
+    checksum,
     compress::Compression,
     error::{Error, Result},
 };
@@ -27,11 +30,12 @@ pub struct Checksummer;
 
 impl Checksummer {
     pub fn checksum64(buf: &[u8]) -> u64 {
-        XxHash64::oneshot(0, buf)
+        checksum::checksum64(buf)
     }
 
-    pub fn checksum32(buf: &[u8]) -> u32 {
-        XxHash32::oneshot(0, buf)
+    /// Checksum of the concatenation of `bufs`, without copying them into a contiguous buffer.
+    pub fn checksum32_vectored(bufs: &[&[u8]]) -> u32 {
+        checksum::checksum32_vectored(bufs)
     }
 }
 
@@ -153,6 +157,62 @@ impl EntrySerializer {
     }
 }
 
+/// The encoded value of a disk cache entry as written to the device, see [`crate::Store::load_raw`].
+///
+/// The bytes are the value encoded by the value type the entry was written with, then compressed with
+/// [`RawValue::compression`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawValue {
+    bytes: Vec<u8>,
+    compression: Compression,
+}
+
+impl RawValue {
+    /// Create a raw value from the bytes compressed with `compression`.
+    pub fn new(bytes: Vec<u8>, compression: Compression) -> Self {
+        Self { bytes, compression }
+    }
+
+    /// The encoded value bytes, still compressed.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The compression algorithm the value is compressed with.
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Decompress the value, returns the value bytes as encoded by [`foyer_common::code::Code::encode`].
+    pub fn decompress(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        match self.compression {
+            Compression::None => buf.extend_from_slice(&self.bytes),
+            Compression::Zstd => {
+                zstd::Decoder::new(&self.bytes[..])
+                    .map_err(Error::from)?
+                    .read_to_end(&mut buf)
+                    .map_err(Error::from)?;
+            }
+            Compression::Lz4 => {
+                lz4::Decoder::new(&self.bytes[..])
+                    .map_err(Error::from)?
+                    .read_to_end(&mut buf)
+                    .map_err(Error::from)?;
+            }
+        }
+        Ok(buf)
+    }
+
+    /// Decompress and decode the value as `V`, which may differ from the value type the entry was written with.
+    pub fn decode<V>(&self) -> Result<V>
+    where
+        V: StorageValue,
+    {
+        EntryDeserializer::deserialize_value(&self.bytes, self.compression)
+    }
+}
+
 #[derive(Debug)]
 pub struct EntryDeserializer;
 
@@ -195,11 +255,42 @@ impl EntryDeserializer {
         Ok((key, value))
     }
 
+    /// Deserialize the key as [`EntryDeserializer::deserialize`], but keep the value as it is written.
+    pub fn deserialize_raw<K>(
+        buffer: &[u8],
+        ken_len: usize,
+        value_len: usize,
+        compression: Compression,
+        checksum: Option<u64>,
+    ) -> Result<(K, RawValue)>
+    where
+        K: StorageKey,
+    {
+        if buffer.len() < value_len + ken_len {
+            return Err(Error::OutOfRange {
+                valid: 0..buffer.len(),
+                get: 0..value_len + ken_len,
+            });
+        }
+
+        let value = RawValue::new(buffer[..value_len].to_vec(), compression);
+        let key = Self::deserialize_key(&buffer[value_len..value_len + ken_len])?;
+
+        if let Some(expected) = checksum {
+            let get = Checksummer::checksum64(&buffer[..value_len + ken_len]);
+            if expected != get {
+                return Err(Error::ChecksumMismatch { expected, get });
+            }
+        }
+
+        Ok((key, value))
+    }
+
     #[cfg_attr(
         feature = "tracing",
         fastrace::trace(name = "foyer::storage::serde::deserialize_key")
     )]
-    fn deserialize_key<K>(buf: &[u8]) -> Result<K>
+    pub fn deserialize_key<K>(buf: &[u8]) -> Result<K>
     where
         K: StorageKey,
     {
@@ -210,7 +301,7 @@ impl EntryDeserializer {
         feature = "tracing",
         fastrace::trace(name = "foyer::storage::serde::deserialize_value")
     )]
-    fn deserialize_value<V>(buf: &[u8], compression: Compression) -> Result<V>
+    pub fn deserialize_value<V>(buf: &[u8], compression: Compression) -> Result<V>
     where
         V: StorageValue,
     {
@@ -227,3 +318,49 @@ impl EntryDeserializer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use foyer_common::code::Code;
+
+    use super::*;
+
+    #[test]
+    fn test_deserialize_raw() {
+        let key = 42u64;
+        let value = vec![b'x'; 64 * 1024];
+        let mut encoded = vec![];
+        value.encode(&mut encoded).unwrap();
+
+        for compression in [Compression::None, Compression::Zstd, Compression::Lz4] {
+            let mut buf = vec![];
+            let info = EntrySerializer::serialize(&key, &value, compression, &mut buf).unwrap();
+            let checksum = Checksummer::checksum64(&buf);
+
+            let (k, raw) = EntryDeserializer::deserialize_raw::<u64>(
+                &buf,
+                info.key_len,
+                info.value_len,
+                compression,
+                Some(checksum),
+            )
+            .unwrap();
+            assert_eq!(k, key);
+            assert_eq!(raw.bytes(), &buf[..info.value_len]);
+            assert_eq!(raw.compression(), compression);
+            assert_eq!(raw.decompress().unwrap(), encoded);
+            assert_eq!(raw.decode::<Vec<u8>>().unwrap(), value);
+            // Decoded with another value type.
+            assert_eq!(raw.decode::<u64>().unwrap(), value.len() as u64);
+
+            let res = EntryDeserializer::deserialize_raw::<u64>(
+                &buf,
+                info.key_len,
+                info.value_len,
+                compression,
+                Some(checksum + 1),
+            );
+            assert!(matches!(res, Err(Error::ChecksumMismatch { .. })));
+        }
+    }
+}
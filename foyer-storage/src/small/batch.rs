@@ -33,6 +33,7 @@ use tokio::sync::oneshot;
 use zstd::zstd_safe::WriteBuf;
 
 use crate::{
+    error::{Error, Result},
     io::PAGE,
     serde::EntrySerializer,
     small::{serde::EntryHeader, set::SetId, set_manager::SetPicker},
@@ -62,6 +63,8 @@ pub struct BatchMut {
     sequence: Sequence,
 
     set_picker: SetPicker,
+    /// Max key len of the entries, see [`EntryHeader::max_key_len`].
+    max_key_len: usize,
 
     waiters: Vec<oneshot::Sender<()>>,
 
@@ -71,7 +74,7 @@ pub struct BatchMut {
 }
 
 impl BatchMut {
-    pub fn new(sets: usize, buffer_size: usize, metrics: Arc<Metrics>) -> Self {
+    pub fn new(set_picker: SetPicker, buffer_size: usize, metrics: Arc<Metrics>) -> Self {
         let buffer_size = bits::align_up(PAGE, buffer_size);
         let buffer = vec![0; buffer_size].into_boxed_slice();
 
@@ -80,14 +83,26 @@ impl BatchMut {
             buffer,
             len: 0,
             sequence: 0,
-            set_picker: SetPicker::new(sets),
+            set_picker,
+            max_key_len: EntryHeader::MAX_KEY_LEN,
             waiters: vec![],
             init: None,
             metrics,
         }
     }
 
-    pub fn insert<K, V, P>(&mut self, piece: Piece<K, V, P>, estimated_size: usize) -> bool
+    /// Limit the keys to [`EntryHeader::MAX_CHUNKED_KEY_LEN`] if the sets carry the chunk bit.
+    pub fn with_chunked(mut self, chunked: bool) -> Self {
+        self.max_key_len = EntryHeader::max_key_len(chunked);
+        self
+    }
+
+    /// Insert the entry, returns the reason if the entry is skipped.
+    ///
+    /// An entry that fails, e.g. its key or value fails to serialize or it doesn't fit in the buffer, is skipped and
+    /// simply not cached, so one bad entry never fails the other entries of the batch. The entries with the same hash
+    /// in the set are deleted even if the entry is skipped.
+    pub fn insert<K, V, P>(&mut self, piece: Piece<K, V, P>, estimated_size: usize) -> Result<()>
     where
         K: StorageKey,
         V: StorageValue,
@@ -110,7 +125,10 @@ impl BatchMut {
 
         if self.len + len > self.buffer.len() {
             tracing::trace!("[sodc batch]: insert {} ignored, reason: buffer overflow", piece.hash());
-            return false;
+            return Err(Error::OutOfRange {
+                valid: 0..self.buffer.len() - self.len,
+                get: 0..len,
+            });
         }
 
         let ser = Instant::now();
@@ -124,11 +142,25 @@ impl BatchMut {
             Ok(info) => info,
             Err(e) => {
                 tracing::warn!("[sodc batch]: serialize entry error: {e}");
-                return false;
+                return Err(e);
             }
         };
 
         assert_eq!(info.key_len + info.value_len + EntryHeader::ENTRY_HEADER_SIZE, len);
+        if info.key_len > self.max_key_len || info.value_len > EntryHeader::MAX_VALUE_LEN {
+            tracing::warn!(
+                "[sodc batch]: insert {} ignored, reason: key len {} or value len {} exceeds the limit",
+                piece.hash(),
+                info.key_len,
+                info.value_len
+            );
+            return Err(anyhow::anyhow!(
+                "key len {} or value len {} exceeds the limit",
+                info.key_len,
+                info.value_len
+            )
+            .into());
+        }
         let header = EntryHeader::new(piece.hash(), info.key_len as usize, info.value_len as usize);
         header.write(&mut self.buffer[self.len..self.len + EntryHeader::ENTRY_HEADER_SIZE]);
 
@@ -143,6 +175,87 @@ impl BatchMut {
         });
         self.len += len;
 
+        Ok(())
+    }
+
+    /// Insert an entry whose key and value are already serialized, `chunk` if it is a chunk or a manifest of an entry
+    /// striped across the sets.
+    pub fn insert_raw(&mut self, hash: u64, key: &[u8], value: &[u8], chunk: bool) -> bool {
+        tracing::trace!("[sodc batch]: insert raw entry");
+
+        if self.init.is_none() {
+            self.init = Some(Instant::now());
+        }
+        self.sequence += 1;
+
+        let sid = self.sid(hash);
+        let len = EntryHeader::ENTRY_HEADER_SIZE + value.len() + key.len();
+
+        let set = &mut self.sets.entry(sid).or_default();
+
+        set.deletes.insert(hash, self.sequence);
+
+        if key.len() > self.max_key_len || value.len() > EntryHeader::MAX_VALUE_LEN {
+            tracing::warn!(
+                "[sodc batch]: insert raw {hash} ignored, reason: key len {} or value len {} exceeds the limit",
+                key.len(),
+                value.len()
+            );
+            return false;
+        }
+
+        if self.len + len > self.buffer.len() {
+            tracing::trace!("[sodc batch]: insert raw {hash} ignored, reason: buffer overflow");
+            return false;
+        }
+
+        // Keep the same layout as `EntrySerializer`: header, value, key.
+        let header = EntryHeader::new(hash, key.len(), value.len()).with_chunk(chunk);
+        let mut cursor = self.len;
+        header.write(&mut self.buffer[cursor..cursor + EntryHeader::ENTRY_HEADER_SIZE]);
+        cursor += EntryHeader::ENTRY_HEADER_SIZE;
+        self.buffer[cursor..cursor + value.len()].copy_from_slice(value);
+        cursor += value.len();
+        self.buffer[cursor..cursor + key.len()].copy_from_slice(key);
+
+        set.items.push(ItemMut {
+            range: self.len..self.len + len,
+            hash,
+            sequence: self.sequence,
+        });
+        self.len += len;
+
+        true
+    }
+
+    /// Insert an entry that is already serialized with its header.
+    pub fn insert_entry(&mut self, hash: u64, entry: &[u8]) -> bool {
+        tracing::trace!("[sodc batch]: insert serialized entry");
+
+        if self.init.is_none() {
+            self.init = Some(Instant::now());
+        }
+        self.sequence += 1;
+
+        let sid = self.sid(hash);
+        let set = &mut self.sets.entry(sid).or_default();
+
+        set.deletes.insert(hash, self.sequence);
+
+        if self.len + entry.len() > self.buffer.len() {
+            tracing::trace!("[sodc batch]: insert serialized {hash} ignored, reason: buffer overflow");
+            return false;
+        }
+
+        self.buffer[self.len..self.len + entry.len()].copy_from_slice(entry);
+
+        set.items.push(ItemMut {
+            range: self.len..self.len + entry.len(),
+            hash,
+            sequence: self.sequence,
+        });
+        self.len += entry.len();
+
         true
     }
 
@@ -171,10 +284,31 @@ impl BatchMut {
         self.set_picker.sid(hash)
     }
 
+    /// Group the following entries with the given routing, after the sets are resized.
+    ///
+    /// Entries already in the batch are grouped with the old routing, they are routed again when applied.
+    pub fn set_set_picker(&mut self, set_picker: SetPicker) {
+        self.set_picker = set_picker;
+    }
+
     pub fn is_empty(&self) -> bool {
         self.init.is_none()
     }
 
+    /// The time when the first submission of the batch is received.
+    pub fn init(&self) -> Option<Instant> {
+        self.init
+    }
+
+    pub fn has_waiters(&self) -> bool {
+        !self.waiters.is_empty()
+    }
+
+    /// Bytes left in the buffer of the batch.
+    pub fn remaining(&self) -> usize {
+        self.buffer.len() - self.len
+    }
+
     pub fn rotate(&mut self) -> Option<Batch> {
         if self.is_empty() {
             return None;
@@ -199,6 +333,7 @@ impl BatchMut {
                     .map(|item| Item {
                         slice: buffer.slice(item.range),
                         hash: item.hash,
+                        inserted_at: None,
                     })
                     .collect_vec();
                 let deletes = batch.deletes.keys().copied().collect();
@@ -222,6 +357,8 @@ impl BatchMut {
 pub struct Item {
     pub slice: Bytes,
     pub hash: u64,
+    /// Insertion time of the entry moved from another set, `None` for a new entry, see [`super::set::SetEntryFields`].
+    pub inserted_at: Option<u64>,
 }
 
 impl Debug for Item {
@@ -250,3 +387,71 @@ pub struct Batch {
     pub waiters: Vec<oneshot::Sender<()>>,
     pub init: Option<Instant>,
 }
+
+#[cfg(test)]
+mod tests {
+    use foyer_common::code::{Code, CodeError};
+    use foyer_memory::TestProperties;
+
+    use super::*;
+
+    /// A value that fails to encode if it is poisoned.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Poisonable(Option<u64>);
+
+    impl Code for Poisonable {
+        fn encode(&self, writer: &mut impl std::io::Write) -> std::result::Result<(), CodeError> {
+            match self.0 {
+                Some(v) => v.encode(writer),
+                None => Err(CodeError::Other("poisoned".into())),
+            }
+        }
+
+        fn decode(reader: &mut impl std::io::Read) -> std::result::Result<Self, CodeError> {
+            u64::decode(reader).map(|v| Self(Some(v)))
+        }
+
+        fn estimated_size(&self) -> usize {
+            8
+        }
+    }
+
+    #[test]
+    fn test_batch_insert_failures() {
+        let mut batch = BatchMut::new(SetPicker::new(4), 4096, Arc::new(Metrics::noop()));
+
+        let res = [Some(1u64), None, Some(3), None, Some(5)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let piece = Piece::detached(i as u64, Poisonable(v), i as u64, TestProperties::default());
+                // The estimated size of the small object disk cache engine is the exact serialized size.
+                batch.insert(piece, 16)
+            })
+            .collect_vec();
+        assert_eq!(
+            res.iter().map(|r| r.is_ok()).collect_vec(),
+            vec![true, false, true, false, true]
+        );
+
+        let piece = Piece::detached(5u64, Poisonable(Some(5)), 5u64, TestProperties::default());
+        assert!(matches!(batch.insert(piece, 4096), Err(Error::OutOfRange { .. })));
+
+        let batch = batch.rotate().unwrap();
+        let mut items = batch
+            .sets
+            .values()
+            .flat_map(|set| set.items.iter().map(|item| item.hash))
+            .collect_vec();
+        items.sort();
+        assert_eq!(items, vec![0, 2, 4]);
+        // The skipped entries still delete the stale entries with the same hashes.
+        let mut deletions = batch
+            .sets
+            .values()
+            .flat_map(|set| set.deletions.iter().copied())
+            .collect_vec();
+        deletions.sort();
+        assert_eq!(deletions, vec![0, 1, 2, 3, 4, 5]);
+    }
+}
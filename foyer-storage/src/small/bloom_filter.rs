@@ -21,9 +21,13 @@ macro_rules! bloom_filter {
         paste! {
             $(
                 /// A [<$type>] bloom filter with N hash hashers.
+                ///
+                /// The seed is mixed into the bit derivation of each hasher, and is not persisted with the filter.
+                /// Seed `0` is the default bit layout.
                 #[derive(Debug, Clone, PartialEq, Eq)]
                 pub struct [<BloomFilter $suffix>]<const N: usize> {
                     data: [$type; N],
+                    seed: u64,
                 }
 
                 impl<const N: usize> Default for [<BloomFilter $suffix>]<N> {
@@ -33,18 +37,32 @@ macro_rules! bloom_filter {
                 }
 
                 impl<const N: usize> [<BloomFilter $suffix>]<N> {
-                    const BYTES: usize = $type::BITS as usize / u8::BITS as usize * N;
+                    pub const BYTES: usize = $type::BITS as usize / u8::BITS as usize * N;
 
                     pub fn new() -> Self {
+                        Self::with_seed(0)
+                    }
+
+                    /// Create a bloom filter whose bits are derived with the given seed.
+                    ///
+                    /// Filters with different seeds set different bits for the same hash, so their false positives are
+                    /// not correlated.
+                    pub fn with_seed(seed: u64) -> Self {
                         Self {
                             data: [0; N],
+                            seed,
                         }
                     }
 
                     pub fn read(raw: &[u8]) -> Self {
+                        Self::read_with_seed(raw, 0)
+                    }
+
+                    /// Read a bloom filter that is written with the given seed.
+                    pub fn read_with_seed(raw: &[u8], seed: u64) -> Self {
                         let mut data = [0; N];
                         data.copy_from_slice(unsafe { std::slice::from_raw_parts(raw.as_ptr() as *const $type, N) });
-                        Self { data }
+                        Self { data, seed }
                     }
 
                     pub fn write(&self, raw: &mut [u8]) {
@@ -54,7 +72,7 @@ macro_rules! bloom_filter {
                     pub fn insert(&mut self, hash: u64) {
                         tracing::trace!("[bloom filter]: insert hash {hash}");
                         for i in 0..N {
-                            let seed = twang_mix64(i as _);
+                            let seed = twang_mix64(i as _) ^ self.seed;
                             let hash = combine_hashes(hash, seed);
                             let bit = hash as usize % $type::BITS as usize;
                             self.data[i] |= 1 << bit;
@@ -63,7 +81,7 @@ macro_rules! bloom_filter {
 
                     pub fn lookup(&self, hash: u64) -> bool {
                         for i in 0..N {
-                            let seed = twang_mix64(i as _);
+                            let seed = twang_mix64(i as _) ^ self.seed;
                             let hash = combine_hashes(hash, seed) as $type;
                             let bit = hash as usize % $type::BITS as usize;
                             if self.data[i] & (1 << bit) == 0 {
@@ -73,6 +91,15 @@ macro_rules! bloom_filter {
                         true
                     }
 
+                    /// Estimate the false positive rate of a filter with the given count of inserted hashes.
+                    ///
+                    /// Each hasher sets one bit of its own word, so a lookup is a false positive if the probed bit of
+                    /// every word is set by other hashes.
+                    pub fn estimated_false_positive_rate(entries: usize) -> f64 {
+                        let bits = $type::BITS as f64;
+                        (1.0 - (1.0 - 1.0 / bits).powi(entries.min(i32::MAX as usize) as i32)).powi(N as i32)
+                    }
+
                     pub fn clear(&mut self) {
                         tracing::trace!("[bloom filter]: clear");
                         self.data = [0; N];
@@ -183,6 +210,17 @@ macro_rules! test_bloom_filter {
                         );
                     }
 
+                    #[test]
+                    fn [<test_bloom_filter_ $suffix:lower _estimated_false_positive_rate>]() {
+                        assert_eq!([<BloomFilter $suffix>]::<N>::estimated_false_positive_rate(0), 0.0);
+                        let mut last = 0.0;
+                        for entries in 1..256 {
+                            let rate = [<BloomFilter $suffix>]::<N>::estimated_false_positive_rate(entries);
+                            assert!(rate > last && rate < 1.0);
+                            last = rate;
+                        }
+                    }
+
                     #[test]
                     fn [<test_bloom_filter_ $suffix:lower _read_write>]() {
                         let mut buf = [0; [<BloomFilter $suffix>]::<N>::BYTES];
@@ -192,6 +230,28 @@ macro_rules! test_bloom_filter {
                         let bf2 = [<BloomFilter $suffix>]::<N>::read(&buf);
                         assert_eq!(bf, bf2);
                     }
+
+                    #[test]
+                    fn [<test_bloom_filter_ $suffix:lower _seed>]() {
+                        let mut bf = [<BloomFilter $suffix>]::<N>::new();
+                        let mut bf0 = [<BloomFilter $suffix>]::<N>::with_seed(0);
+                        let mut bf1 = [<BloomFilter $suffix>]::<N>::with_seed(114514);
+                        for hash in [1, 2, 3, 42] {
+                            bf.insert(hash);
+                            bf0.insert(hash);
+                            bf1.insert(hash);
+                        }
+                        // The default seed keeps the bit layout.
+                        assert_eq!(bf, bf0);
+                        assert_ne!(bf.data, bf1.data);
+                        for hash in [1, 2, 3, 42] {
+                            assert!(bf1.lookup(hash));
+                        }
+
+                        let mut buf = [0; [<BloomFilter $suffix>]::<N>::BYTES];
+                        bf1.write(&mut buf);
+                        assert_eq!(bf1, [<BloomFilter $suffix>]::<N>::read_with_seed(&buf, 114514));
+                    }
                 )*
             }
 
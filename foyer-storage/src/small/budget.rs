@@ -0,0 +1,164 @@
+// Copyright 2025 foyer Project Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{sync::Arc, time::Instant};
+
+use foyer_common::metrics::Metrics;
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+
+/// Budget of the bytes of the serialized entries held in the write buffers, shared by all flushers.
+///
+/// A request larger than the whole budget takes the whole budget, so it waits for all other buffers to be released
+/// instead of waiting forever.
+///
+/// The bytes are held by the entries combined in the batches of the flushers until the batches are flushed. A request
+/// that has to wait raises the pressure of the budget, see [`SerializeBufferBudget::subscribe`], so the other flushers
+/// flush their batches right away instead of holding the bytes until their flush intervals elapse.
+#[derive(Debug, Clone)]
+pub struct SerializeBufferBudget {
+    semaphore: Arc<Semaphore>,
+    capacity: usize,
+    /// Count of the requests that had to wait.
+    pressure: Arc<watch::Sender<u64>>,
+    metrics: Arc<Metrics>,
+}
+
+impl SerializeBufferBudget {
+    pub fn new(capacity: usize, metrics: Arc<Metrics>) -> Self {
+        // Permits are acquired in `u32`, so is the capacity.
+        let capacity = capacity.min(u32::MAX as usize);
+        Self {
+            semaphore: Arc::new(Semaphore::new(capacity)),
+            capacity,
+            pressure: Arc::new(watch::Sender::new(0)),
+            metrics,
+        }
+    }
+
+    fn permits(&self, bytes: usize) -> u32 {
+        bytes.min(self.capacity) as _
+    }
+
+    /// Acquire `bytes` from the budget without waiting, `None` if the budget is exhausted.
+    pub fn try_acquire(&self, bytes: usize) -> Option<SerializeBufferPermit> {
+        let permit = self
+            .semaphore
+            .clone()
+            .try_acquire_many_owned(self.permits(bytes))
+            .ok()?;
+        Some(self.permit(permit))
+    }
+
+    /// Acquire `bytes` from the budget, wait until enough bytes are released if the budget is exhausted.
+    ///
+    /// The pressure of the budget is raised before waiting.
+    pub async fn acquire(&self, bytes: usize) -> SerializeBufferPermit {
+        let now = Instant::now();
+        let permits = self.permits(bytes);
+        let permit = match self.semaphore.clone().try_acquire_many_owned(permits) {
+            Ok(permit) => permit,
+            Err(_) => {
+                self.pressure.send_modify(|pressure| *pressure += 1);
+                self.semaphore.clone().acquire_many_owned(permits).await.unwrap()
+            }
+        };
+        self.metrics
+            .storage_sodc_serialize_buffer_wait_duration
+            .record(now.elapsed().as_secs_f64());
+        self.permit(permit)
+    }
+
+    fn permit(&self, permit: OwnedSemaphorePermit) -> SerializeBufferPermit {
+        self.metrics
+            .storage_sodc_serialize_buffer_usage
+            .increase(permit.num_permits() as _);
+        SerializeBufferPermit {
+            permit,
+            metrics: self.metrics.clone(),
+        }
+    }
+
+    /// Subscribe to the pressure of the budget, the receiver is notified each time a request has to wait.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.pressure.subscribe()
+    }
+
+    /// Bytes acquired and not released yet.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn usage(&self) -> usize {
+        self.capacity - self.semaphore.available_permits()
+    }
+
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// Bytes acquired from the [`SerializeBufferBudget`], released on drop.
+#[derive(Debug)]
+pub struct SerializeBufferPermit {
+    permit: OwnedSemaphorePermit,
+    metrics: Arc<Metrics>,
+}
+
+impl Drop for SerializeBufferPermit {
+    fn drop(&mut self) {
+        self.metrics
+            .storage_sodc_serialize_buffer_usage
+            .decrease(self.permit.num_permits() as _);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_serialize_buffer_budget() {
+        let budget = SerializeBufferBudget::new(100, Arc::new(Metrics::noop()));
+
+        let p1 = budget.try_acquire(60).unwrap();
+        assert_eq!(budget.usage(), 60);
+        assert!(budget.try_acquire(60).is_none());
+
+        let mut pressure = budget.subscribe();
+        let b = budget.clone();
+        let handle = tokio::spawn(async move { b.acquire(60).await });
+        tokio::time::timeout(Duration::from_secs(1), pressure.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!handle.is_finished());
+
+        drop(p1);
+        let p2 = handle.await.unwrap();
+        assert_eq!(budget.usage(), 60);
+        drop(p2);
+        assert_eq!(budget.usage(), 0);
+
+        // A request that doesn't wait leaves the pressure untouched.
+        drop(budget.acquire(60).await);
+        assert!(!pressure.has_changed().unwrap());
+
+        // A request larger than the budget takes the whole budget.
+        let p3 = budget.acquire(1000).await;
+        assert_eq!(budget.usage(), budget.capacity());
+        assert!(budget.try_acquire(1).is_none());
+        drop(p3);
+        assert!(budget.try_acquire(0).is_some());
+    }
+}
@@ -0,0 +1,183 @@
+// Copyright 2025 foyer Project Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fs::File, io::Write, path::Path};
+
+use bytes::{Buf, BufMut};
+
+use super::{bloom_filter::BloomFilterU64, set::SetId};
+use crate::{error::Result, serde::Checksummer};
+
+#[repr(align(8))]
+struct AlignedBloomFilter([u8; 32]);
+
+/// A checkpointed set of the in-memory index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetCheckpoint {
+    pub sid: SetId,
+    /// Content checksum of the set when the bloom filter is taken, see `SetStorage::content_checksum`.
+    pub checksum: u32,
+    pub bloom_filter: BloomFilterU64<4>,
+}
+
+/// Checkpoint of the in-memory index of the small object disk cache, i.e. the loose bloom filters of the sets.
+///
+/// The sets are not scanned when the small object disk cache is opened, the loose bloom filters are restored from the
+/// checkpoint instead. The checkpoint is only valid with the same watermark, set size and routing.
+///
+/// Format:
+///
+/// ```plain
+/// | magic (8B) | watermark (16B) | set size (8B) | base (8B) | data (8B) | sets (8B) |
+/// | sid (8B) | checksum (4B) | bloom filter (32B) | ... | checksum of all above (4B) |
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexCheckpoint {
+    pub watermark: u128,
+    pub set_size: usize,
+    pub base: usize,
+    pub data: usize,
+    pub sets: Vec<SetCheckpoint>,
+}
+
+impl IndexCheckpoint {
+    const MAGIC: u64 = 0x20250714c4ec4b01;
+    const HEADER_SIZE: usize = 8 + 16 + 8 + 8 + 8 + 8;
+    const SET_SIZE: usize = 8 + 4 + 32;
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(Self::HEADER_SIZE + self.sets.len() * Self::SET_SIZE + 4);
+        buf.put_u64(Self::MAGIC);
+        buf.put_u128(self.watermark);
+        buf.put_u64(self.set_size as _);
+        buf.put_u64(self.base as _);
+        buf.put_u64(self.data as _);
+        buf.put_u64(self.sets.len() as _);
+        for set in self.sets.iter() {
+            buf.put_u64(set.sid);
+            buf.put_u32(set.checksum);
+            let mut bloom_filter = [0; 32];
+            set.bloom_filter.write(&mut bloom_filter);
+            buf.put_slice(&bloom_filter);
+        }
+        let checksum = Checksummer::checksum32_vectored(&[&buf]);
+        buf.put_u32(checksum);
+        buf
+    }
+
+    /// Decode the checkpoint, returns `None` if the checkpoint is corrupted.
+    pub fn decode(raw: &[u8]) -> Option<Self> {
+        if raw.len() < Self::HEADER_SIZE + 4 {
+            return None;
+        }
+        let (data, mut checksum) = raw.split_at(raw.len() - 4);
+        if Checksummer::checksum32_vectored(&[data]) != checksum.get_u32() {
+            return None;
+        }
+
+        let mut buf = data;
+        if buf.get_u64() != Self::MAGIC {
+            return None;
+        }
+        let watermark = buf.get_u128();
+        let set_size = buf.get_u64() as usize;
+        let base = buf.get_u64() as usize;
+        let data = buf.get_u64() as usize;
+        let count = buf.get_u64() as usize;
+        if buf.remaining() != count.checked_mul(Self::SET_SIZE)? {
+            return None;
+        }
+        let sets = (0..count)
+            .map(|_| {
+                let sid = buf.get_u64();
+                let checksum = buf.get_u32();
+                // The bloom filter is read as `u64`s, copy it to an aligned buffer first.
+                let mut aligned = AlignedBloomFilter([0; 32]);
+                buf.copy_to_slice(&mut aligned.0);
+                let bloom_filter = BloomFilterU64::read(&aligned.0);
+                SetCheckpoint {
+                    sid,
+                    checksum,
+                    bloom_filter,
+                }
+            })
+            .collect();
+
+        Some(Self {
+            watermark,
+            set_size,
+            base,
+            data,
+            sets,
+        })
+    }
+
+    /// Write the checkpoint to a temporary file and rename it to `path`, so a crash never leaves a partial checkpoint.
+    ///
+    /// The temporary file is synced before the rename, so the checkpoint is durable once renamed.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&self.encode())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Read the checkpoint from `path`, returns `None` if there is no valid checkpoint.
+    pub fn read(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(raw) => Ok(Self::decode(&raw)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_checkpoint_encode_decode() {
+        let mut bloom_filter = BloomFilterU64::new();
+        bloom_filter.insert(42);
+        let checkpoint = IndexCheckpoint {
+            watermark: 114514,
+            set_size: 4096,
+            base: 3,
+            data: 5,
+            sets: vec![
+                SetCheckpoint {
+                    sid: 1,
+                    checksum: 0xdeadbeef,
+                    bloom_filter: bloom_filter.clone(),
+                },
+                SetCheckpoint {
+                    sid: 4,
+                    checksum: 0,
+                    bloom_filter: BloomFilterU64::new(),
+                },
+            ],
+        };
+
+        let mut raw = checkpoint.encode();
+        assert_eq!(IndexCheckpoint::decode(&raw), Some(checkpoint));
+
+        // Corrupted or truncated checkpoints are ignored.
+        raw[20] ^= 1;
+        assert_eq!(IndexCheckpoint::decode(&raw), None);
+        assert_eq!(IndexCheckpoint::decode(&raw[..10]), None);
+    }
+}
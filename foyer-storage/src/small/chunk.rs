@@ -0,0 +1,166 @@
+// Copyright 2025 foyer Project Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::{Buf, BufMut};
+
+use crate::checksum::checksum64;
+
+/// Manifest of a value striped across the sets of the small object disk cache, see
+/// `SmallEngineOptions::with_chunking`.
+///
+/// The encoded value is split into chunks of the chunk size, only the last chunk may be shorter. Each chunk is written
+/// as an entry with the encoded key and the chunk as the value, under the hash [`ChunkManifest::chunk_hash`]. The
+/// manifest is written as an entry with the encoded key and the encoded manifest as the value, under the hash
+/// [`ChunkManifest::manifest_hash`]. The derived hashes route the chunks and the manifest to different sets, so a value
+/// of `N` chunks is read from up to `N + 1` sets.
+///
+/// Format (big-endian):
+///
+/// ```plain
+/// | magic (4B) | chunk size (4B) | chunks (4B) | value len (8B) | value checksum (8B) |
+/// ```
+///
+/// The value checksum is [`checksum64`] of the whole encoded value. The chunks of a value may be evicted, or replaced
+/// by the chunks of a newer value of the same key, independently of the manifest, so the reassembled value is only
+/// taken if it matches the length and the checksum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkManifest {
+    /// Bytes of each chunk except the last one.
+    pub chunk_size: usize,
+    /// Count of the chunks.
+    pub chunks: usize,
+    /// Bytes of the encoded value.
+    pub value_len: usize,
+    /// [`checksum64`] of the encoded value.
+    pub checksum: u64,
+}
+
+impl ChunkManifest {
+    const MAGIC: u32 = 0xf007c401;
+    pub const SIZE: usize = 4 + 4 + 4 + 8 + 8;
+
+    /// Index used to derive the manifest hash, never used by a chunk.
+    const MANIFEST_INDEX: u32 = u32::MAX;
+    /// Max count of the chunks of a value, so the chunk indices never reach the manifest index.
+    pub const MAX_CHUNKS: usize = Self::MANIFEST_INDEX as _;
+
+    /// Create the manifest of the encoded value split into chunks of `chunk_size` bytes.
+    pub fn new(value: &[u8], chunk_size: usize) -> Self {
+        Self {
+            chunk_size,
+            chunks: value.len().div_ceil(chunk_size),
+            value_len: value.len(),
+            checksum: checksum64(value),
+        }
+    }
+
+    /// Hash of the manifest entry of the entry with the hash.
+    pub fn manifest_hash(hash: u64) -> u64 {
+        Self::derive(hash, Self::MANIFEST_INDEX)
+    }
+
+    /// Hash of the `index`-th chunk entry of the entry with the hash.
+    pub fn chunk_hash(hash: u64, index: usize) -> u64 {
+        debug_assert!(index < Self::MAX_CHUNKS);
+        Self::derive(hash, index as _)
+    }
+
+    fn derive(hash: u64, index: u32) -> u64 {
+        let mut buf = [0; 12];
+        (&mut buf[..8]).put_u64(hash);
+        (&mut buf[8..]).put_u32(index);
+        checksum64(&buf)
+    }
+
+    /// Range of the `index`-th chunk in the encoded value.
+    pub fn chunk_range(&self, index: usize) -> std::ops::Range<usize> {
+        let start = index * self.chunk_size;
+        start..(start + self.chunk_size).min(self.value_len)
+    }
+
+    pub fn write(&self, mut buf: impl BufMut) {
+        buf.put_u32(Self::MAGIC);
+        buf.put_u32(self.chunk_size as _);
+        buf.put_u32(self.chunks as _);
+        buf.put_u64(self.value_len as _);
+        buf.put_u64(self.checksum);
+    }
+
+    /// Decode the manifest, returns `None` if the manifest is corrupted.
+    pub fn read(raw: &[u8]) -> Option<Self> {
+        if raw.len() != Self::SIZE {
+            return None;
+        }
+        let mut buf = raw;
+        if buf.get_u32() != Self::MAGIC {
+            return None;
+        }
+        let chunk_size = buf.get_u32() as usize;
+        let chunks = buf.get_u32() as usize;
+        let value_len = buf.get_u64() as usize;
+        let checksum = buf.get_u64();
+        if chunk_size == 0 || chunks != value_len.div_ceil(chunk_size) {
+            return None;
+        }
+        Some(Self {
+            chunk_size,
+            chunks,
+            value_len,
+            checksum,
+        })
+    }
+
+    /// Verify the reassembled value against the manifest.
+    pub fn verify(&self, value: &[u8]) -> bool {
+        value.len() == self.value_len && checksum64(value) == self.checksum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_manifest_serde() {
+        let value = (0..10000).map(|i| i as u8).collect::<Vec<_>>();
+        let manifest = ChunkManifest::new(&value, 4096);
+        assert_eq!(manifest.chunks, 3);
+        assert_eq!(manifest.chunk_range(2), 8192..10000);
+
+        let mut buf = vec![];
+        manifest.write(&mut buf);
+        assert_eq!(buf.len(), ChunkManifest::SIZE);
+        assert_eq!(ChunkManifest::read(&buf), Some(manifest.clone()));
+
+        let value = (0..manifest.chunks)
+            .flat_map(|i| value[manifest.chunk_range(i)].to_vec())
+            .collect::<Vec<_>>();
+        assert!(manifest.verify(&value));
+        assert!(!manifest.verify(&value[..9999]));
+
+        buf[0] ^= 1;
+        assert_eq!(ChunkManifest::read(&buf), None);
+    }
+
+    #[test]
+    fn test_chunk_hashes() {
+        let hash = 114514;
+        let mut hashes = (0..16).map(|i| ChunkManifest::chunk_hash(hash, i)).collect::<Vec<_>>();
+        hashes.push(ChunkManifest::manifest_hash(hash));
+        hashes.push(hash);
+        hashes.sort();
+        hashes.dedup();
+        assert_eq!(hashes.len(), 18);
+    }
+}
@@ -12,23 +12,40 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{fmt::Debug, future::Future, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
+use bytes::Bytes;
 use foyer_common::{
     code::{StorageKey, StorageValue},
     metrics::Metrics,
     properties::Properties,
 };
 use foyer_memory::Piece;
-use futures_util::future::try_join_all;
-use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use parking_lot::RwLock;
+use tokio::sync::{oneshot, watch, OwnedSemaphorePermit, Semaphore};
 
 use super::{
     batch::{Batch, BatchMut, SetBatch},
+    budget::{SerializeBufferBudget, SerializeBufferPermit},
     generic::GenericSmallStorageConfig,
+    serde::EntryHeader,
     set_manager::SetManager,
 };
-use crate::error::{Error, Result};
+use crate::{
+    error::{Error, Result},
+    serde::EntrySerializer,
+    Compression,
+};
 
 pub enum Submission<K, V, P>
 where
@@ -40,12 +57,31 @@ where
         piece: Piece<K, V, P>,
         estimated_size: usize,
     },
+    RawInsertion {
+        hash: u64,
+        key: Bytes,
+        value: Bytes,
+        /// If the entry is a chunk or a manifest of an entry striped across the sets.
+        chunk: bool,
+    },
     Deletion {
         hash: u64,
     },
     Wait {
         tx: oneshot::Sender<()>,
     },
+    /// An entry serialized with its header and recorded in the write buffer, `None` for a deletion.
+    Buffered {
+        hash: u64,
+        entry: Option<Bytes>,
+        sequence: u64,
+    },
+    /// An insertion waiting for the serialize buffer budget before it is recorded in the write buffer.
+    Deferred {
+        submission: Box<Submission<K, V, P>>,
+        bytes: usize,
+        sequence: u64,
+    },
 }
 
 impl<K, V, P> Debug for Submission<K, V, P>
@@ -63,12 +99,58 @@ where
                 .debug_struct("Insertion")
                 .field("estimated_size", estimated_size)
                 .finish(),
+            Self::RawInsertion {
+                hash,
+                key,
+                value,
+                chunk,
+            } => f
+                .debug_struct("RawInsertion")
+                .field("hash", hash)
+                .field("key_len", &key.len())
+                .field("value_len", &value.len())
+                .field("chunk", chunk)
+                .finish(),
             Self::Deletion { hash } => f.debug_struct("Deletion").field("hash", hash).finish(),
             Self::Wait { .. } => f.debug_struct("Wait").finish(),
+            Self::Buffered { hash, entry, sequence } => f
+                .debug_struct("Buffered")
+                .field("hash", hash)
+                .field("len", &entry.as_ref().map(|entry| entry.len()))
+                .field("sequence", sequence)
+                .finish(),
+            Self::Deferred {
+                submission,
+                bytes,
+                sequence,
+            } => f
+                .debug_struct("Deferred")
+                .field("submission", submission)
+                .field("bytes", bytes)
+                .field("sequence", sequence)
+                .finish(),
         }
     }
 }
 
+/// Entries submitted to a flusher with a flush interval that are not written to the sets yet.
+///
+/// The entries are kept serialized, so they can be read before they are flushed. `None` marks a pending deletion.
+///
+/// With a serialize buffer budget, each serialized entry holds its bytes of the budget until it is released.
+#[derive(Debug, Default)]
+struct WriteBuffer {
+    entries: HashMap<u64, (u64, Option<Bytes>, Option<SerializeBufferPermit>)>,
+    sequence: u64,
+}
+
+impl WriteBuffer {
+    /// Release the entries that are written to the sets with the submissions up to `sequence`.
+    fn release(&mut self, sequence: u64) {
+        self.entries.retain(|_, (s, _, _)| *s > sequence);
+    }
+}
+
 pub struct Flusher<K, V, P>
 where
     K: StorageKey,
@@ -76,6 +158,12 @@ where
     P: Properties,
 {
     tx: flume::Sender<Submission<K, V, P>>,
+    write_buffer: Option<Arc<RwLock<WriteBuffer>>>,
+    budget: Option<SerializeBufferBudget>,
+    /// Count of the deferred submissions not recorded in the write buffer yet.
+    deferred: Arc<AtomicUsize>,
+    /// Max key len of the entries, see [`EntryHeader::max_key_len`].
+    max_key_len: usize,
 }
 
 impl<K, V, P> Flusher<K, V, P>
@@ -84,17 +172,41 @@ where
     V: StorageValue,
     P: Properties,
 {
-    pub fn open(config: &GenericSmallStorageConfig<K, V>, set_manager: SetManager, metrics: Arc<Metrics>) -> Self {
+    pub fn open(
+        config: &GenericSmallStorageConfig<K, V>,
+        set_manager: SetManager,
+        budget: Option<SerializeBufferBudget>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
         let (tx, rx) = flume::unbounded();
 
-        let buffer_size = config.buffer_pool_size / config.flushers;
+        // Each set size class has its own flushers.
+        let buffer_size = config.buffer_pool_size / config.flushers / config.set_size_classes.len().max(1);
 
-        let batch = BatchMut::new(set_manager.sets() as _, buffer_size, metrics.clone());
+        let chunked = config.chunk_size.is_some();
+        let max_key_len = EntryHeader::max_key_len(chunked);
+        let batch = BatchMut::new(set_manager.set_picker(), buffer_size, metrics.clone()).with_chunked(chunked);
+
+        let write_buffer = config
+            .flush_interval
+            .map(|_| Arc::new(RwLock::new(WriteBuffer::default())));
+        // Only the serialized entries in the write buffer are budgeted.
+        let budget = budget.filter(|_| write_buffer.is_some());
+        let deferred = Arc::new(AtomicUsize::new(0));
 
         let runner = Runner {
             rx,
             batch,
             flight: Arc::new(Semaphore::new(1)),
+            apply_concurrency: config.apply_concurrency,
+            flush_interval: config.flush_interval,
+            write_buffer: write_buffer.clone(),
+            pressure: budget.as_ref().map(SerializeBufferBudget::subscribe),
+            pressed: false,
+            budget: budget.clone(),
+            deferred: deferred.clone(),
+            max_key_len,
+            sequence: 0,
             set_manager,
             metrics,
         };
@@ -105,16 +217,136 @@ where
             }
         });
 
-        Self { tx }
+        Self {
+            tx,
+            write_buffer,
+            budget,
+            deferred,
+            max_key_len,
+        }
     }
 
     pub fn submit(&self, submission: Submission<K, V, P>) {
+        let Some(write_buffer) = self.write_buffer.as_ref() else {
+            return self.send(submission);
+        };
+
+        let mut permit = None;
+        if let Some(budget) = self.budget.as_ref() {
+            let bytes = match &submission {
+                Submission::Insertion { estimated_size, .. } => EntryHeader::ENTRY_HEADER_SIZE + estimated_size,
+                Submission::RawInsertion { key, value, .. } => EntryHeader::ENTRY_HEADER_SIZE + key.len() + value.len(),
+                _ => 0,
+            };
+            if bytes > 0 {
+                // Keep deferring while there are deferred submissions, so the insertions are recorded in order.
+                permit = match self.deferred.load(Ordering::Acquire) {
+                    0 => budget.try_acquire(bytes),
+                    _ => None,
+                };
+                if permit.is_none() {
+                    return self.defer(write_buffer, submission, bytes);
+                }
+            }
+        }
+
+        let (hash, entry) = match submission {
+            Submission::Insertion { piece, .. } => (
+                piece.hash(),
+                Self::serialize(piece.hash(), piece.key(), piece.value(), self.max_key_len),
+            ),
+            Submission::RawInsertion {
+                hash,
+                key,
+                value,
+                chunk,
+            } => (hash, Self::serialize_raw(hash, &key, &value, chunk, self.max_key_len)),
+            Submission::Deletion { hash } => (hash, None),
+            submission => return self.send(submission),
+        };
+
+        // Send within the lock, so the sequences are received in order.
+        let mut write_buffer = write_buffer.write();
+        write_buffer.sequence += 1;
+        let sequence = write_buffer.sequence;
+        write_buffer.entries.insert(hash, (sequence, entry.clone(), permit));
+        self.send(Submission::Buffered { hash, entry, sequence });
+    }
+
+    /// Let the runner serialize the insertion after the budget is acquired.
+    ///
+    /// The submitter never waits for the budget, and the entry is not visible to [`Flusher::lookup`] until it is
+    /// serialized.
+    fn defer(&self, write_buffer: &RwLock<WriteBuffer>, submission: Submission<K, V, P>, bytes: usize) {
+        let mut write_buffer = write_buffer.write();
+        write_buffer.sequence += 1;
+        let sequence = write_buffer.sequence;
+        self.deferred.fetch_add(1, Ordering::AcqRel);
+        self.send(Submission::Deferred {
+            submission: Box::new(submission),
+            bytes,
+            sequence,
+        });
+    }
+
+    fn send(&self, submission: Submission<K, V, P>) {
         tracing::trace!("[sodc flusher]: submit task: {submission:?}");
         if let Err(e) = self.tx.send(submission) {
             tracing::error!("[sodc flusher]: error raised when submitting task, error: {e}");
         }
     }
 
+    /// Get the pending entry of `hash` in the write buffer, `Some(None)` if the entry is pending to be deleted.
+    ///
+    /// The entry is serialized with its header.
+    pub fn lookup(&self, hash: u64) -> Option<Option<Bytes>> {
+        let write_buffer = self.write_buffer.as_ref()?.read();
+        write_buffer.entries.get(&hash).map(|(_, entry, _)| entry.clone())
+    }
+
+    /// Serialize the entry for the write buffer, returns `None` if the entry cannot be inserted.
+    ///
+    /// An entry that cannot be inserted still deletes the old one, the same as [`BatchMut::insert`].
+    fn serialize(hash: u64, key: &K, value: &V, max_key_len: usize) -> Option<Bytes> {
+        let mut buf = vec![0; EntryHeader::ENTRY_HEADER_SIZE];
+        let info = match EntrySerializer::serialize(key, value, Compression::None, &mut buf) {
+            Ok(info) => info,
+            Err(e) => {
+                tracing::warn!("[sodc flusher]: serialize entry error: {e}");
+                return None;
+            }
+        };
+        if info.key_len > max_key_len || info.value_len > EntryHeader::MAX_VALUE_LEN {
+            tracing::warn!(
+                "[sodc flusher]: insert {hash} ignored, reason: key len {} or value len {} exceeds the limit",
+                info.key_len,
+                info.value_len
+            );
+            return None;
+        }
+        EntryHeader::new(hash, info.key_len, info.value_len).write(&mut buf[..EntryHeader::ENTRY_HEADER_SIZE]);
+        Some(Bytes::from(buf))
+    }
+
+    fn serialize_raw(hash: u64, key: &[u8], value: &[u8], chunk: bool, max_key_len: usize) -> Option<Bytes> {
+        if key.len() > max_key_len || value.len() > EntryHeader::MAX_VALUE_LEN {
+            tracing::warn!(
+                "[sodc flusher]: insert raw {hash} ignored, reason: key len {} or value len {} exceeds the limit",
+                key.len(),
+                value.len()
+            );
+            return None;
+        }
+        // Keep the same layout as `EntrySerializer`: header, value, key.
+        let mut buf = vec![0; EntryHeader::ENTRY_HEADER_SIZE];
+        EntryHeader::new(hash, key.len(), value.len())
+            .with_chunk(chunk)
+            .write(&mut buf[..]);
+        buf.extend_from_slice(value);
+        buf.extend_from_slice(key);
+        Some(Bytes::from(buf))
+    }
+
     pub fn wait(&self) -> impl Future<Output = ()> + Send + 'static {
         let (tx, rx) = oneshot::channel();
         self.submit(Submission::Wait { tx });
@@ -133,6 +365,20 @@ where
     rx: flume::Receiver<Submission<K, V, P>>,
     batch: BatchMut,
     flight: Arc<Semaphore>,
+    apply_concurrency: usize,
+
+    flush_interval: Option<Duration>,
+    write_buffer: Option<Arc<RwLock<WriteBuffer>>>,
+    budget: Option<SerializeBufferBudget>,
+    /// Pressure of the budget, see [`SerializeBufferBudget::subscribe`].
+    pressure: Option<watch::Receiver<u64>>,
+    /// If a request of the budget waits while the batch holds entries, the batch is flushed without waiting for the
+    /// flush interval then.
+    pressed: bool,
+    deferred: Arc<AtomicUsize>,
+    max_key_len: usize,
+    /// Sequence of the last buffered submission received.
+    sequence: u64,
 
     set_manager: SetManager,
 
@@ -148,17 +394,20 @@ where
     pub async fn run(mut self) -> Result<()> {
         loop {
             let flight = self.flight.clone();
+            let deadline = self.deadline();
             tokio::select! {
                 biased;
-                Ok(permit) = flight.acquire_owned(), if !self.batch.is_empty() => {
-                    // TODO(MrCroxx): `rotate()` should always return a `Some(..)` here.
-                    if let Some(batch) = self.batch.rotate() {
-                        self.commit(batch, permit).await;
-                    }
+                Ok(permit) = flight.acquire_owned(), if self.is_ready() => {
+                    self.flush(permit).await;
                 }
                 Ok(submission) = self.rx.recv_async() => {
-                    self.submit(submission);
+                    self.submit(submission).await;
                 }
+                true = Self::pressed(&mut self.pressure), if !self.pressed => {
+                    // The combined entries hold the budget another request waits for.
+                    self.pressed = !self.batch.is_empty();
+                }
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now).into()), if deadline.is_some() && !self.is_ready() => {}
                 // Graceful shutdown.
                 else => break,
             }
@@ -166,23 +415,151 @@ where
         Ok(())
     }
 
-    fn submit(&mut self, submission: Submission<K, V, P>) {
-        let report = |enqueued: bool| {
+    /// Without a flush interval, the batch is committed as soon as the last one is finished. Otherwise, the batch is
+    /// combined until the flush interval elapses, there are waiters, or a request of the budget waits.
+    fn is_ready(&self) -> bool {
+        match (self.flush_interval, self.batch.init()) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(interval), Some(init)) => self.pressed || self.batch.has_waiters() || init.elapsed() >= interval,
+        }
+    }
+
+    /// Wait until the pressure of the budget is raised, never completes without a budget.
+    async fn pressed(pressure: &mut Option<watch::Receiver<u64>>) -> bool {
+        match pressure {
+            Some(pressure) => pressure.changed().await.is_ok(),
+            None => std::future::pending().await,
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        Some(self.batch.init()? + self.flush_interval?)
+    }
+
+    async fn flush(&mut self, permit: OwnedSemaphorePermit) {
+        let sequence = self.sequence;
+        // TODO(MrCroxx): `rotate()` should always return a `Some(..)` here.
+        if let Some(batch) = self.batch.rotate() {
+            self.pressed = false;
+            self.batch.set_set_picker(self.set_manager.set_picker());
+            self.commit(batch, permit).await;
+            if let Some(write_buffer) = self.write_buffer.as_ref() {
+                write_buffer.write().release(sequence);
+            }
+        }
+    }
+
+    async fn submit(&mut self, submission: Submission<K, V, P>) {
+        let report = |metrics: &Metrics, enqueued: bool| {
             if !enqueued {
-                self.metrics.storage_queue_buffer_overflow.increase(1);
+                metrics.storage_queue_buffer_overflow.increase(1);
             }
         };
 
         match submission {
-            Submission::Insertion { piece, estimated_size } => report(self.batch.insert(piece, estimated_size)),
+            Submission::Insertion { piece, estimated_size } => match self.batch.insert(piece, estimated_size) {
+                Ok(()) => {}
+                Err(Error::OutOfRange { .. }) => report(&self.metrics, false),
+                // The entry fails to serialize or exceeds the limits, it is skipped and the batch goes on.
+                Err(_) => self.metrics.storage_error.increase(1),
+            },
+            Submission::RawInsertion {
+                hash,
+                key,
+                value,
+                chunk,
+            } => report(&self.metrics, self.batch.insert_raw(hash, &key, &value, chunk)),
             Submission::Deletion { hash } => self.batch.delete(hash),
             Submission::Wait { tx } => self.batch.wait(tx),
+            Submission::Buffered { hash, entry, sequence } => {
+                match entry {
+                    Some(entry) => {
+                        self.reserve(entry.len()).await;
+                        report(&self.metrics, self.batch.insert_entry(hash, &entry));
+                    }
+                    None => self.batch.delete(hash),
+                }
+                self.sequence = sequence;
+            }
+            Submission::Deferred {
+                submission,
+                bytes,
+                sequence,
+            } => {
+                self.submit_deferred(*submission, bytes, sequence).await;
+                self.deferred.fetch_sub(1, Ordering::AcqRel);
+            }
+        }
+    }
+
+    /// Flush the combined entries first if an entry of `len` bytes doesn't fit.
+    async fn reserve(&mut self, len: usize) {
+        if !self.batch.is_empty() && self.batch.remaining() < len {
+            let permit = self.flight.clone().acquire_owned().await.unwrap();
+            self.flush(permit).await;
+        }
+    }
+
+    async fn submit_deferred(&mut self, submission: Submission<K, V, P>, bytes: usize, sequence: u64) {
+        let (Some(write_buffer), Some(budget)) = (self.write_buffer.clone(), self.budget.clone()) else {
+            unreachable!("deferred submission without write buffer budget");
+        };
+
+        // Release the budget held by the combined entries first, or the runner may wait for itself.
+        if !self.batch.is_empty() {
+            let permit = self.flight.clone().acquire_owned().await.unwrap();
+            self.flush(permit).await;
         }
+        let permit = budget.acquire(bytes).await;
+
+        let (hash, entry) = match submission {
+            Submission::Insertion { piece, .. } => (
+                piece.hash(),
+                Flusher::<K, V, P>::serialize(piece.hash(), piece.key(), piece.value(), self.max_key_len),
+            ),
+            Submission::RawInsertion {
+                hash,
+                key,
+                value,
+                chunk,
+            } => (
+                hash,
+                Flusher::<K, V, P>::serialize_raw(hash, &key, &value, chunk, self.max_key_len),
+            ),
+            _ => unreachable!("only insertions are deferred"),
+        };
+
+        {
+            let mut write_buffer = write_buffer.write();
+            // Never shadow a newer submission of the same entry, e.g. a deletion which is not deferred.
+            if write_buffer.entries.get(&hash).is_none_or(|(s, _, _)| *s < sequence) {
+                write_buffer
+                    .entries
+                    .insert(hash, (sequence, entry.clone(), Some(permit)));
+            }
+        }
+
+        match entry {
+            Some(entry) => {
+                self.reserve(entry.len()).await;
+                if !self.batch.insert_entry(hash, &entry) {
+                    self.metrics.storage_queue_buffer_overflow.increase(1);
+                }
+            }
+            None => self.batch.delete(hash),
+        }
+        self.sequence = sequence;
     }
 
     pub async fn commit(&self, batch: Batch, permit: OwnedSemaphorePermit) {
         tracing::trace!("[sodc flusher] commit batch: {batch:?}");
 
+        // Sets of a batch are disjoint, they can be applied concurrently.
+        let concurrency = match self.apply_concurrency {
+            0 => batch.sets.len().max(1),
+            concurrency => concurrency,
+        };
         let futures = batch.sets.into_iter().map(|(sid, SetBatch { deletions, items })| {
             let set_manager = self.set_manager.clone();
             async move {
@@ -192,7 +569,11 @@ where
             }
         });
 
-        if let Err(e) = try_join_all(futures).await {
+        if let Err(e) = stream::iter(futures)
+            .buffer_unordered(concurrency)
+            .try_collect::<Vec<_>>()
+            .await
+        {
             tracing::error!("[sodc flusher]: error raised when committing batch, error: {e}");
         }
 
@@ -0,0 +1,162 @@
+// Copyright 2025 foyer Project Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::{Buf, BufMut};
+
+use super::set::SetId;
+use crate::{checksum::checksum32, device::RegionId, io::PAGE};
+
+/// Integrity checks of the small object disk cache on top of the checksum of each set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IntegrityMode {
+    /// Only each set is checksummed, see `SmallEngineOptions::with_checksum_on_write`.
+    #[default]
+    Set,
+    /// Each region also ends with a [`RegionFooter`] that checksums the set checksums of the region together with the
+    /// layout of the region. The footers are verified when the store is opened.
+    ///
+    /// The per-set checksums cannot tell a set written at the wrong place, or a write torn across the set boundaries
+    /// that leaves valid sets behind. The footer catches both, at the cost of rewriting the footer on each set write
+    /// and reading the header of every set on open.
+    RegionFooter,
+}
+
+/// Footer at the end of each region of the small object disk cache with [`IntegrityMode::RegionFooter`].
+///
+/// The footer takes the last [`RegionFooter::size`] bytes of the region, a multiple of 4 KiB. The sets are laid out back
+/// to back from the start of the region as usual, but only in the space before the footer, see
+/// [`RegionFooter::layout`].
+///
+/// Format (big-endian):
+///
+/// ```plain
+/// | checksum (4B) | magic (8B) | region (4B) | set size (4B) | first set id (8B) | sets (4B) |
+/// | set checksum (4B) | ... | padding |
+/// ```
+///
+/// The checksum is [`checksum32`] of the footer after it up to the last set checksum. The set checksums
+/// are the checksums stored in the first 4 bytes of the sets of the region, in the order of the set ids. The meta set
+/// is not checksummed by the footer, its set checksum is always `0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionFooter {
+    /// The region the footer belongs to.
+    pub region: RegionId,
+    /// Size of each set of the region in bytes.
+    pub set_size: usize,
+    /// Id of the first set of the region.
+    pub first: SetId,
+    /// Checksums of the sets of the region.
+    pub checksums: Vec<u32>,
+}
+
+impl RegionFooter {
+    const MAGIC: u64 = 0x20251014f007e401;
+    const HEADER_SIZE: usize = 4 + 8 + 4 + 4 + 8 + 4;
+
+    /// Count of the sets a region can hold besides its footer, and the size of the footer in bytes.
+    pub fn layout(set_size: usize, region_size: usize) -> (usize, usize) {
+        let mut sets = region_size / set_size;
+        loop {
+            let size = Self::size(sets);
+            if sets == 0 || sets * set_size + size <= region_size {
+                return (sets, size);
+            }
+            sets -= 1;
+        }
+    }
+
+    /// Size of the footer of a region with `sets` sets in bytes.
+    pub fn size(sets: usize) -> usize {
+        (Self::HEADER_SIZE + sets * 4).div_ceil(PAGE) * PAGE
+    }
+
+    /// Encode the footer to the start of `buf`, `buf` must be at least [`RegionFooter::size`] bytes.
+    pub fn write(&self, buf: &mut [u8]) {
+        let len = Self::HEADER_SIZE + self.checksums.len() * 4;
+        let mut body = &mut buf[4..len];
+        body.put_u64(Self::MAGIC);
+        body.put_u32(self.region);
+        body.put_u32(self.set_size as _);
+        body.put_u64(self.first);
+        body.put_u32(self.checksums.len() as _);
+        for checksum in self.checksums.iter() {
+            body.put_u32(*checksum);
+        }
+        let checksum = checksum32(&buf[4..len]);
+        (&mut buf[..4]).put_u32(checksum);
+    }
+
+    /// Decode the footer, returns `None` if the footer is missing or corrupted.
+    pub fn read(raw: &[u8]) -> Option<Self> {
+        if raw.len() < Self::HEADER_SIZE {
+            return None;
+        }
+        let mut buf = raw;
+        let checksum = buf.get_u32();
+        if buf.get_u64() != Self::MAGIC {
+            return None;
+        }
+        let region = buf.get_u32();
+        let set_size = buf.get_u32() as usize;
+        let first = buf.get_u64();
+        let sets = buf.get_u32() as usize;
+        let len = Self::HEADER_SIZE + sets.checked_mul(4)?;
+        if len > raw.len() || checksum32(&raw[4..len]) != checksum {
+            return None;
+        }
+        let checksums = (0..sets).map(|_| buf.get_u32()).collect();
+        Some(Self {
+            region,
+            set_size,
+            first,
+            checksums,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_footer_layout() {
+        const KIB: usize = 1024;
+
+        assert_eq!(RegionFooter::layout(16 * KIB, 64 * KIB), (3, 4 * KIB));
+        assert_eq!(RegionFooter::layout(20 * KIB, 64 * KIB), (3, 4 * KIB));
+        // 4096 sets don't fit in a single page footer with 4 KiB sets.
+        assert_eq!(RegionFooter::layout(4 * KIB, 16 * 1024 * KIB), (4091, 20 * KIB));
+        assert_eq!(RegionFooter::layout(16 * KIB, 16 * KIB), (0, 4 * KIB));
+    }
+
+    #[test]
+    fn test_region_footer_write_read() {
+        let footer = RegionFooter {
+            region: 3,
+            set_size: 4096,
+            first: 9,
+            checksums: vec![0xdeadbeef, 0, 42],
+        };
+
+        let mut raw = vec![0; RegionFooter::size(3)];
+        footer.write(&mut raw);
+        assert_eq!(RegionFooter::read(&raw), Some(footer));
+
+        // Corrupted or missing footers are ignored.
+        raw[40] ^= 1;
+        assert_eq!(RegionFooter::read(&raw), None);
+        assert_eq!(RegionFooter::read(&vec![0; PAGE]), None);
+    }
+}
@@ -17,30 +17,40 @@ use std::{
     future::Future,
     marker::PhantomData,
     ops::Range,
+    path::PathBuf,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
+use bytes::Bytes;
 use foyer_common::{
     code::{StorageKey, StorageValue},
     metrics::Metrics,
     properties::{Age, Populated, Properties},
 };
 use foyer_memory::Piece;
-use futures_util::future::join_all;
+use futures_util::future::{join_all, try_join_all};
 use itertools::Itertools;
 
 use crate::{
     device::{MonitoredDevice, RegionId},
-    error::Result,
+    error::{Error, Result},
+    event::EventBus,
+    serde::{EntryDeserializer, EntrySerializer, RawValue},
     small::{
+        budget::SerializeBufferBudget,
+        chunk::ChunkManifest,
         flusher::{Flusher, Submission},
-        set_manager::SetManager,
+        footer::IntegrityMode,
+        serde::EntryHeader,
+        set::{Set, SetBloomFilterRebuild, SetDeleteMode, SetEntryFields, SetId, SetLayout},
+        set_manager::{SetManager, SetResizePolicy, SetRouter, SetSizeClass},
     },
     storage::Storage,
-    Dev, Load, Runtime, Statistics, Throttle,
+    Compression, Dev, EntryLocation, Load, Runtime, Statistics, Throttle,
 };
 
 pub struct GenericSmallStorageConfig<K, V>
@@ -49,6 +59,7 @@ where
     V: StorageValue,
 {
     pub set_size: usize,
+    pub set_size_classes: Vec<SetSizeClass>,
     pub set_cache_capacity: usize,
     pub set_cache_shards: usize,
     pub device: MonitoredDevice,
@@ -56,6 +67,26 @@ where
     pub flush: bool,
     pub flushers: usize,
     pub buffer_pool_size: usize,
+    pub flush_interval: Option<Duration>,
+    pub serialize_buffer_budget: Option<usize>,
+    pub apply_yield_interval: usize,
+    pub apply_concurrency: usize,
+    pub checksum_on_write: bool,
+    pub verify_on_read: bool,
+    pub set_layout: SetLayout,
+    pub delete_mode: SetDeleteMode,
+    pub bloom_filter_rebuild: SetBloomFilterRebuild,
+    pub integrity_mode: IntegrityMode,
+    pub secondary_hash: bool,
+    pub inserted_at: bool,
+    pub chunk_size: Option<usize>,
+    pub initial_sets: Option<usize>,
+    pub resize_policy: Option<SetResizePolicy>,
+    pub set_router: Option<Arc<dyn SetRouter>>,
+    pub index_checkpoint: Option<PathBuf>,
+    pub index_checkpoint_interval: Option<Duration>,
+    pub bloom_filter_preload: bool,
+    pub events: EventBus,
     pub runtime: Runtime,
     pub marker: PhantomData<(K, V)>,
 }
@@ -68,6 +99,7 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("GenericSmallStorageConfig")
             .field("set_size", &self.set_size)
+            .field("set_size_classes", &self.set_size_classes)
             .field("set_cache_capacity", &self.set_cache_capacity)
             .field("set_cache_shards", &self.set_cache_shards)
             .field("device", &self.device)
@@ -75,22 +107,91 @@ where
             .field("flush", &self.flush)
             .field("flushers", &self.flushers)
             .field("buffer_pool_size", &self.buffer_pool_size)
+            .field("flush_interval", &self.flush_interval)
+            .field("serialize_buffer_budget", &self.serialize_buffer_budget)
+            .field("apply_yield_interval", &self.apply_yield_interval)
+            .field("apply_concurrency", &self.apply_concurrency)
+            .field("checksum_on_write", &self.checksum_on_write)
+            .field("verify_on_read", &self.verify_on_read)
+            .field("set_layout", &self.set_layout)
+            .field("delete_mode", &self.delete_mode)
+            .field("bloom_filter_rebuild", &self.bloom_filter_rebuild)
+            .field("integrity_mode", &self.integrity_mode)
+            .field("secondary_hash", &self.secondary_hash)
+            .field("inserted_at", &self.inserted_at)
+            .field("chunk_size", &self.chunk_size)
+            .field("initial_sets", &self.initial_sets)
+            .field("resize_policy", &self.resize_policy)
+            .field("set_router", &self.set_router)
+            .field("index_checkpoint", &self.index_checkpoint)
+            .field("index_checkpoint_interval", &self.index_checkpoint_interval)
+            .field("bloom_filter_preload", &self.bloom_filter_preload)
+            .field("events", &self.events)
             .field("runtime", &self.runtime)
             .field("marker", &self.marker)
             .finish()
     }
 }
 
-struct GenericSmallStorageInner<K, V, P>
+impl<K, V> GenericSmallStorageConfig<K, V>
+where
+    K: StorageKey,
+    V: StorageValue,
+{
+    /// The optional fields following each entry header in the sets.
+    pub fn entry_fields(&self) -> SetEntryFields {
+        SetEntryFields {
+            secondary_hash: self.secondary_hash,
+            inserted_at: self.inserted_at,
+            chunked: self.chunk_size.is_some(),
+        }
+    }
+}
+
+/// The sets of a set size class and the flushers writing to them.
+struct SetClass<K, V, P>
 where
     K: StorageKey,
     V: StorageValue,
     P: Properties,
 {
+    /// Bytes of the entries a set of the class can hold.
+    capacity: usize,
     flushers: Vec<Flusher<K, V, P>>,
+    set_manager: SetManager,
+}
+
+impl<K, V, P> SetClass<K, V, P>
+where
+    K: StorageKey,
+    V: StorageValue,
+    P: Properties,
+{
+    // Entries with the same hash MUST be grouped in the same batch.
+    fn flusher(&self, hash: u64) -> &Flusher<K, V, P> {
+        &self.flushers[hash as usize % self.flushers.len()]
+    }
+
+    fn may_contains(&self, hash: u64) -> bool {
+        match self.flusher(hash).lookup(hash) {
+            Some(pending) => pending.is_some(),
+            None => self.set_manager.may_contains(hash),
+        }
+    }
+}
+
+struct GenericSmallStorageInner<K, V, P>
+where
+    K: StorageKey,
+    V: StorageValue,
+    P: Properties,
+{
+    /// Set size classes in the ascending order of their set sizes.
+    classes: Vec<SetClass<K, V, P>>,
+    /// Chunk size of the entries striped across the sets, see [`ChunkManifest`].
+    chunk_size: Option<usize>,
 
     device: MonitoredDevice,
-    set_manager: SetManager,
 
     active: AtomicBool,
 
@@ -146,16 +247,39 @@ where
             config.regions
         );
 
-        let set_manager = SetManager::open(&config).await?;
+        let classes = match config.set_size_classes.is_empty() {
+            true => vec![SetSizeClass::new(config.set_size, 1.0)],
+            false => config.set_size_classes.clone(),
+        };
+        let regions = SetSizeClass::split(&classes, config.regions.clone());
 
-        let flushers = (0..config.flushers)
-            .map(|_| Flusher::open(&config, set_manager.clone(), metrics.clone()))
-            .collect_vec();
+        // The budget is shared by the flushers of all set size classes.
+        let budget = config
+            .serialize_buffer_budget
+            .map(|bytes| SerializeBufferBudget::new(bytes, metrics.clone()));
+
+        let classes = try_join_all(classes.iter().zip_eq(regions).enumerate().map(|(i, (class, regions))| {
+            let config = &config;
+            let budget = &budget;
+            let metrics = metrics.clone();
+            async move {
+                let set_manager = SetManager::open(config, i, class.set_size, regions).await?;
+                let flushers = (0..config.flushers)
+                    .map(|_| Flusher::open(config, set_manager.clone(), budget.clone(), metrics.clone()))
+                    .collect_vec();
+                Ok::<_, Error>(SetClass {
+                    capacity: class.capacity() - config.entry_fields().size(),
+                    flushers,
+                    set_manager,
+                })
+            }
+        }))
+        .await?;
 
         let inner = GenericSmallStorageInner {
-            flushers,
+            classes,
+            chunk_size: config.chunk_size,
             device: config.device,
-            set_manager,
             active: AtomicBool::new(true),
             metrics,
             _runtime: config.runtime,
@@ -165,8 +289,141 @@ where
         Ok(Self { inner })
     }
 
+    /// The only set size class, or an error naming the unsupported operation if there are more.
+    fn single_class(&self, op: &str) -> Result<&SetClass<K, V, P>> {
+        match self.inner.classes.as_slice() {
+            [class] => Ok(class),
+            _ => Err(Error::InvalidConfig(format!(
+                "{op} is not supported with multiple set size classes"
+            ))),
+        }
+    }
+
+    /// Resize the sets in use to `sets` sets, see [`SetManager::resize`].
+    ///
+    /// Only supported with a single set size class.
+    pub async fn resize_sets(&self, sets: usize) -> Result<()> {
+        self.single_class("resizing the sets")?.set_manager.resize(sets).await
+    }
+
+    /// Write the in-memory index to the index checkpoint, see [`SetManager::checkpoint`].
+    pub async fn checkpoint_index(&self) -> Result<()> {
+        try_join_all(self.inner.classes.iter().map(|class| class.set_manager.checkpoint())).await?;
+        Ok(())
+    }
+
+    /// Whether the bloom filters of the sets are preloaded on open, see [`SetManager::open`].
+    pub fn is_bloom_filter_preload_enabled(&self) -> bool {
+        self.inner.classes[0].set_manager.is_bloom_filter_preload_enabled()
+    }
+
+    /// Report how full each set in use is, see [`SetManager::fill_report`].
+    ///
+    /// Only supported with a single set size class.
+    pub async fn fill_report(&self) -> Result<Vec<(SetId, f64)>> {
+        self.single_class("the fill report")?.set_manager.fill_report().await
+    }
+
+    /// Timestamps of the last updates of the non-empty data sets of all set size classes with the indices of their
+    /// classes, see [`SetManager::timestamps`].
+    pub async fn set_timestamps(&self) -> Result<Vec<(usize, SetId, u128)>> {
+        let mut timestamps = vec![];
+        for (index, class) in self.inner.classes.iter().enumerate() {
+            let sets = class.set_manager.timestamps().await?;
+            timestamps.extend(sets.into_iter().map(|(sid, timestamp)| (index, sid, timestamp)));
+        }
+        Ok(timestamps)
+    }
+
+    /// Remove the entry with the hash from the sets of all set size classes on the device, see [`SetManager::remove`].
+    ///
+    /// The buffered writes are flushed first, so no buffered copy of the entry is written after the removal.
+    pub async fn remove(&self, hash: u64, zero: bool) -> Result<bool> {
+        self.wait().await;
+        let mut present = false;
+        for class in self.inner.classes.iter() {
+            present |= class.set_manager.remove(hash, zero).await?;
+        }
+        Ok(present)
+    }
+
+    /// Locate the entry with the hash in the write buffers or the sets of all set size classes, see
+    /// [`SetManager::locate_entry`].
+    pub async fn locate(&self, hash: u64) -> Result<EntryLocation> {
+        for (index, class) in self.inner.classes.iter().enumerate() {
+            match class.flusher(hash).lookup(hash) {
+                Some(Some(_)) => return Ok(EntryLocation::Buffered),
+                // A deletion in the write buffer shadows the entry in the set.
+                Some(None) => continue,
+                None => {}
+            }
+            if let Some(set) = class.set_manager.locate_entry(hash).await? {
+                return Ok(EntryLocation::Small { class: index, set });
+            }
+        }
+        Ok(EntryLocation::Absent)
+    }
+
+    /// Ids of the data sets in use of all set size classes with the indices of their classes, in a stable order.
+    pub fn set_ids(&self) -> Vec<(usize, SetId)> {
+        self.inner
+            .classes
+            .iter()
+            .enumerate()
+            .flat_map(|(class, c)| (1..c.set_manager.sets() as SetId).map(move |sid| (class, sid)))
+            .collect()
+    }
+
+    /// Read a data set of the set size class for scanning, see [`SetManager::scan_set`].
+    pub async fn scan_set(&self, class: usize, sid: SetId) -> Result<Option<Set>> {
+        self.inner.classes[class].set_manager.scan_set(sid).await
+    }
+
+    /// Count of the data sets in use of all set size classes, the meta sets excluded.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn sets(&self) -> usize {
+        self.inner
+            .classes
+            .iter()
+            .map(|class| class.set_manager.sets() - 1)
+            .sum()
+    }
+
+    /// Size of each set of the smallest set size class in bytes.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn set_size(&self) -> usize {
+        self.inner.classes[0].set_manager.set_size()
+    }
+
+    /// Pick the set size class for an entry of `size` bytes serialized, entry header excluded.
+    ///
+    /// The entry goes to the smallest class whose sets can hold it. An entry too large for all classes goes to the
+    /// largest one, which skips it like a single class does.
+    fn class(&self, size: usize) -> usize {
+        let size = EntryHeader::ENTRY_HEADER_SIZE + size;
+        self.inner
+            .classes
+            .iter()
+            .position(|class| class.capacity >= size)
+            .unwrap_or(self.inner.classes.len() - 1)
+    }
+
+    /// Delete the hash from the set size classes other than `class`, where an older entry of another size may be.
+    fn delete_from_others(&self, class: usize, hash: u64) {
+        for (i, other) in self.inner.classes.iter().enumerate() {
+            if i != class && other.may_contains(hash) {
+                other.flusher(hash).submit(Submission::Deletion { hash });
+            }
+        }
+    }
+
     fn wait(&self) -> impl Future<Output = ()> + Send + 'static {
-        let wait_flushers = join_all(self.inner.flushers.iter().map(|flusher| flusher.wait()));
+        let wait_flushers = join_all(
+            self.inner
+                .classes
+                .iter()
+                .flat_map(|class| class.flushers.iter().map(|flusher| flusher.wait())),
+        );
         async move {
             wait_flushers.await;
         }
@@ -175,63 +432,402 @@ where
     async fn close(&self) -> Result<()> {
         self.inner.active.store(false, Ordering::Relaxed);
         self.wait().await;
+        for class in self.inner.classes.iter() {
+            if class.set_manager.is_index_checkpoint_enabled() {
+                class.set_manager.checkpoint().await?;
+            }
+        }
         Ok(())
     }
 
+    /// The chunk size if an entry of `size` bytes serialized is too large for the sets and striped across them.
+    fn chunking(&self, size: usize) -> Option<usize> {
+        let chunk_size = self.inner.chunk_size?;
+        let capacity = self.inner.classes.last().unwrap().capacity;
+        (EntryHeader::ENTRY_HEADER_SIZE + size > capacity || size > EntryHeader::MAX_VALUE_LEN).then_some(chunk_size)
+    }
+
+    /// Delete the manifest of the hash, which an entry written as a whole replaces.
+    fn delete_manifest(&self, hash: u64) {
+        if self.inner.chunk_size.is_none() {
+            return;
+        }
+        let manifest = ChunkManifest::manifest_hash(hash);
+        for class in self.inner.classes.iter() {
+            if class.may_contains(manifest) {
+                class.flusher(manifest).submit(Submission::Deletion { hash: manifest });
+            }
+        }
+    }
+
     fn enqueue(&self, piece: Piece<K, V, P>, estimated_size: usize) {
         if !self.inner.active.load(Ordering::Relaxed) {
             tracing::warn!("cannot enqueue new entry after closed");
             return;
         }
 
-        // Entries with the same hash must be grouped in the batch.
-        let id = piece.hash() as usize % self.inner.flushers.len();
-        self.inner.flushers[id].submit(Submission::Insertion { piece, estimated_size });
+        if let Some(chunk_size) = self.chunking(estimated_size) {
+            let mut buf = vec![];
+            let info = match EntrySerializer::serialize(piece.key(), piece.value(), Compression::None, &mut buf) {
+                Ok(info) => info,
+                Err(e) => {
+                    tracing::warn!("[sodc]: serialize entry error: {e}");
+                    return;
+                }
+            };
+            let buf = Bytes::from(buf);
+            let (value, key) = (buf.slice(..info.value_len), buf.slice(info.value_len..));
+            return self.enqueue_chunked(piece.hash(), key, value, chunk_size);
+        }
+
+        let class = self.class(estimated_size);
+        self.delete_from_others(class, piece.hash());
+        self.delete_manifest(piece.hash());
+        self.inner.classes[class]
+            .flusher(piece.hash())
+            .submit(Submission::Insertion { piece, estimated_size });
+    }
+
+    fn enqueue_raw(&self, hash: u64, key: Bytes, value: Bytes) -> bool {
+        if !self.inner.active.load(Ordering::Relaxed) {
+            tracing::warn!("cannot enqueue new entry after closed");
+            return true;
+        }
+
+        if let Some(chunk_size) = self.chunking(key.len() + value.len()) {
+            self.enqueue_chunked(hash, key, value, chunk_size);
+            return true;
+        }
+
+        self.delete_manifest(hash);
+        self.submit_raw(hash, key, value, false);
+        true
+    }
+
+    fn submit_raw(&self, hash: u64, key: Bytes, value: Bytes, chunk: bool) {
+        let class = self.class(key.len() + value.len());
+        self.delete_from_others(class, hash);
+        self.inner.classes[class]
+            .flusher(hash)
+            .submit(Submission::RawInsertion {
+                hash,
+                key,
+                value,
+                chunk,
+            });
+    }
+
+    /// Stripe the encoded value across the sets in chunks, followed by the manifest, see [`ChunkManifest`].
+    fn enqueue_chunked(&self, hash: u64, key: Bytes, value: Bytes, chunk_size: usize) {
+        // The manifest takes the place of the entry written as a whole.
+        for class in self.inner.classes.iter() {
+            if class.may_contains(hash) {
+                class.flusher(hash).submit(Submission::Deletion { hash });
+            }
+        }
+
+        let manifest = ChunkManifest::new(&value, chunk_size);
+        if manifest.chunks > ChunkManifest::MAX_CHUNKS {
+            tracing::warn!(
+                "[sodc]: insert {hash} ignored, reason: {} chunks exceeds the limit",
+                manifest.chunks
+            );
+            return self.delete_manifest(hash);
+        }
+        // A chunk with the key must fit in a set of the largest class, the same capacity as the flushers use.
+        let capacity = self.inner.classes.last().unwrap().capacity;
+        if key.len() > EntryHeader::MAX_CHUNKED_KEY_LEN
+            || EntryHeader::ENTRY_HEADER_SIZE + key.len() + chunk_size.max(ChunkManifest::SIZE) > capacity
+        {
+            tracing::warn!(
+                "[sodc]: insert {hash} ignored, reason: key len {} leaves no room for a chunk of {chunk_size} bytes",
+                key.len()
+            );
+            return self.delete_manifest(hash);
+        }
+
+        for index in 0..manifest.chunks {
+            let chunk = value.slice(manifest.chunk_range(index));
+            self.submit_raw(ChunkManifest::chunk_hash(hash, index), key.clone(), chunk, true);
+        }
+        let mut buf = Vec::with_capacity(ChunkManifest::SIZE);
+        manifest.write(&mut buf);
+        self.submit_raw(ChunkManifest::manifest_hash(hash), key, Bytes::from(buf), true);
+    }
+
+    /// Load the encoded value striped across the sets with the manifest of the hash, see [`ChunkManifest`].
+    ///
+    /// Returns `None` if the manifest or any chunk is missing, or the reassembled value doesn't match the manifest.
+    async fn load_chunked(&self, hash: u64) -> Result<Option<(K, Vec<u8>)>> {
+        let (key, manifest) = match self.load_raw_entry(ChunkManifest::manifest_hash(hash)).await? {
+            Load::Entry { key, value, .. } => (key, value),
+            _ => return Ok(None),
+        };
+        let Some(manifest) = ChunkManifest::read(manifest.bytes()) else {
+            tracing::warn!(hash, "[sodc load]: corrupted chunk manifest");
+            return Ok(None);
+        };
+
+        let chunks =
+            try_join_all((0..manifest.chunks).map(|index| self.load_raw_entry(ChunkManifest::chunk_hash(hash, index))))
+                .await?;
+        let mut value = Vec::with_capacity(manifest.value_len);
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            match chunk {
+                Load::Entry {
+                    key: k, value: chunk, ..
+                } if k == key && chunk.bytes().len() == manifest.chunk_range(index).len() => {
+                    value.extend_from_slice(chunk.bytes())
+                }
+                _ => return Ok(None),
+            }
+        }
+        if !manifest.verify(&value) {
+            tracing::debug!(hash, "[sodc load]: chunks mismatch the manifest");
+            return Ok(None);
+        }
+        Ok(Some((key, value)))
+    }
+
+    async fn load_chunked_entry(&self, hash: u64) -> Result<Load<K, V>> {
+        match self.load_chunked(hash).await? {
+            Some((key, value)) => Ok(Load::Entry {
+                key,
+                value: EntryDeserializer::deserialize_value(&value, Compression::None)?,
+                populated: Populated { age: Age::Old },
+            }),
+            None => Ok(Load::Miss),
+        }
     }
 
     fn load(&self, hash: u64) -> impl Future<Output = Result<Load<K, V>>> + Send + 'static {
-        let set_manager = self.inner.set_manager.clone();
         let metrics = self.inner.metrics.clone();
 
+        // The write buffer holds the latest writes that are not flushed to the set yet.
+        let classes = self
+            .inner
+            .classes
+            .iter()
+            .map(|class| (class.flusher(hash).lookup(hash), class.set_manager.clone()))
+            .collect_vec();
+        let chunked = self.inner.chunk_size.map(|_| self.clone());
+        let chunk_bit = chunked.is_some();
+
         async move {
-            set_manager
-                .load(hash)
-                .await
-                .inspect_err(|e| {
+            // An entry is only kept in one class, try the classes in order.
+            for (pending, set_manager) in classes {
+                match pending {
+                    Some(Some(entry)) => return Self::decode_pending(&entry, chunk_bit),
+                    Some(None) => continue,
+                    None => {}
+                }
+
+                let res = set_manager.load(hash).await.inspect_err(|e| {
                     tracing::error!(hash, ?e, "[sodc load]: fail to load");
                     metrics.storage_error.increase(1);
-                })
-                .map(|o| match o {
-                    Some((key, value)) => Load::Entry {
+                })?;
+                if let Some((key, value)) = res {
+                    return Ok(Load::Entry {
                         key,
                         value,
                         // Always requires disk cache write for set-associated cache.
                         // TODO(MrCroxx): use a better way to determine the age.
                         populated: Populated { age: Age::Old },
+                    });
+                }
+            }
+            match chunked {
+                Some(this) => this.load_chunked_entry(hash).await,
+                None => Ok(Load::Miss),
+            }
+        }
+    }
+
+    /// Load the entry with the hash the same as [`GenericSmallStorage::load`], but keep the value as it is written.
+    pub fn load_raw(&self, hash: u64) -> impl Future<Output = Result<Load<K, RawValue>>> + Send + 'static {
+        let entry = self.load_raw_entry(hash);
+        let chunked = self.inner.chunk_size.map(|_| self.clone());
+
+        async move {
+            match (entry.await?, chunked) {
+                (Load::Miss, Some(this)) => Ok(match this.load_chunked(hash).await? {
+                    Some((key, value)) => Load::Entry {
+                        key,
+                        value: RawValue::new(value, Compression::None),
+                        populated: Populated { age: Age::Old },
                     },
                     None => Load::Miss,
-                })
+                }),
+                (load, _) => Ok(load),
+            }
+        }
+    }
+
+    /// Load the entry written as a whole with the hash, keep the value as it is written.
+    fn load_raw_entry(&self, hash: u64) -> impl Future<Output = Result<Load<K, RawValue>>> + Send + 'static {
+        let metrics = self.inner.metrics.clone();
+
+        let classes = self
+            .inner
+            .classes
+            .iter()
+            .map(|class| (class.flusher(hash).lookup(hash), class.set_manager.clone()))
+            .collect_vec();
+        let chunk_bit = self.inner.chunk_size.is_some();
+
+        async move {
+            for (pending, set_manager) in classes {
+                match pending {
+                    Some(Some(entry)) => {
+                        let header = EntryHeader::read_with(&entry[..], chunk_bit);
+                        let (key, value) = EntryDeserializer::deserialize_raw(
+                            &entry[EntryHeader::ENTRY_HEADER_SIZE..],
+                            header.key_len(),
+                            header.value_len(),
+                            Compression::None,
+                            None,
+                        )?;
+                        return Ok(Load::Entry {
+                            key,
+                            value,
+                            populated: Populated { age: Age::Young },
+                        });
+                    }
+                    Some(None) => continue,
+                    None => {}
+                }
+
+                let res = set_manager.load_raw(hash).await.inspect_err(|e| {
+                    tracing::error!(hash, ?e, "[sodc load]: fail to load");
+                    metrics.storage_error.increase(1);
+                })?;
+                if let Some((key, value)) = res {
+                    return Ok(Load::Entry {
+                        key,
+                        value,
+                        populated: Populated { age: Age::Old },
+                    });
+                }
+            }
+            Ok(Load::Miss)
+        }
+    }
+
+    fn load_many(&self, hashes: &[u64]) -> impl Future<Output = Vec<Result<Load<K, V>>>> + Send + 'static {
+        let metrics = self.inner.metrics.clone();
+        let hashes = hashes.to_vec();
+
+        // The write buffer holds the latest writes that are not flushed to the set yet.
+        let classes = self
+            .inner
+            .classes
+            .iter()
+            .map(|class| {
+                let pending = hashes
+                    .iter()
+                    .map(|&hash| class.flusher(hash).lookup(hash))
+                    .collect_vec();
+                (pending, class.set_manager.clone())
+            })
+            .collect_vec();
+        let chunked = self.inner.chunk_size.map(|_| self.clone());
+        let chunk_bit = chunked.is_some();
+
+        async move {
+            let mut results: Vec<Option<Result<Load<K, V>>>> = (0..hashes.len()).map(|_| None).collect();
+
+            // An entry is only kept in one class, try the classes in order for the entries not found yet.
+            for (pending, set_manager) in classes {
+                let mut loads = vec![];
+                for (i, pending) in pending.into_iter().enumerate() {
+                    if results[i].is_some() {
+                        continue;
+                    }
+                    match pending {
+                        Some(Some(entry)) => results[i] = Some(Self::decode_pending(&entry, chunk_bit)),
+                        Some(None) => {}
+                        None => loads.push(i),
+                    }
+                }
+
+                let res = set_manager
+                    .load_many(&loads.iter().map(|&i| hashes[i]).collect_vec())
+                    .await;
+                for (i, r) in loads.into_iter().zip_eq(res) {
+                    let hash = hashes[i];
+                    match r {
+                        Ok(Some((key, value))) => {
+                            results[i] = Some(Ok(Load::Entry {
+                                key,
+                                value,
+                                // Always requires disk cache write for set-associated cache.
+                                populated: Populated { age: Age::Old },
+                            }))
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            tracing::error!(hash, ?e, "[sodc load]: fail to load");
+                            metrics.storage_error.increase(1);
+                            results[i] = Some(Err(e));
+                        }
+                    }
+                }
+            }
+
+            // The entries not found as a whole may be striped across the sets.
+            if let Some(this) = chunked {
+                let misses = (0..hashes.len()).filter(|&i| results[i].is_none()).collect_vec();
+                let loads = join_all(misses.iter().map(|&i| this.load_chunked_entry(hashes[i]))).await;
+                for (i, load) in misses.into_iter().zip_eq(loads) {
+                    results[i] = Some(load);
+                }
+            }
+
+            results.into_iter().map(|r| r.unwrap_or(Ok(Load::Miss))).collect()
         }
     }
 
+    /// Decode the pending entry in the write buffer, `chunk_bit` if the sets carry the chunk bit.
+    fn decode_pending(entry: &[u8], chunk_bit: bool) -> Result<Load<K, V>> {
+        let header = EntryHeader::read_with(entry, chunk_bit);
+        let (key, value) = EntryDeserializer::deserialize(
+            &entry[EntryHeader::ENTRY_HEADER_SIZE..],
+            header.key_len(),
+            header.value_len(),
+            Compression::None,
+            None,
+        )?;
+        Ok(Load::Entry {
+            key,
+            value,
+            populated: Populated { age: Age::Young },
+        })
+    }
+
     fn delete(&self, hash: u64) {
         if !self.inner.active.load(Ordering::Relaxed) {
             tracing::warn!("cannot enqueue new entry after closed");
             return;
         }
 
-        // Entries with the same hash MUST be grouped in the same batch.
-        let id = hash as usize % self.inner.flushers.len();
-        self.inner.flushers[id].submit(Submission::Deletion { hash });
+        for class in self.inner.classes.iter() {
+            class.flusher(hash).submit(Submission::Deletion { hash });
+        }
+        self.delete_manifest(hash);
     }
 
     async fn destroy(&self) -> Result<()> {
         // TODO(MrCroxx): reset bloom filters
-        self.inner.set_manager.destroy().await
+        try_join_all(self.inner.classes.iter().map(|class| class.set_manager.destroy())).await?;
+        Ok(())
     }
 
     fn may_contains(&self, hash: u64) -> bool {
-        self.inner.set_manager.may_contains(hash)
+        self.inner.classes.iter().any(|class| class.may_contains(hash))
+            || self.inner.chunk_size.is_some_and(|_| {
+                let manifest = ChunkManifest::manifest_hash(hash);
+                self.inner.classes.iter().any(|class| class.may_contains(manifest))
+            })
     }
 
     fn throttle(&self) -> &Throttle {
@@ -267,10 +863,21 @@ where
         self.enqueue(piece, estimated_size);
     }
 
+    fn enqueue_raw(&self, hash: u64, key: Bytes, value: Bytes) -> bool {
+        self.enqueue_raw(hash, key, value)
+    }
+
     fn load(&self, hash: u64) -> impl Future<Output = Result<Load<Self::Key, Self::Value>>> + Send + 'static {
         self.load(hash)
     }
 
+    fn load_many(
+        &self,
+        hashes: &[u64],
+    ) -> impl Future<Output = Vec<Result<Load<Self::Key, Self::Value>>>> + Send + 'static {
+        self.load_many(hashes)
+    }
+
     fn delete(&self, hash: u64) {
         self.delete(hash)
     }
@@ -301,7 +908,7 @@ mod tests {
     use std::path::Path;
 
     use bytesize::ByteSize;
-    use foyer_common::{hasher::ModHasher, metrics::Metrics};
+    use foyer_common::{code::Code, hasher::ModHasher, metrics::Metrics};
     use foyer_memory::{Cache, CacheBuilder, CacheEntry, FifoConfig, TestProperties};
     use tokio::runtime::Handle;
 
@@ -312,6 +919,7 @@ mod tests {
             Dev,
         },
         serde::EntrySerializer,
+        small::footer::RegionFooter,
         DevExt, DirectFsDeviceOptions,
     };
 
@@ -332,6 +940,8 @@ mod tests {
                     .with_file_size(ByteSize::kib(16).as_u64() as _)
                     .into(),
                 metrics: Arc::new(Metrics::noop()),
+                io_concurrency: None,
+                io_retry: None,
             },
             runtime,
         )
@@ -340,10 +950,20 @@ mod tests {
     }
 
     async fn store_for_test(dir: impl AsRef<Path>) -> GenericSmallStorage<u64, Vec<u8>, TestProperties> {
+        store_for_test_with(dir, 0, None, IntegrityMode::Set).await
+    }
+
+    async fn store_for_test_with(
+        dir: impl AsRef<Path>,
+        apply_concurrency: usize,
+        flush_interval: Option<Duration>,
+        integrity_mode: IntegrityMode,
+    ) -> GenericSmallStorage<u64, Vec<u8>, TestProperties> {
         let device = device_for_test(dir).await;
         let regions = 0..device.regions() as RegionId;
         let config = GenericSmallStorageConfig {
             set_size: ByteSize::kib(4).as_u64() as _,
+            set_size_classes: vec![],
             set_cache_capacity: 4,
             set_cache_shards: 1,
             device,
@@ -351,6 +971,26 @@ mod tests {
             flush: false,
             flushers: 1,
             buffer_pool_size: ByteSize::kib(64).as_u64() as _,
+            flush_interval,
+            serialize_buffer_budget: None,
+            apply_yield_interval: 0,
+            apply_concurrency,
+            checksum_on_write: true,
+            verify_on_read: true,
+            set_layout: SetLayout::default(),
+            delete_mode: SetDeleteMode::default(),
+            bloom_filter_rebuild: SetBloomFilterRebuild::default(),
+            integrity_mode,
+            secondary_hash: false,
+            inserted_at: false,
+            chunk_size: None,
+            initial_sets: None,
+            resize_policy: None,
+            set_router: None,
+            index_checkpoint: None,
+            index_checkpoint_interval: None,
+            bloom_filter_preload: false,
+            events: EventBus::default(),
             runtime: Runtime::new(None, None, Handle::current()),
             marker: PhantomData,
         };
@@ -415,4 +1055,145 @@ mod tests {
         assert_none(&store, &e2).await;
         assert_none(&store, &e3).await;
     }
+
+    #[test_log::test(tokio::test)]
+    async fn test_store_region_footer() {
+        let dir = tempfile::tempdir().unwrap();
+        let region_size = ByteSize::kib(16).as_u64() as usize;
+        let watermark = |store: &GenericSmallStorage<u64, Vec<u8>, TestProperties>| {
+            let set_manager = store.inner.classes[0].set_manager.clone();
+            async move { set_manager.watermark().await }
+        };
+        // The loose bloom filters are not restored after reopening, read the sets directly.
+        let contains = |store: &GenericSmallStorage<u64, Vec<u8>, TestProperties>, hash: u64| {
+            let set_manager = store.inner.classes[0].set_manager.clone();
+            async move {
+                let sid = set_manager.set_picker().sid(hash);
+                set_manager.scan_set(sid).await.unwrap().unwrap().contains(hash)
+            }
+        };
+
+        let memory = cache_for_test();
+        let store = store_for_test_with(dir.path(), 0, None, IntegrityMode::RegionFooter).await;
+        let entries = (1..=32).map(|i| memory.insert(i, vec![i as u8; 42])).collect_vec();
+        for entry in entries.iter() {
+            enqueue(&store, entry.piece());
+        }
+        store.wait().await;
+        let written = watermark(&store).await;
+        let set_picker = store.inner.classes[0].set_manager.set_picker();
+        drop(store);
+
+        // The footers match the sets after reopening, the sets are still valid.
+        let store = store_for_test_with(dir.path(), 0, None, IntegrityMode::RegionFooter).await;
+        assert_eq!(watermark(&store).await, written);
+        for entry in entries.iter() {
+            assert!(contains(&store, entry.hash()).await);
+        }
+        drop(store);
+
+        // A valid footer that mismatches the sets only invalidates the sets of its region.
+        let (region_sets, footer_size) = RegionFooter::layout(ByteSize::kib(4).as_u64() as _, region_size);
+        let path = dir.path().join("foyer-storage-direct-fs-00000001");
+        let mut raw = std::fs::read(&path).unwrap();
+        let mut footer = RegionFooter::read(&raw[region_size - footer_size..]).unwrap();
+        footer.checksums[0] ^= 1;
+        footer.write(&mut raw[region_size - footer_size..]);
+        std::fs::write(&path, raw).unwrap();
+
+        let (invalidated, kept): (Vec<_>, Vec<_>) = entries
+            .iter()
+            .partition(|entry| set_picker.sid(entry.hash()) as usize / region_sets == 1);
+        assert!(!invalidated.is_empty() && !kept.is_empty());
+        for _ in 0..2 {
+            // The footer is rewritten, the region stays valid after reopening again.
+            let store = store_for_test_with(dir.path(), 0, None, IntegrityMode::RegionFooter).await;
+            assert_eq!(watermark(&store).await, written);
+            for entry in invalidated.iter() {
+                assert!(!contains(&store, entry.hash()).await);
+            }
+            for entry in kept.iter() {
+                assert!(contains(&store, entry.hash()).await);
+            }
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_store_apply_concurrency() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let memory = cache_for_test();
+        let store = store_for_test_with(dir.path(), 2, None, IntegrityMode::Set).await;
+
+        let entries = (1..=8)
+            .map(|i| memory.insert(i, vec![i as u8; 128]))
+            .collect::<Vec<_>>();
+        for entry in entries.iter() {
+            enqueue(&store, entry.piece());
+        }
+        store.wait().await;
+
+        for entry in entries.iter() {
+            assert_some(&store, entry).await;
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_store_flush_interval() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let memory = cache_for_test();
+        let store = store_for_test_with(dir.path(), 0, Some(Duration::from_secs(3600)), IntegrityMode::Set).await;
+
+        // Buffered entries are loaded before they are flushed.
+        let e1 = memory.insert(1, vec![1; 42]);
+        let e2 = memory.insert(2, vec![2; 192]);
+        enqueue(&store, e1.piece());
+        enqueue(&store, e2.piece());
+        let e3 = memory.insert(3, vec![3; 64]);
+        let (mut key, mut value) = (vec![], vec![]);
+        e3.key().encode(&mut key).unwrap();
+        e3.value().encode(&mut value).unwrap();
+        assert!(store.enqueue_raw(e3.hash(), Bytes::from(key), Bytes::from(value)));
+        assert!(store.may_contains(e1.hash()));
+        assert_some(&store, &e1).await;
+        assert_some(&store, &e2).await;
+        assert_some(&store, &e3).await;
+        assert!(store.inner.classes[0]
+            .set_manager
+            .load::<u64, Vec<u8>>(e1.hash())
+            .await
+            .unwrap()
+            .is_none());
+
+        // A buffered deletion hides the entry.
+        store.delete(e2.hash());
+        assert!(!store.may_contains(e2.hash()));
+        assert_none(&store, &e2).await;
+
+        // Waiting flushes the buffer.
+        store.wait().await;
+        assert!(store.inner.classes[0].flushers[0].lookup(e1.hash()).is_none());
+        assert!(store.inner.classes[0]
+            .set_manager
+            .load::<u64, Vec<u8>>(e1.hash())
+            .await
+            .unwrap()
+            .is_some());
+        assert_some(&store, &e1).await;
+        assert_none(&store, &e2).await;
+
+        // Flushed once the interval elapses.
+        let store = store_for_test_with(dir.path(), 0, Some(Duration::from_millis(10)), IntegrityMode::Set).await;
+        let e4 = memory.insert(4, vec![4; 42]);
+        enqueue(&store, e4.piece());
+        for _ in 0..100 {
+            if store.inner.classes[0].flushers[0].lookup(e4.hash()).is_none() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(store.inner.classes[0].flushers[0].lookup(e4.hash()).is_none());
+        assert_some(&store, &e4).await;
+    }
 }
@@ -14,7 +14,11 @@
 
 pub mod batch;
 pub mod bloom_filter;
+pub mod budget;
+pub mod checkpoint;
+pub mod chunk;
 pub mod flusher;
+pub mod footer;
 pub mod generic;
 pub mod serde;
 pub mod set;
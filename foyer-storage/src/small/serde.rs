@@ -12,33 +12,105 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use bytes::{Buf, BufMut};
 
-/// max key/value len: `64 KiB - 1`
+use crate::checksum::checksum32;
+
+/// max key len: `32 KiB - 1`, max value len: `64 KiB - 1`
+///
+/// Zero key/value len is valid, e.g. for a key or value type that encodes into no bytes. The entry then only takes
+/// the space of the header.
 ///
 /// # Format
 ///
 /// ```plain
-/// | hash 64b | key len 16b | value len 16b |
+/// | hash 64b | tombstone 1b | key len 15b | value len 16b |
+/// ```
+///
+/// The tombstone bit is the most significant bit of the 16-bit key len field. It marks an entry as deleted in place
+/// without compacting the set, see [`super::set::SetDeleteMode::Tombstone`]. Entries written before the bit was
+/// introduced never have keys of `32 KiB` or more, so the bit is always unset for them.
+///
+/// Sets written with the secondary hash enabled follow each header with the secondary hash of the entry:
+///
+/// ```plain
+/// | header 96b | secondary hash 32b |
+/// ```
+///
+/// The secondary hash is [`EntryHeader::secondary_hash`] of the encoded key, independent of the hash of the entry. An
+/// entry whose key mismatches its secondary hash is skipped on load, see [`super::set::SetEntry::is_intact`].
+///
+/// Sets written with the insertion time enabled follow each header, and the secondary hash if any, with the insertion
+/// time of the entry in milliseconds since the unix epoch:
+///
+/// ```plain
+/// | header 96b | secondary hash 32b (optional) | inserted at 64b |
+/// ```
+///
+/// The insertion time is taken when the entry is first written to a set, and kept when the entry is moved to another
+/// set, see [`super::set::SetEntryFields`].
+///
+/// Sets written with chunking enabled take the next bit of the key len field as the chunk bit, which marks the chunks
+/// and the manifests of the entries striped across the sets, see [`super::chunk::ChunkManifest`]. The keys are limited
+/// to [`EntryHeader::MAX_CHUNKED_KEY_LEN`] then:
+///
+/// ```plain
+/// | hash 64b | tombstone 1b | chunk 1b | key len 14b | value len 16b |
 /// ```
+///
+/// The chunks and the manifests are only read by their hashes, the iterations over the entries of a set skip them.
 #[derive(Debug, PartialEq, Eq)]
 pub struct EntryHeader {
     hash: u64,
     key_len: u16,
     value_len: u16,
+    tombstone: bool,
+    chunk: bool,
 }
 
 impl EntryHeader {
     pub const ENTRY_HEADER_SIZE: usize = (16 + 16 + 64) / 8;
+    pub const MAX_KEY_LEN: usize = (1 << 15) - 1;
+    /// Max key len with chunking enabled, the chunk bit takes a bit of the key len field.
+    pub const MAX_CHUNKED_KEY_LEN: usize = (1 << 14) - 1;
+    pub const MAX_VALUE_LEN: usize = u16::MAX as _;
+    pub const SECONDARY_HASH_SIZE: usize = 32 / 8;
+    pub const INSERTED_AT_SIZE: usize = 64 / 8;
+
+    const TOMBSTONE_BIT: u16 = 1 << 15;
+    const CHUNK_BIT: u16 = 1 << 14;
+    /// Offset of the key len field along with the tombstone bit.
+    const KEY_LEN_OFFSET: usize = 8;
 
     pub fn new(hash: u64, key_len: usize, value_len: usize) -> Self {
+        debug_assert!(key_len <= Self::MAX_KEY_LEN && value_len <= Self::MAX_VALUE_LEN);
         Self {
             hash,
             key_len: key_len as _,
             value_len: value_len as _,
+            tombstone: false,
+            chunk: false,
+        }
+    }
+
+    /// Max key len of the entries in the sets with or without the chunk bit.
+    pub fn max_key_len(chunked: bool) -> usize {
+        if chunked {
+            Self::MAX_CHUNKED_KEY_LEN
+        } else {
+            Self::MAX_KEY_LEN
         }
     }
 
+    /// Mark the entry as a chunk or a manifest of an entry striped across the sets.
+    pub fn with_chunk(mut self, chunk: bool) -> Self {
+        debug_assert!(!chunk || self.key_len as usize <= Self::MAX_CHUNKED_KEY_LEN);
+        self.chunk = chunk;
+        self
+    }
+
     #[inline]
     pub fn hash(&self) -> u64 {
         self.hash
@@ -55,26 +127,70 @@ impl EntryHeader {
     }
 
     #[inline]
-    pub fn entry_len(&self) -> usize {
-        Self::ENTRY_HEADER_SIZE + self.key_len() + self.value_len()
+    pub fn is_tombstone(&self) -> bool {
+        self.tombstone
+    }
+
+    /// Whether the entry is a chunk or a manifest of an entry striped across the sets.
+    #[inline]
+    pub fn is_chunk(&self) -> bool {
+        self.chunk
     }
 
     pub fn write(&self, mut buf: impl BufMut) {
+        let mut key_len = self.key_len;
+        if self.tombstone {
+            key_len |= Self::TOMBSTONE_BIT;
+        }
+        if self.chunk {
+            key_len |= Self::CHUNK_BIT;
+        }
         buf.put_u64(self.hash);
-        buf.put_u16(self.key_len);
+        buf.put_u16(key_len);
         buf.put_u16(self.value_len);
     }
 
-    pub fn read(mut buf: impl Buf) -> Self {
+    /// Read the header of an entry of a set written without chunking.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn read(buf: impl Buf) -> Self {
+        Self::read_with(buf, false)
+    }
+
+    /// Read the header of an entry, `chunked` if the set is written with chunking, so the chunk bit is taken.
+    pub fn read_with(mut buf: impl Buf, chunked: bool) -> Self {
         let hash = buf.get_u64();
         let key_len = buf.get_u16();
         let value_len = buf.get_u16();
+        let chunk = chunked && key_len & Self::CHUNK_BIT != 0;
+        let mut mask = Self::TOMBSTONE_BIT;
+        if chunked {
+            mask |= Self::CHUNK_BIT;
+        }
         Self {
             hash,
-            key_len,
+            key_len: key_len & !mask,
             value_len,
+            tombstone: key_len & Self::TOMBSTONE_BIT != 0,
+            chunk,
         }
     }
+
+    /// Secondary hash of the entry with the encoded key, i.e. [`checksum32`] of the key.
+    #[inline]
+    pub fn secondary_hash(key: &[u8]) -> u32 {
+        checksum32(key)
+    }
+
+    /// Insertion time of an entry written now, in milliseconds since the unix epoch.
+    #[inline]
+    pub fn inserted_at_now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as _
+    }
+
+    /// Mark the entry whose header starts at `buf` as a tombstone in place.
+    pub fn mark_tombstone(buf: &mut [u8]) {
+        buf[Self::KEY_LEN_OFFSET] |= (Self::TOMBSTONE_BIT >> 8) as u8;
+    }
 }
 
 #[cfg(test)]
@@ -87,10 +203,52 @@ mod tests {
             hash: 114514,
             key_len: 114,
             value_len: 514,
+            tombstone: false,
+            chunk: false,
         };
         let mut buf = vec![];
         header.write(&mut buf);
         let h = EntryHeader::read(&buf[..]);
         assert_eq!(header, h);
     }
+
+    #[test]
+    fn test_entry_header_tombstone() {
+        let header = EntryHeader::new(114514, EntryHeader::MAX_KEY_LEN, EntryHeader::MAX_VALUE_LEN);
+        let mut buf = vec![];
+        header.write(&mut buf);
+
+        EntryHeader::mark_tombstone(&mut buf);
+        let h = EntryHeader::read(&buf[..]);
+        assert!(h.is_tombstone());
+        assert_eq!(h.hash(), 114514);
+        assert_eq!(h.key_len(), EntryHeader::MAX_KEY_LEN);
+        assert_eq!(h.value_len(), EntryHeader::MAX_VALUE_LEN);
+
+        let mut buf2 = vec![];
+        h.write(&mut buf2);
+        assert_eq!(buf, buf2);
+    }
+
+    #[test]
+    fn test_entry_header_chunk() {
+        let header = EntryHeader::new(114514, EntryHeader::MAX_CHUNKED_KEY_LEN, 514).with_chunk(true);
+        let mut buf = vec![];
+        header.write(&mut buf);
+
+        let h = EntryHeader::read_with(&buf[..], true);
+        assert_eq!(h, header);
+        EntryHeader::mark_tombstone(&mut buf);
+        let h = EntryHeader::read_with(&buf[..], true);
+        assert!(h.is_chunk() && h.is_tombstone());
+        assert_eq!(h.key_len(), EntryHeader::MAX_CHUNKED_KEY_LEN);
+
+        // Without chunking, the bit belongs to the key len.
+        let header = EntryHeader::new(114514, EntryHeader::MAX_KEY_LEN, 514);
+        let mut buf = vec![];
+        header.write(&mut buf);
+        let h = EntryHeader::read(&buf[..]);
+        assert!(!h.is_chunk());
+        assert_eq!(h.key_len(), EntryHeader::MAX_KEY_LEN);
+    }
 }
@@ -13,41 +13,268 @@
 // limitations under the License.
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fmt::Debug,
-    ops::Range,
+    io::{Read, Seek, SeekFrom},
+    ops::{Deref, DerefMut, Range},
     sync::Arc,
-    time::{Instant, SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use bytes::{Buf, BufMut};
+use bytes::{Buf, BufMut, Bytes};
 use foyer_common::{
+    bits,
     code::{StorageKey, StorageValue},
     metrics::Metrics,
 };
 
 use super::{batch::Item, bloom_filter::BloomFilterU64, serde::EntryHeader};
 use crate::{
+    checksum,
     error::Result,
-    io::buffer::IoBuffer,
-    serde::{Checksummer, EntryDeserializer},
+    io::{
+        buffer::{IoBuf, IoBuffer},
+        PAGE,
+    },
+    serde::{Checksummer, EntryDeserializer, RawValue},
     Compression,
 };
 
+/// Id of a set of the small object disk cache engine.
 pub type SetId = u64;
 
+/// Layout of the entries in the data part of a set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SetLayout {
+    /// Each entry is laid out as the entry header, the value and the key, one after another.
+    ///
+    /// ```plain
+    /// | header | value | key | header | value | key | ... | free |
+    /// ```
+    #[default]
+    Interleaved,
+    /// The entry headers and keys are packed from the start of the data, the values are packed from the end.
+    ///
+    /// ```plain
+    /// | header | key | header | key | ... | free | ... | value | value |
+    /// ```
+    ///
+    /// The values are in the same order as the keys from the end, the value of the oldest entry is at the end of the
+    /// data. Scanning the keys of a set only touches the contiguous key region, while getting a value touches both
+    /// regions.
+    Split,
+}
+
+impl SetLayout {
+    fn version(&self) -> u8 {
+        match self {
+            Self::Interleaved => 1,
+            Self::Split => 2,
+        }
+    }
+
+    /// Lengths of the entry in the head region and the tail region.
+    fn entry_lens(&self, key_len: usize, value_len: usize) -> (usize, usize) {
+        match self {
+            Self::Interleaved => (EntryHeader::ENTRY_HEADER_SIZE + key_len + value_len, 0),
+            Self::Split => (EntryHeader::ENTRY_HEADER_SIZE + key_len, value_len),
+        }
+    }
+}
+
+/// Optional fields following each entry header in a set, see [`EntryHeader`].
+///
+/// The fields in use are persisted in the set header, a set written with other fields is loaded as an empty set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SetEntryFields {
+    /// If each entry carries the secondary hash of its key.
+    pub secondary_hash: bool,
+    /// If each entry carries its insertion time, [`EntryHeader::INSERTED_AT_SIZE`] bytes per entry.
+    pub inserted_at: bool,
+    /// If the entry headers carry the chunk bit, see [`EntryHeader`]. Takes no extra bytes.
+    pub chunked: bool,
+}
+
+impl SetEntryFields {
+    /// Size of the fields following each entry header.
+    pub fn size(&self) -> usize {
+        let mut size = 0;
+        if self.secondary_hash {
+            size += EntryHeader::SECONDARY_HASH_SIZE;
+        }
+        if self.inserted_at {
+            size += EntryHeader::INSERTED_AT_SIZE;
+        }
+        size
+    }
+}
+
+/// Options to read a set of the small object disk cache outside of the store, see [`read_set_entries`].
+///
+/// The options must match the [`crate::SmallEngineOptions`] the set is written with, see
+/// [`crate::SmallEngineOptions::set_read_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetReadOptions {
+    verify: bool,
+    layout: SetLayout,
+    fields: SetEntryFields,
+}
+
+impl Default for SetReadOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SetReadOptions {
+    /// Create the options to read a set written with the default small object disk cache options.
+    pub fn new() -> Self {
+        Self {
+            verify: true,
+            layout: SetLayout::default(),
+            fields: SetEntryFields::default(),
+        }
+    }
+
+    /// Set whether to verify the set checksum, must be disabled for sets written without a checksum.
+    ///
+    /// Default: `true`.
+    pub fn with_verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Set the entry layout of the set.
+    ///
+    /// Default: [`SetLayout::Interleaved`].
+    pub fn with_set_layout(mut self, layout: SetLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Set whether the entries carry the secondary hash of their keys.
+    ///
+    /// Default: `false`.
+    pub fn with_secondary_hash(mut self, secondary_hash: bool) -> Self {
+        self.fields.secondary_hash = secondary_hash;
+        self
+    }
+
+    /// Set whether the entries carry their insertion time.
+    ///
+    /// Default: `false`.
+    pub fn with_entry_inserted_at(mut self, inserted_at: bool) -> Self {
+        self.fields.inserted_at = inserted_at;
+        self
+    }
+
+    /// Set whether the set is written with chunking enabled.
+    ///
+    /// Default: `false`.
+    pub fn with_chunking(mut self, chunked: bool) -> Self {
+        self.fields.chunked = chunked;
+        self
+    }
+}
+
+/// Alignment of the set size, and so of the sets on the device.
+///
+/// The sets are laid out back to back from the start of each region, so an aligned set size aligns every set on the
+/// device. The set size is rounded up to the alignment, the padding is used as set data capacity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SetAlignment {
+    /// Align the set size to 4 KiB, the set size is always a multiple of 4 KiB.
+    #[default]
+    Page,
+    /// Round the set size up to the next power of two.
+    PowerOfTwo,
+    /// Align the set size to the given alignment, which must be a power of two multiple of 4 KiB.
+    Bytes(usize),
+}
+
+impl SetAlignment {
+    /// Round the set size up to the alignment.
+    pub fn align(&self, set_size: usize) -> usize {
+        match self {
+            Self::Page => bits::align_up(PAGE, set_size),
+            Self::PowerOfTwo => set_size.next_power_of_two().max(PAGE),
+            Self::Bytes(alignment) => bits::align_up(*alignment, set_size),
+        }
+    }
+}
+
+/// How entries are deleted from a set.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SetDeleteMode {
+    /// Compact the set on each deletion, moving the entries after the deleted ones forward.
+    #[default]
+    Compact,
+    /// Mark the deleted entries as tombstones in place, see [`EntryHeader`] for the tombstone bit.
+    ///
+    /// Tombstones are skipped on reads and only reclaimed when the set runs out of space, or when the tombstones take
+    /// more than `ratio` of the set data capacity after a deletion.
+    Tombstone {
+        /// Ratio of the set data capacity, range: (0 ~ 1]
+        ratio: f64,
+    },
+}
+
+/// When the bloom filter of a set is rebuilt on deletions.
+///
+/// A deleted hash left in the bloom filter is a false positive of the set, a lookup of it scans the set and finds
+/// nothing. So a stale hash raises the false positive probability (FPP) of the set by as much as a live entry does.
+/// The FPP of a bloom filter with `n` hashes grows with `n`, a set with `d` stale hashes behaves as if it holds
+/// `n + d` entries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SetBloomFilterRebuild {
+    /// Rebuild the bloom filter from the live entries on each deletion, so the deleted hashes never pass it.
+    #[default]
+    Eager,
+    /// Leave the bloom filter unchanged on deletions, and only rebuild it once `interval` deleted hashes are left in
+    /// it, or when the set is compacted for other reasons.
+    ///
+    /// The deletions skip inserting every live entry into the bloom filter again, at the cost of up to `interval`
+    /// stale hashes in the bloom filter. The count of the stale hashes is not persisted, it restarts from `0` when the
+    /// set is loaded again.
+    Lazy {
+        /// Count of the deleted hashes left in the bloom filter before rebuilding, must be positive.
+        interval: usize,
+    },
+}
+
 /// # Format
 ///
 /// ```plain
 /// | checksum (4B) | ns timestamp (16B) | len (4B) |
 /// | bloom filter (4 * 8B = 32B) |
+/// | entry count (4B) |
+/// | layout version (1B) | tail len (4B) |
+/// | tombstone len (4B) |
 /// ```
+///
+/// The data part after the header has a head region growing from the start and a tail region growing from the end,
+/// `len` is the total length of both. The tail region is only used by [`SetLayout::Split`], see [`SetLayout`] for the
+/// entry layouts. `len` includes the entries marked as tombstones, the entry count doesn't.
+///
+/// The entry count, the layout version, the tail len and the tombstone len were appended to the header in turn. Sets
+/// written with a previous header fail the checksum verification and are loaded as empty sets, so are sets written
+/// with a different layout from the configured one.
+///
+/// The most significant bit of the layout version is set if the entries carry the secondary hash, the next bit is set
+/// if the entries carry the insertion time, the third bit is set if the entry headers carry the chunk bit, see
+/// [`EntryHeader`]. Enabling or disabling any of them also loads the sets written before as empty sets.
 pub struct SetStorage {
     /// Set checksum.
     checksum: u32,
 
-    /// Set written data length.
+    /// Set written data length, including the head region and the tail region.
     len: usize,
+    /// Set written tail region length.
+    tail: usize,
+    /// Count of the entries in the set, excluding tombstones.
+    entries: usize,
+    /// Length of the entries marked as tombstones.
+    tombstones: usize,
     /// Set data length capacity.
     capacity: usize,
     /// Set size.
@@ -56,6 +283,18 @@ pub struct SetStorage {
     timestamp: u128,
     /// Set bloom filter.
     bloom_filter: BloomFilterU64<4>,
+    /// Set entry layout.
+    layout: SetLayout,
+    /// Optional fields following each entry header.
+    fields: SetEntryFields,
+    /// How entries are deleted, not persisted.
+    delete_mode: SetDeleteMode,
+    /// When the bloom filter is rebuilt on deletions, not persisted.
+    bloom_filter_rebuild: SetBloomFilterRebuild,
+    /// Count of the deleted hashes left in the bloom filter, not persisted.
+    stale_hashes: usize,
+    /// If the set is corrupted on load and loaded as an empty set, not persisted.
+    corrupted: bool,
 
     buffer: IoBuffer,
 
@@ -67,71 +306,448 @@ impl Debug for SetStorage {
         f.debug_struct("SetStorage")
             .field("checksum", &self.checksum)
             .field("len", &self.len)
+            .field("tail", &self.tail)
+            .field("entries", &self.entries)
+            .field("tombstones", &self.tombstones)
             .field("capacity", &self.capacity)
             .field("size", &self.size)
             .field("timestamp", &self.timestamp)
             .field("bloom_filter", &self.bloom_filter)
+            .field("layout", &self.layout)
+            .field("fields", &self.fields)
+            .field("delete_mode", &self.delete_mode)
+            .field("bloom_filter_rebuild", &self.bloom_filter_rebuild)
+            .field("stale_hashes", &self.stale_hashes)
+            .field("corrupted", &self.corrupted)
             .finish()
     }
 }
 
+/// Cursor of the head region and the tail region.
+#[derive(Debug, Clone, Copy, Default)]
+struct Cursor {
+    head: usize,
+    tail: usize,
+}
+
+// The set header is persisted, pin its layout: checksum, timestamp, len, bloom filter, entries, version, tail and
+// tombstones, see `SetStorage::load_with_fields` for the offsets.
+const _: () = {
+    assert!(SetStorage::SET_HEADER_SIZE == 4 + 16 + 4 + BloomFilterU64::<4>::BYTES + 4 + 1 + 4 + 4);
+    assert!(BloomFilterU64::<4>::BYTES == 56 - 24);
+    assert!(EntryHeader::ENTRY_HEADER_SIZE == 8 + 2 + 2);
+};
+
 impl SetStorage {
-    pub const SET_HEADER_SIZE: usize = 56;
+    pub const SET_HEADER_SIZE: usize = 69;
+
+    /// Bit of the layout version set if the entries carry the secondary hash.
+    const SECONDARY_HASH_VERSION_BIT: u8 = 1 << 7;
+    /// Bit of the layout version set if the entries carry the insertion time.
+    const INSERTED_AT_VERSION_BIT: u8 = 1 << 6;
+    /// Bit of the layout version set if the entry headers carry the chunk bit.
+    const CHUNKED_VERSION_BIT: u8 = 1 << 5;
 
     /// Load the set storage from buffer.
     ///
     /// If `after` is set and the set storage is before the timestamp, load an empty set storage.
+    #[cfg_attr(not(test), expect(dead_code))]
     pub fn load(buffer: IoBuffer, watermark: u128, metrics: Arc<Metrics>) -> Self {
-        assert!(buffer.len() >= Self::SET_HEADER_SIZE);
+        Self::load_with(buffer, watermark, true, SetLayout::default(), metrics)
+    }
+
+    /// Load the set storage from buffer, verifying the set checksum only if `verify` is set.
+    ///
+    /// The set length, timestamp and layout are always checked. Loading without verifying is required for sets
+    /// written without a checksum, see [`SetStorage::update_with_checksum`]. A set written with another layout is
+    /// loaded as an empty set with the given `layout`.
+    pub fn load_with(
+        buffer: IoBuffer,
+        watermark: u128,
+        verify: bool,
+        layout: SetLayout,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self::load_with_fields(buffer, watermark, verify, layout, SetEntryFields::default(), metrics)
+    }
+
+    /// Load the set storage from buffer as [`SetStorage::load_with`], with entries carrying the given optional
+    /// `fields`, see [`SetEntryFields`].
+    ///
+    /// A set written with other fields is loaded as an empty set.
+    #[cfg_attr(feature = "tracing", fastrace::trace(name = "foyer::storage::small::set::load"))]
+    pub fn load_with_fields(
+        buffer: IoBuffer,
+        watermark: u128,
+        verify: bool,
+        layout: SetLayout,
+        fields: SetEntryFields,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        // An io buffer is always rounded up to the page size, so a loaded set always has room for data besides the set
+        // header. Only the header helpers, e.g. `len_from_header`, see sets without any.
+        assert!(buffer.len() > Self::SET_HEADER_SIZE);
 
         let checksum = (&buffer[0..4]).get_u32();
         let timestamp = (&buffer[4..20]).get_u128();
         let len = (&buffer[20..24]).get_u32() as usize;
         let bloom_filter = BloomFilterU64::read(&buffer[24..56]);
+        let entries = (&buffer[56..60]).get_u32() as usize;
+        let version = buffer[60];
+        let tail = (&buffer[61..65]).get_u32() as usize;
+        let tombstones = (&buffer[65..69]).get_u32() as usize;
 
         let mut this = Self {
             checksum,
             len,
+            tail,
+            entries,
+            tombstones,
             capacity: buffer.len() - Self::SET_HEADER_SIZE,
             size: buffer.len(),
             timestamp,
             bloom_filter,
+            layout,
+            fields,
+            delete_mode: SetDeleteMode::default(),
+            bloom_filter_rebuild: SetBloomFilterRebuild::default(),
+            stale_hashes: 0,
+            corrupted: false,
             buffer,
             metrics,
         };
 
-        this.verify(watermark);
+        this.verify(watermark, version, verify);
+
+        #[cfg(feature = "tracing")]
+        fastrace::local::LocalSpan::add_properties(|| {
+            [("bytes", this.size.to_string()), ("len", this.len.to_string())]
+        });
 
         this
     }
 
-    fn verify(&mut self, watermark: u128) {
-        if Self::SET_HEADER_SIZE + self.len >= self.buffer.len() || self.timestamp < watermark {
+    /// Load the set storage from buffer, and clear it if any entry is malformed.
+    ///
+    /// A set that passes the checks of [`SetStorage::load_with`] may still contain entries out of bounds, e.g. a set
+    /// written by another program with a valid checksum. All entries are walked to check the bounds, the entry count,
+    /// the tombstone len and the bloom filter, so a loaded set never panics on the following reads. Meant for loading
+    /// untrusted sets, at the cost of a full scan on load.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn load_strict(buffer: IoBuffer, watermark: u128, layout: SetLayout, metrics: Arc<Metrics>) -> Self {
+        Self::load_with(buffer, watermark, true, layout, metrics).strict()
+    }
+
+    /// Clear the set if any entry is malformed, see [`SetStorage::load_strict`].
+    fn strict(mut self) -> Self {
+        if !self.is_well_formed() {
+            // malformed entries
+            self.clear();
+            self.corrupted = true;
+        }
+        self
+    }
+
+    /// Load the set storage of `size` bytes at `offset` of the source, e.g. a region file opened by external tools.
+    ///
+    /// Only the set is read, not the whole source. The set is loaded with the given options without a watermark and
+    /// fully validated, see [`SetStorage::load_strict`].
+    ///
+    /// Returns an error if `size` is not a valid set size, i.e. a multiple of the page size greater than the set
+    /// header size, if the source ends before `size` bytes are read, or if the set is written with a layout or entry
+    /// fields other than the options. A set never written is loaded as empty.
+    pub fn load_from<R: Read + Seek>(r: &mut R, offset: u64, size: usize, options: &SetReadOptions) -> Result<Self> {
+        if size <= Self::SET_HEADER_SIZE || size % PAGE != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "set size {size} must be a multiple of {PAGE} and greater than the set header size {}",
+                    Self::SET_HEADER_SIZE
+                ),
+            )
+            .into());
+        }
+
+        let mut buffer = IoBuffer::new(size);
+        r.seek(SeekFrom::Start(offset))?;
+        let mut read = 0;
+        while read < size {
+            match r.read(&mut buffer[read..]) {
+                Ok(0) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        format!("short read of the set at offset {offset}, read {read} of {size} bytes"),
+                    )
+                    .into())
+                }
+                Ok(n) => read += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        // A set never written has a zeroed header.
+        let version = buffer[60];
+        let expected = Self::version(options.layout, options.fields);
+        if version != 0 && version != expected {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("set at offset {offset} is written with version {version:#04x}, but {expected:#04x} is expected by the read options"),
+            )
+            .into());
+        }
+
+        Ok(Self::load_with_fields(
+            buffer,
+            0,
+            options.verify,
+            options.layout,
+            options.fields,
+            Arc::new(Metrics::noop()),
+        )
+        .strict())
+    }
+
+    /// Build a set storage of `size` bytes from scratch with the given items laid out densely in the default layout.
+    ///
+    /// Items are packed in order, an item that doesn't fit in the remaining capacity is skipped and returned as a
+    /// leftover, so smaller items after it can still be packed. The set header is updated after packing.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn pack(items: Vec<Item>, size: usize, metrics: Arc<Metrics>) -> (Self, Vec<Item>) {
+        assert!(size > Self::SET_HEADER_SIZE);
+
+        let mut this = Self {
+            checksum: 0,
+            len: 0,
+            tail: 0,
+            entries: 0,
+            tombstones: 0,
+            capacity: size - Self::SET_HEADER_SIZE,
+            size,
+            timestamp: 0,
+            bloom_filter: BloomFilterU64::default(),
+            layout: SetLayout::default(),
+            fields: SetEntryFields::default(),
+            delete_mode: SetDeleteMode::default(),
+            bloom_filter_rebuild: SetBloomFilterRebuild::default(),
+            stale_hashes: 0,
+            corrupted: false,
+            buffer: IoBuffer::new(size),
+            metrics,
+        };
+
+        let mut leftovers = vec![];
+        for item in items {
+            if this.len + this.item_len(&item) > this.capacity {
+                leftovers.push(item);
+                continue;
+            }
+            this.push(&item);
+        }
+        this.update();
+
+        (this, leftovers)
+    }
+
+    fn verify(&mut self, watermark: u128, version: u8, checksum: bool) {
+        if self.timestamp < watermark || version != Self::version(self.layout, self.fields) {
+            // stale set or another layout
+            self.clear();
+        } else if Self::SET_HEADER_SIZE + self.len > self.buffer.len()
+            || self.tail > self.len
+            || self.tombstones > self.len
+        {
             // invalid len
             self.clear();
-        } else {
-            let c = Checksummer::checksum32(&self.buffer[4..Self::SET_HEADER_SIZE + self.len]);
+            self.corrupted = true;
+        } else if checksum {
+            let c = self.calculate_checksum();
             if c != self.checksum {
                 // checksum mismatch
                 self.clear();
+                self.corrupted = true;
+            }
+        }
+    }
+
+    /// Length of the set data of `size` bytes from the set header only, without reading the entries.
+    ///
+    /// The length, timestamp, layout and entry fields are checked the same as [`SetStorage::load_with_fields`],
+    /// returns `0` for a set that would be loaded as empty. The checksum is
+    /// not verified, so a corrupted set may report a stale length.
+    pub fn len_from_header(
+        header: &[u8],
+        size: usize,
+        watermark: u128,
+        layout: SetLayout,
+        fields: SetEntryFields,
+    ) -> usize {
+        assert!(header.len() >= Self::SET_HEADER_SIZE);
+
+        let timestamp = (&header[4..20]).get_u128();
+        let len = (&header[20..24]).get_u32() as usize;
+        let version = header[60];
+        let tail = (&header[61..65]).get_u32() as usize;
+        let tombstones = (&header[65..69]).get_u32() as usize;
+
+        if size == Self::SET_HEADER_SIZE {
+            return 0;
+        }
+        if Self::SET_HEADER_SIZE + len > size
+            || tail > len
+            || tombstones > len
+            || timestamp < watermark
+            || version != Self::version(layout, fields)
+        {
+            return 0;
+        }
+        len
+    }
+
+    /// Read the timestamp of the last update from the set header, `None` if the set is empty or invalid, see
+    /// [`SetStorage::len_from_header`].
+    pub fn timestamp_from_header(
+        header: &[u8],
+        size: usize,
+        watermark: u128,
+        layout: SetLayout,
+        fields: SetEntryFields,
+    ) -> Option<u128> {
+        (Self::len_from_header(header, size, watermark, layout, fields) > 0).then(|| (&header[4..20]).get_u128())
+    }
+
+    /// Read the bloom filter from the set header, `None` if the set is empty or invalid, see
+    /// [`SetStorage::len_from_header`].
+    pub fn bloom_filter_from_header(
+        header: &[u8],
+        size: usize,
+        watermark: u128,
+        layout: SetLayout,
+        fields: SetEntryFields,
+    ) -> Option<BloomFilterU64<4>> {
+        (Self::len_from_header(header, size, watermark, layout, fields) > 0)
+            .then(|| BloomFilterU64::read(&header[24..56]))
+    }
+
+    /// Layout version persisted in the set header.
+    fn version(layout: SetLayout, fields: SetEntryFields) -> u8 {
+        let mut version = layout.version();
+        if fields.secondary_hash {
+            version |= Self::SECONDARY_HASH_VERSION_BIT;
+        }
+        if fields.inserted_at {
+            version |= Self::INSERTED_AT_VERSION_BIT;
+        }
+        if fields.chunked {
+            version |= Self::CHUNKED_VERSION_BIT;
+        }
+        version
+    }
+
+    /// Read the entry header, the chunk bit is only taken if the set carries it, see [`SetEntryFields::chunked`].
+    fn read_header(&self, buf: &[u8]) -> EntryHeader {
+        EntryHeader::read_with(buf, self.fields.chunked)
+    }
+
+    /// Size of the entry header in the set, including the optional fields if enabled.
+    fn entry_header_size(&self) -> usize {
+        EntryHeader::ENTRY_HEADER_SIZE + self.fields.size()
+    }
+
+    /// Lengths of the entry in the head region and the tail region, see [`SetLayout::entry_lens`].
+    fn entry_lens(&self, key_len: usize, value_len: usize) -> (usize, usize) {
+        let (head, tail) = self.layout.entry_lens(key_len, value_len);
+        (head + self.entry_header_size() - EntryHeader::ENTRY_HEADER_SIZE, tail)
+    }
+
+    /// Length of the item in the set, the item is serialized without the optional fields.
+    fn item_len(&self, item: &Item) -> usize {
+        item.slice.len() + self.entry_header_size() - EntryHeader::ENTRY_HEADER_SIZE
+    }
+
+    /// Walk the entries and check them against the set header without panicking, see [`SetStorage::load_strict`].
+    fn is_well_formed(&self) -> bool {
+        let head = self.head();
+        if self.layout == SetLayout::Interleaved && self.tail != 0 {
+            return false;
+        }
+
+        let mut cursor = Cursor::default();
+        let mut entries = 0;
+        let mut tombstones = 0;
+        while cursor.head < head {
+            if cursor.head + self.entry_header_size() > head {
+                return false;
+            }
+            let header = self.read_header(&self.data()[cursor.head..cursor.head + EntryHeader::ENTRY_HEADER_SIZE]);
+            let (h, t) = self.entry_lens(header.key_len(), header.value_len());
+            if cursor.head + h > head || cursor.tail + t > self.tail {
+                return false;
+            }
+            cursor.head += h;
+            cursor.tail += t;
+            if header.is_tombstone() {
+                tombstones += h + t;
+            } else if self.bloom_filter.lookup(header.hash()) {
+                entries += 1;
+            } else {
+                return false;
             }
         }
+        cursor.tail == self.tail && entries == self.entries && tombstones == self.tombstones
+    }
+
+    fn calculate_checksum(&self) -> u32 {
+        checksum::set_checksum_with(&self.buffer[..self.size], self.head(), self.tail)
     }
 
     pub fn update(&mut self) {
+        self.update_with_checksum(true);
+    }
+
+    /// Update the set header, calculating the set checksum only if `checksum` is set.
+    ///
+    /// Without calculating, the checksum is written as `0`, so the set must be loaded without verifying, see
+    /// [`SetStorage::load_with`].
+    pub fn update_with_checksum(&mut self, checksum: bool) {
         self.bloom_filter.write(&mut self.buffer[24..56]);
         (&mut self.buffer[20..24]).put_u32(self.len as _);
+        (&mut self.buffer[56..60]).put_u32(self.entries as _);
+        self.buffer[60] = Self::version(self.layout, self.fields);
+        (&mut self.buffer[61..65]).put_u32(self.tail as _);
+        (&mut self.buffer[65..69]).put_u32(self.tombstones as _);
         self.timestamp = SetTimestamp::current();
         (&mut self.buffer[4..20]).put_u128(self.timestamp);
-        self.checksum = Checksummer::checksum32(&self.buffer[4..Self::SET_HEADER_SIZE + self.len]);
+        self.checksum = if checksum { self.calculate_checksum() } else { 0 };
         (&mut self.buffer[0..4]).put_u32(self.checksum);
     }
 
+    /// Timestamp of the last update of the set.
+    pub fn timestamp(&self) -> u128 {
+        self.timestamp
+    }
+
+    /// Checksum of the set header and data as of the last load or update.
+    ///
+    /// The checksummed range includes the last updated timestamp, so sets with the same entries but updated at
+    /// different times have different checksums. Use [`SetStorage::content_checksum`] to compare the set content.
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    /// Checksum of the entry data of the set, excluding the set header.
+    ///
+    /// Sets with the same entries in the same order have the same content checksum, regardless of when they were
+    /// written, which can be used to detect duplicated sets. It is calculated over the live data on each call, there
+    /// is no need to update the set first.
+    pub fn content_checksum(&self) -> u32 {
+        Checksummer::checksum32_vectored(&[self.data(), self.tail_data()])
+    }
+
     pub fn bloom_filter(&self) -> &BloomFilterU64<4> {
         &self.bloom_filter
     }
 
-    #[cfg_attr(not(test), expect(dead_code))]
     pub fn len(&self) -> usize {
         self.len
     }
@@ -141,355 +757,2701 @@ impl SetStorage {
         self.len == 0
     }
 
-    pub fn clear(&mut self) {
-        self.len = 0;
-        self.bloom_filter.clear();
+    /// Count of the entries in the set, maintained on updates without scanning the set.
+    pub fn entry_count(&self) -> usize {
+        self.entries
     }
 
-    pub fn into_io_buffer(self) -> IoBuffer {
-        self.buffer
+    /// Entry layout of the set.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn layout(&self) -> SetLayout {
+        self.layout
     }
 
-    pub fn apply(&mut self, deletions: &HashSet<u64>, items: Vec<Item>) {
-        self.deletes(deletions);
-        self.append(items);
+    /// Set how entries are deleted by the following updates.
+    pub fn set_delete_mode(&mut self, delete_mode: SetDeleteMode) {
+        self.delete_mode = delete_mode;
     }
 
-    fn deletes(&mut self, deletes: &HashSet<u64>) {
-        if deletes.is_empty() {
-            return;
-        }
-
-        let mut rcursor = 0;
-        let mut wcursor = 0;
-        // Rebuild bloom filter.
-        self.bloom_filter.clear();
-
-        while rcursor < self.len {
-            let header = EntryHeader::read(
-                &self.buffer
-                    [Self::SET_HEADER_SIZE + rcursor..Self::SET_HEADER_SIZE + rcursor + EntryHeader::ENTRY_HEADER_SIZE],
-            );
-
-            if !deletes.contains(&header.hash()) {
-                if rcursor != wcursor {
-                    self.buffer.copy_within(
-                        Self::SET_HEADER_SIZE + rcursor..Self::SET_HEADER_SIZE + header.entry_len(),
-                        wcursor,
-                    );
-                }
-                wcursor += header.entry_len();
-                self.bloom_filter.insert(header.hash());
-            }
-
-            rcursor += header.entry_len();
-        }
+    /// Set when the bloom filter is rebuilt by the following deletions.
+    pub fn set_bloom_filter_rebuild(&mut self, bloom_filter_rebuild: SetBloomFilterRebuild) {
+        self.bloom_filter_rebuild = bloom_filter_rebuild;
+    }
 
-        self.len = wcursor;
+    /// Length of the entries marked as tombstones, which can be reclaimed by [`SetStorage::compact`].
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn tombstones(&self) -> usize {
+        self.tombstones
     }
 
-    fn append(&mut self, items: Vec<Item>) {
-        let (skip, size, _) = items
-            .iter()
-            .rev()
-            .fold((items.len(), 0, true), |(skip, size, proceed), item| {
-                let proceed = proceed && size + item.slice.len() <= self.size - Self::SET_HEADER_SIZE;
-                if proceed {
-                    (skip - 1, size + item.slice.len(), proceed)
-                } else {
-                    (skip, size, proceed)
-                }
-            });
+    /// Ratio of the set data capacity taken by the tombstones, see [`SetDeleteMode::Tombstone`].
+    pub fn tombstone_ratio(&self) -> f64 {
+        self.tombstones as f64 / self.capacity.max(1) as f64
+    }
 
-        self.reserve(size);
-        let mut cursor = Self::SET_HEADER_SIZE + self.len;
-        for item in items.iter().skip(skip) {
-            self.buffer[cursor..cursor + item.slice.len()].copy_from_slice(&item.slice);
-            self.bloom_filter.insert(item.hash);
-            cursor += item.slice.len();
-        }
-        self.len = cursor - Self::SET_HEADER_SIZE;
+    /// Reclaim the space of the tombstones by moving the live entries forward.
+    pub fn compact(&mut self) {
+        self.compact_tombstones();
     }
 
-    pub fn get<K, V>(&self, hash: u64) -> Result<Option<(K, V)>>
-    where
-        K: StorageKey,
-        V: StorageValue,
-    {
-        if !self.bloom_filter.lookup(hash) {
-            return Ok(None);
-        }
-        for entry in self.iter() {
-            if hash == entry.hash {
-                let now = Instant::now();
-                let (k, v) = EntryDeserializer::deserialize(
-                    &entry.buf[EntryHeader::ENTRY_HEADER_SIZE..],
-                    entry.key_len,
-                    entry.value_len,
-                    Compression::None,
-                    None,
-                )?;
-                self.metrics
-                    .storage_entry_deserialize_duration
-                    .record(now.elapsed().as_secs_f64());
-                return Ok(Some((k, v)));
-            }
+    /// Remove the entries marked as tombstones, move the live entries forward and rebuild the bloom filter from them.
+    ///
+    /// Unlike the deletions of [`SetStorage::apply`], which take the hashes to delete, only the entries already marked
+    /// as tombstones are removed. The set is left untouched if there is no tombstone.
+    ///
+    /// Returns the bytes reclaimed.
+    pub fn compact_tombstones(&mut self) -> usize {
+        if self.tombstones == 0 {
+            return 0;
         }
-        Ok(None)
+        let len = self.len;
+        self.compact_with(&HashSet::new(), true);
+        len - self.len
     }
 
-    /// from:
+    /// Remove the entries with the hash by compacting them out of the set, regardless of the delete mode.
     ///
-    /// ```plain
-    /// 0        wipe          len       capacity
-    /// |_________|ooooooooooooo|___________|
-    /// ```
-    ///
-    /// to:
+    /// The tombstones are compacted out as well, so no entry with the hash is left in the data of the set. The bytes
+    /// moved by the compaction are left behind in the unused space of the set until they are overwritten, with `zero`
+    /// the unused space is zeroed instead.
     ///
-    /// ```plain
-    /// 0     new len = len - wipe       capacity
-    /// |ooooooooooooo|_____________________|
-    /// ```
-    fn reserve(&mut self, required: usize) {
-        let remains = self.capacity - self.len;
-        if remains >= required {
-            return;
+    /// Returns whether a live entry with the hash was in the set.
+    pub fn remove(&mut self, hash: u64, zero: bool) -> bool {
+        let present = self.iter().any(|entry| entry.hash == hash);
+        self.compact_with(&HashSet::from([hash]), true);
+        if zero {
+            self.zero_unused();
         }
+        present
+    }
 
-        let mut wipe = 0;
-        for entry in self.iter() {
-            wipe += entry.len();
-            if remains + wipe >= required {
-                break;
-            }
-        }
-        self.buffer.copy_within(
-            Self::SET_HEADER_SIZE + wipe..Self::SET_HEADER_SIZE + self.len,
-            Self::SET_HEADER_SIZE,
-        );
-        self.len -= wipe;
-        assert!(self.capacity - self.len >= required);
-        let mut bloom_filter = BloomFilterU64::default();
-        for entry in self.iter() {
-            bloom_filter.insert(entry.hash);
-        }
-        self.bloom_filter = bloom_filter;
+    /// Bytes of the unused space of the set, between the head region and the tail region of the data.
+    ///
+    /// The space is never read by the set, but it may still hold the bytes of the entries deleted, wiped or moved by
+    /// compactions, which are written to the device with the set.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn unused(&self) -> &[u8] {
+        &self.buffer[self.unused_range()]
     }
 
-    fn iter(&self) -> SetIter<'_> {
-        SetIter::open(self)
+    /// Zero the unused space of the set, see [`SetStorage::unused`].
+    ///
+    /// Call it before writing the set to prevent the stale bytes from leaking to the device.
+    pub fn zero_unused(&mut self) {
+        let range = self.unused_range();
+        self.buffer[range].fill(0);
     }
 
-    fn data(&self) -> &[u8] {
-        &self.buffer[Self::SET_HEADER_SIZE..self.size]
+    fn unused_range(&self) -> Range<usize> {
+        Self::SET_HEADER_SIZE + self.head()..self.size - self.tail
     }
-}
 
-pub struct SetEntry<'a> {
-    offset: usize,
-    hash: u64,
-    buf: &'a [u8],
-    key_len: usize,
-    value_len: usize,
-}
+    /// If the set is corrupted on load, i.e. the lengths in the set header are out of range, the checksum mismatches,
+    /// or any entry is malformed with [`SetStorage::load_strict`]. A corrupted set is loaded as an empty set.
+    ///
+    /// A set written before the watermark or with another layout is loaded as an empty set too, but it is not corrupted.
+    pub fn is_corrupted(&self) -> bool {
+        self.corrupted
+    }
 
-impl SetEntry<'_> {
-    /// Length of the entry with header, key and value included.
-    pub fn len(&self) -> usize {
-        debug_assert_eq!(
-            self.buf.len(),
-            EntryHeader::ENTRY_HEADER_SIZE + self.key_len + self.value_len
-        );
-        self.buf.len()
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.tail = 0;
+        self.entries = 0;
+        self.tombstones = 0;
+        self.bloom_filter.clear();
+        self.stale_hashes = 0;
     }
 
-    /// Range of the entry in the set data.
-    #[expect(unused)]
-    pub fn range(&self) -> Range<usize> {
-        self.offset..self.offset + self.len()
+    /// Raw bytes of the whole set, including the set header and the padding after the data, without consuming the set.
+    ///
+    /// The bytes can be written to the device directly while the set keeps serving reads. The set header is only
+    /// written on [`SetStorage::update`], call it before taking the bytes, or the bytes carry a stale header.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.size]
     }
-}
 
-pub struct SetIter<'a> {
+    #[cfg_attr(not(any(test, feature = "fuzzing")), expect(dead_code))]
+    pub fn into_io_buffer(self) -> IoBuffer {
+        self.buffer
+    }
+
+    /// Delete the entries with the given hashes, then append the items.
+    ///
+    /// Returns the hashes of the entries wiped to make room for the items, oldest first. Unlike the deleted ones, the
+    /// wiped entries were still valid, so whoever tracks the entries of the set must forget them.
+    ///
+    /// Either side may be empty, the batches often carry only deletions or nothing for a set:
+    ///
+    /// - no deletions and no items: a no-op, the set is left untouched and nothing is wiped.
+    /// - deletions only: the entries are deleted, nothing is wiped.
+    /// - items only: the items are appended, the oldest entries are wiped only if the items don't fit.
+    /// - both: the deletions are applied first, so the space they free is reused by the items.
+    pub fn apply(&mut self, deletions: &HashSet<u64>, items: Vec<Item>) -> Vec<u64> {
+        if deletions.is_empty() && items.is_empty() {
+            return vec![];
+        }
+        self.deletes(deletions);
+        self.append(&items)
+    }
+
+    /// Same as [`SetStorage::apply`] without deletions, but copies from the borrowed items instead of consuming them.
+    ///
+    /// The items are left intact, e.g. to retry them against another set if they don't fit in this one.
+    ///
+    /// Returns the hashes of the wiped entries.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn append_refs(&mut self, items: &[Item]) -> Vec<u64> {
+        if items.is_empty() {
+            return vec![];
+        }
+        self.append(items)
+    }
+
+    /// Same as [`SetStorage::apply`], but yields to the async runtime every `yield_interval` scanned entries.
+    ///
+    /// `yield_interval == 0` means never yield.
+    pub async fn apply_async(&mut self, deletions: &HashSet<u64>, items: Vec<Item>, yield_interval: usize) -> Vec<u64> {
+        if deletions.is_empty() && items.is_empty() {
+            return vec![];
+        }
+        self.deletes_async(deletions, yield_interval).await;
+        self.append(&items)
+    }
+
+    fn deletes(&mut self, deletes: &HashSet<u64>) {
+        if let SetDeleteMode::Tombstone { ratio } = self.delete_mode {
+            self.mark_tombstones(deletes);
+            if self.exceeds(ratio) {
+                self.compact();
+            }
+            return;
+        }
+        if deletes.is_empty() && self.tombstones == 0 {
+            return;
+        }
+        let rebuild = self.rebuilds_bloom_filter(deletes.len());
+        self.compact_with(deletes, rebuild);
+    }
+
+    /// Whether the deletion of `deletes` hashes rebuilds the bloom filter, see [`SetBloomFilterRebuild`].
+    fn rebuilds_bloom_filter(&mut self, deletes: usize) -> bool {
+        match self.bloom_filter_rebuild {
+            SetBloomFilterRebuild::Eager => true,
+            SetBloomFilterRebuild::Lazy { interval } => self.stale_hashes + deletes >= interval,
+        }
+    }
+
+    /// Prepare to rebuild the bloom filter and the entry count while walking the entries, or to only recount the
+    /// entries, leaving the deleted hashes in the bloom filter.
+    fn reset_index(&mut self, rebuild: bool, deletes: usize) {
+        if rebuild {
+            self.bloom_filter.clear();
+            self.stale_hashes = 0;
+        } else {
+            self.stale_hashes += deletes;
+        }
+        self.entries = 0;
+    }
+
+    /// Remove the deleted entries and the tombstones by moving the retained entries forward.
+    ///
+    /// The bloom filter is rebuilt from the retained entries only if `rebuild` is set.
+    fn compact_with(&mut self, deletes: &HashSet<u64>, rebuild: bool) {
+        let head = self.head();
+        let mut rcursor = Cursor::default();
+        let mut wcursor = Cursor::default();
+        self.reset_index(rebuild, deletes.len());
+
+        while rcursor.head < head {
+            (rcursor, wcursor) = self.retain_entry(deletes, rebuild, rcursor, wcursor);
+        }
+
+        self.len = wcursor.head + wcursor.tail;
+        self.tail = wcursor.tail;
+        self.tombstones = 0;
+    }
+
+    async fn deletes_async(&mut self, deletes: &HashSet<u64>, yield_interval: usize) {
+        let (deletes, rebuild) = if let SetDeleteMode::Tombstone { ratio } = self.delete_mode {
+            self.mark_tombstones(deletes);
+            if !self.exceeds(ratio) {
+                return;
+            }
+            // The deleted entries are tombstones now, reclaim them the same as `compact`.
+            (deletes, true)
+        } else if deletes.is_empty() && self.tombstones == 0 {
+            return;
+        } else {
+            (deletes, self.rebuilds_bloom_filter(deletes.len()))
+        };
+
+        let head = self.head();
+        let mut rcursor = Cursor::default();
+        let mut wcursor = Cursor::default();
+        let mut scanned = 0;
+        self.reset_index(rebuild, deletes.len());
+
+        while rcursor.head < head {
+            (rcursor, wcursor) = self.retain_entry(deletes, rebuild, rcursor, wcursor);
+            scanned += 1;
+            if yield_interval > 0 && scanned % yield_interval == 0 {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        self.len = wcursor.head + wcursor.tail;
+        self.tail = wcursor.tail;
+        self.tombstones = 0;
+    }
+
+    fn exceeds(&self, ratio: f64) -> bool {
+        self.tombstone_ratio() > ratio
+    }
+
+    /// Mark the deleted entries as tombstones in place, and recount the entries.
+    ///
+    /// The bloom filter is rebuilt as [`SetBloomFilterRebuild`] says.
+    fn mark_tombstones(&mut self, deletes: &HashSet<u64>) {
+        if deletes.is_empty() {
+            return;
+        }
+
+        let head = self.head();
+        let mut cursor = 0;
+        let rebuild = self.rebuilds_bloom_filter(deletes.len());
+        self.reset_index(rebuild, deletes.len());
+
+        while cursor < head {
+            let offset = Self::SET_HEADER_SIZE + cursor;
+            let header = self.read_header(&self.buffer[offset..offset + EntryHeader::ENTRY_HEADER_SIZE]);
+            let (h, t) = self.entry_lens(header.key_len(), header.value_len());
+            cursor += h;
+            if header.is_tombstone() {
+                continue;
+            }
+            if deletes.contains(&header.hash()) {
+                EntryHeader::mark_tombstone(&mut self.buffer[offset..offset + EntryHeader::ENTRY_HEADER_SIZE]);
+                self.tombstones += h + t;
+                continue;
+            }
+            if rebuild {
+                self.bloom_filter.insert(header.hash());
+            }
+            self.entries += 1;
+        }
+    }
+
+    /// Move the entry at `rcursor` to `wcursor` if it is neither deleted nor a tombstone, return the advanced cursors.
+    ///
+    /// The part in the tail region is moved towards the end. It never overlaps with the retained entries before it.
+    /// The retained entry is inserted into the bloom filter only if `rebuild` is set.
+    fn retain_entry(
+        &mut self,
+        deletes: &HashSet<u64>,
+        rebuild: bool,
+        rcursor: Cursor,
+        wcursor: Cursor,
+    ) -> (Cursor, Cursor) {
+        let header = self.read_header(
+            &self.buffer[Self::SET_HEADER_SIZE + rcursor.head
+                ..Self::SET_HEADER_SIZE + rcursor.head + EntryHeader::ENTRY_HEADER_SIZE],
+        );
+        let (head, tail) = self.entry_lens(header.key_len(), header.value_len());
+        let next = Cursor {
+            head: rcursor.head + head,
+            tail: rcursor.tail + tail,
+        };
+
+        if header.is_tombstone() || deletes.contains(&header.hash()) {
+            return (next, wcursor);
+        }
+
+        if rcursor.head != wcursor.head {
+            self.buffer.copy_within(
+                Self::SET_HEADER_SIZE + rcursor.head..Self::SET_HEADER_SIZE + rcursor.head + head,
+                Self::SET_HEADER_SIZE + wcursor.head,
+            );
+        }
+        if rcursor.tail != wcursor.tail {
+            self.buffer.copy_within(
+                self.size - rcursor.tail - tail..self.size - rcursor.tail,
+                self.size - wcursor.tail - tail,
+            );
+        }
+        if rebuild {
+            self.bloom_filter.insert(header.hash());
+        }
+        self.entries += 1;
+
+        (
+            next,
+            Cursor {
+                head: wcursor.head + head,
+                tail: wcursor.tail + tail,
+            },
+        )
+    }
+
+    /// Append the items to the set, the oldest entries are wiped if the set runs out of capacity.
+    ///
+    /// Only the newest items that fit in the set are appended. The rest cannot be split to other sets, because an entry
+    /// is only looked up in the set its hash is routed to.
+    ///
+    /// The existing entries with the same hash and the same key as the appended items are replaced first, so the older
+    /// entries don't shadow the appended ones. Entries of other keys with the same hash are kept.
+    ///
+    /// Returns the hashes of the wiped entries.
+    fn append(&mut self, items: &[Item]) -> Vec<u64> {
+        let (skip, size, _) = items
+            .iter()
+            .rev()
+            .fold((items.len(), 0, true), |(skip, size, proceed), item| {
+                let len = self.item_len(item);
+                let proceed = proceed && size + len <= self.size - Self::SET_HEADER_SIZE;
+                if proceed {
+                    (skip - 1, size + len, proceed)
+                } else {
+                    (skip, size, proceed)
+                }
+            });
+
+        if skip > 0 {
+            tracing::debug!(
+                "[sodc set]: {skip} of {} items ignored, reason: exceed the set capacity",
+                items.len()
+            );
+        }
+
+        self.replace(&items[skip..]);
+        let wiped = self.reserve(size);
+        for item in items.iter().skip(skip) {
+            self.push(item);
+        }
+        wiped
+    }
+
+    /// Mark the existing entries with the same hash and the same key as the items as tombstones.
+    ///
+    /// The tombstones are reclaimed right away with [`SetDeleteMode::Compact`], otherwise when they exceed the ratio or
+    /// the set runs out of space.
+    fn replace(&mut self, items: &[Item]) {
+        if items.iter().all(|item| !self.bloom_filter.lookup(item.hash)) {
+            return;
+        }
+
+        // Items are serialized in `SetLayout::Interleaved`, the key is after the value.
+        let mut keys = HashMap::<u64, Vec<&[u8]>>::new();
+        for item in items {
+            let header = self.read_header(&item.slice[..EntryHeader::ENTRY_HEADER_SIZE]);
+            let key = &item.slice[EntryHeader::ENTRY_HEADER_SIZE + header.value_len()..];
+            keys.entry(item.hash).or_default().push(key);
+        }
+
+        let head = self.head();
+        let mut cursor = 0;
+        let mut replaced = false;
+        while cursor < head {
+            let offset = Self::SET_HEADER_SIZE + cursor;
+            let header = self.read_header(&self.buffer[offset..offset + EntryHeader::ENTRY_HEADER_SIZE]);
+            let (h, t) = self.entry_lens(header.key_len(), header.value_len());
+            cursor += h;
+            if header.is_tombstone() {
+                continue;
+            }
+            let collided = keys.get(&header.hash()).is_some_and(|keys| {
+                let start = match self.layout {
+                    SetLayout::Interleaved => offset + self.entry_header_size() + header.value_len(),
+                    SetLayout::Split => offset + self.entry_header_size(),
+                };
+                let key = &self.buffer[start..start + header.key_len()];
+                keys.contains(&key)
+            });
+            if collided {
+                EntryHeader::mark_tombstone(&mut self.buffer[offset..offset + EntryHeader::ENTRY_HEADER_SIZE]);
+                self.tombstones += h + t;
+                self.entries -= 1;
+                replaced = true;
+            }
+        }
+
+        if !replaced {
+            return;
+        }
+        match self.delete_mode {
+            SetDeleteMode::Compact => self.compact(),
+            SetDeleteMode::Tombstone { ratio } if self.exceeds(ratio) => self.compact(),
+            SetDeleteMode::Tombstone { .. } => {}
+        }
+    }
+
+    /// Push the serialized entry of the item to the set, the caller guarantees that there is enough space.
+    ///
+    /// The item is serialized in [`SetLayout::Interleaved`], it is split here for [`SetLayout::Split`]. The secondary
+    /// hash is calculated here if enabled, so is the insertion time unless the item carries one.
+    fn push(&mut self, item: &Item) {
+        let mut cursor = Self::SET_HEADER_SIZE + self.head();
+        let header = self.read_header(&item.slice[..EntryHeader::ENTRY_HEADER_SIZE]);
+        let (value, key) = item.slice[EntryHeader::ENTRY_HEADER_SIZE..].split_at(header.value_len());
+        self.buffer[cursor..cursor + EntryHeader::ENTRY_HEADER_SIZE]
+            .copy_from_slice(&item.slice[..EntryHeader::ENTRY_HEADER_SIZE]);
+        cursor += EntryHeader::ENTRY_HEADER_SIZE;
+        if self.fields.secondary_hash {
+            (&mut self.buffer[cursor..cursor + EntryHeader::SECONDARY_HASH_SIZE])
+                .put_u32(EntryHeader::secondary_hash(key));
+            cursor += EntryHeader::SECONDARY_HASH_SIZE;
+        }
+        if self.fields.inserted_at {
+            (&mut self.buffer[cursor..cursor + EntryHeader::INSERTED_AT_SIZE])
+                .put_u64(item.inserted_at.unwrap_or_else(EntryHeader::inserted_at_now));
+            cursor += EntryHeader::INSERTED_AT_SIZE;
+        }
+        match self.layout {
+            SetLayout::Interleaved => {
+                self.buffer[cursor..cursor + value.len()].copy_from_slice(value);
+                self.buffer[cursor + value.len()..cursor + value.len() + key.len()].copy_from_slice(key);
+                self.len += self.item_len(item);
+            }
+            SetLayout::Split => {
+                let head = self.entry_header_size() + key.len();
+                self.buffer[cursor..cursor + key.len()].copy_from_slice(key);
+                let end = self.size - self.tail;
+                self.buffer[end - value.len()..end].copy_from_slice(value);
+                self.len += head + value.len();
+                self.tail += value.len();
+            }
+        }
+        self.bloom_filter.insert(item.hash);
+        self.entries += 1;
+    }
+
+    /// Get the first entry with the given hash, both the key and the value are deserialized.
+    ///
+    /// With the secondary hash enabled, an entry whose key mismatches its secondary hash is skipped, see
+    /// [`SetEntry::is_intact`].
+    #[cfg_attr(feature = "tracing", fastrace::trace(name = "foyer::storage::small::set::get"))]
+    pub fn get<K, V>(&self, hash: u64) -> Result<Option<(K, V)>>
+    where
+        K: StorageKey,
+        V: StorageValue,
+    {
+        if !self.bloom_filter.lookup(hash) {
+            return Ok(None);
+        }
+        for entry in self.iter() {
+            if hash == entry.hash && entry.is_intact() {
+                let now = Instant::now();
+                let v = EntryDeserializer::deserialize_value(entry.value, Compression::None)?;
+                let k = EntryDeserializer::deserialize_key(entry.key)?;
+                self.metrics
+                    .storage_entry_deserialize_duration
+                    .record(now.elapsed().as_secs_f64());
+                return Ok(Some((k, v)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Get the entry with the given hash as [`SetStorage::get`], only the key is deserialized.
+    pub fn get_raw<K>(&self, hash: u64) -> Result<Option<(K, RawValue)>>
+    where
+        K: StorageKey,
+    {
+        if !self.bloom_filter.lookup(hash) {
+            return Ok(None);
+        }
+        for entry in self.iter() {
+            if hash == entry.hash && entry.is_intact() {
+                let k = EntryDeserializer::deserialize_key(entry.key)?;
+                return Ok(Some((k, RawValue::new(entry.value.to_vec(), Compression::None))));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Whether the set holds an entry with the given hash, only the entry headers are read.
+    pub fn contains(&self, hash: u64) -> bool {
+        self.bloom_filter.lookup(hash) && self.iter().any(|entry| entry.hash == hash)
+    }
+
+    /// Get the value of the entry with the given hash and key.
+    ///
+    /// Unlike [`SetStorage::get`], which returns the first entry with the same hash, the key of the entry is compared
+    /// and the scan continues on mismatch. So the right value is returned even if the hash collides.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn get_exact<K, V>(&self, hash: u64, key: &K) -> Result<Option<V>>
+    where
+        K: StorageKey,
+        V: StorageValue,
+    {
+        if !self.bloom_filter.lookup(hash) {
+            return Ok(None);
+        }
+        for entry in self.iter_entries().filter(|entry| entry.hash == hash) {
+            let now = Instant::now();
+            let k: K = EntryDeserializer::deserialize_key(entry.key)?;
+            if &k != key {
+                continue;
+            }
+            let v = EntryDeserializer::deserialize_value(entry.value, Compression::None)?;
+            self.metrics
+                .storage_entry_deserialize_duration
+                .record(now.elapsed().as_secs_f64());
+            return Ok(Some(v));
+        }
+        Ok(None)
+    }
+
+    /// Iterate the entries in the set, both keys and values are deserialized.
+    pub fn entries<K, V>(&self) -> impl Iterator<Item = Result<(K, V)>> + '_
+    where
+        K: StorageKey,
+        V: StorageValue,
+    {
+        self.iter_entries().map(|entry| {
+            let key = EntryDeserializer::deserialize_key(entry.key)?;
+            let value = EntryDeserializer::deserialize_value(entry.value, Compression::None)?;
+            Ok((key, value))
+        })
+    }
+
+    /// Iterate the insertion times of the entries in the set in the same order as [`SetStorage::entries`], `None` if
+    /// the set is written without them, see [`SetEntryFields::inserted_at`].
+    pub fn inserted_ats(&self) -> impl Iterator<Item = Option<SystemTime>> + '_ {
+        self.iter_entries().map(|entry| entry.inserted_at())
+    }
+
+    /// Iterate the hashes, keys and values of the entries in the set, both keys and values are deserialized.
+    pub fn entries_with_hash<K, V>(&self) -> impl Iterator<Item = Result<(u64, K, V)>> + '_
+    where
+        K: StorageKey,
+        V: StorageValue,
+    {
+        self.iter_entries().map(|entry| {
+            let key = EntryDeserializer::deserialize_key(entry.key)?;
+            let value = EntryDeserializer::deserialize_value(entry.value, Compression::None)?;
+            Ok((entry.hash, key, value))
+        })
+    }
+
+    /// Iterate the hashes and keys of the entries in the set.
+    ///
+    /// Only keys are deserialized, values are skipped with the value length recorded in the entry header. With
+    /// [`SetLayout::Split`], the values are not touched at all.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn keys<K>(&self) -> impl Iterator<Item = Result<(u64, K)>> + '_
+    where
+        K: StorageKey,
+    {
+        self.iter_entries().map(|entry| {
+            let key = EntryDeserializer::deserialize_key(entry.key)?;
+            Ok((entry.hash, key))
+        })
+    }
+
+    /// from:
+    ///
+    /// ```plain
+    /// 0     wipe head      head              len - head    wipe tail  capacity
+    /// |_________|ooooooooooo|_______________________|oooooooooo|_________|
+    /// ```
+    ///
+    /// to:
+    ///
+    /// ```plain
+    /// 0  new head = head - wipe head                 new tail = tail - wipe tail
+    /// |ooooooooooo|______________________________________________|oooooooooo|
+    /// ```
+    ///
+    /// The tail region is always empty with [`SetLayout::Interleaved`].
+    ///
+    /// The tombstones are reclaimed first, the oldest entries are only wiped if it is still not enough. Returns the
+    /// hashes of the wiped entries, oldest first.
+    fn reserve(&mut self, required: usize) -> Vec<u64> {
+        if self.capacity - self.len < required && self.tombstones > 0 {
+            self.compact();
+        }
+        let remains = self.capacity - self.len;
+        if remains >= required {
+            return vec![];
+        }
+
+        let mut wipe = Cursor::default();
+        let mut wiped = vec![];
+        for entry in self.iter() {
+            let (head, tail) = self.entry_lens(entry.key.len(), entry.value.len());
+            wipe.head += head;
+            wipe.tail += tail;
+            wiped.push(entry.hash);
+            if remains + wipe.head + wipe.tail >= required {
+                break;
+            }
+        }
+        let head = self.head();
+        self.buffer.copy_within(
+            Self::SET_HEADER_SIZE + wipe.head..Self::SET_HEADER_SIZE + head,
+            Self::SET_HEADER_SIZE,
+        );
+        self.buffer.copy_within(
+            self.size - self.tail..self.size - wipe.tail,
+            self.size - self.tail + wipe.tail,
+        );
+        self.len -= wipe.head + wipe.tail;
+        self.tail -= wipe.tail;
+        self.entries -= wiped.len();
+        assert!(self.capacity - self.len >= required);
+        let mut bloom_filter = BloomFilterU64::default();
+        for entry in self.iter() {
+            bloom_filter.insert(entry.hash);
+        }
+        self.bloom_filter = bloom_filter;
+        self.stale_hashes = 0;
+        wiped
+    }
+
+    fn iter(&self) -> SetIter<'_> {
+        SetIter::open(self)
+    }
+
+    /// Iterate the entries of the set, skipping the chunks and the manifests of the entries striped across the sets.
+    ///
+    /// The chunks and the manifests are only read by their hashes, they never decode as the entries of the cache.
+    fn iter_entries(&self) -> impl Iterator<Item = SetEntry<'_>> + '_ {
+        self.iter().filter(|entry| !entry.chunk)
+    }
+
+    /// Serialize the entries of the set as items from the oldest to the newest, to append them to another set.
+    ///
+    /// The items are serialized in [`SetLayout::Interleaved`] as given to [`SetStorage::apply`], tombstones are skipped.
+    /// The insertion times of the entries are kept if recorded.
+    pub fn items(&self) -> Vec<Item> {
+        self.iter()
+            .map(|entry| {
+                let mut buf = Vec::with_capacity(EntryHeader::ENTRY_HEADER_SIZE + entry.value.len() + entry.key.len());
+                EntryHeader::new(entry.hash, entry.key.len(), entry.value.len())
+                    .with_chunk(entry.chunk)
+                    .write(&mut buf);
+                buf.extend_from_slice(entry.value);
+                buf.extend_from_slice(entry.key);
+                Item {
+                    slice: Bytes::from(buf),
+                    hash: entry.hash,
+                    inserted_at: entry.inserted_at,
+                }
+            })
+            .collect()
+    }
+
+    /// Iterate the entries of the set from the newest to the oldest.
+    ///
+    /// Entries are variable-length, their offsets are collected with a forward pass first.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn iter_rev(&self) -> impl Iterator<Item = SetEntry<'_>> + '_ {
+        self.iter_entries().collect::<Vec<_>>().into_iter().rev()
+    }
+
+    /// Iterate the entries of the set whose hash is in `range`, from the oldest to the newest.
+    ///
+    /// Entries are not sorted by hash, all entries are still scanned.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn iter_range(&self, range: Range<u64>) -> impl Iterator<Item = SetEntry<'_>> + '_ {
+        self.iter_entries().filter(move |entry| range.contains(&entry.hash))
+    }
+
+    /// Length of the written head region.
+    fn head(&self) -> usize {
+        self.len - self.tail
+    }
+
+    /// Live bytes of the head region of the data, i.e. the entry headers, and the keys and the values with
+    /// [`SetLayout::Interleaved`] or only the keys with [`SetLayout::Split`].
+    ///
+    /// The unused space after it is excluded, see [`SetStorage::unused`].
+    fn data(&self) -> &[u8] {
+        &self.buffer[Self::SET_HEADER_SIZE..Self::SET_HEADER_SIZE + self.head()]
+    }
+
+    /// Live bytes of the tail region of the data, i.e. the values with [`SetLayout::Split`], the latest one first.
+    fn tail_data(&self) -> &[u8] {
+        &self.buffer[self.size - self.tail..self.size]
+    }
+
+    /// Check that every entry of the set is present in the bloom filter, i.e. the bloom filter has no false negative.
+    ///
+    /// A false negative makes [`SetStorage::get`] miss a present entry. Unlike [`SetStorage::check_invariants`], only
+    /// the bloom filter is checked and nothing panics.
+    #[cfg_attr(not(any(test, feature = "fuzzing")), expect(dead_code))]
+    pub fn validate_bloom_filter(&self) -> bool {
+        self.iter().all(|entry| self.bloom_filter.lookup(entry.hash))
+    }
+
+    /// Check the invariants between the set header and the entries, panic on violation.
+    ///
+    /// All entry headers are walked, so it is only meant for tests and fuzzing.
+    #[cfg(any(test, feature = "fuzzing"))]
+    pub fn check_invariants(&self) {
+        assert!(
+            self.len <= self.capacity,
+            "len {} exceeds capacity {}",
+            self.len,
+            self.capacity
+        );
+        assert!(self.tail <= self.len, "tail {} exceeds len {}", self.tail, self.len);
+        if self.layout == SetLayout::Interleaved {
+            assert_eq!(self.tail, 0, "tail region is used with the interleaved layout");
+        }
+
+        let head = self.head();
+        let mut cursor = Cursor::default();
+        let mut entries = 0;
+        let mut tombstones = 0;
+        while cursor.head < head {
+            assert!(
+                cursor.head + self.entry_header_size() <= head,
+                "entry header at {} crosses head {head}",
+                cursor.head
+            );
+            let header = self.read_header(&self.data()[cursor.head..cursor.head + EntryHeader::ENTRY_HEADER_SIZE]);
+            let (h, t) = self.entry_lens(header.key_len(), header.value_len());
+            cursor.head += h;
+            cursor.tail += t;
+            if header.is_tombstone() {
+                tombstones += h + t;
+            } else {
+                entries += 1;
+                assert!(
+                    self.bloom_filter.lookup(header.hash()),
+                    "entry {} is missing in the bloom filter",
+                    header.hash()
+                );
+            }
+        }
+        assert_eq!(cursor.head, head, "entries overrun head");
+        assert_eq!(cursor.tail, self.tail, "entries mismatch tail");
+        assert_eq!(entries, self.entries, "entry count mismatch");
+        assert_eq!(tombstones, self.tombstones, "tombstone len mismatch");
+    }
+}
+
+/// Read the entries of the set of `size` bytes at `offset` of the source, oldest first.
+///
+/// Meant for external tools that inspect a region file of the small object disk cache without loading the whole file,
+/// with the offset of the set located by [`crate::region_offset`]. Only the set is read, and all its entries are
+/// checked against the bounds of the set, so a malformed set is read as empty instead of panicking. The chunks and the
+/// manifests of the entries striped across the sets are skipped.
+///
+/// Returns an error if `size` is not a valid set size, if the source ends before `size` bytes are read, if the set is
+/// written with a layout or entry fields other than the `options`, or if any entry fails to deserialize.
+pub fn read_set_entries<K, V, R>(r: &mut R, offset: u64, size: usize, options: &SetReadOptions) -> Result<Vec<(K, V)>>
+where
+    K: StorageKey,
+    V: StorageValue,
+    R: Read + Seek,
+{
+    SetStorage::load_from(r, offset, size, options)?.entries().collect()
+}
+
+/// A read-only set that can be shared among readers.
+#[derive(Debug, Clone)]
+pub struct Set {
+    storage: Arc<SetStorage>,
+}
+
+impl Deref for Set {
+    type Target = SetStorage;
+
+    fn deref(&self) -> &Self::Target {
+        &self.storage
+    }
+}
+
+impl From<SetStorage> for Set {
+    fn from(storage: SetStorage) -> Self {
+        Self {
+            storage: Arc::new(storage),
+        }
+    }
+}
+
+impl Set {
+    /// Get the raw bytes of the set as an io buffer, without copying.
+    pub fn bytes(&self) -> SetBytes {
+        SetBytes(self.clone())
+    }
+}
+
+/// Raw bytes of a [`Set`], which can be written to the device directly.
+#[derive(Debug)]
+pub struct SetBytes(Set);
+
+impl Deref for SetBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_bytes()
+    }
+}
+
+impl IoBuf for SetBytes {}
+
+/// A set that is exclusively owned by a writer.
+///
+/// The modifications are only visible after the set is published with [`SetMut::publish`]. Dropping a modified
+/// [`SetMut`] without publishing it loses the modifications, and a warning is logged.
+#[derive(Debug)]
+#[must_use = "the modifications of the set are lost if it is not published"]
+pub struct SetMut {
+    /// Always `Some` until the set is published.
+    storage: Option<SetStorage>,
+    dirty: bool,
+}
+
+impl Deref for SetMut {
+    type Target = SetStorage;
+
+    fn deref(&self) -> &Self::Target {
+        self.storage.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for SetMut {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.dirty = true;
+        self.storage.as_mut().unwrap()
+    }
+}
+
+impl Drop for SetMut {
+    fn drop(&mut self) {
+        if self.dirty && self.storage.is_some() {
+            tracing::warn!(
+                "[sodc set]: set is modified but dropped without being published, the modifications are lost: {:?}",
+                self.storage
+            );
+        }
+    }
+}
+
+impl SetMut {
+    pub fn new(storage: SetStorage) -> Self {
+        Self {
+            storage: Some(storage),
+            dirty: false,
+        }
+    }
+
+    /// Seal the modifications and publish the set as a read-only [`Set`].
+    ///
+    /// The set header is updated before publishing, so there is no need to call [`SetStorage::update`] first.
+    ///
+    /// The published set is never modified again. Any reader that receives the [`Set`] from the writer, e.g. via the
+    /// set cache or by acquiring the set lock after the writer releases it, is guaranteed to see the fully written set.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn publish(self) -> Set {
+        self.publish_with_checksum(true)
+    }
+
+    /// Seal the modifications and publish the set, calculating the set checksum only if `checksum` is set.
+    ///
+    /// See [`SetStorage::update_with_checksum`].
+    pub fn publish_with_checksum(mut self, checksum: bool) -> Set {
+        let mut storage = self.storage.take().unwrap();
+        storage.update_with_checksum(checksum);
+        Set::from(storage)
+    }
+}
+
+pub struct SetEntry<'a> {
+    hash: u64,
+    /// Secondary hash of the entry, `None` if the set is written without it.
+    secondary: Option<u32>,
+    /// Insertion time of the entry in milliseconds since the unix epoch, `None` if the set is written without it.
+    inserted_at: Option<u64>,
+    /// If the entry is a chunk or a manifest of an entry striped across the sets, see [`EntryHeader::is_chunk`].
+    chunk: bool,
+    key: &'a [u8],
+    value: &'a [u8],
+}
+
+impl SetEntry<'_> {
+    /// Whether the key of the entry matches its secondary hash, always `true` if the set is written without it.
+    ///
+    /// The sets are not always verified on read, so a key torn or corrupted on the device is only caught by the
+    /// secondary hash before the entry is taken as the entry of the hash.
+    pub fn is_intact(&self) -> bool {
+        self.secondary
+            .is_none_or(|secondary| secondary == EntryHeader::secondary_hash(self.key))
+    }
+
+    /// Insertion time of the entry, `None` if the set is written without it, see [`SetEntryFields::inserted_at`].
+    pub fn inserted_at(&self) -> Option<SystemTime> {
+        self.inserted_at
+            .map(|millis| UNIX_EPOCH + Duration::from_millis(millis))
+    }
+}
+
+pub struct SetIter<'a> {
     set: &'a SetStorage,
-    offset: usize,
+    cursor: Cursor,
+}
+
+impl<'a> SetIter<'a> {
+    fn open(set: &'a SetStorage) -> Self {
+        Self {
+            set,
+            cursor: Cursor::default(),
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        self.cursor.head < self.set.head()
+    }
+
+    fn end(&mut self) -> Option<Option<SetEntry<'a>>> {
+        self.cursor.head = self.set.head();
+        None
+    }
+
+    fn next(&mut self) -> Option<SetEntry<'a>> {
+        loop {
+            let entry = self.advance()?;
+            if let Some(entry) = entry {
+                return Some(entry);
+            }
+        }
+    }
+
+    /// Advance over one entry, return `Some(None)` if the entry is a tombstone.
+    ///
+    /// The entries of a set loaded without verifying may overrun the set, the iteration ends at the first entry that
+    /// overruns instead of reading out of bounds.
+    fn advance(&mut self) -> Option<Option<SetEntry<'a>>> {
+        if !self.is_valid() {
+            return None;
+        }
+        let data = self.set.data();
+        let offset = self.cursor.head;
+        if offset + self.set.entry_header_size() > data.len() {
+            return self.end();
+        }
+        let header = self
+            .set
+            .read_header(&data[offset..offset + EntryHeader::ENTRY_HEADER_SIZE]);
+        let (head, tail) = self.set.entry_lens(header.key_len(), header.value_len());
+        if offset + head > data.len() || self.cursor.tail + tail > self.set.tail {
+            return self.end();
+        }
+        let offset = offset + EntryHeader::ENTRY_HEADER_SIZE;
+        let (secondary, offset) = if self.set.fields.secondary_hash {
+            (
+                Some((&data[offset..offset + EntryHeader::SECONDARY_HASH_SIZE]).get_u32()),
+                offset + EntryHeader::SECONDARY_HASH_SIZE,
+            )
+        } else {
+            (None, offset)
+        };
+        let (inserted_at, offset) = if self.set.fields.inserted_at {
+            (
+                Some((&data[offset..offset + EntryHeader::INSERTED_AT_SIZE]).get_u64()),
+                offset + EntryHeader::INSERTED_AT_SIZE,
+            )
+        } else {
+            (None, offset)
+        };
+        let (key, value) = match self.set.layout {
+            SetLayout::Interleaved => (
+                &data[offset + header.value_len()..offset + header.value_len() + header.key_len()],
+                &data[offset..offset + header.value_len()],
+            ),
+            SetLayout::Split => {
+                let end = self.set.tail - self.cursor.tail;
+                (
+                    &data[offset..offset + header.key_len()],
+                    &self.set.tail_data()[end - header.value_len()..end],
+                )
+            }
+        };
+        self.cursor.head += head;
+        self.cursor.tail += tail;
+        if header.is_tombstone() {
+            return Some(None);
+        }
+        Some(Some(SetEntry {
+            hash: header.hash(),
+            secondary,
+            inserted_at,
+            chunk: header.is_chunk(),
+            key,
+            value,
+        }))
+    }
+}
+
+impl<'a> Iterator for SetIter<'a> {
+    type Item = SetEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next()
+    }
 }
 
-impl<'a> SetIter<'a> {
-    fn open(set: &'a SetStorage) -> Self {
-        Self { set, offset: 0 }
+pub struct SetTimestamp;
+
+impl SetTimestamp {
+    pub fn current() -> u128 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos()
     }
+}
 
-    fn is_valid(&self) -> bool {
-        self.offset < self.set.len
+#[cfg(test)]
+mod tests {
+
+    use foyer_common::{
+        code::{Code, CodeError},
+        hasher::ModHasher,
+        metrics::Metrics,
+    };
+    use foyer_memory::{Cache, CacheBuilder, CacheEntry};
+
+    use super::*;
+    use crate::{error::Error, serde::EntrySerializer, Compression};
+
+    fn to_bytes(entry: &CacheEntry<u64, Vec<u8>>) -> Bytes {
+        to_bytes_with_hash(entry.key(), entry.value(), entry.hash())
     }
 
-    fn next(&mut self) -> Option<SetEntry<'a>> {
-        if !self.is_valid() {
-            return None;
+    fn to_bytes_with_hash<K: StorageKey, V: StorageValue>(key: &K, value: &V, hash: u64) -> Bytes {
+        let mut buf = vec![];
+
+        // reserve header
+        let header = EntryHeader::new(0, 0, 0);
+        header.write(&mut buf);
+
+        let info = EntrySerializer::serialize(key, value, Compression::None, &mut buf).unwrap();
+
+        let header = EntryHeader::new(hash, info.key_len, info.value_len);
+        header.write(&mut buf[0..EntryHeader::ENTRY_HEADER_SIZE]);
+
+        Bytes::from(buf)
+    }
+
+    fn assert_some(storage: &SetStorage, entry: &CacheEntry<u64, Vec<u8>>) {
+        let ret = storage.get::<u64, Vec<u8>>(entry.hash()).unwrap();
+        let (k, v) = ret.unwrap();
+        assert_eq!(&k, entry.key());
+        assert_eq!(&v, entry.value());
+    }
+
+    fn assert_none(storage: &SetStorage, hash: u64) {
+        let ret = storage.get::<u64, Vec<u8>>(hash).unwrap();
+        assert!(ret.is_none());
+    }
+
+    /// A key/value type that encodes into no bytes.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct Empty;
+
+    impl Code for Empty {
+        fn encode(&self, _: &mut impl std::io::Write) -> std::result::Result<(), CodeError> {
+            Ok(())
         }
-        let header = EntryHeader::read(&self.set.data()[self.offset..self.offset + EntryHeader::ENTRY_HEADER_SIZE]);
-        let entry = SetEntry {
-            offset: self.offset,
-            hash: header.hash(),
-            buf: &self.set.data()
-                [self.offset..self.offset + EntryHeader::ENTRY_HEADER_SIZE + header.key_len() + header.value_len()],
-            key_len: header.key_len(),
-            value_len: header.value_len(),
+
+        fn decode(_: &mut impl std::io::Read) -> std::result::Result<Self, CodeError> {
+            Ok(Self)
+        }
+
+        fn estimated_size(&self) -> usize {
+            0
+        }
+    }
+
+    fn memory_for_test() -> Cache<u64, Vec<u8>> {
+        CacheBuilder::new(100).build()
+    }
+
+    fn buffer_for_test() -> IoBuffer {
+        // `IoBuffer` is not initialized, zero it to make sure it is not loaded as a valid set.
+        let mut buf = IoBuffer::new(PAGE);
+        buf.fill(0);
+        buf
+    }
+
+    #[test]
+    fn test_set_storage_basic() {
+        let memory = memory_for_test();
+
+        // load will result in an empty set
+        let buf = buffer_for_test();
+        let mut storage = SetStorage::load(buf, 0, Arc::new(Metrics::noop()));
+        assert!(storage.is_empty());
+
+        let e1 = memory.insert(1, vec![b'1'; 42]);
+        let s1 = to_bytes(&e1);
+        storage.apply(
+            &HashSet::from_iter([2, 4]),
+            vec![Item {
+                slice: s1.clone(),
+                hash: e1.hash(),
+                inserted_at: None,
+            }],
+        );
+        assert_eq!(storage.len(), s1.len());
+        assert_eq!(storage.entry_count(), 1);
+        assert_some(&storage, &e1);
+
+        let e2 = memory.insert(2, vec![b'2'; 97]);
+        let s2 = to_bytes(&e2);
+        storage.apply(
+            &HashSet::from_iter([e1.hash(), 3, 5]),
+            vec![Item {
+                slice: s2.clone(),
+                hash: e2.hash(),
+                inserted_at: None,
+            }],
+        );
+        assert_eq!(storage.len(), s2.len());
+        assert_eq!(storage.entry_count(), 1);
+        assert_none(&storage, e1.hash());
+        assert_some(&storage, &e2);
+
+        let e3 = memory.insert(3, vec![b'3'; 211]);
+        let s3 = to_bytes(&e3);
+        storage.apply(
+            &HashSet::from_iter([e1.hash()]),
+            vec![Item {
+                slice: s3.clone(),
+                hash: e3.hash(),
+                inserted_at: None,
+            }],
+        );
+        assert_eq!(storage.len(), s2.len() + s3.len());
+        assert_eq!(storage.entry_count(), 2);
+        assert_none(&storage, e1.hash());
+        assert_some(&storage, &e2);
+        assert_some(&storage, &e3);
+
+        let e4 = memory.insert(4, vec![b'4'; 3800]);
+        let s4 = to_bytes(&e4);
+        storage.apply(
+            &HashSet::from_iter([e1.hash()]),
+            vec![Item {
+                slice: s4.clone(),
+                hash: e4.hash(),
+                inserted_at: None,
+            }],
+        );
+        assert_eq!(storage.len(), s4.len());
+        assert_eq!(storage.entry_count(), 1);
+        assert_none(&storage, e1.hash());
+        assert_none(&storage, e2.hash());
+        assert_none(&storage, e3.hash());
+        assert_some(&storage, &e4);
+
+        // test recovery
+        storage.update();
+        let bytes = storage.into_io_buffer();
+        let mut buf = buffer_for_test();
+        buf[0..bytes.len()].copy_from_slice(&bytes);
+        let mut storage = SetStorage::load(buf, 0, Arc::new(Metrics::noop()));
+
+        assert_eq!(storage.len(), s4.len());
+        assert_eq!(storage.entry_count(), 1);
+        assert_none(&storage, e1.hash());
+        assert_none(&storage, e2.hash());
+        assert_none(&storage, e3.hash());
+        assert_some(&storage, &e4);
+
+        // test oversize entry
+        let e5 = memory.insert(5, vec![b'5'; 20 * 1024]);
+        let s5 = to_bytes(&e5);
+        storage.apply(
+            &HashSet::new(),
+            vec![Item {
+                slice: s5.clone(),
+                hash: e5.hash(),
+                inserted_at: None,
+            }],
+        );
+        assert_eq!(storage.len(), s4.len());
+        assert_eq!(storage.entry_count(), 1);
+        assert_none(&storage, e1.hash());
+        assert_none(&storage, e2.hash());
+        assert_none(&storage, e3.hash());
+        assert_some(&storage, &e4);
+    }
+
+    #[test]
+    fn test_set_storage_keys() {
+        let memory = memory_for_test();
+
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+        assert_eq!(storage.keys::<u64>().count(), 0);
+
+        let entries = (0..4)
+            .map(|i| memory.insert(i, vec![i as u8; 100 * (i as usize + 1)]))
+            .collect::<Vec<_>>();
+        storage.apply(
+            &HashSet::new(),
+            entries
+                .iter()
+                .map(|e| Item {
+                    slice: to_bytes(e),
+                    hash: e.hash(),
+                    inserted_at: None,
+                })
+                .collect(),
+        );
+
+        let keys = storage.keys::<u64>().collect::<Result<Vec<_>>>().unwrap();
+        let expected = entries.iter().map(|e| (e.hash(), *e.key())).collect::<Vec<_>>();
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn test_set_storage_iter_rev() {
+        let memory = memory_for_test();
+
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+        assert_eq!(storage.iter_rev().count(), 0);
+
+        let entries = (0..4)
+            .map(|i| memory.insert(i, vec![i as u8; 100 * (i as usize + 1)]))
+            .collect::<Vec<_>>();
+        storage.apply(
+            &HashSet::new(),
+            entries
+                .iter()
+                .map(|e| Item {
+                    slice: to_bytes(e),
+                    hash: e.hash(),
+                    inserted_at: None,
+                })
+                .collect(),
+        );
+
+        let hashes = storage.iter_rev().map(|entry| entry.hash).collect::<Vec<_>>();
+        let expected = entries.iter().rev().map(|e| e.hash()).collect::<Vec<_>>();
+        assert_eq!(hashes, expected);
+    }
+
+    #[test]
+    fn test_set_storage_validate_bloom_filter() {
+        let memory = memory_for_test();
+        let items = |entries: &[CacheEntry<u64, Vec<u8>>]| {
+            entries
+                .iter()
+                .map(|e| Item {
+                    slice: to_bytes(e),
+                    hash: e.hash(),
+                    inserted_at: None,
+                })
+                .collect::<Vec<_>>()
         };
-        self.offset += entry.len();
-        Some(entry)
+
+        for delete_mode in [SetDeleteMode::Compact, SetDeleteMode::Tombstone { ratio: 0.5 }] {
+            let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+            storage.set_delete_mode(delete_mode);
+            assert!(storage.validate_bloom_filter());
+
+            let entries = (0..8)
+                .map(|i| memory.insert(i, vec![i as u8; 1000]))
+                .collect::<Vec<_>>();
+            storage.apply(&HashSet::new(), items(&entries[..3]));
+            assert!(storage.validate_bloom_filter());
+
+            // Rebuilt on deletion.
+            storage.apply(&HashSet::from_iter([entries[1].hash()]), vec![]);
+            assert!(storage.validate_bloom_filter());
+
+            // Rebuilt on wiping the oldest entries.
+            let wiped = storage.apply(&HashSet::new(), items(&entries[3..]));
+            assert!(!wiped.is_empty());
+            assert!(storage.validate_bloom_filter());
+
+            storage.compact();
+            assert!(storage.validate_bloom_filter());
+
+            storage.bloom_filter.clear();
+            assert!(!storage.validate_bloom_filter());
+        }
     }
-}
 
-impl<'a> Iterator for SetIter<'a> {
-    type Item = SetEntry<'a>;
+    #[test]
+    fn test_set_storage_len_from_header() {
+        let memory = memory_for_test();
+        let items = (0..4)
+            .map(|i| {
+                let e = memory.insert(i, vec![i as u8; 100]);
+                Item {
+                    slice: to_bytes(&e),
+                    hash: e.hash(),
+                    inserted_at: None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut storage =
+            SetStorage::load_with(buffer_for_test(), 0, true, SetLayout::Split, Arc::new(Metrics::noop()));
+        storage.apply(&HashSet::new(), items);
+        storage.update();
+        let len = storage.len();
+        assert!(len > 0);
+        let buffer = storage.into_io_buffer();
+
+        let header = &buffer[..SetStorage::SET_HEADER_SIZE];
+        assert_eq!(
+            SetStorage::len_from_header(header, PAGE, 0, SetLayout::Split, SetEntryFields::default()),
+            len
+        );
+        // Sets that would be loaded as empty report no data.
+        assert_eq!(
+            SetStorage::len_from_header(header, PAGE, u128::MAX, SetLayout::Split, SetEntryFields::default()),
+            0
+        );
+        assert_eq!(
+            SetStorage::len_from_header(header, PAGE, 0, SetLayout::Interleaved, SetEntryFields::default()),
+            0
+        );
+        assert_eq!(
+            SetStorage::len_from_header(
+                header,
+                PAGE,
+                0,
+                SetLayout::Split,
+                SetEntryFields {
+                    secondary_hash: true,
+                    inserted_at: false,
+                    chunked: false,
+                }
+            ),
+            0
+        );
+        assert_eq!(
+            SetStorage::len_from_header(header, len, 0, SetLayout::Split, SetEntryFields::default()),
+            0
+        );
+        assert_eq!(
+            SetStorage::len_from_header(
+                &buffer_for_test(),
+                PAGE,
+                0,
+                SetLayout::Interleaved,
+                SetEntryFields::default()
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn test_set_storage_zero_capacity() {
+        let memory = memory_for_test();
+        let e1 = memory.insert(1, vec![b'1'; 42]);
+
+        // A valid empty set header, as is written to a set of exactly the set header size.
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+        storage.update();
+        let header = storage.as_bytes()[..SetStorage::SET_HEADER_SIZE].to_vec();
+        let len = |header: &[u8]| {
+            SetStorage::len_from_header(
+                header,
+                SetStorage::SET_HEADER_SIZE,
+                0,
+                SetLayout::Interleaved,
+                SetEntryFields::default(),
+            )
+        };
+        assert_eq!(len(&header), 0);
+        assert_eq!(
+            SetStorage::timestamp_from_header(
+                &header,
+                SetStorage::SET_HEADER_SIZE,
+                0,
+                SetLayout::Interleaved,
+                SetEntryFields::default()
+            ),
+            None
+        );
+
+        // A set of exactly the set header size has no data capacity, it is always empty, whatever the header says.
+        // Such a set can only be seen from its header, a set buffer is always rounded up to the page size.
+        let buffer = IoBuffer::new(SetStorage::SET_HEADER_SIZE);
+        assert_eq!(buffer.len(), PAGE);
+        assert_eq!(
+            SetStorage::load(buffer, 0, Arc::new(Metrics::noop())).capacity,
+            PAGE - SetStorage::SET_HEADER_SIZE
+        );
+        storage.apply(
+            &HashSet::new(),
+            vec![Item {
+                slice: to_bytes(&e1),
+                hash: e1.hash(),
+                inserted_at: None,
+            }],
+        );
+        storage.update();
+        let header = storage.as_bytes()[..SetStorage::SET_HEADER_SIZE].to_vec();
+        assert_eq!(len(&header), 0);
+        assert_eq!(
+            SetStorage::bloom_filter_from_header(
+                &header,
+                SetStorage::SET_HEADER_SIZE,
+                0,
+                SetLayout::Interleaved,
+                SetEntryFields::default()
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_set_storage_load_strict() {
+        let memory = memory_for_test();
+        let entries = (0..4).map(|i| memory.insert(i, vec![i as u8; 100])).collect::<Vec<_>>();
+        let items = || {
+            entries
+                .iter()
+                .map(|e| Item {
+                    slice: to_bytes(e),
+                    hash: e.hash(),
+                    inserted_at: None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for layout in [SetLayout::Interleaved, SetLayout::Split] {
+            let load = |buffer: IoBuffer| SetStorage::load_strict(buffer, 0, layout, Arc::new(Metrics::noop()));
+
+            let mut storage = load(buffer_for_test());
+            storage.apply(&HashSet::from_iter([entries[0].hash()]), items());
+            storage.apply(&HashSet::from_iter([entries[1].hash()]), vec![]);
+            storage.update();
+            let storage = load(storage.into_io_buffer());
+            assert_eq!(storage.entry_count(), 3);
+            assert_none(&storage, entries[1].hash());
+            assert_some(&storage, &entries[2]);
+
+            // Corrupt the value len of the first entry, the checksum is recalculated on update.
+            let corrupt = |mut storage: SetStorage, offset: usize, bytes: &[u8]| {
+                let start = SetStorage::SET_HEADER_SIZE + offset;
+                storage.buffer[start..start + bytes.len()].copy_from_slice(bytes);
+                storage.update();
+                storage.into_io_buffer()
+            };
+            let buffer = corrupt(load(storage.into_io_buffer()), 10, &u16::MAX.to_be_bytes());
+            let loose = SetStorage::load_with(buffer.clone(), 0, true, layout, Arc::new(Metrics::noop()));
+            assert_eq!(loose.entry_count(), 3);
+            // Iterating the loosely loaded set ends at the overrunning entry instead of reading out of bounds.
+            assert_eq!(loose.iter().count(), 0);
+            assert_none(&loose, entries[2].hash());
+            let strict = load(buffer);
+            assert!(strict.is_empty());
+            assert_eq!(strict.iter().count(), 0);
+
+            // A mismatched entry count is malformed too.
+            let mut storage = load(buffer_for_test());
+            storage.apply(&HashSet::new(), items());
+            storage.entries += 1;
+            storage.update();
+            assert!(load(storage.into_io_buffer()).is_empty());
+
+            // An entry missing in the bloom filter is malformed.
+            let mut storage = load(buffer_for_test());
+            storage.apply(&HashSet::new(), items());
+            storage.bloom_filter.clear();
+            storage.update();
+            assert!(load(storage.into_io_buffer()).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_set_storage_iter_range() {
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+        assert_eq!(storage.iter_range(0..u64::MAX).count(), 0);
+
+        let hashes = [7, 1, u64::MAX, 3, 5, 0];
+        storage.apply(
+            &HashSet::new(),
+            hashes
+                .iter()
+                .map(|&hash| Item {
+                    slice: to_bytes_with_hash(&hash, &vec![hash as u8; 16], hash),
+                    hash,
+                    inserted_at: None,
+                })
+                .collect(),
+        );
+        let range = |range: Range<u64>| storage.iter_range(range).map(|entry| entry.hash).collect::<Vec<_>>();
+
+        // The range is half-open, `u64::MAX` is never included.
+        assert_eq!(range(0..u64::MAX), vec![7, 1, 3, 5, 0]);
+        assert_eq!(range(1..5), vec![1, 3]);
+        assert_eq!(range(5..8), vec![7, 5]);
+        assert_eq!(range(u64::MAX - 1..u64::MAX), Vec::<u64>::new());
+        assert_eq!(range(4..4), Vec::<u64>::new());
+
+        // Shards of the keyspace partition the entries.
+        let mut shards = (0..4u64)
+            .flat_map(|i| range(i * (u64::MAX / 4)..(i + 1) * (u64::MAX / 4)))
+            .collect::<Vec<_>>();
+        shards.sort();
+        assert_eq!(shards, vec![0, 1, 3, 5, 7]);
+    }
+
+    #[test]
+    fn test_set_storage_zero_length_fields() {
+        let memory = memory_for_test();
+
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+
+        let e1 = memory.insert(1, vec![b'1'; 100]);
+        let items = vec![
+            Item {
+                slice: to_bytes_with_hash(&Empty, &Empty, 2),
+                hash: 2,
+                inserted_at: None,
+            },
+            Item {
+                slice: to_bytes(&e1),
+                hash: e1.hash(),
+                inserted_at: None,
+            },
+            Item {
+                slice: to_bytes_with_hash(&Empty, &Empty, 3),
+                hash: 3,
+                inserted_at: None,
+            },
+            Item {
+                slice: to_bytes_with_hash(&4u64, &Empty, 4),
+                hash: 4,
+                inserted_at: None,
+            },
+        ];
+        storage.apply(&HashSet::new(), items);
+
+        assert_eq!(storage.entry_count(), 4);
+        assert_eq!(storage.iter().count(), 4);
+        assert_eq!(
+            storage.iter_rev().map(|entry| entry.hash).collect::<Vec<_>>(),
+            vec![4, 3, e1.hash(), 2]
+        );
+        assert_eq!(
+            storage
+                .iter()
+                .filter(|entry| entry.key.is_empty() && entry.value.is_empty())
+                .count(),
+            2
+        );
+        assert!(storage.bloom_filter().lookup(2));
+        assert!(storage.bloom_filter().lookup(3));
+
+        assert_eq!(storage.get::<Empty, Empty>(2).unwrap(), Some((Empty, Empty)));
+        assert_eq!(storage.get::<Empty, Empty>(3).unwrap(), Some((Empty, Empty)));
+        assert_eq!(storage.get::<u64, Empty>(4).unwrap(), Some((4, Empty)));
+        assert_eq!(storage.get_exact::<u64, Empty>(4, &4).unwrap(), Some(Empty));
+        assert_some(&storage, &e1);
+
+        // Entries with zero-length fields survive a reload.
+        storage.update();
+        let bytes = storage.into_io_buffer();
+        let mut buf = buffer_for_test();
+        buf.copy_from_slice(&bytes);
+        let storage = SetStorage::load(buf, 0, Arc::new(Metrics::noop()));
+        assert_eq!(storage.entry_count(), 4);
+        assert_eq!(storage.get::<Empty, Empty>(3).unwrap(), Some((Empty, Empty)));
+        assert_some(&storage, &e1);
+
+        // Deleting an entry with zero-length fields keeps the others intact.
+        let mut storage = storage;
+        storage.apply(&HashSet::from_iter([2]), vec![]);
+        assert_eq!(storage.entry_count(), 3);
+        assert_eq!(storage.get::<Empty, Empty>(2).unwrap(), None);
+        assert_eq!(storage.get::<u64, Empty>(4).unwrap(), Some((4, Empty)));
+        assert_some(&storage, &e1);
+    }
+
+    #[test]
+    fn test_set_storage_delete_in_middle() {
+        let memory = memory_for_test();
+
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+        let entries = (0..4).map(|i| memory.insert(i, vec![i as u8; 100])).collect::<Vec<_>>();
+        storage.apply(
+            &HashSet::new(),
+            entries
+                .iter()
+                .map(|e| Item {
+                    slice: to_bytes(e),
+                    hash: e.hash(),
+                    inserted_at: None,
+                })
+                .collect(),
+        );
+
+        assert_eq!(storage.entry_count(), 4);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.next()
+        storage.apply(&HashSet::from_iter([entries[1].hash()]), vec![]);
+        assert_eq!(storage.entry_count(), 3);
+        assert_some(&storage, &entries[0]);
+        assert_none(&storage, entries[1].hash());
+        assert_some(&storage, &entries[2]);
+        assert_some(&storage, &entries[3]);
     }
-}
 
-pub struct SetTimestamp;
+    #[test]
+    fn test_set_storage_apply_empty() {
+        let memory = memory_for_test();
+        let entries = (0..4).map(|i| memory.insert(i, vec![i as u8; 100])).collect::<Vec<_>>();
+        let items = |entries: &[CacheEntry<u64, Vec<u8>>]| {
+            entries
+                .iter()
+                .map(|e| Item {
+                    slice: to_bytes(e),
+                    hash: e.hash(),
+                    inserted_at: None,
+                })
+                .collect::<Vec<_>>()
+        };
 
-impl SetTimestamp {
-    pub fn current() -> u128 {
-        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos()
+        for delete_mode in [SetDeleteMode::Compact, SetDeleteMode::Tombstone { ratio: 0.5 }] {
+            let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+            storage.set_delete_mode(delete_mode);
+
+            // Empty deletions and empty items.
+            assert!(storage.apply(&HashSet::new(), vec![]).is_empty());
+            assert_eq!(storage.entry_count(), 0);
+            assert_eq!(storage.len(), 0);
+
+            // Empty deletions and non-empty items.
+            assert!(storage.apply(&HashSet::new(), items(&entries[..3])).is_empty());
+            assert_eq!(storage.entry_count(), 3);
+            let buffer = storage.buffer.to_vec();
+            assert!(storage.apply(&HashSet::new(), vec![]).is_empty());
+            assert_eq!(&storage.buffer[..], &buffer[..]);
+
+            // Non-empty deletions and empty items.
+            assert!(storage
+                .apply(&HashSet::from_iter([entries[1].hash()]), vec![])
+                .is_empty());
+            assert_eq!(storage.entry_count(), 2);
+            assert_some(&storage, &entries[0]);
+            assert_none(&storage, entries[1].hash());
+            assert_some(&storage, &entries[2]);
+            // Deleting a missing entry changes nothing.
+            assert!(storage
+                .apply(&HashSet::from_iter([entries[3].hash()]), vec![])
+                .is_empty());
+            assert_eq!(storage.entry_count(), 2);
+
+            // Non-empty deletions and non-empty items.
+            assert!(storage
+                .apply(&HashSet::from_iter([entries[0].hash()]), items(&entries[3..]))
+                .is_empty());
+            assert_eq!(storage.entry_count(), 2);
+            assert_none(&storage, entries[0].hash());
+            assert_some(&storage, &entries[2]);
+            assert_some(&storage, &entries[3]);
+            storage.check_invariants();
+        }
     }
-}
-
-#[cfg(test)]
-mod tests {
 
-    use bytes::Bytes;
-    use foyer_common::metrics::Metrics;
-    use foyer_memory::{Cache, CacheBuilder, CacheEntry};
+    #[test]
+    fn test_set_storage_append_replace() {
+        let item = |key: u64, value: &Vec<u8>, hash: u64| Item {
+            slice: to_bytes_with_hash(&key, value, hash),
+            hash,
+            inserted_at: None,
+        };
 
-    use super::*;
-    use crate::{io::PAGE, serde::EntrySerializer, Compression};
+        for layout in [SetLayout::Interleaved, SetLayout::Split] {
+            for delete_mode in [SetDeleteMode::Compact, SetDeleteMode::Tombstone { ratio: 0.5 }] {
+                let mut storage = SetStorage::load_with(buffer_for_test(), 0, false, layout, Arc::new(Metrics::noop()));
+                storage.set_delete_mode(delete_mode);
+
+                // Updating an existing key replaces the old value.
+                storage.apply(&HashSet::new(), vec![item(1, &vec![1; 100], 1)]);
+                storage.apply(&HashSet::new(), vec![item(1, &vec![2; 100], 1)]);
+                assert_eq!(storage.entry_count(), 1);
+                assert_eq!(storage.get::<u64, Vec<u8>>(1).unwrap(), Some((1, vec![2; 100])));
+
+                // Entries of other keys with the same hash are kept.
+                storage.apply(
+                    &HashSet::new(),
+                    vec![item(2, &vec![3; 100], 42), item(3, &vec![4; 100], 42)],
+                );
+                storage.apply(&HashSet::new(), vec![item(2, &vec![5; 100], 42)]);
+                assert_eq!(storage.entry_count(), 3);
+                assert_eq!(storage.get_exact::<u64, Vec<u8>>(42, &2).unwrap(), Some(vec![5; 100]));
+                assert_eq!(storage.get_exact::<u64, Vec<u8>>(42, &3).unwrap(), Some(vec![4; 100]));
+                storage.check_invariants();
+
+                // The replaced entries stay reclaimed after reloading.
+                storage.update();
+                let storage =
+                    SetStorage::load_with(storage.into_io_buffer(), 0, true, layout, Arc::new(Metrics::noop()));
+                assert_eq!(storage.entry_count(), 3);
+                assert_eq!(storage.get::<u64, Vec<u8>>(1).unwrap(), Some((1, vec![2; 100])));
+                assert_eq!(storage.get_exact::<u64, Vec<u8>>(42, &2).unwrap(), Some(vec![5; 100]));
+                assert_eq!(storage.get_exact::<u64, Vec<u8>>(42, &3).unwrap(), Some(vec![4; 100]));
+            }
+        }
+    }
 
-    fn to_bytes(entry: &CacheEntry<u64, Vec<u8>>) -> Bytes {
-        let mut buf = vec![];
+    #[tokio::test]
+    async fn test_set_storage_apply_async() {
+        let memory = memory_for_test();
 
-        // reserve header
-        let header = EntryHeader::new(0, 0, 0);
-        header.write(&mut buf);
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+        let entries = (0..16).map(|i| memory.insert(i, vec![i as u8; 16])).collect::<Vec<_>>();
+        storage
+            .apply_async(
+                &HashSet::new(),
+                entries
+                    .iter()
+                    .map(|e| Item {
+                        slice: to_bytes(e),
+                        hash: e.hash(),
+                        inserted_at: None,
+                    })
+                    .collect(),
+                3,
+            )
+            .await;
+
+        let deletions = entries.iter().step_by(2).map(|e| e.hash()).collect();
+        storage.apply_async(&deletions, vec![], 3).await;
+        for (i, e) in entries.iter().enumerate() {
+            if i % 2 == 0 {
+                assert_none(&storage, e.hash());
+            } else {
+                assert_some(&storage, e);
+            }
+        }
+    }
 
-        let info = EntrySerializer::serialize(entry.key(), entry.value(), Compression::None, &mut buf).unwrap();
+    #[test]
+    fn test_set_storage_checksum() {
+        let memory = memory_for_test();
+        let e1 = memory.insert(1, vec![b'1'; 42]);
 
-        let header = EntryHeader::new(entry.hash(), info.key_len, info.value_len);
-        header.write(&mut buf[0..EntryHeader::ENTRY_HEADER_SIZE]);
+        let build = || {
+            let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+            storage.apply(
+                &HashSet::new(),
+                vec![Item {
+                    slice: to_bytes(&e1),
+                    hash: e1.hash(),
+                    inserted_at: None,
+                }],
+            );
+            storage.update();
+            storage
+        };
 
-        Bytes::from(buf)
+        let s1 = build();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        let s2 = build();
+        assert_ne!(s1.timestamp(), s2.timestamp());
+        assert_ne!(s1.checksum(), s2.checksum());
+        assert_eq!(s1.content_checksum(), s2.content_checksum());
+
+        let (checksum, content_checksum) = (s1.checksum(), s1.content_checksum());
+        let bytes = s1.into_io_buffer();
+        let mut buf = buffer_for_test();
+        buf.copy_from_slice(&bytes);
+        let mut loaded = SetStorage::load(buf, 0, Arc::new(Metrics::noop()));
+        assert_eq!(loaded.checksum(), checksum);
+        assert_eq!(loaded.content_checksum(), content_checksum);
+
+        // The content checksum follows the live data without updating the set.
+        loaded.apply(&HashSet::from_iter([e1.hash()]), vec![]);
+        assert_eq!(loaded.checksum(), checksum);
+        assert_ne!(loaded.content_checksum(), content_checksum);
     }
 
-    fn assert_some(storage: &SetStorage, entry: &CacheEntry<u64, Vec<u8>>) {
-        let ret = storage.get::<u64, Vec<u8>>(entry.hash()).unwrap();
-        let (k, v) = ret.unwrap();
-        assert_eq!(&k, entry.key());
-        assert_eq!(&v, entry.value());
-    }
+    #[test]
+    fn test_set_storage_pack() {
+        let memory = memory_for_test();
 
-    fn assert_none(storage: &SetStorage, hash: u64) {
-        let ret = storage.get::<u64, Vec<u8>>(hash).unwrap();
-        assert!(ret.is_none());
-    }
+        let e1 = memory.insert(1, vec![b'1'; 1024]);
+        let e2 = memory.insert(2, vec![b'2'; 3000]);
+        let e3 = memory.insert(3, vec![b'3'; 1024]);
+        let items = [&e1, &e2, &e3]
+            .into_iter()
+            .map(|e| Item {
+                slice: to_bytes(e),
+                hash: e.hash(),
+                inserted_at: None,
+            })
+            .collect();
+
+        let (storage, leftovers) = SetStorage::pack(items, PAGE, Arc::new(Metrics::noop()));
+        assert_eq!(
+            leftovers.iter().map(|item| item.hash).collect::<Vec<_>>(),
+            vec![e2.hash()]
+        );
+        assert_eq!(storage.len(), to_bytes(&e1).len() + to_bytes(&e3).len());
+        assert_eq!(storage.entry_count(), 2);
+        assert_some(&storage, &e1);
+        assert_none(&storage, e2.hash());
+        assert_some(&storage, &e3);
 
-    fn memory_for_test() -> Cache<u64, Vec<u8>> {
-        CacheBuilder::new(100).build()
+        // The packed set can be loaded back.
+        let bytes = storage.into_io_buffer();
+        let mut buf = buffer_for_test();
+        buf.copy_from_slice(&bytes);
+        let loaded = SetStorage::load(buf, 0, Arc::new(Metrics::noop()));
+        assert_eq!(loaded.entry_count(), 2);
+        assert_some(&loaded, &e1);
+        assert_some(&loaded, &e3);
     }
 
     #[test]
-    fn test_set_storage_basic() {
+    fn test_set_storage_checksum_on_write() {
         let memory = memory_for_test();
+        let e1 = memory.insert(1, vec![b'1'; 42]);
 
-        // load will result in an empty set
-        let buf = IoBuffer::new(PAGE);
-        let mut storage = SetStorage::load(buf, 0, Arc::new(Metrics::noop()));
-        assert!(storage.is_empty());
+        let build = |checksum: bool| {
+            let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+            storage.apply(
+                &HashSet::new(),
+                vec![Item {
+                    slice: to_bytes(&e1),
+                    hash: e1.hash(),
+                    inserted_at: None,
+                }],
+            );
+            storage.update_with_checksum(checksum);
+            storage.into_io_buffer()
+        };
+        let load = |bytes: &IoBuffer, verify: bool| {
+            let mut buf = buffer_for_test();
+            buf.copy_from_slice(bytes);
+            SetStorage::load_with(buf, 0, verify, SetLayout::default(), Arc::new(Metrics::noop()))
+        };
 
+        // Checksummed sets can be loaded with or without verifying.
+        let bytes = build(true);
+        assert_some(&load(&bytes, true), &e1);
+        assert_some(&load(&bytes, false), &e1);
+        assert!(!load(&bytes, true).is_corrupted());
+
+        // Sets written without a checksum can only be loaded without verifying.
+        let bytes = build(false);
+        assert_eq!(load(&bytes, false).checksum(), 0);
+        assert_some(&load(&bytes, false), &e1);
+        assert!(load(&bytes, true).is_empty());
+        assert!(load(&bytes, true).is_corrupted());
+
+        // The set length is still checked without verifying.
+        let mut bytes = build(false);
+        (&mut bytes[20..24]).put_u32(PAGE as _);
+        assert!(load(&bytes, false).is_empty());
+        assert!(load(&bytes, false).is_corrupted());
+
+        // Stale sets are empty but not corrupted.
+        let bytes = build(true);
+        let mut buf = buffer_for_test();
+        buf.copy_from_slice(&bytes);
+        let stale = SetStorage::load_with(buf, u128::MAX, true, SetLayout::default(), Arc::new(Metrics::noop()));
+        assert!(stale.is_empty());
+        assert!(!stale.is_corrupted());
+    }
+
+    #[test]
+    fn test_set_storage_as_bytes() {
+        let memory = memory_for_test();
         let e1 = memory.insert(1, vec![b'1'; 42]);
-        let s1 = to_bytes(&e1);
+        let e2 = memory.insert(2, vec![b'2'; 42]);
+
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
         storage.apply(
-            &HashSet::from_iter([2, 4]),
+            &HashSet::new(),
             vec![Item {
-                slice: s1.clone(),
+                slice: to_bytes(&e1),
                 hash: e1.hash(),
+                inserted_at: None,
             }],
         );
-        assert_eq!(storage.len(), s1.len());
-        assert_some(&storage, &e1);
+        storage.update();
+        assert_eq!(storage.as_bytes().len(), PAGE);
 
-        let e2 = memory.insert(2, vec![b'2'; 97]);
-        let s2 = to_bytes(&e2);
+        let load = |bytes: &[u8]| {
+            let mut buf = buffer_for_test();
+            buf.copy_from_slice(bytes);
+            SetStorage::load(buf, 0, Arc::new(Metrics::noop()))
+        };
+        assert_some(&load(storage.as_bytes()), &e1);
+
+        // The set is still usable after the bytes are taken.
+        assert_some(&storage, &e1);
         storage.apply(
-            &HashSet::from_iter([e1.hash(), 3, 5]),
+            &HashSet::new(),
             vec![Item {
-                slice: s2.clone(),
+                slice: to_bytes(&e2),
                 hash: e2.hash(),
+                inserted_at: None,
             }],
         );
-        assert_eq!(storage.len(), s2.len());
-        assert_none(&storage, e1.hash());
-        assert_some(&storage, &e2);
+        // The header is stale until the set is updated.
+        assert_none(&load(storage.as_bytes()), e2.hash());
+        storage.update();
+        let loaded = load(storage.as_bytes());
+        assert_some(&loaded, &e1);
+        assert_some(&loaded, &e2);
+        let bytes = storage.as_bytes().to_vec();
+        assert_eq!(bytes, &storage.into_io_buffer()[..]);
+    }
 
-        let e3 = memory.insert(3, vec![b'3'; 211]);
-        let s3 = to_bytes(&e3);
+    /// The bytes of the set with the timestamp zeroed and the checksum recalculated, so they are deterministic.
+    fn golden_bytes(storage: SetStorage) -> Vec<u8> {
+        let mut bytes = storage.into_io_buffer().to_vec();
+        bytes[4..20].fill(0);
+        let checksum = checksum::set_checksum(&bytes).unwrap();
+        bytes[0..4].copy_from_slice(&checksum.to_be_bytes());
+        bytes
+    }
+
+    /// Pins the on-disk format of the sets. If this test fails, the format is changed, and the layout version must be
+    /// bumped, see [`SetLayout`], so sets written in the old format are not loaded with the new one.
+    ///
+    /// The bloom filter is written in the native endian, the golden bytes are of little-endian machines.
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn test_set_storage_golden_bytes() {
+        const HASH: u64 = 0x0123_4567_89ab_cdef;
+
+        let load = |bytes: &[u8]| {
+            let mut buf = buffer_for_test();
+            buf[..bytes.len()].copy_from_slice(bytes);
+            SetStorage::load(buf, 0, Arc::new(Metrics::noop()))
+        };
+
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+        storage.update();
+        let expected = [
+            &[0xc9, 0xf6, 0x1c, 0x17][..], // checksum
+            &[0; 16],                      // timestamp, zeroed
+            &[0; 4],                       // len
+            &[0; 32],                      // bloom filter
+            &[0; 4],                       // entries
+            &[0x01],                       // version
+            &[0; 4],                       // tail
+            &[0; 4],                       // tombstones
+        ]
+        .concat();
+        let bytes = golden_bytes(storage);
+        assert_eq!(&bytes[..SetStorage::SET_HEADER_SIZE], &expected[..]);
+        assert!(bytes[SetStorage::SET_HEADER_SIZE..].iter().all(|b| *b == 0));
+        assert_eq!(load(&expected).len(), 0);
+
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
         storage.apply(
-            &HashSet::from_iter([e1.hash()]),
+            &HashSet::new(),
             vec![Item {
-                slice: s3.clone(),
-                hash: e3.hash(),
+                slice: to_bytes_with_hash(&42u64, &vec![7u8; 5], HASH),
+                hash: HASH,
+                inserted_at: None,
             }],
         );
-        assert_eq!(storage.len(), s2.len() + s3.len());
-        assert_none(&storage, e1.hash());
-        assert_some(&storage, &e2);
-        assert_some(&storage, &e3);
+        storage.update();
+        let expected = [
+            &[0x20, 0x1f, 0x83, 0x36][..], // checksum
+            &[0; 16],                      // timestamp, zeroed
+            &[0x00, 0x00, 0x00, 0x21],     // len
+            &[
+                0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80,
+            ], // bloom filter
+            &[0x00, 0x00, 0x00, 0x01],     // entries
+            &[0x01],                       // version
+            &[0; 4],                       // tail
+            &[0; 4],                       // tombstones
+            &[0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef], // entry hash
+            &[0x00, 0x08],                 // key len
+            &[0x00, 0x0d],                 // value len
+            &[
+                0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x07, 0x07, 0x07, 0x07,
+            ], // value
+            &[0x2a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // key
+        ]
+        .concat();
+        let bytes = golden_bytes(storage);
+        assert_eq!(&bytes[..expected.len()], &expected[..]);
+        assert!(bytes[expected.len()..].iter().all(|b| *b == 0));
+
+        // The golden bytes are loaded back, i.e. sets written before the change are still recovered.
+        let loaded = load(&expected);
+        assert_eq!(loaded.get::<u64, Vec<u8>>(HASH).unwrap(), Some((42, vec![7; 5])));
+    }
 
-        let e4 = memory.insert(4, vec![b'4'; 3800]);
-        let s4 = to_bytes(&e4);
-        storage.apply(
-            &HashSet::from_iter([e1.hash()]),
+    #[test]
+    fn test_set_publish() {
+        let memory = memory_for_test();
+
+        let mut set = SetMut::new(SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop())));
+        let e1 = memory.insert(1, vec![b'1'; 42]);
+        set.apply(
+            &HashSet::new(),
             vec![Item {
-                slice: s4.clone(),
-                hash: e4.hash(),
+                slice: to_bytes(&e1),
+                hash: e1.hash(),
+                inserted_at: None,
             }],
         );
-        assert_eq!(storage.len(), s4.len());
-        assert_none(&storage, e1.hash());
-        assert_none(&storage, e2.hash());
-        assert_none(&storage, e3.hash());
-        assert_some(&storage, &e4);
+        assert!(set.dirty);
+        let set = set.publish();
+        assert_some(&set, &e1);
+
+        // The published bytes can be loaded as the same set without an explicit `update`.
+        let mut buf = buffer_for_test();
+        buf.copy_from_slice(&set.bytes());
+        let storage = SetStorage::load(buf, 0, Arc::new(Metrics::noop()));
+        assert_eq!(storage.timestamp(), set.timestamp());
+        assert_some(&storage, &e1);
+    }
 
-        // test recovery
+    #[test]
+    fn test_set_storage_get_exact_with_hash_collision() {
+        let memory: Cache<u128, Vec<u8>, ModHasher> =
+            CacheBuilder::new(100).with_hash_builder(ModHasher::default()).build();
+
+        let e1 = memory.insert(1, vec![b'1'; 42]);
+        let e2 = memory.insert(1 + 1 + u64::MAX as u128, vec![b'2'; 42]);
+        assert_eq!(e1.hash(), e2.hash());
+        let hash = e1.hash();
+
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+        storage.apply(
+            &HashSet::new(),
+            [&e1, &e2]
+                .into_iter()
+                .map(|e| Item {
+                    slice: to_bytes_with_hash(e.key(), e.value(), e.hash()),
+                    hash,
+                    inserted_at: None,
+                })
+                .collect(),
+        );
+
+        // `get` can only return the first entry with the hash.
+        let (k, _) = storage.get::<u128, Vec<u8>>(hash).unwrap().unwrap();
+        assert_eq!(&k, e1.key());
+
+        let v1 = storage.get_exact::<u128, Vec<u8>>(hash, e1.key()).unwrap();
+        let v2 = storage.get_exact::<u128, Vec<u8>>(hash, e2.key()).unwrap();
+        let v3 = storage.get_exact::<u128, Vec<u8>>(hash, &42).unwrap();
+        assert_eq!(v1.as_ref(), Some(e1.value()));
+        assert_eq!(v2.as_ref(), Some(e2.value()));
+        assert_eq!(v3, None);
+    }
+
+    #[test]
+    fn test_set_storage_secondary_hash() {
+        let memory: Cache<u128, Vec<u8>, ModHasher> =
+            CacheBuilder::new(100).with_hash_builder(ModHasher::default()).build();
+
+        let e1 = memory.insert(1, vec![b'1'; 42]);
+        let e2 = memory.insert(1 + 1 + u64::MAX as u128, vec![b'2'; 42]);
+        assert_eq!(e1.hash(), e2.hash());
+        let hash = e1.hash();
+        let items = || {
+            [&e1, &e2]
+                .into_iter()
+                .map(|e| Item {
+                    slice: to_bytes_with_hash(e.key(), e.value(), e.hash()),
+                    hash,
+                    inserted_at: None,
+                })
+                .collect::<Vec<_>>()
+        };
+        // Flip the secondary hash of the first entry, it follows the first entry header in both layouts.
+        let corrupt = |buf: &mut IoBuffer| {
+            buf[SetStorage::SET_HEADER_SIZE + EntryHeader::ENTRY_HEADER_SIZE] ^= 1;
+        };
+
+        for layout in [SetLayout::Interleaved, SetLayout::Split] {
+            let load = |buf: IoBuffer, verify: bool, secondary_hash: bool| {
+                let fields = SetEntryFields {
+                    secondary_hash,
+                    inserted_at: false,
+                    chunked: false,
+                };
+                SetStorage::load_with_fields(buf, 0, verify, layout, fields, Arc::new(Metrics::noop()))
+            };
+
+            let mut storage = load(buffer_for_test(), true, true);
+            storage.apply(&HashSet::new(), items());
+            storage.update();
+            storage.check_invariants();
+            assert_eq!(
+                storage.len(),
+                items().iter().map(|item| item.slice.len()).sum::<usize>() + 2 * EntryHeader::SECONDARY_HASH_SIZE
+            );
+            assert!(storage.iter().all(|entry| entry.is_intact()));
+            let (k, v) = storage.get::<u128, Vec<u8>>(hash).unwrap().unwrap();
+            assert_eq!((&k, &v), (e1.key(), e1.value()));
+            assert_eq!(
+                storage.get_exact::<u128, Vec<u8>>(hash, e2.key()).unwrap().as_ref(),
+                Some(e2.value())
+            );
+            assert_eq!(storage.items().len(), 2);
+
+            let mut buf = storage.into_io_buffer();
+            let mut copy = IoBuffer::new(PAGE);
+            copy.copy_from_slice(&buf);
+
+            // The entry mismatching its secondary hash is skipped on get, the next entry of the hash is taken.
+            corrupt(&mut buf);
+            let storage = load(buf, false, true);
+            assert_eq!(storage.entry_count(), 2);
+            let (k, v) = storage.get::<u128, Vec<u8>>(hash).unwrap().unwrap();
+            assert_eq!((&k, &v), (e2.key(), e2.value()));
+            let (k, _) = storage.get_raw::<u128>(hash).unwrap().unwrap();
+            assert_eq!(&k, e2.key());
+
+            // Sets written with the secondary hash are loaded as empty sets without it.
+            let storage = load(copy, true, false);
+            assert!(storage.is_empty());
+        }
+
+        // Without the secondary hash, only the hash is compared.
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+        storage.apply(&HashSet::new(), items());
+        assert!(storage.iter().all(|entry| entry.is_intact()));
+        let (k, _) = storage.get::<u128, Vec<u8>>(hash).unwrap().unwrap();
+        assert_eq!(&k, e1.key());
+    }
+
+    #[test]
+    fn test_set_storage_inserted_at() {
+        let memory = memory_for_test();
+        let fields = SetEntryFields {
+            secondary_hash: true,
+            inserted_at: true,
+            chunked: false,
+        };
+        let entries = (0..4).map(|i| memory.insert(i, vec![i as u8; 100])).collect::<Vec<_>>();
+        let items = |inserted_at: Option<u64>| {
+            entries
+                .iter()
+                .map(|e| Item {
+                    slice: to_bytes(e),
+                    hash: e.hash(),
+                    inserted_at,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for layout in [SetLayout::Interleaved, SetLayout::Split] {
+            let load = |buf: IoBuffer, fields: SetEntryFields| {
+                SetStorage::load_with_fields(buf, 0, true, layout, fields, Arc::new(Metrics::noop()))
+            };
+
+            let before = SystemTime::now() - Duration::from_millis(1);
+            let mut storage = load(buffer_for_test(), fields);
+            storage.apply(&HashSet::new(), items(None));
+            storage.update();
+            storage.check_invariants();
+            let after = SystemTime::now() + Duration::from_millis(1);
+            assert_eq!(
+                storage.len(),
+                entries.iter().map(|e| to_bytes(e).len()).sum::<usize>()
+                    + entries.len() * (EntryHeader::SECONDARY_HASH_SIZE + EntryHeader::INSERTED_AT_SIZE)
+            );
+            entries.iter().for_each(|e| assert_some(&storage, e));
+            let inserted_ats = storage.inserted_ats().map(Option::unwrap).collect::<Vec<_>>();
+            assert_eq!(inserted_ats.len(), entries.len());
+            assert!(inserted_ats.iter().all(|t| (before..after).contains(t)));
+
+            // The insertion times are kept when the entries are moved to another set.
+            let mut moved = load(buffer_for_test(), fields);
+            moved.apply(&HashSet::new(), storage.items());
+            assert_eq!(
+                moved.inserted_ats().collect::<Vec<_>>(),
+                storage.inserted_ats().collect::<Vec<_>>()
+            );
+
+            // The insertion time carried by an item is written as is.
+            let mut storage = load(buffer_for_test(), fields);
+            storage.apply(&HashSet::new(), items(Some(42)));
+            storage.update();
+            assert!(storage
+                .inserted_ats()
+                .all(|t| t == Some(UNIX_EPOCH + Duration::from_millis(42))));
+
+            let buf = storage.into_io_buffer();
+            let mut copy = IoBuffer::new(PAGE);
+            copy.copy_from_slice(&buf);
+
+            let storage = load(buf, fields);
+            assert_eq!(storage.entry_count(), entries.len());
+            entries.iter().for_each(|e| assert_some(&storage, e));
+            assert!(storage.iter_rev().all(|entry| entry.inserted_at().is_some()));
+
+            // Sets written with the insertion time are loaded as empty sets without it.
+            let storage = load(
+                copy,
+                SetEntryFields {
+                    secondary_hash: true,
+                    inserted_at: false,
+                    chunked: false,
+                },
+            );
+            assert!(storage.is_empty());
+        }
+
+        // Without the insertion time, none is reported.
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+        storage.apply(&HashSet::new(), items(Some(42)));
+        assert!(storage.inserted_ats().all(|t| t.is_none()));
+    }
+
+    #[test]
+    fn test_set_storage_split_layout() {
+        let memory = memory_for_test();
+        let load =
+            |buf: IoBuffer, layout: SetLayout| SetStorage::load_with(buf, 0, true, layout, Arc::new(Metrics::noop()));
+        let items = |entries: &[CacheEntry<u64, Vec<u8>>]| {
+            entries
+                .iter()
+                .map(|e| Item {
+                    slice: to_bytes(e),
+                    hash: e.hash(),
+                    inserted_at: None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut storage = load(buffer_for_test(), SetLayout::Split);
+        assert_eq!(storage.layout(), SetLayout::Split);
+        let entries = (0..4)
+            .map(|i| memory.insert(i, vec![i as u8; 100 * (i as usize + 1)]))
+            .collect::<Vec<_>>();
+        storage.apply(&HashSet::new(), items(&entries));
+        assert_eq!(storage.entry_count(), 4);
+        assert_eq!(storage.len(), entries.iter().map(|e| to_bytes(e).len()).sum::<usize>());
+        entries.iter().for_each(|e| assert_some(&storage, e));
+        let keys = storage.keys::<u64>().collect::<Result<Vec<_>>>().unwrap();
+        let expected = entries.iter().map(|e| (e.hash(), *e.key())).collect::<Vec<_>>();
+        assert_eq!(keys, expected);
+
+        // Deleting an entry in the middle moves both the keys and the values of the newer entries.
+        storage.apply(&HashSet::from_iter([entries[1].hash()]), vec![]);
+        assert_eq!(storage.entry_count(), 3);
+        assert_some(&storage, &entries[0]);
+        assert_none(&storage, entries[1].hash());
+        assert_some(&storage, &entries[2]);
+        assert_some(&storage, &entries[3]);
+
+        // Appending beyond the capacity wipes the oldest entries from both regions.
+        let e5 = memory.insert(5, vec![b'5'; 3400]);
+        let wiped = storage.apply(&HashSet::new(), items(std::slice::from_ref(&e5)));
+        assert_eq!(wiped, vec![entries[0].hash(), entries[2].hash()]);
+        assert_none(&storage, entries[0].hash());
+        assert_none(&storage, entries[2].hash());
+        assert_some(&storage, &entries[3]);
+        assert_some(&storage, &e5);
+        assert_eq!(storage.entry_count(), 2);
+
+        // The set can be reloaded with the same layout only.
         storage.update();
         let bytes = storage.into_io_buffer();
-        let mut buf = IoBuffer::new(PAGE);
-        buf[0..bytes.len()].copy_from_slice(&bytes);
+        let mut buf = buffer_for_test();
+        buf.copy_from_slice(&bytes);
+        let loaded = load(buf, SetLayout::Split);
+        assert_eq!(loaded.entry_count(), 2);
+        assert_some(&loaded, &entries[3]);
+        assert_some(&loaded, &e5);
+
+        let mut buf = buffer_for_test();
+        buf.copy_from_slice(&bytes);
+        let loaded = load(buf, SetLayout::Interleaved);
+        assert!(loaded.is_empty());
+        assert_none(&loaded, e5.hash());
+    }
+
+    #[test]
+    fn test_set_storage_remove() {
+        let memory = memory_for_test();
+        let item = |e: &CacheEntry<u64, Vec<u8>>| Item {
+            slice: to_bytes(e),
+            hash: e.hash(),
+            inserted_at: None,
+        };
+        let contains = |storage: &SetStorage, byte: u8| storage.as_bytes().windows(100).any(|w| w == [byte; 100]);
+
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+        storage.set_delete_mode(SetDeleteMode::Tombstone { ratio: 1.0 });
+        let entries = (0..4)
+            .map(|i| memory.insert(i, vec![0xa0 + i as u8; 100]))
+            .collect::<Vec<_>>();
+        storage.apply(&HashSet::new(), entries.iter().map(item).collect());
+        storage.apply(&HashSet::from_iter([entries[1].hash()]), vec![]);
+        assert_eq!(storage.tombstones(), to_bytes(&entries[1]).len());
+
+        // Removing compacts the entry and the tombstones out regardless of the delete mode.
+        assert!(storage.remove(entries[3].hash(), false));
+        assert_eq!(storage.tombstones(), 0);
+        assert_eq!(storage.entry_count(), 2);
+        assert_eq!(storage.len(), to_bytes(&entries[0]).len() + to_bytes(&entries[2]).len());
+        assert_none(&storage, entries[3].hash());
+        // The bytes past the end of the set are left behind.
+        assert!(contains(&storage, 0xa3));
+
+        // The tombstone is not a live entry, zeroing wipes the bytes left behind.
+        assert!(!storage.remove(entries[1].hash(), true));
+        assert!(!contains(&storage, 0xa3));
+        assert!(storage.unused().iter().all(|b| *b == 0));
+        assert_some(&storage, &entries[0]);
+        assert_some(&storage, &entries[2]);
+    }
+
+    #[test]
+    fn test_set_storage_lazy_bloom_filter_rebuild() {
+        let memory = memory_for_test();
+
+        for delete_mode in [SetDeleteMode::Compact, SetDeleteMode::Tombstone { ratio: 1.0 }] {
+            let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+            storage.set_delete_mode(delete_mode);
+            storage.set_bloom_filter_rebuild(SetBloomFilterRebuild::Lazy { interval: 3 });
+            let entries = (0..4).map(|i| memory.insert(i, vec![i as u8; 42])).collect::<Vec<_>>();
+            let items = entries
+                .iter()
+                .map(|e| Item {
+                    slice: to_bytes(e),
+                    hash: e.hash(),
+                    inserted_at: None,
+                })
+                .collect();
+            storage.apply(&HashSet::new(), items);
+
+            // The deleted hashes are left in the bloom filter until the interval is reached.
+            storage.apply(&HashSet::from_iter([entries[0].hash()]), vec![]);
+            storage.apply(&HashSet::from_iter([entries[1].hash()]), vec![]);
+            assert!(storage.bloom_filter().lookup(entries[0].hash()));
+            assert!(storage.bloom_filter().lookup(entries[1].hash()));
+            assert_none(&storage, entries[0].hash());
+            assert_none(&storage, entries[1].hash());
+            assert_eq!(storage.entry_count(), 2);
+            storage.check_invariants();
+
+            storage.apply(&HashSet::from_iter([entries[2].hash()]), vec![]);
+            assert_eq!(storage.stale_hashes, 0);
+            assert!(!storage.bloom_filter().lookup(entries[0].hash()));
+            assert!(!storage.bloom_filter().lookup(entries[1].hash()));
+            assert!(!storage.bloom_filter().lookup(entries[2].hash()));
+            assert_some(&storage, &entries[3]);
+            storage.check_invariants();
+        }
+    }
+
+    #[test]
+    fn test_set_storage_zero_unused() {
+        let memory = memory_for_test();
+
+        for layout in [SetLayout::Interleaved, SetLayout::Split] {
+            let mut buffer = buffer_for_test();
+            buffer.fill(0);
+            let mut storage = SetStorage::load_with(buffer, 0, false, layout, Arc::new(Metrics::noop()));
+            let entries = (0..4)
+                .map(|i| memory.insert(i, vec![0xa0 + i as u8; 100]))
+                .collect::<Vec<_>>();
+            let items = entries
+                .iter()
+                .map(|e| Item {
+                    slice: to_bytes(e),
+                    hash: e.hash(),
+                    inserted_at: None,
+                })
+                .collect();
+            storage.apply(&HashSet::new(), items);
+            assert_eq!(storage.unused().len(), storage.capacity - storage.len());
+            assert!(storage.unused().iter().all(|b| *b == 0));
+
+            // The bytes moved by the compaction are left behind in the unused space.
+            storage.apply(&HashSet::from_iter([entries[0].hash()]), vec![]);
+            assert!(storage.unused().iter().any(|b| *b != 0));
+
+            // Only the live bytes are exposed as the data of the set.
+            assert_eq!(storage.data().len() + storage.tail_data().len(), storage.len());
+            assert_eq!(
+                storage.data().len() + storage.unused().len() + storage.tail_data().len(),
+                storage.capacity
+            );
+
+            storage.zero_unused();
+            assert!(storage.unused().iter().all(|b| *b == 0));
+            for e in &entries[1..] {
+                assert_some(&storage, e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_storage_append_refs() {
+        let memory = memory_for_test();
+
+        for layout in [SetLayout::Interleaved, SetLayout::Split] {
+            let mut a = SetStorage::load_with(buffer_for_test(), 0, false, layout, Arc::new(Metrics::noop()));
+            let mut b = SetStorage::load_with(buffer_for_test(), 0, false, layout, Arc::new(Metrics::noop()));
+            let entries = (0..4).map(|i| memory.insert(i, vec![i as u8; 100])).collect::<Vec<_>>();
+            let items = entries
+                .iter()
+                .map(|e| Item {
+                    slice: to_bytes(e),
+                    hash: e.hash(),
+                    inserted_at: None,
+                })
+                .collect::<Vec<_>>();
+
+            assert!(a.append_refs(&[]).is_empty());
+            assert_eq!(a.len(), 0);
+
+            // The same items are appended to both sets.
+            assert!(a.append_refs(&items).is_empty());
+            assert!(b.append_refs(&items).is_empty());
+            for e in &entries {
+                assert_some(&a, e);
+                assert_some(&b, e);
+            }
+            assert_eq!(a.data(), b.data());
+            assert_eq!(a.tail_data(), b.tail_data());
+        }
+    }
+
+    #[test]
+    fn test_set_storage_tombstone() {
+        let memory = memory_for_test();
+        let item = |e: &CacheEntry<u64, Vec<u8>>| Item {
+            slice: to_bytes(e),
+            hash: e.hash(),
+            inserted_at: None,
+        };
+
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+        storage.set_delete_mode(SetDeleteMode::Tombstone { ratio: 0.1 });
+        let entries = (0..4)
+            .map(|i| memory.insert(i, vec![i as u8; 100 * (i as usize + 1)]))
+            .collect::<Vec<_>>();
+        storage.apply(&HashSet::new(), entries.iter().map(item).collect());
+        let len = storage.len();
+
+        // Deleting an entry only marks it as a tombstone.
+        storage.apply(&HashSet::from_iter([entries[1].hash()]), vec![]);
+        assert_eq!(storage.len(), len);
+        assert_eq!(storage.tombstones(), to_bytes(&entries[1]).len());
+        assert_eq!(storage.entry_count(), 3);
+        assert_some(&storage, &entries[0]);
+        assert_none(&storage, entries[1].hash());
+        assert_some(&storage, &entries[2]);
+        assert_some(&storage, &entries[3]);
+        let keys = storage.keys::<u64>().map(|r| r.unwrap().1).collect::<Vec<_>>();
+        assert_eq!(keys, vec![0, 2, 3]);
+
+        // Tombstones are persisted.
+        storage.update();
+        let bytes = storage.into_io_buffer();
+        let mut buf = buffer_for_test();
+        buf.copy_from_slice(&bytes);
         let mut storage = SetStorage::load(buf, 0, Arc::new(Metrics::noop()));
+        storage.set_delete_mode(SetDeleteMode::Tombstone { ratio: 0.1 });
+        assert_eq!(storage.len(), len);
+        assert_eq!(storage.tombstones(), to_bytes(&entries[1]).len());
+        assert_eq!(storage.entry_count(), 3);
+        assert_none(&storage, entries[1].hash());
+
+        // An entry that only fits after reclaiming the tombstones doesn't wipe any live entry.
+        let overhead = to_bytes(&entries[0]).len() - entries[0].value().len();
+        let e4 = memory.insert(4, vec![4; storage.capacity - len + storage.tombstones() - overhead]);
+        assert!(storage.apply(&HashSet::new(), vec![item(&e4)]).is_empty());
+        assert_eq!(storage.len(), storage.capacity);
+        assert_eq!(storage.tombstones(), 0);
+        assert_eq!(storage.entry_count(), 4);
+        assert_some(&storage, &entries[0]);
+        assert_some(&storage, &entries[2]);
+        assert_some(&storage, &entries[3]);
+        assert_some(&storage, &e4);
 
-        assert_eq!(storage.len(), s4.len());
-        assert_none(&storage, e1.hash());
-        assert_none(&storage, e2.hash());
-        assert_none(&storage, e3.hash());
+        // The set is compacted once the tombstones exceed the ratio.
+        storage.apply(&HashSet::from_iter([entries[2].hash()]), vec![]);
+        assert_eq!(storage.tombstones(), to_bytes(&entries[2]).len());
+        assert_eq!(storage.len(), storage.capacity);
+        storage.apply(&HashSet::from_iter([entries[3].hash()]), vec![]);
+        assert_eq!(storage.tombstones(), 0);
+        assert_eq!(
+            storage.len(),
+            storage.capacity - to_bytes(&entries[2]).len() - to_bytes(&entries[3]).len()
+        );
+        assert_eq!(storage.entry_count(), 2);
+        assert_some(&storage, &entries[0]);
         assert_some(&storage, &e4);
+    }
 
-        // test oversize entry
-        let e5 = memory.insert(5, vec![b'5'; 20 * 1024]);
-        let s5 = to_bytes(&e5);
+    #[test]
+    fn test_set_storage_compact_tombstones() {
+        let memory = memory_for_test();
+        let item = |e: &CacheEntry<u64, Vec<u8>>| Item {
+            slice: to_bytes(e),
+            hash: e.hash(),
+            inserted_at: None,
+        };
+
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
+        storage.set_delete_mode(SetDeleteMode::Tombstone { ratio: 1.0 });
+        storage.set_bloom_filter_rebuild(SetBloomFilterRebuild::Lazy { interval: usize::MAX });
+        assert_eq!(storage.compact_tombstones(), 0);
+
+        let entries = (0..8)
+            .map(|i| memory.insert(i, vec![i as u8; 100 + i as usize]))
+            .collect::<Vec<_>>();
+        storage.apply(&HashSet::new(), entries.iter().map(item).collect());
+        let len = storage.len();
+
+        // Tombstone half of the entries, the hashes are left in the bloom filter.
+        let deletes = entries.iter().step_by(2).map(|e| e.hash()).collect::<HashSet<_>>();
+        storage.apply(&deletes, vec![]);
+        let tombstones = entries.iter().step_by(2).map(|e| to_bytes(e).len()).sum::<usize>();
+        assert_eq!(storage.len(), len);
+        assert_eq!(storage.tombstones(), tombstones);
+        assert_eq!(storage.tombstone_ratio(), tombstones as f64 / storage.capacity as f64);
+        assert!(deletes.iter().all(|&hash| storage.bloom_filter.lookup(hash)));
+
+        assert_eq!(storage.compact_tombstones(), tombstones);
+        assert_eq!(storage.len(), len - tombstones);
+        assert_eq!(storage.tombstones(), 0);
+        assert_eq!(storage.entry_count(), 4);
+        assert_eq!(storage.stale_hashes, 0);
+        assert!(storage.validate_bloom_filter());
+        storage.check_invariants();
+        for (i, entry) in entries.iter().enumerate() {
+            match i % 2 {
+                0 => assert_none(&storage, entry.hash()),
+                _ => assert_some(&storage, entry),
+            }
+        }
+        let keys = storage.keys::<u64>().map(|r| r.unwrap().1).collect::<Vec<_>>();
+        assert_eq!(keys, vec![1, 3, 5, 7]);
+
+        // Nothing left to reclaim.
+        assert_eq!(storage.compact_tombstones(), 0);
+        assert_eq!(storage.len(), len - tombstones);
+    }
+
+    /// Compare a full key scan of a set in the interleaved layout and the split layout.
+    ///
+    /// Run with `cargo test -p foyer-storage --release -- --ignored --nocapture bench_set_storage_key_scan`.
+    #[test]
+    #[ignore]
+    fn bench_set_storage_key_scan() {
+        const SET_SIZE: usize = 64 * 1024;
+        const SCANS: usize = 10_000;
+
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(1024).build();
+        for value_len in [16, 256, 1024] {
+            let entries = (0..(SET_SIZE / (value_len + 32)) as u64)
+                .map(|i| memory.insert(i, vec![i as u8; value_len]))
+                .collect::<Vec<_>>();
+            for layout in [SetLayout::Interleaved, SetLayout::Split] {
+                let mut buf = IoBuffer::new(SET_SIZE);
+                buf.fill(0);
+                let mut storage = SetStorage::load_with(buf, 0, true, layout, Arc::new(Metrics::noop()));
+                storage.apply(
+                    &HashSet::new(),
+                    entries
+                        .iter()
+                        .map(|e| Item {
+                            slice: to_bytes(e),
+                            hash: e.hash(),
+                            inserted_at: None,
+                        })
+                        .collect(),
+                );
+                let now = Instant::now();
+                let mut sum = 0;
+                for _ in 0..SCANS {
+                    sum += storage.keys::<u64>().map(|res| res.unwrap().1).sum::<u64>();
+                }
+                let elapsed = now.elapsed();
+                std::hint::black_box(sum);
+                println!(
+                    "value len: {value_len:>5}, entries: {:>5}, layout: {layout:?}, key scan: {:?}/set",
+                    storage.entry_count(),
+                    elapsed / SCANS as u32
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_storage_load_from() {
+        let memory = memory_for_test();
+        let e1 = memory.insert(1, vec![b'1'; 42]);
+
+        let mut storage = SetStorage::load(buffer_for_test(), 0, Arc::new(Metrics::noop()));
         storage.apply(
             &HashSet::new(),
             vec![Item {
-                slice: s5.clone(),
-                hash: e5.hash(),
+                slice: to_bytes(&e1),
+                hash: e1.hash(),
+                inserted_at: None,
             }],
         );
-        assert_eq!(storage.len(), s4.len());
-        assert_none(&storage, e1.hash());
-        assert_none(&storage, e2.hash());
-        assert_none(&storage, e3.hash());
-        assert_some(&storage, &e4);
+        storage.update();
+
+        let options = SetReadOptions::default();
+        // Place the set at the second page of the source.
+        let mut source = vec![0; PAGE];
+        source.extend_from_slice(&storage.into_io_buffer());
+        let mut source = std::io::Cursor::new(source);
+
+        let loaded = SetStorage::load_from(&mut source, PAGE as _, PAGE, &options).unwrap();
+        assert_eq!(loaded.entry_count(), 1);
+        assert_some(&loaded, &e1);
+        let entries = read_set_entries::<u64, Vec<u8>, _>(&mut source, PAGE as _, PAGE, &options).unwrap();
+        assert_eq!(entries, vec![(*e1.key(), e1.value().clone())]);
+
+        let loaded = SetStorage::load_from(&mut source, 0, PAGE, &options).unwrap();
+        assert!(loaded.is_empty());
+
+        // Malformed entries with a valid checksum.
+        source.get_mut()[PAGE + SetStorage::SET_HEADER_SIZE + 10..PAGE + SetStorage::SET_HEADER_SIZE + 12]
+            .copy_from_slice(&u16::MAX.to_be_bytes());
+        let loaded = SetStorage::load_from(&mut source, PAGE as _, PAGE, &options).unwrap();
+        assert!(loaded.is_empty());
+
+        // Short read.
+        let err = SetStorage::load_from(&mut source, PAGE as u64 + 1, PAGE, &options).unwrap_err();
+        assert!(matches!(err, Error::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof));
+
+        // Invalid set size.
+        let err = SetStorage::load_from(&mut source, 0, SetStorage::SET_HEADER_SIZE, &options).unwrap_err();
+        assert!(matches!(err, Error::Io(e) if e.kind() == std::io::ErrorKind::InvalidInput));
+        let err = SetStorage::load_from(&mut source, 0, PAGE + 1, &options).unwrap_err();
+        assert!(matches!(err, Error::Io(e) if e.kind() == std::io::ErrorKind::InvalidInput));
+
+        // Sets written with other options.
+        for (layout, checksum) in [(SetLayout::Split, true), (SetLayout::Interleaved, false)] {
+            let mut storage = SetStorage::load_with(buffer_for_test(), 0, false, layout, Arc::new(Metrics::noop()));
+            storage.apply(
+                &HashSet::new(),
+                vec![Item {
+                    slice: to_bytes(&e1),
+                    hash: e1.hash(),
+                    inserted_at: None,
+                }],
+            );
+            storage.update_with_checksum(checksum);
+            let mut source = std::io::Cursor::new(storage.into_io_buffer().to_vec());
+
+            let options = SetReadOptions::default().with_set_layout(layout).with_verify(checksum);
+            let entries = read_set_entries::<u64, Vec<u8>, _>(&mut source, 0, PAGE, &options).unwrap();
+            assert_eq!(entries, vec![(*e1.key(), e1.value().clone())]);
+
+            if layout == SetLayout::Split {
+                let err =
+                    read_set_entries::<u64, Vec<u8>, _>(&mut source, 0, PAGE, &SetReadOptions::default()).unwrap_err();
+                assert!(matches!(err, Error::Io(e) if e.kind() == std::io::ErrorKind::InvalidData));
+            }
+        }
     }
 }
@@ -14,16 +14,17 @@
 
 use itertools::Itertools;
 use ordered_hash_map::OrderedHashMap;
-use parking_lot::{MappedRwLockReadGuard, RwLock, RwLockReadGuard};
+use parking_lot::RwLock;
 
-use super::set::{SetId, SetStorage};
+use super::set::{Set, SetId};
 
 /// In-memory set cache to reduce disk io.
 ///
-/// Simple FIFO cache.
+/// Simple LRU cache. A set must be invalidated or replaced before it is updated on the device, so a cached set is
+/// never stale.
 #[derive(Debug)]
 pub struct SetCache {
-    shards: Vec<RwLock<OrderedHashMap<SetId, SetStorage>>>,
+    shards: Vec<RwLock<OrderedHashMap<SetId, Set>>>,
     shard_capacity: usize,
 }
 
@@ -36,24 +37,32 @@ impl SetCache {
         Self { shards, shard_capacity }
     }
 
-    pub fn insert(&self, id: SetId, storage: SetStorage) {
+    pub fn insert(&self, id: SetId, set: Set) {
         let mut shard = self.shards[self.shard(&id)].write();
-        if shard.len() == self.shard_capacity {
+        if shard.remove(&id).is_none() && shard.len() == self.shard_capacity {
             shard.pop_front();
         }
 
         assert!(shard.len() < self.shard_capacity);
 
-        shard.insert(id, storage);
+        shard.insert(id, set);
     }
 
-    pub fn invalid(&self, id: &SetId) {
+    /// Remove the set from the cache, returns if the set was cached.
+    pub fn invalid(&self, id: &SetId) -> bool {
         let mut shard = self.shards[self.shard(id)].write();
-        shard.remove(id);
+        shard.remove(id).is_some()
     }
 
-    pub fn lookup(&self, id: &SetId) -> Option<MappedRwLockReadGuard<'_, SetStorage>> {
-        RwLockReadGuard::try_map(self.shards[self.shard(id)].read(), |shard| shard.get(id)).ok()
+    pub fn lookup(&self, id: &SetId) -> Option<Set> {
+        let mut shard = self.shards[self.shard(id)].write();
+        shard.move_to_back(id);
+        shard.get(id).cloned()
+    }
+
+    /// Get the cached set without refreshing its recency.
+    pub fn peek(&self, id: &SetId) -> Option<Set> {
+        self.shards[self.shard(id)].read().get(id).cloned()
     }
 
     pub fn clear(&self) {
@@ -64,3 +73,44 @@ impl SetCache {
         *id as usize % self.shards.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use foyer_common::metrics::Metrics;
+
+    use super::*;
+    use crate::{
+        io::{buffer::IoBuffer, PAGE},
+        small::set::SetStorage,
+    };
+
+    fn set() -> Set {
+        let mut buffer = IoBuffer::new(PAGE);
+        buffer.fill(0);
+        Set::from(SetStorage::load(buffer, 0, Arc::new(Metrics::noop())))
+    }
+
+    #[test]
+    fn test_set_cache_lru() {
+        let cache = SetCache::new(2, 1);
+        cache.insert(1, set());
+        cache.insert(2, set());
+
+        // Set 1 is recently used, set 2 is evicted.
+        assert!(cache.lookup(&1).is_some());
+        cache.insert(3, set());
+        assert!(cache.lookup(&2).is_none());
+        assert!(cache.lookup(&1).is_some());
+        assert!(cache.lookup(&3).is_some());
+
+        // Replacing a cached set evicts nothing.
+        cache.insert(3, set());
+        assert!(cache.lookup(&1).is_some());
+
+        assert!(cache.invalid(&1));
+        assert!(!cache.invalid(&1));
+        assert!(cache.lookup(&1).is_none());
+    }
+}
@@ -12,28 +12,48 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::HashSet, fmt::Debug, ops::Range, sync::Arc};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    ops::Range,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use bytes::{Buf, BufMut};
 use foyer_common::{
+    asyncify::asyncify_with_runtime,
     code::{StorageKey, StorageValue},
     metrics::Metrics,
 };
+use futures_util::{StreamExt, TryStreamExt};
 use itertools::Itertools;
-use parking_lot::RwLock;
-use tokio::sync::RwLock as AsyncRwLock;
+use parking_lot::{Mutex, RwLock};
+use tokio::sync::{Mutex as AsyncMutex, RwLock as AsyncRwLock};
 
 use super::{
     batch::Item,
     bloom_filter::BloomFilterU64,
+    checkpoint::{IndexCheckpoint, SetCheckpoint},
+    footer::{IntegrityMode, RegionFooter},
     generic::GenericSmallStorageConfig,
-    set::{SetId, SetStorage, SetTimestamp},
+    set::{
+        Set, SetBloomFilterRebuild, SetDeleteMode, SetEntryFields, SetId, SetLayout, SetMut, SetStorage, SetTimestamp,
+    },
     set_cache::SetCache,
 };
 use crate::{
     device::{Dev, MonitoredDevice, RegionId},
-    error::Result,
+    error::{Error, Result},
+    event::{EventBus, StoreEvent},
     io::{buffer::IoBuffer, PAGE},
+    serde::RawValue,
+    Runtime,
 };
 
 /// # Lock Order
@@ -54,23 +74,50 @@ use crate::{
 /// update:
 ///
 /// ```plain
-/// lock(W) set => lock(e) set cache => invalid set cache => unlock(e) set cache => update set => lock(w) bloom filter => unlock(w) bloom filter => unlock(w) set
+/// lock(W) set => lock(e) set cache => invalid set cache => unlock(e) set cache => update set => lock(w) bloom filter => unlock(w) bloom filter => lock(e) set cache => insert set cache if invalidated => unlock(e) set cache => unlock(w) set
 /// ```
+///
+/// Loads and updates hold the resize gate in read mode all along, and each step of a resize holds it in write mode, so
+/// the routing never changes in the middle of a load or an update.
 struct SetManagerInner {
     // TODO(MrCroxx): Refine this!!! Make `Set` a RAII type.
+    /// Locks of all the sets the device can hold, including the ones not in use yet.
     sets: Vec<AsyncRwLock<()>>,
     /// As a cache, it is okay that the bloom filter returns a false-negative result, which doesn't break the
     /// correctness.
     loose_bloom_filters: Vec<RwLock<BloomFilterU64<4>>>,
     set_cache: SetCache,
     metadata: AsyncRwLock<Metadata>,
-    set_picker: SetPicker,
+    set_picker: RwLock<SetPicker>,
+    resize_gate: AsyncRwLock<()>,
+    /// Serializes resizes, so a manual resize and an automatic one never fight over the target.
+    resizer: AsyncMutex<()>,
+    resize_policy: Option<SetResizePolicy>,
+    usage: SetUsage,
+    /// Path of the index checkpoint, the stamps are only maintained if it is set.
+    index_checkpoint: Option<PathBuf>,
+    bloom_filter_preload: bool,
+    stamps: Vec<Mutex<IndexStamp>>,
+    /// Footers of the regions, empty unless [`IntegrityMode::RegionFooter`] is used.
+    footers: Vec<AsyncMutex<RegionFooter>>,
 
     set_size: usize,
     device: MonitoredDevice,
     regions: Range<RegionId>,
+    /// Count of the sets each region holds.
+    region_sets: usize,
+    footer_size: usize,
     flush: bool,
+    apply_yield_interval: usize,
+    checksum_on_write: bool,
+    verify_on_read: bool,
+    set_layout: SetLayout,
+    entry_fields: SetEntryFields,
+    delete_mode: SetDeleteMode,
+    bloom_filter_rebuild: SetBloomFilterRebuild,
 
+    events: EventBus,
+    runtime: Runtime,
     metrics: Arc<Metrics>,
 }
 
@@ -85,74 +132,473 @@ impl Debug for SetManager {
             .field("sets", &self.inner.sets)
             .field("loose_bloom_filters", &self.inner.loose_bloom_filters)
             .field("set_picker", &self.inner.set_picker)
+            .field("resize_policy", &self.inner.resize_policy)
+            .field("usage", &self.inner.usage)
+            .field("index_checkpoint", &self.inner.index_checkpoint)
+            .field("bloom_filter_preload", &self.inner.bloom_filter_preload)
             .field("set_cache", &self.inner.set_cache)
             .field("metadata", &self.inner.metadata)
             .field("set_size", &self.inner.set_size)
             .field("device", &self.inner.device)
             .field("regions", &self.inner.regions)
+            .field("region_sets", &self.inner.region_sets)
+            .field("footer_size", &self.inner.footer_size)
             .field("flush", &self.inner.flush)
+            .field("apply_yield_interval", &self.inner.apply_yield_interval)
+            .field("checksum_on_write", &self.inner.checksum_on_write)
+            .field("verify_on_read", &self.inner.verify_on_read)
+            .field("set_layout", &self.inner.set_layout)
+            .field("entry_fields", &self.inner.entry_fields)
+            .field("delete_mode", &self.inner.delete_mode)
+            .field("bloom_filter_rebuild", &self.inner.bloom_filter_rebuild)
             .field("metrics", &self.inner.metrics)
             .finish()
     }
 }
 
 impl SetManager {
-    pub async fn open<K, V>(config: &GenericSmallStorageConfig<K, V>) -> Result<Self>
+    /// Max count of the sets read concurrently by [`SetManager::load_many`].
+    pub const LOAD_MANY_CONCURRENCY: usize = 16;
+    /// Max count of the set headers read concurrently by [`SetManager::fill_report`], [`SetManager::timestamps`] and
+    /// the region footer verification.
+    pub const FILL_REPORT_CONCURRENCY: usize = 16;
+
+    /// Open the sets of the set size class `class` with `set_size` bytes each, laid out on `regions`.
+    ///
+    /// The meta set is the first set of the regions, so the classes on disjoint regions never overlap.
+    pub async fn open<K, V>(
+        config: &GenericSmallStorageConfig<K, V>,
+        class: usize,
+        set_size: usize,
+        regions: Range<RegionId>,
+    ) -> Result<Self>
     where
         K: StorageKey,
         V: StorageValue,
     {
         let device = config.device.clone();
-        let regions = config.regions.clone();
 
-        let sets = (device.region_size() / config.set_size) * (regions.end - regions.start) as usize;
+        let (region_sets, footer_size) = match config.integrity_mode {
+            IntegrityMode::Set => (device.region_size() / set_size, 0),
+            IntegrityMode::RegionFooter => RegionFooter::layout(set_size, device.region_size()),
+        };
+        if region_sets == 0 {
+            return Err(Error::InvalidConfig(format!(
+                "small object disk cache region size {} cannot hold a set of {set_size} bytes and the {footer_size} bytes region footer",
+                device.region_size()
+            )));
+        }
+        let sets = region_sets * (regions.end - regions.start) as usize;
         assert!(sets > 0); // TODO: assert > 1? Set with id = 0 is used as metadata.
 
-        let set_picker = SetPicker::new(sets);
+        let configured = match config.initial_sets {
+            Some(initial) => SetPicker::with_base(initial, initial),
+            None => SetPicker::new(sets),
+        };
 
         // load & flush metadata
-        let metadata = Metadata::load(&device).await?;
-        metadata.flush(&device).await?;
+        let mut metadata = Metadata::load(&device, regions.start).await?;
+        // Metadata without the routing is written before sets can be resized, when all sets are in use.
+        let previous = metadata.set_picker.clone().unwrap_or(SetPicker::new(sets));
+        let set_picker = if previous.base == configured.base && previous.sets() <= sets {
+            previous
+        } else {
+            // The sets are written with another routing, invalidate them.
+            tracing::info!(
+                ?previous,
+                ?configured,
+                "[sodc set manager]: set routing changed, invalidate all sets"
+            );
+            metadata.watermark = SetTimestamp::current();
+            configured
+        };
+        let footers = match config.integrity_mode {
+            IntegrityMode::Set => vec![],
+            IntegrityMode::RegionFooter => {
+                let (footers, valid) =
+                    Self::verify_footers(&device, regions.clone(), set_size, region_sets, footer_size).await?;
+                if !valid {
+                    // No region can be trusted, e.g. the device is new or the mode is just enabled.
+                    metadata.watermark = SetTimestamp::current();
+                }
+                footers.into_iter().map(AsyncMutex::new).collect_vec()
+            }
+        };
+        let set_picker = set_picker.with_router(config.set_router.clone(), class);
+        metadata.set_picker = Some(set_picker.clone());
+        metadata.flush(&device, regions.start).await?;
         let metadata = AsyncRwLock::new(metadata);
 
         let set_cache = SetCache::new(config.set_cache_capacity, config.set_cache_shards);
         let loose_bloom_filters = (0..sets).map(|_| RwLock::new(BloomFilterU64::new())).collect_vec();
 
+        let usage = SetUsage::new(sets);
+        let stamps = (0..sets).map(|_| Mutex::new(IndexStamp::Unknown)).collect_vec();
         let sets = (0..sets).map(|_| AsyncRwLock::default()).collect_vec();
 
         let inner = SetManagerInner {
             sets,
             loose_bloom_filters,
             set_cache,
-            set_picker,
+            set_picker: RwLock::new(set_picker),
+            resize_gate: AsyncRwLock::default(),
+            resizer: AsyncMutex::default(),
+            resize_policy: config.resize_policy.clone(),
+            usage,
+            index_checkpoint: config.index_checkpoint.clone(),
+            bloom_filter_preload: config.bloom_filter_preload,
+            stamps,
+            footers,
             metadata,
-            set_size: config.set_size,
+            set_size,
             device,
             regions,
+            region_sets,
+            footer_size,
             flush: config.flush,
+            apply_yield_interval: config.apply_yield_interval,
+            checksum_on_write: config.checksum_on_write,
+            verify_on_read: config.verify_on_read,
+            set_layout: config.set_layout,
+            entry_fields: config.entry_fields(),
+            delete_mode: config.delete_mode,
+            bloom_filter_rebuild: config.bloom_filter_rebuild,
+            events: config.events.clone(),
+            runtime: config.runtime.clone(),
             metrics: config.device.metrics().clone(),
         };
         let inner = Arc::new(inner);
-        Ok(Self { inner })
+        let this = Self { inner };
+
+        if let Some(path) = this.inner.index_checkpoint.clone() {
+            this.restore(path).await?;
+            if let Some(interval) = config.index_checkpoint_interval {
+                this.checkpoint_periodically(interval);
+            }
+        } else if this.inner.bloom_filter_preload {
+            this.preload_bloom_filters().await?;
+        }
+
+        Ok(this)
+    }
+
+    /// Verify the footer of each region against the layout and the set checksums of the region, see [`RegionFooter`].
+    ///
+    /// The sets of a region whose footer is missing or doesn't match cannot be trusted. If some regions still match,
+    /// only the sets of the other regions are invalidated, by zeroing their headers, and their footers are rewritten.
+    /// If no region matches, the footers are rewritten from the sets on the device and `false` is returned, so the
+    /// caller invalidates all sets at once instead. Returns the footers of the regions.
+    async fn verify_footers(
+        device: &MonitoredDevice,
+        regions: Range<RegionId>,
+        set_size: usize,
+        region_sets: usize,
+        footer_size: usize,
+    ) -> Result<(Vec<RegionFooter>, bool)> {
+        let mut footers = Vec::with_capacity(regions.len());
+        let mut invalid = vec![];
+        let (mut missing, mut corrupted) = (0, 0);
+        for (index, region) in regions.clone().enumerate() {
+            let first = (index * region_sets) as SetId;
+            let checksums = futures_util::stream::iter(first..first + region_sets as SetId)
+                .map(|sid| async move {
+                    // The meta set is not checksummed by the footer.
+                    if sid == 0 {
+                        return Ok(0);
+                    }
+                    let offset = ((sid as usize % region_sets) * set_size) as u64;
+                    let (buf, res) = device.read(IoBuffer::new(PAGE), region, offset).await;
+                    res?;
+                    Ok::<_, Error>((&buf[..4]).get_u32())
+                })
+                .buffered(Self::FILL_REPORT_CONCURRENCY)
+                .try_collect()
+                .await?;
+            let expected = RegionFooter {
+                region,
+                set_size,
+                first,
+                checksums,
+            };
+
+            let offset = (device.region_size() - footer_size) as u64;
+            let (buf, res) = device.read(IoBuffer::new(footer_size), region, offset).await;
+            res?;
+            match RegionFooter::read(&buf) {
+                Some(footer) if footer == expected => {}
+                Some(_) => {
+                    corrupted += 1;
+                    invalid.push(index);
+                }
+                None => {
+                    missing += 1;
+                    invalid.push(index);
+                }
+            }
+            footers.push(expected);
+        }
+
+        if invalid.is_empty() {
+            return Ok((footers, true));
+        }
+        if invalid.len() == regions.len() {
+            tracing::info!(
+                "[sodc set manager]: {corrupted} region footers mismatch the sets and {missing} are missing, invalidate all sets"
+            );
+            for footer in footers.iter() {
+                Self::flush_footer(device, footer, footer_size).await?;
+            }
+            return Ok((footers, false));
+        }
+
+        tracing::warn!(
+            "[sodc set manager]: {corrupted} region footers mismatch the sets and {missing} are missing, invalidate the sets of the regions"
+        );
+        for index in invalid {
+            let footer = &mut footers[index];
+            let region = footer.region;
+            futures_util::stream::iter(footer.first..footer.first + region_sets as SetId)
+                .filter(|sid| std::future::ready(*sid != 0))
+                .map(|sid| async move {
+                    // A zeroed header never matches the layout version, the set is loaded as an empty set.
+                    let mut buf = IoBuffer::new(PAGE);
+                    buf.fill(0);
+                    let offset = ((sid as usize % region_sets) * set_size) as u64;
+                    let (_, res) = device.write(buf, region, offset).await;
+                    res?;
+                    Ok::<_, Error>(())
+                })
+                .buffered(Self::FILL_REPORT_CONCURRENCY)
+                .try_collect::<Vec<_>>()
+                .await?;
+            footer.checksums.fill(0);
+            Self::flush_footer(device, footer, footer_size).await?;
+        }
+        Ok((footers, true))
+    }
+
+    /// Write the footer to the end of its region.
+    async fn flush_footer(device: &MonitoredDevice, footer: &RegionFooter, footer_size: usize) -> Result<()> {
+        let mut buf = IoBuffer::new(footer_size);
+        buf.fill(0);
+        footer.write(&mut buf[..]);
+        let offset = (device.region_size() - footer_size) as u64;
+        let (_, res) = device.write(buf, footer.region, offset).await;
+        res?;
+        Ok(())
+    }
+
+    /// Restore the loose bloom filters from the index checkpoint.
+    ///
+    /// Without a valid checkpoint, the sets in use are scanned in the background instead. The restored sets are
+    /// verified on their first read, see [`SetManager::verify_stamp`].
+    async fn restore(&self, path: PathBuf) -> Result<()> {
+        let checkpoint = asyncify_with_runtime(self.inner.runtime.read(), move || IndexCheckpoint::read(&path)).await?;
+        let watermark = self.watermark().await;
+        let set_picker = self.set_picker();
+
+        let checkpoint = checkpoint.filter(|checkpoint| {
+            checkpoint.watermark == watermark
+                && checkpoint.set_size == self.inner.set_size
+                && checkpoint.base == set_picker.base
+                && checkpoint.data == set_picker.data
+        });
+        let Some(checkpoint) = checkpoint else {
+            tracing::info!("[sodc set manager]: no valid index checkpoint, scan sets in the background");
+            let this = self.clone();
+            self.inner.runtime.read().spawn(async move {
+                if let Err(e) = this.scan().await {
+                    tracing::warn!("[sodc set manager]: scan sets error: {e}");
+                }
+            });
+            return Ok(());
+        };
+
+        tracing::info!(
+            "[sodc set manager]: restore {} sets from the index checkpoint",
+            checkpoint.sets.len()
+        );
+        for set in checkpoint.sets {
+            if set.sid == 0 || set.sid as usize > set_picker.data {
+                continue;
+            }
+            *self.inner.loose_bloom_filters[set.sid as usize].write() = set.bloom_filter;
+            *self.inner.stamps[set.sid as usize].lock() = IndexStamp::Checkpointed(set.checksum);
+        }
+        Ok(())
+    }
+
+    /// Load the loose bloom filters of the sets in use from the set headers.
+    ///
+    /// Only the first page of each set is read, up to [`SetManager::FILL_REPORT_CONCURRENCY`] concurrently. The
+    /// filters take 32 bytes of memory for each set.
+    async fn preload_bloom_filters(&self) -> Result<()> {
+        let watermark = self.watermark().await;
+        let sets = self.set_picker().data;
+
+        let loaded: Vec<_> = futures_util::stream::iter(1..=sets as SetId)
+            .map(|sid| async move {
+                let (region, offset) = self.locate(sid);
+                let (buf, res) = self.inner.device.read(IoBuffer::new(PAGE), region, offset).await;
+                res?;
+                let bloom_filter = SetStorage::bloom_filter_from_header(
+                    &buf,
+                    self.inner.set_size,
+                    watermark,
+                    self.inner.set_layout,
+                    self.inner.entry_fields,
+                );
+                Ok::<_, Error>(bloom_filter.map(|bloom_filter| (sid, bloom_filter)))
+            })
+            .buffered(Self::FILL_REPORT_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        let mut count = 0;
+        for (sid, bloom_filter) in loaded.into_iter().flatten() {
+            *self.inner.loose_bloom_filters[sid as usize].write() = bloom_filter;
+            count += 1;
+        }
+        tracing::info!("[sodc set manager]: preload bloom filters of {count} non-empty sets out of {sets} sets");
+        Ok(())
+    }
+
+    /// Rebuild the loose bloom filters of the sets that are neither restored nor read yet.
+    async fn scan(&self) -> Result<()> {
+        let sets = self.set_picker().data;
+        for sid in 1..=sets as SetId {
+            let _gate = self.inner.resize_gate.read().await;
+            if sid as usize > self.set_picker().data {
+                break;
+            }
+            let _set = self.inner.sets[sid as usize].read().await;
+            if matches!(*self.inner.stamps[sid as usize].lock(), IndexStamp::Unknown) {
+                self.storage(sid).await?;
+            }
+        }
+        tracing::info!("[sodc set manager]: scan {sets} sets finished");
+        Ok(())
+    }
+
+    fn checkpoint_periodically(&self, interval: Duration) {
+        let inner = Arc::downgrade(&self.inner);
+        self.inner.runtime.write().spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                // Stop once the set manager is dropped.
+                let Some(inner) = inner.upgrade() else { break };
+                if let Err(e) = (Self { inner }).checkpoint().await {
+                    tracing::warn!("[sodc set manager]: checkpoint index error: {e}");
+                }
+            }
+        });
+    }
+
+    pub fn is_index_checkpoint_enabled(&self) -> bool {
+        self.inner.index_checkpoint.is_some()
+    }
+
+    pub fn is_bloom_filter_preload_enabled(&self) -> bool {
+        self.inner.bloom_filter_preload
+    }
+
+    /// Write the loose bloom filters of the sets in use to the index checkpoint.
+    pub async fn checkpoint(&self) -> Result<()> {
+        let Some(path) = self.inner.index_checkpoint.clone() else {
+            return Err(Error::InvalidConfig(
+                "small object disk cache index checkpoint is not enabled".to_string(),
+            ));
+        };
+
+        let gate = self.inner.resize_gate.read().await;
+        let set_picker = self.set_picker();
+        let mut sets = vec![];
+        for sid in 1..=set_picker.data as SetId {
+            // Wait for the ongoing update, so the bloom filter matches the checksum.
+            let _set = self.inner.sets[sid as usize].read().await;
+            let checksum = match *self.inner.stamps[sid as usize].lock() {
+                IndexStamp::Unknown => continue,
+                IndexStamp::Checkpointed(checksum) | IndexStamp::Verified(checksum) => checksum,
+            };
+            sets.push(SetCheckpoint {
+                sid,
+                checksum,
+                bloom_filter: self.inner.loose_bloom_filters[sid as usize].read().clone(),
+            });
+        }
+        let checkpoint = IndexCheckpoint {
+            watermark: self.watermark().await,
+            set_size: self.inner.set_size,
+            base: set_picker.base,
+            data: set_picker.data,
+            sets,
+        };
+        drop(gate);
+
+        asyncify_with_runtime(self.inner.runtime.write(), move || checkpoint.write(&path)).await
+    }
+
+    /// Verify the loose bloom filter of a set read from the device against its stamp.
+    ///
+    /// A set that is updated after the index checkpoint, or not restored from it, gets its own bloom filter.
+    fn verify_stamp(&self, sid: SetId, storage: &SetStorage) {
+        if self.inner.index_checkpoint.is_none() {
+            return;
+        }
+        let mut stamp = self.inner.stamps[sid as usize].lock();
+        let checksum = match *stamp {
+            IndexStamp::Verified(_) => return,
+            IndexStamp::Checkpointed(checksum) => Some(checksum),
+            IndexStamp::Unknown => None,
+        };
+        let actual = storage.content_checksum();
+        if checksum != Some(actual) {
+            *self.inner.loose_bloom_filters[sid as usize].write() = storage.bloom_filter().clone();
+        }
+        *stamp = IndexStamp::Verified(actual);
     }
 
     pub fn may_contains(&self, hash: u64) -> bool {
-        let sid = self.inner.set_picker.sid(hash);
+        let sid = self.inner.set_picker.read().sid(hash);
         self.inner.loose_bloom_filters[sid as usize].read().lookup(hash)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        fastrace::trace(name = "foyer::storage::small::set_manager::load")
+    )]
     pub async fn load<K, V>(&self, hash: u64) -> Result<Option<(K, V)>>
     where
         K: StorageKey,
         V: StorageValue,
     {
-        let sid = self.inner.set_picker.sid(hash);
+        self.load_with(hash, |set| set.get(hash)).await
+    }
+
+    /// Load the entry with the given hash as [`SetManager::load`], but keep the value as it is written.
+    pub async fn load_raw<K>(&self, hash: u64) -> Result<Option<(K, RawValue)>>
+    where
+        K: StorageKey,
+    {
+        self.load_with(hash, |set| set.get_raw(hash)).await
+    }
+
+    async fn load_with<T, F>(&self, hash: u64, get: F) -> Result<Option<T>>
+    where
+        F: FnOnce(&SetStorage) -> Result<Option<T>>,
+    {
+        let gate = self.inner.resize_gate.read().await;
+        let sid = self.inner.set_picker.read().sid(hash);
+
+        #[cfg(feature = "tracing")]
+        fastrace::local::LocalSpan::add_property(|| ("set_id", sid.to_string()));
 
         tracing::trace!("[sodc set manager]: load {hash} from set {sid}");
 
         // Query bloom filter.
         if !self.inner.loose_bloom_filters[sid as usize].read().lookup(hash) {
             tracing::trace!("[sodc set manager]: set {sid} bloom filter miss for {hash}");
+            self.inner.metrics.storage_sodc_bloom_filter_skip.increase(1);
             return Ok(None);
         }
 
@@ -161,53 +607,521 @@ impl SetManager {
 
         // Query form set cache.
         if let Some(cached) = self.inner.set_cache.lookup(&sid) {
-            return cached.get(hash);
+            return get(&cached);
         }
 
         // Set cache miss, load from disk.
-        let storage = self.storage(sid).await?;
-        let res = storage.get(hash);
+        let storage = Set::from(self.storage(sid).await?);
+        let res = get(&storage);
 
         // Update set cache on cache miss.
         self.inner.set_cache.insert(sid, storage);
 
         // Release set lock.
         drop(set);
+        drop(gate);
 
         res
     }
 
+    /// Locate the set that holds an entry with the given hash, without deserializing any entry.
+    ///
+    /// Like [`SetManager::scan_set`], the set cache is left untouched.
+    pub async fn locate_entry(&self, hash: u64) -> Result<Option<SetId>> {
+        let _gate = self.inner.resize_gate.read().await;
+        let sid = self.inner.set_picker.read().sid(hash);
+
+        if !self.inner.loose_bloom_filters[sid as usize].read().lookup(hash) {
+            self.inner.metrics.storage_sodc_bloom_filter_skip.increase(1);
+            return Ok(None);
+        }
+
+        let _set = self.inner.sets[sid as usize].read().await;
+        let contains = match self.inner.set_cache.peek(&sid) {
+            Some(cached) => cached.contains(hash),
+            None => self.storage(sid).await?.contains(hash),
+        };
+        Ok(contains.then_some(sid))
+    }
+
+    /// Load the entries with the given hashes, the results are in the order of the hashes.
+    ///
+    /// The hashes are grouped by the sets they are routed to, each set is read at most once, and up to
+    /// [`SetManager::LOAD_MANY_CONCURRENCY`] sets are read concurrently. If a set fails to load, every hash routed to
+    /// it fails, only the last one gets the original error, the others get its message.
+    pub async fn load_many<K, V>(&self, hashes: &[u64]) -> Vec<Result<Option<(K, V)>>>
+    where
+        K: StorageKey,
+        V: StorageValue,
+    {
+        let gate = self.inner.resize_gate.read().await;
+
+        let mut groups = HashMap::<SetId, Vec<usize>>::new();
+        {
+            let set_picker = self.inner.set_picker.read();
+            for (i, &hash) in hashes.iter().enumerate() {
+                let sid = set_picker.sid(hash);
+                if self.inner.loose_bloom_filters[sid as usize].read().lookup(hash) {
+                    groups.entry(sid).or_default().push(i);
+                } else {
+                    self.inner.metrics.storage_sodc_bloom_filter_skip.increase(1);
+                }
+            }
+        }
+
+        let mut results = hashes.iter().map(|_| Ok(None)).collect_vec();
+        let mut loads = futures_util::stream::iter(groups)
+            .map(|(sid, indices)| async move {
+                tracing::trace!("[sodc set manager]: load {} hashes from set {sid}", indices.len());
+
+                // Acquire set lock.
+                let set = self.inner.sets[sid as usize].read().await;
+
+                let res = match self.inner.set_cache.lookup(&sid) {
+                    Some(cached) => Ok(indices.iter().map(|&i| (i, cached.get(hashes[i]))).collect_vec()),
+                    None => self.storage(sid).await.map(|storage| {
+                        let storage = Set::from(storage);
+                        let res = indices.iter().map(|&i| (i, storage.get(hashes[i]))).collect_vec();
+                        // Update set cache on cache miss.
+                        self.inner.set_cache.insert(sid, storage);
+                        res
+                    }),
+                };
+
+                // Release set lock.
+                drop(set);
+
+                (indices, res)
+            })
+            .buffer_unordered(Self::LOAD_MANY_CONCURRENCY);
+
+        while let Some((mut indices, res)) = loads.next().await {
+            match res {
+                Ok(res) => res.into_iter().for_each(|(i, r)| results[i] = r),
+                Err(e) => {
+                    let last = indices.pop().unwrap();
+                    for i in indices {
+                        results[i] = Err(anyhow::anyhow!(e.to_string()).into());
+                    }
+                    results[last] = Err(e);
+                }
+            }
+        }
+        drop(loads);
+        drop(gate);
+
+        results
+    }
+
+    /// Report how full each set in use is, i.e. the data length over the data capacity of the set, in the order of
+    /// the set ids.
+    ///
+    /// Only the first page of each set is read for the set header, see [`SetStorage::len_from_header`], and the sets
+    /// in the set cache are not read at all. Up to [`SetManager::FILL_REPORT_CONCURRENCY`] set headers are read
+    /// concurrently.
+    pub async fn fill_report(&self) -> Result<Vec<(SetId, f64)>> {
+        let _gate = self.inner.resize_gate.read().await;
+
+        let watermark = self.watermark().await;
+        let capacity = (self.inner.set_size - SetStorage::SET_HEADER_SIZE) as f64;
+        let sets = self.set_picker().data;
+
+        futures_util::stream::iter(1..=sets as SetId)
+            .map(|sid| async move {
+                // Acquire set lock, so the set header is not read while the set is being written.
+                let _set = self.inner.sets[sid as usize].read().await;
+
+                let len = match self.inner.set_cache.lookup(&sid) {
+                    Some(cached) => cached.len(),
+                    None => {
+                        let (region, offset) = self.locate(sid);
+                        let (buf, res) = self.inner.device.read(IoBuffer::new(PAGE), region, offset).await;
+                        res?;
+                        SetStorage::len_from_header(
+                            &buf,
+                            self.inner.set_size,
+                            watermark,
+                            self.inner.set_layout,
+                            self.inner.entry_fields,
+                        )
+                    }
+                };
+                Ok((sid, len as f64 / capacity))
+            })
+            .buffered(Self::FILL_REPORT_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    /// Report the timestamps of the last updates of the non-empty sets in use, in the order of the set ids.
+    ///
+    /// Like [`SetManager::fill_report`], only the set headers are read, and the sets in the set cache are not read at
+    /// all.
+    pub async fn timestamps(&self) -> Result<Vec<(SetId, u128)>> {
+        let _gate = self.inner.resize_gate.read().await;
+
+        let watermark = self.watermark().await;
+        let sets = self.set_picker().data;
+
+        let timestamps: Vec<_> = futures_util::stream::iter(1..=sets as SetId)
+            .map(|sid| async move {
+                // Acquire set lock, so the set header is not read while the set is being written.
+                let _set = self.inner.sets[sid as usize].read().await;
+
+                let timestamp = match self.inner.set_cache.lookup(&sid) {
+                    Some(cached) => (cached.entry_count() > 0).then(|| cached.timestamp()),
+                    None => {
+                        let (region, offset) = self.locate(sid);
+                        let (buf, res) = self.inner.device.read(IoBuffer::new(PAGE), region, offset).await;
+                        res?;
+                        SetStorage::timestamp_from_header(
+                            &buf,
+                            self.inner.set_size,
+                            watermark,
+                            self.inner.set_layout,
+                            self.inner.entry_fields,
+                        )
+                    }
+                };
+                Ok::<_, Error>(timestamp.map(|timestamp| (sid, timestamp)))
+            })
+            .buffered(Self::FILL_REPORT_CONCURRENCY)
+            .try_collect()
+            .await?;
+        Ok(timestamps.into_iter().flatten().collect())
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        fastrace::trace(name = "foyer::storage::small::set_manager::update")
+    )]
     pub async fn update(&self, sid: SetId, deletions: &HashSet<u64>, items: Vec<Item>) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        fastrace::local::LocalSpan::add_properties(|| {
+            [
+                ("set_id", sid.to_string()),
+                ("deletions", deletions.len().to_string()),
+                ("items", items.len().to_string()),
+            ]
+        });
+
+        let gate = self.inner.resize_gate.read().await;
+        let set_picker = self.inner.set_picker.read().clone();
+
+        if deletions
+            .iter()
+            .copied()
+            .chain(items.iter().map(|item| item.hash))
+            .all(|hash| set_picker.sid(hash) == sid)
+        {
+            self.update_set(sid, deletions, items).await?;
+        } else {
+            // The sets are resized after the batch is built, route the entries again.
+            let mut sets: HashMap<SetId, (HashSet<u64>, Vec<Item>)> = HashMap::new();
+            for &hash in deletions {
+                sets.entry(set_picker.sid(hash)).or_default().0.insert(hash);
+            }
+            for item in items {
+                sets.entry(set_picker.sid(item.hash)).or_default().1.push(item);
+            }
+            for (sid, (deletions, items)) in sets {
+                self.update_set(sid, &deletions, items).await?;
+            }
+        }
+
+        drop(gate);
+
+        if self.should_grow() {
+            self.grow_in_background();
+        }
+
+        Ok(())
+    }
+
+    async fn update_set(&self, sid: SetId, deletions: &HashSet<u64>, items: Vec<Item>) -> Result<()> {
         // Acquire set lock.
         let set = self.inner.sets[sid as usize].write().await;
 
-        self.inner.set_cache.invalid(&sid);
+        let cached = self.inner.set_cache.invalid(&sid);
 
         let mut storage = self.storage(sid).await?;
-        storage.apply(deletions, items);
-        storage.update();
+        storage.set_delete_mode(self.inner.delete_mode);
+        storage.set_bloom_filter_rebuild(self.inner.bloom_filter_rebuild);
+        let mut storage = SetMut::new(storage);
+        let wiped = storage
+            .apply_async(deletions, items, self.inner.apply_yield_interval)
+            .await;
+        // The tombstones may cross the ratio without a deletion, e.g. a set written with a larger ratio before.
+        if let SetDeleteMode::Tombstone { ratio } = self.inner.delete_mode {
+            if storage.tombstone_ratio() > ratio {
+                let reclaimed = storage.compact_tombstones();
+                tracing::trace!("[sodc set manager]: {reclaimed} bytes of tombstones reclaimed from set {sid}");
+            }
+        }
+        if !wiped.is_empty() {
+            tracing::trace!(
+                "[sodc set manager]: {} entries wiped from set {sid} to make room",
+                wiped.len()
+            );
+        }
+        let storage = self.persist(sid, storage).await?;
+        // Keep the hot set cached with the update.
+        if cached {
+            self.inner.set_cache.insert(sid, storage);
+        }
+
+        // Release set lock.
+        drop(set);
+
+        Ok(())
+    }
+
+    /// Remove the entries with the hash from its set on the device, regardless of the delete mode.
+    ///
+    /// The set is always read and rewritten without the entries and the tombstones, even if the bloom filter misses,
+    /// and the region is flushed even if flushing is disabled. With `zero`, the unused space of the set is zeroed as
+    /// well, see [`SetStorage::remove`].
+    ///
+    /// Returns whether a live entry with the hash was in the set.
+    pub async fn remove(&self, hash: u64, zero: bool) -> Result<bool> {
+        let gate = self.inner.resize_gate.read().await;
+        let sid = self.inner.set_picker.read().sid(hash);
+
+        // Acquire set lock.
+        let set = self.inner.sets[sid as usize].write().await;
+
+        let cached = self.inner.set_cache.invalid(&sid);
+
+        let mut storage = SetMut::new(self.storage(sid).await?);
+        let present = storage.remove(hash, zero);
+        let storage = self.persist(sid, storage).await?;
+        if !self.inner.flush {
+            let (region, _) = self.locate(sid);
+            self.inner.device.flush(Some(region)).await?;
+        }
+        if cached {
+            self.inner.set_cache.insert(sid, storage);
+        }
+
+        // Release set lock.
+        drop(set);
+        drop(gate);
+
+        Ok(present)
+    }
+
+    /// Publish the set and write it to the device, the caller must hold the set lock.
+    async fn persist(&self, sid: SetId, storage: SetMut) -> Result<Set> {
+        let storage = storage.publish_with_checksum(self.inner.checksum_on_write);
+
+        #[cfg(feature = "tracing")]
+        fastrace::local::LocalSpan::add_properties(|| {
+            [
+                ("bytes", storage.bytes().len().to_string()),
+                ("len", storage.len().to_string()),
+            ]
+        });
 
         *self.inner.loose_bloom_filters[sid as usize].write() = storage.bloom_filter().clone();
+        self.inner.usage.record(sid, storage.len(), storage.entry_count());
+        if self.inner.index_checkpoint.is_some() {
+            *self.inner.stamps[sid as usize].lock() = IndexStamp::Verified(storage.content_checksum());
+        }
 
-        let buffer = storage.into_io_buffer();
         let (region, offset) = self.locate(sid);
-        let (_, res) = self.inner.device.write(buffer, region, offset).await;
+        let (_, res) = self.inner.device.write(storage.bytes(), region, offset).await;
         res?;
+        if let Some(footer) = self.inner.footers.get((region - self.inner.regions.start) as usize) {
+            let mut footer = footer.lock().await;
+            footer.checksums[sid as usize % self.inner.region_sets] = storage.checksum();
+            Self::flush_footer(&self.inner.device, &footer, self.inner.footer_size).await?;
+        }
         if self.inner.flush {
             self.inner.device.flush(Some(region)).await?;
         }
 
-        // Release set lock.
-        drop(set);
+        Ok(storage)
+    }
 
-        Ok(())
+    /// Resize the sets in use to `sets` sets, the meta set excluded.
+    ///
+    /// The sets are split or merged one at a time. Each step rewrites one or two sets while loads and updates wait, so
+    /// they always see the entries where the current routing points to. Merging may wipe the oldest entries of the
+    /// merged set if they don't fit.
+    ///
+    /// `sets` must be in `[initial sets, all sets - 1]`, the initial sets are the level 0 sets of the routing.
+    pub async fn resize(&self, sets: usize) -> Result<()> {
+        let base = self.inner.set_picker.read().base;
+        let max = self.inner.sets.len() - 1;
+        if sets < base || sets > max {
+            return Err(Error::OutOfRange {
+                valid: base..max + 1,
+                get: sets..sets + 1,
+            });
+        }
+
+        let _resizer = self.inner.resizer.lock().await;
+        let from = self.inner.set_picker.read().data;
+        if from == sets {
+            return Ok(());
+        }
+
+        self.inner.events.emit(StoreEvent::ResizeStarted { from, to: sets });
+        let res = self.resize_to(sets).await;
+        let resized = self.inner.set_picker.read().data;
+        self.inner.events.emit(StoreEvent::ResizeFinished { sets: resized });
+        res
+    }
+
+    /// Split or merge the sets one at a time until `sets` sets are in use, the caller must hold the resizer lock.
+    async fn resize_to(&self, sets: usize) -> Result<()> {
+        loop {
+            let _gate = self.inner.resize_gate.write().await;
+            let set_picker = self.inner.set_picker.read().clone();
+            let resized = match set_picker.data.cmp(&sets) {
+                Ordering::Equal => return Ok(()),
+                Ordering::Less => self.split(set_picker).await?,
+                Ordering::Greater => self.merge(set_picker).await?,
+            };
+
+            let mut metadata = self.inner.metadata.write().await;
+            metadata.set_picker = Some(resized.clone());
+            metadata.flush(&self.inner.device, self.inner.regions.start).await?;
+            *self.inner.set_picker.write() = resized;
+        }
+    }
+
+    /// Split the next set of the linear hashing into a new set, the caller must hold the resize gate.
+    async fn split(&self, set_picker: SetPicker) -> Result<SetPicker> {
+        let grown = set_picker.grow();
+        let (src, dst) = set_picker.split();
+        tracing::debug!("[sodc set manager]: split set {src} into set {dst}");
+
+        let mut storage = self.storage(src).await?;
+        // Entries routed to neither set can only be left by an interrupted resize, drop them.
+        let (moved, stayed): (Vec<_>, Vec<_>) = storage
+            .items()
+            .into_iter()
+            .filter(|item| grown.sid(item.hash) == src || grown.sid(item.hash) == dst)
+            .partition(|item| grown.sid(item.hash) == dst);
+
+        // The new set may hold the stale entries of an earlier resize, always start from an empty one.
+        let mut target = self.storage(dst).await?;
+        target.clear();
+        target.set_delete_mode(self.inner.delete_mode);
+        target.set_bloom_filter_rebuild(self.inner.bloom_filter_rebuild);
+        let mut target = SetMut::new(target);
+        target.apply(&HashSet::new(), moved);
+        self.persist(dst, target).await?;
+
+        storage.clear();
+        storage.set_delete_mode(self.inner.delete_mode);
+        storage.set_bloom_filter_rebuild(self.inner.bloom_filter_rebuild);
+        let mut storage = SetMut::new(storage);
+        storage.apply(&HashSet::new(), stayed);
+        self.persist(src, storage).await?;
+
+        self.inner.set_cache.invalid(&src);
+        self.inner.set_cache.invalid(&dst);
+
+        Ok(grown)
+    }
+
+    /// Merge the last set back into the set it is split from, the caller must hold the resize gate.
+    async fn merge(&self, set_picker: SetPicker) -> Result<SetPicker> {
+        let shrunk = set_picker.shrink();
+        let (dst, src) = shrunk.split();
+        tracing::debug!("[sodc set manager]: merge set {src} into set {dst}");
+
+        let moved = self
+            .storage(src)
+            .await?
+            .items()
+            .into_iter()
+            .filter(|item| shrunk.sid(item.hash) == dst)
+            .collect_vec();
+        // Delete the copies an interrupted merge may have left.
+        let deletions = moved.iter().map(|item| item.hash).collect();
+
+        let mut storage = self.storage(dst).await?;
+        storage.set_delete_mode(self.inner.delete_mode);
+        storage.set_bloom_filter_rebuild(self.inner.bloom_filter_rebuild);
+        let mut storage = SetMut::new(storage);
+        storage.apply(&deletions, moved);
+        self.persist(dst, storage).await?;
+
+        *self.inner.loose_bloom_filters[src as usize].write() = BloomFilterU64::new();
+        self.inner.usage.record(src, 0, 0);
+        *self.inner.stamps[src as usize].lock() = IndexStamp::Unknown;
+        self.inner.set_cache.invalid(&src);
+        self.inner.set_cache.invalid(&dst);
+
+        Ok(shrunk)
+    }
+
+    fn should_grow(&self) -> bool {
+        let Some(policy) = self.inner.resize_policy.as_ref() else {
+            return false;
+        };
+        let sets = self.inner.set_picker.read().data;
+        if sets + 1 >= self.inner.sets.len() {
+            return false;
+        }
+        let capacity = self.inner.set_size - SetStorage::SET_HEADER_SIZE;
+        let (len, entries) = self.inner.usage.total();
+        let fill = len as f64 / (capacity * sets) as f64;
+        let fpp = BloomFilterU64::<4>::estimated_false_positive_rate(entries / sets);
+        fill > policy.fill_ratio || fpp > policy.false_positive_rate
+    }
+
+    fn grow_in_background(&self) {
+        let Some(policy) = self.inner.resize_policy.clone() else {
+            return;
+        };
+        let this = self.clone();
+        self.inner.runtime.write().spawn(async move {
+            // Skip if another resize is in progress, it will be checked again on the following updates.
+            let Ok(resizer) = this.inner.resizer.try_lock() else {
+                return;
+            };
+            drop(resizer);
+            let sets = this.inner.set_picker.read().data;
+            let target = ((sets as f64 * (1.0 + policy.growth)).ceil() as usize)
+                .max(sets + 1)
+                .min(this.inner.sets.len() - 1);
+            tracing::info!("[sodc set manager]: grow sets from {sets} to {target}");
+            if let Err(e) = this.resize(target).await {
+                tracing::warn!("[sodc set manager]: grow sets from {sets} to {target} error: {e}");
+            }
+        });
+    }
+
+    /// Read the set with the given id for scanning, returns `None` if the set is no longer in use.
+    ///
+    /// The set is taken from the set cache if cached, otherwise read from the device. Either way the set cache is
+    /// left untouched, so a scan doesn't evict the sets of the foreground traffic.
+    pub async fn scan_set(&self, sid: SetId) -> Result<Option<Set>> {
+        let _gate = self.inner.resize_gate.read().await;
+        if sid == 0 || sid as usize > self.set_picker().data {
+            return Ok(None);
+        }
+        let _set = self.inner.sets[sid as usize].read().await;
+        if let Some(cached) = self.inner.set_cache.peek(&sid) {
+            return Ok(Some(cached));
+        }
+        self.storage(sid).await.map(|storage| Some(Set::from(storage)))
     }
 
+    /// Count of the sets in use, including the meta set.
     pub fn sets(&self) -> usize {
-        self.inner.sets.len()
+        self.inner.set_picker.read().sets()
+    }
+
+    /// Routing of the sets in use.
+    pub fn set_picker(&self) -> SetPicker {
+        self.inner.set_picker.read().clone()
     }
 
-    #[expect(dead_code)]
     pub fn set_size(&self) -> usize {
         self.inner.set_size
     }
@@ -227,7 +1141,7 @@ impl SetManager {
 
         let watermark = SetTimestamp::current();
         metadata.watermark = watermark;
-        metadata.flush(&self.inner.device).await
+        metadata.flush(&self.inner.device, self.inner.regions.start).await
     }
 
     async fn storage(&self, id: SetId) -> Result<SetStorage> {
@@ -235,29 +1149,189 @@ impl SetManager {
         let buf = IoBuffer::new(self.inner.set_size);
         let (buf, res) = self.inner.device.read(buf, region, offset).await;
         res?;
-        let storage = SetStorage::load(buf, self.watermark().await, self.inner.metrics.clone());
+        let storage = SetStorage::load_with_fields(
+            buf,
+            self.watermark().await,
+            self.inner.verify_on_read,
+            self.inner.set_layout,
+            self.inner.entry_fields,
+            self.inner.metrics.clone(),
+        );
+        if storage.is_corrupted() {
+            tracing::warn!("[sodc set manager]: set {id} is corrupted, loaded as an empty set");
+            self.inner.events.emit(StoreEvent::SetCorrupted { set: id });
+        }
+        self.verify_stamp(id, &storage);
         Ok(storage)
     }
 
     #[inline]
-    fn region_sets(&self) -> usize {
-        self.inner.device.region_size() / self.inner.set_size
+    fn locate(&self, id: SetId) -> (RegionId, u64) {
+        let (region, offset) = locate_in(id, self.inner.set_size, self.inner.region_sets);
+        (self.inner.regions.start + region, offset)
     }
+}
 
-    #[inline]
-    fn locate(&self, id: SetId) -> (RegionId, u64) {
-        let region_sets = self.region_sets();
-        let region = id as RegionId / region_sets as RegionId;
-        let offset = ((id as usize % region_sets) * self.inner.set_size) as u64;
-        (region, offset)
+/// Locate the region of the set and the offset of the set in the region, with `region_sets` sets in each region.
+///
+/// Sets are laid out back to back from the start of each region, a set never crosses regions. The tail of a region
+/// that cannot hold a whole set is left as padding, or holds the footer with [`IntegrityMode::RegionFooter`].
+#[inline]
+fn locate_in(id: SetId, set_size: usize, region_sets: usize) -> (RegionId, u64) {
+    let region = id as RegionId / region_sets as RegionId;
+    let offset = ((id as usize % region_sets) * set_size) as u64;
+    (region, offset)
+}
+
+/// Get the byte offset of the set with the given id on the device, as the small object disk cache engine addresses it.
+///
+/// `base_offset` is the byte offset of the first region on the device, regions follow it back to back with
+/// `region_size` bytes each. The padding at the tail of each region is accounted for if `region_size` is not a
+/// multiple of `set_size`.
+///
+/// The set with id `0` holds the engine metadata instead of entries.
+///
+/// With [`IntegrityMode::RegionFooter`], each region only holds the sets that fit before the footer, see
+/// [`RegionFooter::layout`], so `integrity_mode` must be the one the engine is configured with.
+///
+/// # Panics
+///
+/// Panics if `set_size` is `0` or greater than `region_size`, or the region cannot hold a set besides its footer.
+pub fn region_offset(
+    set_id: SetId,
+    set_size: usize,
+    region_size: usize,
+    integrity_mode: IntegrityMode,
+    base_offset: u64,
+) -> u64 {
+    assert!(
+        set_size > 0 && set_size <= region_size,
+        "set size {set_size} must be in (0, {region_size}]"
+    );
+    let region_sets = match integrity_mode {
+        IntegrityMode::Set => region_size / set_size,
+        IntegrityMode::RegionFooter => RegionFooter::layout(set_size, region_size).0,
+    };
+    assert!(
+        region_sets > 0,
+        "region size {region_size} cannot hold a set besides its footer"
+    );
+    let (region, offset) = locate_in(set_id, set_size, region_sets);
+    base_offset + region as u64 * region_size as u64 + offset
+}
+
+/// Routes the entries to the data sets of the small object disk cache by the hashes of their keys.
+///
+/// A custom router can co-locate related entries in the same set, e.g. keys with a common prefix hashed to the same
+/// prefix hash, or balance the fill of the sets for a known key distribution. Both loads and updates route with the
+/// registered router, so they always agree.
+///
+/// The router must be deterministic, and must be the same across restarts with the same device. Otherwise, the
+/// entries written before are missed or read from the wrong sets. The sets cannot be resized with a custom router.
+pub trait SetRouter: Send + Sync + Debug + 'static {
+    /// Route the hash to a data set in `0..sets`, the meta set is skipped by the caller.
+    fn route(&self, hash: u64, sets: usize) -> SetId;
+
+    /// Route the hash to a data set in `0..sets` of the set size class `class`, the meta set is skipped by the caller.
+    ///
+    /// The classes are indexed in the ascending order of their set sizes, see [`SetSizeClass`]. Each class has its own
+    /// sets, so the same hash may be routed differently in each class.
+    ///
+    /// Default: [`SetRouter::route`], the class is ignored.
+    fn route_in_class(&self, class: usize, hash: u64, sets: usize) -> SetId {
+        let _ = class;
+        self.route(hash, sets)
+    }
+}
+
+/// A size class of the sets of the small object disk cache, see `SmallEngineOptions::with_set_size_classes`.
+///
+/// Each class has its own sets of `set_size` bytes on its own part of the regions, the regions are allocated to the
+/// classes in proportion to their ratios.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SetSizeClass {
+    /// Size of each set of the class in bytes, must be a multiple of 4 KiB.
+    pub set_size: usize,
+    /// Share of the regions allocated to the class, relative to the sum of the ratios of all classes.
+    pub ratio: f64,
+}
+
+impl SetSizeClass {
+    /// Create a size class with `set_size` bytes sets and `ratio` share of the regions.
+    pub fn new(set_size: usize, ratio: f64) -> Self {
+        Self { set_size, ratio }
+    }
+
+    /// Bytes of the entries a set of the class can hold, i.e. the set size without the set header.
+    pub fn capacity(&self) -> usize {
+        self.set_size.saturating_sub(SetStorage::SET_HEADER_SIZE)
+    }
+
+    /// Split the regions into consecutive ranges for the classes, in the order of the classes.
+    ///
+    /// Each class gets at least one region if any is left, and the last class gets the rest, so the ranges may be empty
+    /// if there are fewer regions than classes.
+    pub(crate) fn split(classes: &[Self], regions: Range<RegionId>) -> Vec<Range<RegionId>> {
+        let total = classes.iter().map(|class| class.ratio).sum::<f64>();
+        let count = (regions.end - regions.start) as f64;
+        let mut start = regions.start;
+        classes
+            .iter()
+            .enumerate()
+            .map(|(i, class)| {
+                let left = regions.end - start;
+                let len = if i + 1 == classes.len() {
+                    left
+                } else {
+                    ((count * class.ratio / total) as RegionId).max(1).min(left)
+                };
+                start += len;
+                start - len..start
+            })
+            .collect()
     }
 }
 
+/// Routes the hashes to the sets with linear hashing, so the sets in use can grow or shrink one set at a time.
+///
+/// `base` sets are in use at level 0. Growing splits the sets of the current level in order, the entries of a split
+/// set are either kept or moved to the new set at the end. Shrinking merges the last set back into the set it is split
+/// from. Without resizing, a hash is routed by `hash % base` as before.
+///
+/// Each set has a fixed slot on the device (see `SetManager::locate`), so recovery needs no index beyond the routing
+/// kept in the metadata. With the default [`SetDeleteMode::Compact`], deleted entries are dropped from the set in place
+/// when the set is updated, so a sparse set holds no stale bytes. With [`SetDeleteMode::Tombstone`], deleted entries
+/// are left in the set as tombstones until the set runs out of space for new entries, or the tombstones take more than
+/// the ratio of the set data capacity when the set is updated, then the set is compacted in place to reclaim them.
+///
+/// A [`SetRouter`] replaces the linear hashing if registered.
 #[derive(Debug, Clone)]
 pub struct SetPicker {
-    sets: usize,
+    /// Count of the data sets at level 0.
+    base: usize,
+    /// Count of the data sets in use.
+    data: usize,
+    router: Option<Arc<dyn SetRouter>>,
+    /// The set size class routed to by the router.
+    class: usize,
 }
 
+impl PartialEq for SetPicker {
+    fn eq(&self, other: &Self) -> bool {
+        self.base == other.base
+            && self.data == other.data
+            && self.class == other.class
+            && match (self.router.as_ref(), other.router.as_ref()) {
+                (None, None) => true,
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                _ => false,
+            }
+    }
+}
+
+impl Eq for SetPicker {}
+
 impl SetPicker {
     /// Create a [`SetPicker`] with a total size count.
     ///
@@ -267,12 +1341,164 @@ impl SetPicker {
     ///
     /// The 0th set will be used as the meta set.
     pub fn new(sets: usize) -> Self {
-        Self { sets }
+        Self::with_base(sets - 1, sets - 1)
     }
 
+    /// Create a [`SetPicker`] with `base` data sets at level 0 and `data` data sets in use, the meta set excluded.
+    fn with_base(base: usize, data: usize) -> Self {
+        assert!(
+            base > 0 && data >= base,
+            "invalid set routing, base: {base}, data: {data}"
+        );
+        Self {
+            base,
+            data,
+            router: None,
+            class: 0,
+        }
+    }
+
+    /// Route the hashes of the set size class `class` with the given router instead of the linear hashing.
+    fn with_router(mut self, router: Option<Arc<dyn SetRouter>>, class: usize) -> Self {
+        self.router = router;
+        self.class = class;
+        self
+    }
+
+    /// Count of the sets in use, including the meta set.
+    pub fn sets(&self) -> usize {
+        self.data + 1
+    }
+
+    /// Count of the data sets at the start of the current level.
+    fn level(&self) -> usize {
+        let mut level = self.base;
+        while level * 2 <= self.data {
+            level *= 2;
+        }
+        level
+    }
+
+    /// Pick the set for the given hash.
     pub fn sid(&self, hash: u64) -> SetId {
+        if let Some(router) = self.router.as_ref() {
+            let index = router.route_in_class(self.class, hash, self.data);
+            assert!(
+                index < self.data as SetId,
+                "set router {router:?} routes hash {hash} to set {index} out of 0..{}",
+                self.data
+            );
+            // skip the meta set
+            return index + 1;
+        }
+
+        let level = self.level() as u64;
+        let mut index = hash % level;
+        if index < self.data as u64 - level {
+            // The set is already split in the current level.
+            index = hash % (level * 2);
+        }
         // skip the meta set
-        hash % (self.sets as SetId - 1) + 1
+        index + 1
+    }
+
+    /// The set to split by the next growth, and the new set its entries are moved to.
+    fn split(&self) -> (SetId, SetId) {
+        let src = self.data - self.level() + 1;
+        (src as SetId, self.data as SetId + 1)
+    }
+
+    fn grow(&self) -> Self {
+        Self::with_base(self.base, self.data + 1).with_router(self.router.clone(), self.class)
+    }
+
+    fn shrink(&self) -> Self {
+        Self::with_base(self.base, self.data - 1).with_router(self.router.clone(), self.class)
+    }
+}
+
+/// Policy to grow the sets in use automatically.
+///
+/// After each update, the sets are grown in the background if the average fill ratio of the sets in use or the
+/// estimated false positive rate of their bloom filters exceeds the threshold, until all sets of the device are in use.
+/// The sets are never shrunk automatically, use `Store::resize_sets` instead.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SetResizePolicy {
+    /// Grow the sets if the average bytes of the entries in a set exceeds this ratio of the set capacity.
+    pub fill_ratio: f64,
+    /// Grow the sets if the estimated false positive rate of the bloom filter of an average set exceeds this threshold.
+    pub false_positive_rate: f64,
+    /// Ratio of the sets to add on each growth.
+    pub growth: f64,
+}
+
+impl Default for SetResizePolicy {
+    fn default() -> Self {
+        Self {
+            fill_ratio: 0.8,
+            false_positive_rate: 0.01,
+            growth: 0.5,
+        }
+    }
+}
+
+impl SetResizePolicy {
+    pub(crate) fn verify(&self) -> Result<()> {
+        let valid = |v: f64| v > 0.0 && v <= 1.0;
+        let positive = self.growth > 0.0;
+        if !valid(self.fill_ratio) || !valid(self.false_positive_rate) || !positive {
+            return Err(Error::InvalidConfig(format!(
+                "small object disk cache set resize policy fill ratio and false positive rate must be in (0, 1], growth must be positive: {self:?}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// What the loose bloom filter of a set is known to match.
+#[derive(Debug, Clone, Copy)]
+enum IndexStamp {
+    /// The set is neither restored from the index checkpoint nor read since opened.
+    Unknown,
+    /// Restored from the index checkpoint with the content checksum of the set then, not verified yet.
+    Checkpointed(u32),
+    /// Matches the set with the content checksum.
+    Verified(u32),
+}
+
+/// Bytes and entries of the sets, recorded on updates to decide when to grow.
+#[derive(Debug)]
+struct SetUsage {
+    sets: Vec<(AtomicUsize, AtomicUsize)>,
+    len: AtomicUsize,
+    entries: AtomicUsize,
+}
+
+impl SetUsage {
+    fn new(sets: usize) -> Self {
+        Self {
+            sets: (0..sets).map(|_| Default::default()).collect(),
+            len: AtomicUsize::default(),
+            entries: AtomicUsize::default(),
+        }
+    }
+
+    fn record(&self, sid: SetId, len: usize, entries: usize) {
+        let (l, e) = &self.sets[sid as usize];
+        let old = l.swap(len, AtomicOrdering::Relaxed);
+        self.len.fetch_add(len, AtomicOrdering::Relaxed);
+        self.len.fetch_sub(old, AtomicOrdering::Relaxed);
+        let old = e.swap(entries, AtomicOrdering::Relaxed);
+        self.entries.fetch_add(entries, AtomicOrdering::Relaxed);
+        self.entries.fetch_sub(old, AtomicOrdering::Relaxed);
+    }
+
+    fn total(&self) -> (usize, usize) {
+        (
+            self.len.load(AtomicOrdering::Relaxed),
+            self.entries.load(AtomicOrdering::Relaxed),
+        )
     }
 }
 
@@ -280,50 +1506,192 @@ impl SetPicker {
 struct Metadata {
     /// watermark timestamp
     watermark: u128,
+    /// Routing of the sets, `None` if the metadata is written before the sets can be resized.
+    set_picker: Option<SetPicker>,
 }
 
 impl Default for Metadata {
     fn default() -> Self {
         Self {
             watermark: SetTimestamp::current(),
+            set_picker: None,
         }
     }
 }
 
 impl Metadata {
-    const MAGIC: u64 = 0x20230512deadbeef;
-    const SIZE: usize = 8 + 16;
+    /// Magic of the metadata without the routing.
+    const MAGIC_V1: u64 = 0x20230512deadbeef;
+    const MAGIC: u64 = 0x20250601deadbeef;
+    const SIZE: usize = 8 + 16 + 8 + 8;
 
     fn write(&self, mut buf: impl BufMut) {
+        let set_picker = self
+            .set_picker
+            .as_ref()
+            .expect("set routing must be set before flushing metadata");
         buf.put_u64(Self::MAGIC);
         buf.put_u128(self.watermark);
+        buf.put_u64(set_picker.base as u64);
+        buf.put_u64(set_picker.data as u64);
     }
 
     fn read(mut buf: impl Buf) -> Self {
         let magic = buf.get_u64();
         let watermark = buf.get_u128();
 
-        if magic != Self::MAGIC || watermark > SetTimestamp::current() {
+        if (magic != Self::MAGIC && magic != Self::MAGIC_V1) || watermark > SetTimestamp::current() {
+            return Self::default();
+        }
+
+        if magic == Self::MAGIC_V1 {
+            return Self {
+                watermark,
+                set_picker: None,
+            };
+        }
+
+        let base = buf.get_u64() as usize;
+        let data = buf.get_u64() as usize;
+        if base == 0 || data < base {
             return Self::default();
         }
 
-        Self { watermark }
+        Self {
+            watermark,
+            set_picker: Some(SetPicker::with_base(base, data)),
+        }
     }
 
-    async fn flush(&self, device: &MonitoredDevice) -> Result<()> {
+    /// Write the metadata to the meta set at the start of `region`.
+    async fn flush(&self, device: &MonitoredDevice, region: RegionId) -> Result<()> {
         let mut buf = IoBuffer::new(PAGE);
         self.write(&mut buf[..]);
-        let (_, res) = device.write(buf, 0, 0).await;
+        let (_, res) = device.write(buf, region, 0).await;
         res?;
         Ok(())
     }
 
-    async fn load(device: &MonitoredDevice) -> Result<Self> {
+    /// Read the metadata from the meta set at the start of `region`.
+    async fn load(device: &MonitoredDevice, region: RegionId) -> Result<Self> {
         let buf = IoBuffer::new(PAGE);
 
-        let (buf, res) = device.read(buf, 0, 0).await;
+        let (buf, res) = device.read(buf, region, 0).await;
         res?;
         let metadata = Metadata::read(&buf[..Metadata::SIZE]);
         Ok(metadata)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_size_class_split() {
+        let classes = [SetSizeClass::new(4096, 1.0), SetSizeClass::new(65536, 3.0)];
+        assert_eq!(SetSizeClass::split(&classes, 0..8), vec![0..2, 2..8]);
+        assert_eq!(SetSizeClass::split(&classes, 4..6), vec![4..5, 5..6]);
+        // Fewer regions than classes.
+        assert_eq!(SetSizeClass::split(&classes, 0..1), vec![0..1, 1..1]);
+        assert_eq!(SetSizeClass::split(&classes[..1], 0..8), vec![0..8]);
+    }
+
+    #[test]
+    fn test_set_picker() {
+        // Routing is unchanged without resizing.
+        let picker = SetPicker::new(8);
+        for hash in 0..1000 {
+            assert_eq!(picker.sid(hash), hash % 7 + 1);
+        }
+
+        let mut picker = SetPicker::with_base(3, 3);
+        for _ in 0..20 {
+            let grown = picker.grow();
+            let (src, dst) = picker.split();
+            for hash in 0..10000 {
+                let (old, new) = (picker.sid(hash), grown.sid(hash));
+                // Only the entries of the split set move, and only to the new set.
+                if old != new {
+                    assert_eq!((old, new), (src, dst));
+                }
+                assert!(new >= 1 && new as usize <= grown.data);
+            }
+            assert_eq!(grown.shrink(), picker);
+            picker = grown;
+        }
+    }
+
+    #[test]
+    fn test_set_router() {
+        #[derive(Debug)]
+        struct PrefixRouter;
+
+        impl SetRouter for PrefixRouter {
+            fn route(&self, hash: u64, sets: usize) -> SetId {
+                (hash >> 8) % sets as SetId
+            }
+        }
+
+        let picker = SetPicker::new(8).with_router(Some(Arc::new(PrefixRouter)), 0);
+        for hash in 0..256 {
+            assert_eq!(picker.sid(hash), 1);
+            assert_eq!(picker.sid(hash + 256 * 3), 4);
+        }
+        assert_ne!(picker, SetPicker::new(8));
+        assert_eq!(picker, picker.clone());
+    }
+
+    #[test]
+    fn test_metadata() {
+        let metadata = Metadata {
+            watermark: SetTimestamp::current(),
+            set_picker: Some(SetPicker::with_base(3, 10)),
+        };
+        let mut buf = [0; Metadata::SIZE];
+        metadata.write(&mut buf[..]);
+        let read = Metadata::read(&buf[..]);
+        assert_eq!(read.watermark, metadata.watermark);
+        assert_eq!(read.set_picker, metadata.set_picker);
+
+        // Metadata without the routing.
+        let mut buf = [0; Metadata::SIZE];
+        (&mut buf[..]).put_u64(Metadata::MAGIC_V1);
+        (&mut buf[8..]).put_u128(metadata.watermark);
+        let read = Metadata::read(&buf[..]);
+        assert_eq!(read.watermark, metadata.watermark);
+        assert_eq!(read.set_picker, None);
+    }
+
+    #[test]
+    fn test_region_offset() {
+        const KIB: usize = 1024;
+
+        const SET: IntegrityMode = IntegrityMode::Set;
+
+        // Sets fill the regions exactly.
+        assert_eq!(region_offset(0, 16 * KIB, 64 * KIB, SET, 0), 0);
+        assert_eq!(region_offset(3, 16 * KIB, 64 * KIB, SET, 0), 48 * KIB as u64);
+        assert_eq!(region_offset(4, 16 * KIB, 64 * KIB, SET, 0), 64 * KIB as u64);
+        assert_eq!(region_offset(5, 16 * KIB, 64 * KIB, SET, 4096), 80 * KIB as u64 + 4096);
+
+        // Regions with padding at the tail, 3 sets per region.
+        assert_eq!(region_offset(2, 20 * KIB, 64 * KIB, SET, 0), 40 * KIB as u64);
+        assert_eq!(region_offset(3, 20 * KIB, 64 * KIB, SET, 0), 64 * KIB as u64);
+        assert_eq!(region_offset(7, 20 * KIB, 64 * KIB, SET, 0), 148 * KIB as u64);
+
+        for id in 0..100 {
+            let (region, offset) = locate_in(id, 20 * KIB, 3);
+            assert_eq!(
+                region_offset(id, 20 * KIB, 64 * KIB, SET, 0),
+                region as u64 * 64 * KIB as u64 + offset
+            );
+        }
+
+        // The footer takes the space of the last set, 3 sets per region.
+        const FOOTER: IntegrityMode = IntegrityMode::RegionFooter;
+        assert_eq!(region_offset(2, 16 * KIB, 64 * KIB, FOOTER, 0), 32 * KIB as u64);
+        assert_eq!(region_offset(3, 16 * KIB, 64 * KIB, FOOTER, 0), 64 * KIB as u64);
+        assert_eq!(region_offset(7, 16 * KIB, 64 * KIB, FOOTER, 0), 144 * KIB as u64);
+    }
+}
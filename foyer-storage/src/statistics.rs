@@ -12,10 +12,93 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{
+    fmt::Debug,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
 
 use crate::IopsCounter;
 
+/// A lock-free latency histogram with logarithmic buckets.
+///
+/// Each power-of-two range is divided into [`LatencyHistogram::SUB_BUCKETS`] linear sub-buckets, so the relative
+/// error of the recorded values is bounded to `1 / SUB_BUCKETS`. Recording a value costs a single atomic increment.
+struct LatencyHistogram {
+    buckets: Box<[AtomicU64]>,
+}
+
+impl Debug for LatencyHistogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LatencyHistogram").finish()
+    }
+}
+
+impl LatencyHistogram {
+    const SUB_BUCKET_BITS: u32 = 4;
+    const SUB_BUCKETS: u64 = 1 << Self::SUB_BUCKET_BITS;
+    const BUCKETS: usize = (Self::SUB_BUCKETS * (u64::BITS - Self::SUB_BUCKET_BITS + 1) as u64) as usize;
+
+    fn new() -> Self {
+        Self {
+            buckets: (0..Self::BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    fn index(nanos: u64) -> usize {
+        if nanos < Self::SUB_BUCKETS {
+            return nanos as usize;
+        }
+        let shift = u64::BITS - 1 - nanos.leading_zeros() - Self::SUB_BUCKET_BITS;
+        let sub = (nanos >> shift) - Self::SUB_BUCKETS;
+        (Self::SUB_BUCKETS * (shift as u64 + 1) + sub) as usize
+    }
+
+    /// The upper bound of the values in the bucket.
+    fn upper(index: usize) -> u64 {
+        let index = index as u64;
+        if index < Self::SUB_BUCKETS {
+            return index;
+        }
+        let shift = index / Self::SUB_BUCKETS - 1;
+        let sub = index % Self::SUB_BUCKETS + Self::SUB_BUCKETS;
+        (((sub as u128 + 1) << shift) - 1).min(u64::MAX as u128) as u64
+    }
+
+    fn record(&self, duration: Duration) {
+        let nanos = duration.as_nanos().min(u64::MAX as u128) as u64;
+        self.buckets[Self::index(nanos)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn counts(&self) -> Vec<u64> {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    fn percentile(&self, p: f64) -> Option<Duration> {
+        Self::percentile_of(&self.counts(), p)
+    }
+
+    /// The percentile of the bucket counts collected by [`LatencyHistogram::counts`].
+    fn percentile_of(counts: &[u64], p: f64) -> Option<Duration> {
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((p.clamp(0.0, 100.0) / 100.0 * total as f64).ceil() as u64).max(1);
+        let mut acc = 0;
+        for (index, count) in counts.iter().enumerate() {
+            acc += count;
+            if acc >= rank {
+                return Some(Duration::from_nanos(Self::upper(index)));
+            }
+        }
+        unreachable!()
+    }
+}
+
 /// The statistics of the disk cache, which is used by the pickers.
 #[derive(Debug)]
 pub struct Statistics {
@@ -27,6 +110,12 @@ pub struct Statistics {
     disk_read_ios: AtomicUsize,
 
     disk_flush_ios: AtomicUsize,
+
+    disk_write_latency: LatencyHistogram,
+    disk_read_latency: LatencyHistogram,
+
+    free_space: AtomicUsize,
+    reserved_space: AtomicUsize,
 }
 
 impl Statistics {
@@ -39,6 +128,10 @@ impl Statistics {
             disk_write_ios: AtomicUsize::new(0),
             disk_read_ios: AtomicUsize::new(0),
             disk_flush_ios: AtomicUsize::new(0),
+            disk_write_latency: LatencyHistogram::new(),
+            disk_read_latency: LatencyHistogram::new(),
+            free_space: AtomicUsize::new(0),
+            reserved_space: AtomicUsize::new(0),
         }
     }
 
@@ -62,6 +155,52 @@ impl Statistics {
         self.disk_read_ios.load(Ordering::Relaxed)
     }
 
+    /// Get the disk cache write latency at the given percentile.
+    ///
+    /// `p` is in range `[0.0, 100.0]`, e.g. `99.9` for p999. Returns `None` if no write has been recorded.
+    ///
+    /// The result is the upper bound of the histogram bucket, whose relative error is less than `1/16`.
+    pub fn write_latency_percentile(&self, p: f64) -> Option<Duration> {
+        self.disk_write_latency.percentile(p)
+    }
+
+    /// Get the disk cache read latency at the given percentile.
+    ///
+    /// See [`Statistics::write_latency_percentile`].
+    pub fn read_latency_percentile(&self, p: f64) -> Option<Duration> {
+        self.disk_read_latency.percentile(p)
+    }
+
+    /// Get the free space of the disk cache in bytes, i.e. the total size of the clean regions of the large object disk
+    /// cache.
+    ///
+    /// The sets of the small object disk cache are updated in place, so they are never counted as free space.
+    pub fn free_space(&self) -> usize {
+        self.free_space.load(Ordering::Relaxed)
+    }
+
+    /// Get the space of the disk cache reserved for reclaiming in bytes, see `StoreBuilder::with_reserved_space`.
+    ///
+    /// New entries are rejected while the free space is not greater than the reserved space.
+    pub fn reserved_space(&self) -> usize {
+        self.reserved_space.load(Ordering::Relaxed)
+    }
+
+    /// Record a region becomes clean and update the free space.
+    pub fn record_region_clean(&self, bytes: usize) {
+        self.free_space.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Record a clean region is taken for writing and update the free space.
+    pub fn record_region_taken(&self, bytes: usize) {
+        self.free_space.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    /// Set the reserved space.
+    pub fn set_reserved_space(&self, bytes: usize) {
+        self.reserved_space.store(bytes, Ordering::Relaxed);
+    }
+
     /// Record the write IO and update the statistics.
     pub fn record_disk_write(&self, bytes: usize) {
         self.disk_write_bytes.fetch_add(bytes, Ordering::Relaxed);
@@ -76,8 +215,180 @@ impl Statistics {
             .fetch_add(self.iops_counter.count(bytes), Ordering::Relaxed);
     }
 
+    /// Record the latency of a write IO.
+    pub fn record_disk_write_latency(&self, duration: Duration) {
+        self.disk_write_latency.record(duration);
+    }
+
+    /// Record the latency of a read IO.
+    pub fn record_disk_read_latency(&self, duration: Duration) {
+        self.disk_read_latency.record(duration);
+    }
+
     /// Record the read IO and update the statistics.
     pub fn record_disk_flush(&self) {
         self.disk_flush_ios.fetch_add(1, Ordering::Relaxed);
     }
+
+    /// Take a snapshot of the statistics.
+    ///
+    /// The fields are loaded one by one without stopping the recording, so they may be slightly out of sync with
+    /// each other under load.
+    pub fn snapshot(&self) -> StatisticsSnapshot {
+        StatisticsSnapshot {
+            disk_write_bytes: self.disk_write_bytes(),
+            disk_read_bytes: self.disk_read_bytes(),
+            disk_write_ios: self.disk_write_ios(),
+            disk_read_ios: self.disk_read_ios(),
+            disk_flush_ios: self.disk_flush_ios.load(Ordering::Relaxed),
+            free_space: self.free_space(),
+            reserved_space: self.reserved_space(),
+            disk_write_latency: self.disk_write_latency.counts(),
+            disk_read_latency: self.disk_read_latency.counts(),
+        }
+    }
+}
+
+/// A point-in-time copy of the [`Statistics`] of a disk cache, see [`Statistics::snapshot`].
+///
+/// Snapshots of several disk caches, e.g. one per disk, can be combined into the statistics of the pool with
+/// [`StatisticsSnapshot::merge`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatisticsSnapshot {
+    /// The disk cache written bytes.
+    pub disk_write_bytes: usize,
+    /// The disk cache read bytes.
+    pub disk_read_bytes: usize,
+    /// The disk cache written ios.
+    pub disk_write_ios: usize,
+    /// The disk cache read ios.
+    pub disk_read_ios: usize,
+    /// The disk cache flush ios.
+    pub disk_flush_ios: usize,
+    /// The free space of the disk cache in bytes, see [`Statistics::free_space`].
+    pub free_space: usize,
+    /// The space of the disk cache reserved for reclaiming in bytes, see [`Statistics::reserved_space`].
+    pub reserved_space: usize,
+
+    /// Bucket counts of the write latency histogram.
+    disk_write_latency: Vec<u64>,
+    /// Bucket counts of the read latency histogram.
+    disk_read_latency: Vec<u64>,
+}
+
+impl StatisticsSnapshot {
+    /// Merge the snapshots of several disk caches into one.
+    ///
+    /// All fields are summed:
+    ///
+    /// - The bytes and the ios are counters of each disk cache, the sums count the bytes and the ios of all of them.
+    /// - The free space and the reserved space are gauges of the space of each disk cache. The disk caches own
+    ///   disjoint space, so the sums are the space of the pool. Ratios derived from the sums, e.g. the free space over
+    ///   the total capacity, are the averages of the disk caches weighted by their capacities.
+    /// - The latency histograms are summed bucket by bucket, so the percentiles of the merged snapshot are the
+    ///   percentiles of all ios of the pool, i.e. a busier disk cache weighs more. No per-cache percentile is
+    ///   averaged, which would be meaningless.
+    ///
+    /// Merging no snapshot returns an empty snapshot.
+    pub fn merge(snapshots: &[StatisticsSnapshot]) -> StatisticsSnapshot {
+        fn merge_counts(a: &mut Vec<u64>, b: &[u64]) {
+            if a.len() < b.len() {
+                a.resize(b.len(), 0);
+            }
+            a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+        }
+
+        snapshots
+            .iter()
+            .fold(StatisticsSnapshot::default(), |mut merged, snapshot| {
+                merged.disk_write_bytes += snapshot.disk_write_bytes;
+                merged.disk_read_bytes += snapshot.disk_read_bytes;
+                merged.disk_write_ios += snapshot.disk_write_ios;
+                merged.disk_read_ios += snapshot.disk_read_ios;
+                merged.disk_flush_ios += snapshot.disk_flush_ios;
+                merged.free_space += snapshot.free_space;
+                merged.reserved_space += snapshot.reserved_space;
+                merge_counts(&mut merged.disk_write_latency, &snapshot.disk_write_latency);
+                merge_counts(&mut merged.disk_read_latency, &snapshot.disk_read_latency);
+                merged
+            })
+    }
+
+    /// Get the write latency at the given percentile, see [`Statistics::write_latency_percentile`].
+    pub fn write_latency_percentile(&self, p: f64) -> Option<Duration> {
+        LatencyHistogram::percentile_of(&self.disk_write_latency, p)
+    }
+
+    /// Get the read latency at the given percentile, see [`Statistics::read_latency_percentile`].
+    pub fn read_latency_percentile(&self, p: f64) -> Option<Duration> {
+        LatencyHistogram::percentile_of(&self.disk_read_latency, p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_histogram_buckets() {
+        for nanos in [0, 1, 15, 16, 17, 31, 32, 1000, 123_456_789, u64::MAX] {
+            let index = LatencyHistogram::index(nanos);
+            assert!(index < LatencyHistogram::BUCKETS);
+            let upper = LatencyHistogram::upper(index);
+            assert!(upper >= nanos, "nanos: {nanos}, upper: {upper}");
+            assert!(upper - nanos <= nanos / LatencyHistogram::SUB_BUCKETS + 1);
+        }
+    }
+
+    #[test]
+    fn test_latency_percentile() {
+        let histogram = LatencyHistogram::new();
+        assert_eq!(histogram.percentile(50.0), None);
+
+        for us in 1..=1000 {
+            histogram.record(Duration::from_micros(us));
+        }
+
+        for (p, expected) in [(50.0, 500), (99.0, 990), (100.0, 1000)] {
+            let us = histogram.percentile(p).unwrap().as_secs_f64() * 1e6;
+            let expected = expected as f64;
+            assert!(us >= expected && us <= expected * 1.0625, "p: {p}, get: {us}us");
+        }
+    }
+
+    #[test]
+    fn test_statistics_snapshot_merge() {
+        let stats = (0..2).map(|_| Statistics::new(IopsCounter::PerIo)).collect::<Vec<_>>();
+        for (i, s) in stats.iter().enumerate() {
+            s.record_region_clean(1000 * (i + 1));
+            s.set_reserved_space(100);
+            s.record_disk_write(10 * (i + 1));
+            s.record_disk_read(1);
+            s.record_disk_flush();
+        }
+        // 99 fast writes on one disk cache and 1 slow write on the other.
+        for _ in 0..99 {
+            stats[0].record_disk_write_latency(Duration::from_micros(10));
+        }
+        stats[1].record_disk_write_latency(Duration::from_millis(10));
+
+        let merged = StatisticsSnapshot::merge(&stats.iter().map(|s| s.snapshot()).collect::<Vec<_>>());
+        assert_eq!(merged.disk_write_bytes, 30);
+        assert_eq!(merged.disk_read_bytes, 2);
+        assert_eq!(merged.disk_write_ios, 2);
+        assert_eq!(merged.disk_read_ios, 2);
+        assert_eq!(merged.disk_flush_ios, 2);
+        assert_eq!(merged.free_space, 3000);
+        assert_eq!(merged.reserved_space, 200);
+
+        // Percentiles are of all ios, not averaged among the disk caches.
+        let p50 = merged.write_latency_percentile(50.0).unwrap();
+        let p100 = merged.write_latency_percentile(100.0).unwrap();
+        assert!(p50 < Duration::from_micros(11), "p50: {p50:?}");
+        assert!(p100 >= Duration::from_millis(10), "p100: {p100:?}");
+        assert_eq!(merged.read_latency_percentile(50.0), None);
+
+        assert_eq!(StatisticsSnapshot::merge(&[]), StatisticsSnapshot::default());
+        assert_eq!(StatisticsSnapshot::merge(&[stats[0].snapshot()]), stats[0].snapshot());
+    }
 }
@@ -20,6 +20,7 @@ use std::{
 };
 
 use auto_enums::auto_enum;
+use bytes::Bytes;
 use foyer_common::{
     code::{StorageKey, StorageValue},
     properties::Properties,
@@ -92,6 +93,9 @@ pub trait Selector: Send + Sync + 'static + Debug {
     type Properties: Properties;
 
     fn select(&self, piece: &Piece<Self::Key, Self::Value, Self::Properties>, estimated_size: usize) -> Selection;
+
+    /// Select the engine for an entry whose key and value are already serialized into `size` bytes.
+    fn select_raw(&self, hash: u64, size: usize) -> Selection;
 }
 
 pub struct Either<K, V, P, SL, SR, SE>
@@ -147,6 +151,26 @@ where
     }
 }
 
+impl<K, V, P, SL, SR, SE> Either<K, V, P, SL, SR, SE>
+where
+    K: StorageKey,
+    V: StorageValue,
+    P: Properties,
+    SL: Storage<Key = K, Value = V, Properties = P>,
+    SR: Storage<Key = K, Value = V, Properties = P>,
+    SE: Selector<Key = K, Value = V, Properties = P>,
+{
+    /// Get the left engine.
+    pub fn left(&self) -> &SL {
+        &self.left
+    }
+
+    /// Get the right engine.
+    pub fn right(&self) -> &SR {
+        &self.right
+    }
+}
+
 impl<K, V, P, SL, SR, SE> Storage for Either<K, V, P, SL, SR, SE>
 where
     K: StorageKey,
@@ -193,6 +217,20 @@ where
         }
     }
 
+    fn enqueue_raw(&self, hash: u64, key: Bytes, value: Bytes) -> bool {
+        // Only delete from the engine not selected, an entry the selected engine skips must not wipe the other copy.
+        match self.selector.select_raw(hash, key.len() + value.len()) {
+            Selection::Left => {
+                self.right.delete(hash);
+                self.left.enqueue_raw(hash, key, value)
+            }
+            Selection::Right => {
+                self.left.delete(hash);
+                self.right.enqueue_raw(hash, key, value)
+            }
+        }
+    }
+
     #[auto_enum(Future)]
     fn load(&self, hash: u64) -> impl Future<Output = Result<Load<Self::Key, Self::Value>>> + Send + 'static {
         let fleft = self.left.load(hash);
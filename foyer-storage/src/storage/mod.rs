@@ -17,11 +17,13 @@ pub mod noop;
 
 use std::{fmt::Debug, future::Future, sync::Arc};
 
+use bytes::Bytes;
 use foyer_common::{
     code::{StorageKey, StorageValue},
     properties::Properties,
 };
 use foyer_memory::Piece;
+use futures_util::future::join_all;
 
 use crate::{error::Result, Load, Statistics, Throttle};
 
@@ -49,6 +51,14 @@ pub trait Storage: Send + Sync + 'static + Clone + Debug {
     /// Push a in-memory cache piece to the disk cache write queue.
     fn enqueue(&self, piece: Piece<Self::Key, Self::Value, Self::Properties>, estimated_size: usize);
 
+    /// Push an already serialized entry to the disk cache write queue.
+    ///
+    /// `key` and `value` must be encoded the same way as [`StorageKey`] and [`StorageValue`] are encoded by the disk
+    /// cache, the disk cache writes them as they are.
+    ///
+    /// Returns `false` if the disk cache doesn't support raw entries, and the entry is ignored.
+    fn enqueue_raw(&self, hash: u64, key: Bytes, value: Bytes) -> bool;
+
     /// Load a cache entry from the disk cache.
     ///
     /// `load` may return a false-positive result on entry key hash collision. It's the caller's responsibility to
@@ -56,6 +66,18 @@ pub trait Storage: Send + Sync + 'static + Clone + Debug {
     #[must_use]
     fn load(&self, hash: u64) -> impl Future<Output = Result<Load<Self::Key, Self::Value>>> + Send + 'static;
 
+    /// Load the cache entries of the given hashes from the disk cache, the results are in the order of the hashes.
+    ///
+    /// The default implementation loads the entries concurrently with [`Storage::load`]. The same as `load`, the
+    /// results may be false-positive on entry key hash collision.
+    #[must_use]
+    fn load_many(
+        &self,
+        hashes: &[u64],
+    ) -> impl Future<Output = Vec<Result<Load<Self::Key, Self::Value>>>> + Send + 'static {
+        join_all(hashes.iter().map(|&hash| self.load(hash)).collect::<Vec<_>>())
+    }
+
     /// Delete the cache entry with the given key from the disk cache.
     fn delete(&self, hash: u64);
 
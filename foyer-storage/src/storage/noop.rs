@@ -19,6 +19,7 @@ use std::{
     sync::Arc,
 };
 
+use bytes::Bytes;
 use foyer_common::{
     code::{StorageKey, StorageValue},
     properties::Properties,
@@ -91,6 +92,10 @@ where
 
     fn enqueue(&self, _piece: Piece<Self::Key, Self::Value, Self::Properties>, _estimated_size: usize) {}
 
+    fn enqueue_raw(&self, _hash: u64, _key: Bytes, _value: Bytes) -> bool {
+        false
+    }
+
     fn load(&self, _: u64) -> impl Future<Output = Result<Load<Self::Key, Self::Value>>> + Send + 'static {
         ready(Ok(Load::Miss))
     }
@@ -12,18 +12,36 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{borrow::Cow, fmt::Debug, hash::Hash, marker::PhantomData, sync::Arc, time::Instant};
+use std::{
+    borrow::Cow,
+    cmp::Reverse,
+    collections::{hash_map::Entry as HashMapEntry, HashMap},
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use bytes::Bytes;
 use equivalent::Equivalent;
 use foyer_common::{
     bits,
     code::{HashBuilder, StorageKey, StorageValue},
     metrics::Metrics,
-    properties::{Populated, Properties},
+    properties::{Age, Populated, Properties, Source},
     runtime::BackgroundShutdownRuntime,
 };
 use foyer_memory::{Cache, Piece};
-use tokio::runtime::Handle;
+use futures_core::Stream;
+use futures_util::{future::join_all, FutureExt};
+use itertools::Itertools;
+use parking_lot::Mutex;
+use tokio::{
+    runtime::Handle,
+    sync::{oneshot, OwnedRwLockWriteGuard},
+};
 
 #[cfg(feature = "test_utils")]
 use crate::test_utils::*;
@@ -31,26 +49,38 @@ use crate::{
     compress::Compression,
     device::{
         monitor::{Monitored, MonitoredConfig},
-        DeviceConfig, RegionId,
+        DeviceConfig, MonitoredDevice, RegionId, RetryPolicy,
     },
     engine::{EngineConfig, EngineEnum, SizeSelector},
     error::{Error, Result},
-    io::PAGE,
+    event::{EventBus, StoreEvent},
+    io::{throttle::IoThrottler, PAGE},
     large::{generic::GenericLargeStorageConfig, recover::RecoverMode, tombstone::TombstoneLogConfig},
+    loader::Loader,
     picker::{
         utils::{AdmitAllPicker, FifoPicker, InvalidRatioPicker, IoThrottlerTarget, RejectAllPicker},
-        AdmissionPicker, EvictionPicker, ReinsertionPicker,
+        AdmissionPicker, EvictionPicker, ReinsertionPicker, ResidentAdmissionPicker,
     },
     runtime::Runtime,
-    serde::EntrySerializer,
-    small::generic::GenericSmallStorageConfig,
+    scan::{ScanOptions, ScanProgress},
+    serde::{EntrySerializer, RawValue},
+    small::{
+        footer::IntegrityMode,
+        generic::{GenericSmallStorage, GenericSmallStorageConfig},
+        serde::EntryHeader,
+        set::{
+            SetAlignment, SetBloomFilterRebuild, SetDeleteMode, SetEntryFields, SetId, SetLayout, SetReadOptions,
+            SetStorage,
+        },
+        set_manager::{SetResizePolicy, SetRouter, SetSizeClass},
+    },
     statistics::Statistics,
     storage::{
         either::{EitherConfig, Order},
         Storage,
     },
     ChainedAdmissionPickerBuilder, Dev, DevExt, DirectFileDeviceOptions, DirectFsDeviceOptions, IoThrottlerPicker,
-    Pick, Throttle,
+    MemoryDeviceOptions, Pick, Throttle,
 };
 
 /// Load result.
@@ -71,6 +101,29 @@ pub enum Load<K, V> {
     Miss,
 }
 
+/// Where an entry lives in the hybrid cache, see [`Store::locate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryLocation {
+    /// In the in-memory cache, never reported by [`Store::locate`], which only looks into the disk cache.
+    Memory,
+    /// In the write buffer of the small object disk cache, not written to the device yet.
+    Buffered,
+    /// In a set of the small object disk cache.
+    Small {
+        /// Index of the set size class of the set, see [`SmallEngineOptions::with_set_size_classes`].
+        class: usize,
+        /// The set.
+        set: SetId,
+    },
+    /// In a region of the large object disk cache.
+    Large {
+        /// The region.
+        region: RegionId,
+    },
+    /// Not cached.
+    Absent,
+}
+
 impl<K, V> Load<K, V> {
     /// Return `Some` with the entry if load success, otherwise return `None`.
     pub fn entry(self) -> Option<(K, V, Populated)> {
@@ -125,11 +178,21 @@ where
     P: Properties,
 {
     hasher: Arc<S>,
+    /// The hasher of the disk cache, `None` to reuse the hasher of the in-memory cache.
+    disk_hasher: Option<Arc<dyn DiskHashBuilder>>,
 
     engine: EngineEnum<K, V, P>,
+    /// The device of the engine, `None` for the noop engine.
+    device: Option<MonitoredDevice>,
 
     admission_picker: Arc<dyn AdmissionPicker>,
+    resident_admission_picker: Option<Arc<dyn ResidentAdmissionPicker>>,
     load_throttler: Option<IoThrottlerPicker>,
+    read_timeout: Option<Duration>,
+
+    loader: Option<Arc<dyn Loader<K, V>>>,
+    /// Waiters of the ongoing loader calls, used to deduplicate concurrent loads of the same key.
+    inflight: Mutex<HashMap<K, Vec<oneshot::Sender<LoaderResult<V>>>>>,
 
     compression: Compression,
 
@@ -137,6 +200,7 @@ where
 
     statistics: Arc<Statistics>,
     metrics: Arc<Metrics>,
+    events: EventBus,
 
     #[cfg(feature = "test_utils")]
     load_throttle_switch: LoadThrottleSwitch,
@@ -151,9 +215,13 @@ where
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Store")
+            .field("disk_hasher", &self.inner.disk_hasher)
             .field("engine", &self.inner.engine)
             .field("admission_picker", &self.inner.admission_picker)
+            .field("resident_admission_picker", &self.inner.resident_admission_picker)
             .field("load_throttler", &self.inner.load_throttler)
+            .field("read_timeout", &self.inner.read_timeout)
+            .field("loader", &self.inner.loader)
             .field("compression", &self.inner.compression)
             .field("runtimes", &self.inner.runtime)
             .finish()
@@ -188,17 +256,65 @@ where
         self.inner.engine.close().await
     }
 
+    /// Shut down the disk cache cooperatively, so the on-disk state is consistent and fast to reopen.
+    ///
+    /// `shutdown` takes the following steps:
+    ///
+    /// 1. Stop accepting new entries, [`Store::enqueue`] on any clone of the store is ignored afterwards.
+    /// 2. Flush the pending entries, including the write buffers of the small object disk cache (see
+    ///    [`SmallEngineOptions::with_flush_interval`]), and wait for the ongoing flush and reclaim tasks to finish.
+    /// 3. Write the index checkpoint of the small object disk cache if enabled, see
+    ///    [`SmallEngineOptions::with_index_checkpoint`].
+    /// 4. Sync the device.
+    ///
+    /// After it resolves, the store is recovered without a scan when it is built again with the index checkpoint
+    /// enabled. If the process crashes before it resolves, the store is still recoverable as after any other crash,
+    /// the sets updated after the checkpoint are verified by their checksums on the first read.
+    pub async fn shutdown(self) -> Result<()> {
+        let now = Instant::now();
+        self.inner.engine.close().await?;
+        if let Some(device) = self.inner.device.as_ref() {
+            device.flush(None).await?;
+        }
+        tracing::info!("[store]: shutdown consumes {:?}", now.elapsed());
+        Ok(())
+    }
+
     /// Return if the given key can be picked by the admission picker.
     pub fn pick(&self, hash: u64) -> Pick {
         self.inner.admission_picker.pick(&self.inner.statistics, hash)
     }
 
+    /// Calculate the hash of the key used by the disk cache, see [`StoreBuilder::with_disk_hasher`].
+    fn hash<Q>(&self, key: &Q) -> u64
+    where
+        Q: Hash + ?Sized,
+    {
+        match &self.inner.disk_hasher {
+            Some(disk_hasher) => {
+                let mut hasher = disk_hasher.build_boxed_hasher();
+                key.hash(&mut hasher);
+                hasher.finish()
+            }
+            None => self.inner.hasher.hash_one(key),
+        }
+    }
+
     /// Push a in-memory cache piece to the disk cache write queue.
+    ///
+    /// The hash of the piece is recalculated with the disk hasher if the store has one.
     pub fn enqueue(&self, piece: Piece<K, V, P>, force: bool) {
+        let piece = match self.inner.disk_hasher {
+            Some(_) => {
+                let hash = self.hash(piece.key());
+                piece.with_hash(hash)
+            }
+            None => piece,
+        };
         tracing::trace!(hash = piece.hash(), "[store]: enqueue piece");
         let now = Instant::now();
 
-        if force || self.pick(piece.hash()).admitted() {
+        if force || (self.pick(piece.hash()).admitted() && self.pick_resident(&piece).admitted()) {
             let estimated_size = EntrySerializer::estimated_size(piece.key(), piece.value());
             self.inner.engine.enqueue(piece, estimated_size);
         }
@@ -210,12 +326,56 @@ where
             .record(now.elapsed().as_secs_f64());
     }
 
+    /// Consult the resident admission picker for the entries populated from the disk cache.
+    ///
+    /// Entries from the outer system may be updates of existing keys and are always admitted.
+    fn pick_resident(&self, piece: &Piece<K, V, P>) -> Pick {
+        match (&self.inner.resident_admission_picker, piece.properties().source()) {
+            (Some(picker), Some(Source::Populated(_))) => picker.pick(
+                &self.inner.statistics,
+                piece.hash(),
+                self.inner.engine.may_contains(piece.hash()),
+            ),
+            _ => Pick::Admit,
+        }
+    }
+
+    /// Push an already serialized entry to the disk cache write queue.
+    ///
+    /// The entry bypasses the serializer. `hash` must be the hash of the key calculated by the disk hasher of the store (see
+    /// [`StoreBuilder::with_disk_hasher`]), and
+    /// `key` and `value` must be encoded the same way [`StorageKey`] and [`StorageValue`] are encoded, or the entry
+    /// cannot be loaded later.
+    ///
+    /// Only the small object disk cache supports raw entries for now. Returns `false` if the entry is ignored because
+    /// the engine doesn't support raw entries.
+    pub fn enqueue_raw(&self, hash: u64, key: Bytes, value: Bytes, force: bool) -> bool {
+        tracing::trace!(hash, "[store]: enqueue raw entry");
+        let now = Instant::now();
+
+        let enqueued = if force || self.pick(hash).admitted() {
+            self.inner.engine.enqueue_raw(hash, key, value)
+        } else {
+            true
+        };
+
+        self.inner.metrics.storage_enqueue.increase(1);
+        self.inner
+            .metrics
+            .storage_enqueue_duration
+            .record(now.elapsed().as_secs_f64());
+
+        enqueued
+    }
+
     /// Load a cache entry from the disk cache.
+    ///
+    /// Returns [`Error::Timeout`] if the load exceeds the read timeout, see [`StoreBuilder::with_read_timeout`].
     pub async fn load<Q>(&self, key: &Q) -> Result<Load<K, V>>
     where
         Q: Hash + Equivalent<K> + ?Sized + Send + Sync + 'static,
     {
-        let hash = self.inner.hasher.hash_one(key);
+        let hash = self.hash(key);
 
         #[cfg(feature = "test_utils")]
         if self.inner.load_throttle_switch.is_throttled() {
@@ -237,7 +397,16 @@ where
         }
 
         let future = self.inner.engine.load(hash);
-        match self.inner.runtime.read().spawn(future).await.unwrap() {
+        let handle = self.inner.runtime.read().spawn(future);
+        let res = match self.inner.read_timeout {
+            // The spawned load keeps running on timeout, its result is discarded when it completes.
+            Some(timeout) => match tokio::time::timeout(timeout, handle).await {
+                Ok(res) => res.unwrap(),
+                Err(_) => return Err(Error::Timeout(timeout)),
+            },
+            None => handle.await.unwrap(),
+        };
+        match res {
             Ok(Load::Entry {
                 key: k,
                 value: v,
@@ -253,15 +422,281 @@ where
         }
     }
 
+    /// Load a cache entry from the disk cache as [`Store::load`], but keep the value as it is written instead of
+    /// decoding it as `V`.
+    ///
+    /// The raw value carries the compression it was written with, see [`RawValue::compression`]. It can be
+    /// decoded as any value type with [`RawValue::decode`], or decompressed with [`RawValue::decompress`] for a
+    /// custom decoder. The load is never throttled.
+    ///
+    /// This enables a schema migration of the values in place:
+    ///
+    /// 1. Open the store written with the old value type with the new value type `V`, the key type unchanged.
+    /// 2. For each key to migrate, load the raw value with [`Store::load_raw`] and decode it with the old value type or
+    ///    a versioned decoder.
+    /// 3. Convert the value to `V` and write it back with [`Store::enqueue`], which overrides the old entry.
+    ///
+    /// Until an entry is migrated, loading it with [`Store::load`] may fail to decode the value.
+    ///
+    /// Returns [`Error::Timeout`] if the load exceeds the read timeout, see [`StoreBuilder::with_read_timeout`].
+    pub async fn load_raw<Q>(&self, key: &Q) -> Result<Load<K, RawValue>>
+    where
+        Q: Hash + Equivalent<K> + ?Sized + Send + Sync + 'static,
+    {
+        let hash = self.hash(key);
+
+        let future = match &self.inner.engine {
+            EngineEnum::Noop(_) => return Ok(Load::Miss),
+            EngineEnum::Large(large) => large.load_raw(hash).boxed(),
+            EngineEnum::Small(small) => small.load_raw(hash).boxed(),
+            // Same order as loads, see `Engine::MIXED_LOAD_ORDER`.
+            EngineEnum::Mixed(mixed) => {
+                let large = mixed.right().load_raw(hash);
+                let small = mixed.left().load_raw(hash);
+                async move {
+                    match large.await? {
+                        Load::Miss => small.await,
+                        load => Ok(load),
+                    }
+                }
+                .boxed()
+            }
+        };
+        let handle = self.inner.runtime.read().spawn(future);
+        let res = match self.inner.read_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, handle).await {
+                Ok(res) => res.unwrap(),
+                Err(_) => return Err(Error::Timeout(timeout)),
+            },
+            None => handle.await.unwrap(),
+        };
+        match res? {
+            Load::Entry {
+                key: k,
+                value,
+                populated,
+            } if key.equivalent(&k) => Ok(Load::Entry {
+                key: k,
+                value,
+                populated,
+            }),
+            Load::Entry { .. } | Load::Miss => Ok(Load::Miss),
+            Load::Throttled => Ok(Load::Throttled),
+        }
+    }
+
+    /// Get the value of the given key from the disk cache, or from the loader on disk cache miss.
+    ///
+    /// The value loaded from the loader is inserted into the disk cache, and concurrent misses of the same key call
+    /// the loader only once. Without a loader, `Ok(None)` is returned on disk cache miss.
+    ///
+    /// See [`StoreBuilder::with_loader`].
+    pub async fn get(&self, key: &K) -> Result<Option<V>>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        if let Load::Entry { value, .. } = self.load(key).await? {
+            return Ok(Some(value));
+        }
+        self.fetch(key).await
+    }
+
+    /// Get the values of the given keys from the disk cache, or from the loader on disk cache miss, the results are in
+    /// the order of the keys.
+    ///
+    /// The same as [`Store::get`], but the keys missed by the memory cache are loaded from the disk cache in a batch.
+    /// With the small object disk cache, the keys routed to the same set share one set read, and the sets are read
+    /// concurrently. The read timeout applies to the whole batch. The keys missed by the disk cache are fetched from
+    /// the loader concurrently, if there is one.
+    pub async fn get_many(&self, keys: &[K]) -> Vec<Result<Option<V>>>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let mut results = keys.iter().map(|_| None).collect::<Vec<Option<Result<Option<V>>>>>();
+
+        // Throttled keys skip the disk cache, they are fetched from the loader as misses.
+        let mut loads = vec![];
+        for (i, key) in keys.iter().enumerate() {
+            let hash = self.hash(key);
+
+            #[cfg(feature = "test_utils")]
+            if self.inner.load_throttle_switch.is_throttled() {
+                continue;
+            }
+
+            if let Some(throttler) = self.inner.load_throttler.as_ref() {
+                if let Pick::Throttled(_) = throttler.pick(&self.inner.statistics, hash) {
+                    continue;
+                }
+            }
+            loads.push((i, hash));
+        }
+
+        if !loads.is_empty() {
+            let future = self
+                .inner
+                .engine
+                .load_many(&loads.iter().map(|(_, hash)| *hash).collect::<Vec<_>>());
+            let handle = self.inner.runtime.read().spawn(future);
+            let res = match self.inner.read_timeout {
+                // The spawned load keeps running on timeout, its result is discarded when it completes.
+                Some(timeout) => match tokio::time::timeout(timeout, handle).await {
+                    Ok(res) => Ok(res.unwrap()),
+                    Err(_) => Err(timeout),
+                },
+                None => Ok(handle.await.unwrap()),
+            };
+            match res {
+                Ok(res) => {
+                    for ((i, _), r) in loads.into_iter().zip(res) {
+                        results[i] = match r {
+                            Ok(Load::Entry { key, value, .. }) if keys[i].equivalent(&key) => Some(Ok(Some(value))),
+                            Ok(_) => None,
+                            Err(e) => Some(Err(e)),
+                        };
+                    }
+                }
+                Err(timeout) => loads
+                    .into_iter()
+                    .for_each(|(i, _)| results[i] = Some(Err(Error::Timeout(timeout)))),
+            }
+        }
+
+        join_all(results.into_iter().enumerate().map(|(i, r)| async move {
+            match r {
+                Some(r) => r,
+                None => self.fetch(&keys[i]).await,
+            }
+        }))
+        .await
+    }
+
+    /// Fetch the value of the given key from the loader on disk cache miss, see [`Store::get`].
+    async fn fetch(&self, key: &K) -> Result<Option<V>>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let Some(loader) = self.inner.loader.as_ref() else {
+            return Ok(None);
+        };
+
+        loop {
+            let rx = match self.inner.inflight.lock().entry(key.clone()) {
+                HashMapEntry::Occupied(mut o) => {
+                    let (tx, rx) = oneshot::channel();
+                    o.get_mut().push(tx);
+                    rx
+                }
+                HashMapEntry::Vacant(v) => {
+                    v.insert(vec![]);
+                    break;
+                }
+            };
+            match rx.await {
+                Ok(res) => return res.map_err(|e| anyhow::anyhow!(e).into()),
+                // The leader is cancelled, retry.
+                Err(_) => continue,
+            }
+        }
+
+        // Remove the inflight record even if the future is cancelled, so the waiters can retry.
+        let guard = InflightGuard {
+            inflight: &self.inner.inflight,
+            key: Some(key),
+        };
+
+        let res = loader.load(key).await;
+
+        if let Ok(Some(value)) = res.as_ref() {
+            let hash = self.hash(key);
+            self.enqueue(Piece::detached(key.clone(), value.clone(), hash, P::default()), false);
+        }
+
+        for tx in guard.take() {
+            let _ = tx.send(res.as_ref().map(|v| v.clone()).map_err(|e| e.to_string()));
+        }
+
+        res.map_err(Error::from)
+    }
+
+    /// Locate the entry with the given key in the disk cache without deserializing it, e.g. to audit where the entries
+    /// are placed.
+    ///
+    /// The large object disk cache is looked up in its in-memory index. The small object disk cache is looked up in
+    /// the write buffers, then in the set the key is routed to, which is skipped by the bloom filter or read and
+    /// scanned for the hash without deserializing any entry. Cheaper than [`Store::load`], but like
+    /// [`Store::may_contains`], an entry with a colliding hash is reported as the entry of the key.
+    pub async fn locate<Q>(&self, key: &Q) -> Result<EntryLocation>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = self.hash(key);
+        match &self.inner.engine {
+            EngineEnum::Noop(_) => Ok(EntryLocation::Absent),
+            EngineEnum::Large(large) => Ok(large.locate(hash)),
+            EngineEnum::Small(small) => small.locate(hash).await,
+            // Same order as loads, see `Engine::MIXED_LOAD_ORDER`.
+            EngineEnum::Mixed(mixed) => match mixed.right().locate(hash) {
+                EntryLocation::Absent => mixed.left().locate(hash).await,
+                location => Ok(location),
+            },
+        }
+    }
+
     /// Delete the cache entry with the given key from the disk cache.
     pub fn delete<'a, Q>(&'a self, key: &'a Q)
     where
         Q: Hash + Equivalent<K> + ?Sized,
     {
-        let hash = self.inner.hasher.hash_one(key);
+        let hash = self.hash(key);
         self.inner.engine.delete(hash)
     }
 
+    /// Remove the entry with the given key from the device, return whether the entry was cached.
+    ///
+    /// Unlike [`Store::delete`], which may only mark the entry as a tombstone (see [`SetDeleteMode`]), the set of the
+    /// entry is compacted without it and rewritten, and the device is flushed before returning, so the bytes of the
+    /// entry are no longer part of the set on the device. The writes buffered before are flushed first.
+    ///
+    /// Note: the compaction moves the following entries over the removed one, but the bytes past the new end of the
+    /// set are overwritten only when the set grows again, so a part of the removed entry may persist on the device
+    /// until then. Use [`Store::secure_remove`] to zero them. Copies kept by the device itself, e.g. by the flash
+    /// translation layer of an SSD, are out of reach.
+    ///
+    /// Returns an error if the disk cache has no small object disk cache or has a large object disk cache, which only
+    /// drops the entry from its index.
+    pub async fn remove<Q>(&self, key: &Q) -> Result<bool>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_with(key, false).await
+    }
+
+    /// Same as [`Store::remove`], but also zeroes the unused space of the set, so no byte of the removed entry is left
+    /// in the set on the device.
+    pub async fn secure_remove<Q>(&self, key: &Q) -> Result<bool>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_with(key, true).await
+    }
+
+    async fn remove_with<Q>(&self, key: &Q, zero: bool) -> Result<bool>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = self.hash(key);
+        match &self.inner.engine {
+            EngineEnum::Small(small) => small.remove(hash, zero).await,
+            _ => Err(Error::InvalidConfig(
+                "only the small object disk cache supports removing entries from the device".to_string(),
+            )),
+        }
+    }
+
     /// Check if the disk cache contains a cached entry with the given key.
     ///
     /// `contains` may return a false-positive result if there is a hash collision with the given key.
@@ -269,7 +704,7 @@ where
     where
         Q: Hash + Equivalent<K> + ?Sized,
     {
-        let hash = self.inner.hasher.hash_one(key);
+        let hash = self.hash(key);
         self.inner.engine.may_contains(hash)
     }
 
@@ -298,6 +733,35 @@ where
         self.inner.engine.wait().await
     }
 
+    /// Bring the disk cache to a quiescent and durable state, and keep it there until the returned guard is dropped.
+    ///
+    /// `quiesce` waits for the pending writes to finish (see [`Store::wait`]), blocks new writes to the device, and
+    /// syncs the device. While the guard is held, the on-disk state doesn't change, so it can be snapshotted, e.g. with
+    /// `cp --reflink` or LVM snapshots. The on-disk state is crash-consistent, the same as the state recovered after a
+    /// crash at that point.
+    ///
+    /// While quiesced:
+    ///
+    /// - [`Store::load`] and [`Store::get`] still work. But loading from a set of the small object disk cache that is
+    ///   being updated waits until the update is written.
+    /// - [`Store::enqueue`] and [`Store::delete`] return immediately as usual, the writes are buffered in memory and
+    ///   written after the guard is dropped. When the buffers are full, the new entries are dropped, the same as when
+    ///   the device is too slow. The large object disk cache applies deletions to its in-memory index immediately, the
+    ///   small object disk cache queues them with the writes.
+    /// - [`Store::wait`], [`Store::close`] and [`Store::destroy`] wait until the guard is dropped.
+    ///
+    /// Entries written after `quiesce` is called and before the writes are blocked may or may not be in the snapshot.
+    /// Quiescing the noop disk cache is a no-op.
+    pub async fn quiesce(&self) -> Result<QuiesceGuard> {
+        let Some(device) = self.inner.device.as_ref() else {
+            return Ok(QuiesceGuard { _gate: None });
+        };
+        self.inner.engine.wait().await;
+        let gate = device.quiesce().await;
+        device.flush(None).await?;
+        Ok(QuiesceGuard { _gate: Some(gate) })
+    }
+
     /// Return the estimated serialized size of the entry.
     pub fn entry_estimated_size(&self, key: &K, value: &V) -> usize {
         EntrySerializer::estimated_size(key, value)
@@ -309,12 +773,230 @@ where
         &self.inner.load_throttle_switch
     }
 
+    /// Resize the sets in use of the small object disk cache to `sets` sets.
+    ///
+    /// The sets are split or merged one at a time, while loads and updates of the disk cache wait for each step. The
+    /// entries are moved to the sets the new routing points to, merging sets may wipe the oldest entries of the merged
+    /// set if they don't fit. `sets` must be in `[initial sets, all sets - 1]`, see
+    /// [`SmallEngineOptions::with_initial_sets`].
+    ///
+    /// Returns an error if the disk cache has no small object disk cache or it has multiple set size classes.
+    pub async fn resize_sets(&self, sets: usize) -> Result<()> {
+        match &self.inner.engine {
+            EngineEnum::Small(small) => small.resize_sets(sets).await,
+            EngineEnum::Mixed(mixed) => mixed.left().resize_sets(sets).await,
+            _ => Err(Error::InvalidConfig(
+                "only the small object disk cache supports resizing sets".to_string(),
+            )),
+        }
+    }
+
+    /// Write the in-memory index of the small object disk cache to the index checkpoint.
+    ///
+    /// The checkpoint is restored when the store is built again, see [`SmallEngineOptions::with_index_checkpoint`].
+    ///
+    /// Returns an error if the disk cache has no small object disk cache or the index checkpoint is not enabled.
+    pub async fn checkpoint_index(&self) -> Result<()> {
+        match &self.inner.engine {
+            EngineEnum::Small(small) => small.checkpoint_index().await,
+            EngineEnum::Mixed(mixed) => mixed.left().checkpoint_index().await,
+            _ => Err(Error::InvalidConfig(
+                "only the small object disk cache supports index checkpoints".to_string(),
+            )),
+        }
+    }
+
+    /// Whether the bloom filters of the small object disk cache are preloaded on open, see
+    /// [`SmallEngineOptions::with_bloom_filter_preload`].
+    ///
+    /// Returns `false` if the disk cache has no small object disk cache.
+    pub fn is_bloom_filter_preload_enabled(&self) -> bool {
+        match &self.inner.engine {
+            EngineEnum::Small(small) => small.is_bloom_filter_preload_enabled(),
+            EngineEnum::Mixed(mixed) => mixed.left().is_bloom_filter_preload_enabled(),
+            _ => false,
+        }
+    }
+
+    /// Report how full each set of the small object disk cache is, in the order of the set ids.
+    ///
+    /// Each item is a set in use and the ratio of its data length to its data capacity, range: [0 ~ 1]. Sets that are
+    /// always full evict entries to make room, sets that are mostly empty are cold, a skew of them suggests the keys
+    /// are not routed evenly, see [`SmallEngineOptions::with_set_router`]. Only the set headers are read, so the
+    /// report is cheap enough to be taken periodically.
+    ///
+    /// Returns an error if the disk cache has no small object disk cache or it has multiple set size classes.
+    pub async fn fill_report(&self) -> Result<Vec<(SetId, f64)>> {
+        match &self.inner.engine {
+            EngineEnum::Small(small) => small.fill_report().await,
+            EngineEnum::Mixed(mixed) => mixed.left().fill_report().await,
+            _ => Err(Error::InvalidConfig(
+                "only the small object disk cache supports fill reports".to_string(),
+            )),
+        }
+    }
+
+    /// Load up to `n` of the most recently written entries from the small object disk cache to the in-memory cache,
+    /// e.g. to restore the hit ratio faster after a restart. Returns the count of the loaded entries.
+    ///
+    /// The entries that are likely hot are picked first:
+    ///
+    /// 1. The sets are visited from the most recently updated one, by the timestamps in the set headers.
+    /// 2. The entries of each set are visited from the most recently appended one.
+    ///
+    /// Entries already in the in-memory cache are skipped. Loading stops once `n` entries are loaded or the in-memory
+    /// cache is full, so the loaded entries don't evict each other. The entries are inserted as populated young
+    /// entries, as if they were just loaded from the disk cache.
+    ///
+    /// Returns an error if the disk cache has no small object disk cache, the entries of the large object disk cache
+    /// are not loaded.
+    pub async fn warm_up(&self, memory: &Cache<K, V, S, P>, n: usize) -> Result<usize> {
+        let small = match &self.inner.engine {
+            EngineEnum::Small(small) => small,
+            EngineEnum::Mixed(mixed) => mixed.left(),
+            _ => {
+                return Err(Error::InvalidConfig(
+                    "only the small object disk cache supports warming up".to_string(),
+                ))
+            }
+        };
+
+        let mut sets = small.set_timestamps().await?;
+        sets.sort_by_key(|(_, _, timestamp)| Reverse(*timestamp));
+
+        let mut loaded = 0;
+        for (class, sid, _) in sets {
+            if loaded >= n || memory.usage() >= memory.capacity() {
+                break;
+            }
+            let Some(set) = small.scan_set(class, sid).await? else {
+                continue;
+            };
+            let entries = set.entries::<K, V>().collect::<Result<Vec<_>>>()?;
+            for (key, value) in entries.into_iter().rev() {
+                if loaded >= n || memory.usage() >= memory.capacity() {
+                    break;
+                }
+                if memory.contains(&key) {
+                    continue;
+                }
+                let properties = P::default().with_source(Source::Populated(Populated { age: Age::Young }));
+                memory.insert_with_properties(key, value, properties);
+                loaded += 1;
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Iterate the entries of the small object disk cache in the ascending order of their hashes, e.g. to merge-join
+    /// them against another dataset sorted by the same hashes.
+    ///
+    /// Entries of a set are in the insertion order, and the entries of any hash range are spread over all sets. So all
+    /// sets in use are read and their entries deserialized and sorted before the first entry is yielded, then the
+    /// sets are merged with a k-way merge. The memory cost is about the size of the data in use of the small object
+    /// disk cache, i.e. up to the set size times the count of the sets in use, plus the deserialization overhead.
+    /// Entries with the same hash are yielded in the insertion order within a set.
+    ///
+    /// The sets are read one at a time like [`Store::scan_with_options`] without throttling, entries written after
+    /// their sets are read are not seen.
+    ///
+    /// Returns an error if the disk cache has no small object disk cache, the entries of the large object disk cache
+    /// are not iterated.
+    pub async fn iter_sorted(&self) -> Result<impl Iterator<Item = (u64, K, V)>> {
+        let small = match &self.inner.engine {
+            EngineEnum::Small(small) => small,
+            EngineEnum::Mixed(mixed) => mixed.left(),
+            _ => {
+                return Err(Error::InvalidConfig(
+                    "only the small object disk cache supports sorted iteration".to_string(),
+                ))
+            }
+        };
+
+        let mut sets = vec![];
+        for (class, sid) in small.set_ids() {
+            let Some(set) = small.scan_set(class, sid).await? else {
+                continue;
+            };
+            let mut entries = set.entries_with_hash::<K, V>().collect::<Result<Vec<_>>>()?;
+            // Stable, so the entries with the same hash keep the insertion order.
+            entries.sort_by_key(|(hash, _, _)| *hash);
+            sets.push(entries);
+        }
+        Ok(sets.into_iter().kmerge_by(|(a, _, _), (b, _, _)| a < b))
+    }
+
+    /// Scan all entries of the disk cache with the default options, see [`Store::scan_with_options`].
+    pub fn scan_with_progress(&self) -> impl Stream<Item = Result<ScanProgress<K, V>>> + Send + 'static {
+        self.scan_with_options(ScanOptions::default())
+    }
+
+    /// Scan all entries of the disk cache set by set, e.g. for a backup or an export, yielding the progress after
+    /// each set.
+    ///
+    /// The scan is read-only. The sets are read one at a time under the set locks like loads, the set cache is left
+    /// untouched, and the reads are throttled with [`ScanOptions::with_throughput`] and [`ScanOptions::with_iops`] to
+    /// coexist with the foreground traffic. Entries written to the sets already scanned are not seen, and the sets no
+    /// longer in use after resizing are skipped. An interrupted scan can be resumed with [`ScanOptions::with_start`].
+    /// The stream ends after the first error.
+    ///
+    /// Only the small object disk cache supports scanning for now, the stream yields an error otherwise.
+    pub fn scan_with_options(
+        &self,
+        options: ScanOptions,
+    ) -> impl Stream<Item = Result<ScanProgress<K, V>>> + Send + 'static {
+        let state = match &self.inner.engine {
+            EngineEnum::Small(small) => {
+                let ids = small.set_ids();
+                Ok(ScanState {
+                    small: small.clone(),
+                    sets_done: options.start.min(ids.len()),
+                    ids,
+                    throttler: IoThrottler::new(options.throughput, options.iops),
+                    entries: options.entries,
+                    entries_seen: 0,
+                    bytes_seen: 0,
+                })
+            }
+            _ => Err(Error::InvalidConfig(
+                "only the small object disk cache supports scanning".to_string(),
+            )),
+        };
+        futures_util::stream::unfold(Some(state), |state| async move {
+            let mut state = match state? {
+                Ok(state) => state,
+                Err(e) => return Some((Err(e), None)),
+            };
+            match state.next().await {
+                Ok(Some(progress)) => Some((Ok(progress), Some(Ok(state)))),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
+
+    /// Subscribe to the lifecycle events of the disk cache emitted from now on, see [`StoreEvent`].
+    ///
+    /// Unlike the metrics, the events are discrete, e.g. for alerting and audit logs. Each subscriber buffers up to
+    /// 1024 events, a subscriber that falls behind misses the oldest events instead of backing up the disk cache, and
+    /// receives a [`StoreEvent::Lagged`] with the count of the missed events.
+    pub fn subscribe(&self) -> impl Stream<Item = StoreEvent> + Send + 'static {
+        self.inner.events.subscribe()
+    }
+
     /// If the disk cache is enabled.
     pub fn is_enabled(&self) -> bool {
         !matches! { self.inner.engine, EngineEnum::Noop(_)}
     }
 }
 
+/// Guard returned by [`Store::quiesce`], writes to the disk cache are blocked until it is dropped.
+#[must_use = "writes are unblocked once the guard is dropped"]
+#[derive(Debug)]
+pub struct QuiesceGuard {
+    _gate: Option<OwnedRwLockWriteGuard<()>>,
+}
+
 /// The configurations for the device.
 #[derive(Debug, Clone)]
 pub enum DeviceOptions {
@@ -336,6 +1018,12 @@ impl From<DirectFsDeviceOptions> for DeviceOptions {
     }
 }
 
+impl From<MemoryDeviceOptions> for DeviceOptions {
+    fn from(options: MemoryDeviceOptions) -> Self {
+        Self::DeviceConfig(options.into())
+    }
+}
+
 /// [`Engine`] controls the ratio of the large object disk cache and the small object disk cache.
 ///
 /// If [`Engine::Mixed`] is used, it will use the `Either` engine
@@ -425,6 +1113,109 @@ pub enum RuntimeOptions {
     },
 }
 
+type LoaderResult<V> = std::result::Result<Option<V>, String>;
+
+struct InflightGuard<'a, K, V>
+where
+    K: StorageKey,
+{
+    inflight: &'a Mutex<HashMap<K, Vec<oneshot::Sender<LoaderResult<V>>>>>,
+    /// Taken on [`InflightGuard::take`], so the drop doesn't remove the record of a following leader of the key.
+    key: Option<&'a K>,
+}
+
+impl<K, V> InflightGuard<'_, K, V>
+where
+    K: StorageKey,
+{
+    fn take(mut self) -> Vec<oneshot::Sender<LoaderResult<V>>> {
+        let key = self.key.take().unwrap();
+        self.inflight.lock().remove(key).unwrap_or_default()
+    }
+}
+
+impl<K, V> Drop for InflightGuard<'_, K, V>
+where
+    K: StorageKey,
+{
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.inflight.lock().remove(key);
+        }
+    }
+}
+
+/// Hash builder of the disk cache with the hasher type erased, see [`StoreBuilder::with_disk_hasher`].
+trait DiskHashBuilder: Send + Sync + Debug + 'static {
+    fn build_boxed_hasher(&self) -> Box<dyn Hasher>;
+}
+
+impl<H> DiskHashBuilder for H
+where
+    H: HashBuilder + Debug,
+{
+    fn build_boxed_hasher(&self) -> Box<dyn Hasher> {
+        Box::new(self.build_hasher())
+    }
+}
+
+/// State of a full scan of the disk cache, see [`Store::scan_with_options`].
+struct ScanState<K, V, P>
+where
+    K: StorageKey,
+    V: StorageValue,
+    P: Properties,
+{
+    small: GenericSmallStorage<K, V, P>,
+    ids: Vec<(usize, SetId)>,
+    throttler: IoThrottler,
+    entries: bool,
+    sets_done: usize,
+    entries_seen: usize,
+    bytes_seen: usize,
+}
+
+impl<K, V, P> ScanState<K, V, P>
+where
+    K: StorageKey,
+    V: StorageValue,
+    P: Properties,
+{
+    /// Scan the next set, returns `None` once all sets are scanned.
+    async fn next(&mut self) -> Result<Option<ScanProgress<K, V>>> {
+        let Some(&(class, sid)) = self.ids.get(self.sets_done) else {
+            return Ok(None);
+        };
+
+        let wait = self.throttler.probe();
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+
+        let mut entries = vec![];
+        let mut inserted_at = vec![];
+        if let Some(set) = self.small.scan_set(class, sid).await? {
+            self.throttler.reduce(set.as_bytes().len() as f64, 1.0);
+            self.entries_seen += set.entry_count();
+            self.bytes_seen += set.len();
+            if self.entries {
+                entries = set.entries().collect::<Result<_>>()?;
+                inserted_at = set.inserted_ats().collect();
+            }
+        }
+        self.sets_done += 1;
+
+        Ok(Some(ScanProgress {
+            sets_done: self.sets_done,
+            sets_total: self.ids.len(),
+            entries_seen: self.entries_seen,
+            bytes_seen: self.bytes_seen,
+            entries,
+            inserted_at,
+        }))
+    }
+}
+
 /// The builder of the disk cache.
 pub struct StoreBuilder<K, V, S, P>
 where
@@ -442,9 +1233,19 @@ where
     runtime_config: RuntimeOptions,
 
     admission_picker: Arc<dyn AdmissionPicker>,
+    resident_admission_picker: Option<Arc<dyn ResidentAdmissionPicker>>,
+    loader: Option<Arc<dyn Loader<K, V>>>,
     compression: Compression,
     recover_mode: RecoverMode,
     flush: bool,
+    direct_io: Option<bool>,
+    large_object_threshold: usize,
+    read_timeout: Option<Duration>,
+    io_concurrency: Option<usize>,
+    io_retry: Option<RetryPolicy>,
+    reserved_space: usize,
+    serialize_buffer_budget: Option<usize>,
+    disk_hasher: Option<Arc<dyn DiskHashBuilder>>,
 }
 
 impl<K, V, S, P> Debug for StoreBuilder<K, V, S, P>
@@ -463,9 +1264,19 @@ where
             .field("engine", &self.engine)
             .field("runtime_config", &self.runtime_config)
             .field("admission_picker", &self.admission_picker)
+            .field("resident_admission_picker", &self.resident_admission_picker)
+            .field("loader", &self.loader)
             .field("compression", &self.compression)
             .field("recover_mode", &self.recover_mode)
             .field("flush", &self.flush)
+            .field("direct_io", &self.direct_io)
+            .field("large_object_threshold", &self.large_object_threshold)
+            .field("read_timeout", &self.read_timeout)
+            .field("io_concurrency", &self.io_concurrency)
+            .field("io_retry", &self.io_retry)
+            .field("reserved_space", &self.reserved_space)
+            .field("serialize_buffer_budget", &self.serialize_buffer_budget)
+            .field("disk_hasher", &self.disk_hasher)
             .finish()
     }
 }
@@ -498,9 +1309,19 @@ where
             runtime_config: RuntimeOptions::Disabled,
 
             admission_picker: Arc::<AdmitAllPicker>::default(),
+            resident_admission_picker: None,
+            loader: None,
             compression: Compression::default(),
             recover_mode: RecoverMode::default(),
             flush: false,
+            direct_io: None,
+            large_object_threshold: Engine::OBJECT_SIZE_THRESHOLD,
+            read_timeout: None,
+            io_concurrency: None,
+            io_retry: None,
+            reserved_space: 0,
+            serialize_buffer_budget: None,
+            disk_hasher: None,
         }
     }
 
@@ -510,7 +1331,16 @@ where
         self
     }
 
-    /// Enable/disable `sync` after writes.
+    /// Use a memory device with the given capacity in bytes for the disk cache store.
+    ///
+    /// The entries are kept in memory with the same format as on disk and are lost once the store is dropped. It is a
+    /// shortcut of [`StoreBuilder::with_device_options`] with [`MemoryDeviceOptions`], use the latter to set the region
+    /// size or the throttle.
+    pub fn with_memory_device(self, capacity: usize) -> Self {
+        self.with_device_options(MemoryDeviceOptions::new(capacity))
+    }
+
+    /// Enable/disable `sync` after writes.
     ///
     /// Default: `false`.
     pub fn with_flush(mut self, flush: bool) -> Self {
@@ -518,6 +1348,20 @@ where
         self
     }
 
+    /// Enable/disable direct I/O (`O_DIRECT`) of the device, overriding the option of the device options.
+    ///
+    /// Direct I/O bypasses the page cache, which requires all I/O buffers, offsets and lengths to be aligned to the
+    /// block size. foyer always uses 4 KiB aligned buffers, and the set size of the small object disk cache must be a
+    /// multiple of 4 KiB (see [`SmallEngineOptions::with_set_size`]). For devices with a larger block size or that
+    /// prefer power of two aligned I/O, align the sets with [`SmallEngineOptions::with_set_alignment`]. If the file
+    /// system doesn't support direct I/O, buffered I/O is used instead and a warning is logged.
+    ///
+    /// Default: the option of the device options, which enables direct I/O by default.
+    pub fn with_direct_io(mut self, direct_io: bool) -> Self {
+        self.direct_io = Some(direct_io);
+        self
+    }
+
     /// Set the compression algorithm of the disk cache store.
     ///
     /// Default: [`Compression::None`].
@@ -526,6 +1370,119 @@ where
         self
     }
 
+    /// Set the threshold of the estimated serialized size for routing entries to the large object disk cache.
+    ///
+    /// Only takes effect with [`Engine::Mixed`]. Entries smaller than the threshold are written to the small object
+    /// disk cache, others are written to the large object disk cache. On lookup, the large object disk cache is
+    /// checked first, because its index is in memory and a miss involves no disk operations, then the small object
+    /// disk cache (see [`Engine::MIXED_LOAD_ORDER`]).
+    ///
+    /// Entries under the threshold must fit in a set of the small object disk cache, otherwise building the store
+    /// fails.
+    ///
+    /// Default: [`Engine::OBJECT_SIZE_THRESHOLD`].
+    pub fn with_large_object_threshold(mut self, threshold: usize) -> Self {
+        self.large_object_threshold = threshold;
+        self
+    }
+
+    /// Set the deadline of loading an entry from the disk cache store.
+    ///
+    /// A load that exceeds the deadline returns [`Error::Timeout`], which the caller can treat as a miss, so a degraded
+    /// disk doesn't block the caller indefinitely. The disk read in flight cannot be cancelled, it keeps running in
+    /// the background and its result is discarded when it completes.
+    ///
+    /// Default: no deadline.
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the max count of the concurrent operations of the device.
+    ///
+    /// Reads, writes and flushes share the budget, an operation waits in the queue until an ongoing one completes
+    /// once the limit is reached. The in-flight and queued operations are exported with the
+    /// `foyer_storage_disk_io_concurrency` metric. The tombstone log of the large object disk cache is not limited.
+    ///
+    /// Default: no limit.
+    pub fn with_io_concurrency(mut self, io_concurrency: usize) -> Self {
+        self.io_concurrency = Some(io_concurrency);
+        self
+    }
+
+    /// Set the retry policy of the device operations on transient errors.
+    ///
+    /// A read, write or flush of the device failed with a retryable error is retried with exponential backoff, see
+    /// [`RetryPolicy::is_retryable`] for the retryable errors. The operation fails with the last error once the
+    /// attempts are exhausted, and right away on other errors, e.g. `ENOSPC`. Each retry is counted by the
+    /// `disk_retry` op of the `foyer_storage_inner_op_total` metric. The tombstone log of the large object disk cache
+    /// is not retried.
+    ///
+    /// Default: no retry.
+    pub fn with_io_retry(mut self, policy: RetryPolicy) -> Self {
+        self.io_retry = Some(policy);
+        self
+    }
+
+    /// Set the space in bytes of the large object disk cache reserved as the headroom for reclaiming.
+    ///
+    /// The reserved space is rounded up to whole regions. The reclaimers keep the reserved regions clean in addition
+    /// to the clean region threshold, and only the entries reinserted while reclaiming can be written to them. New
+    /// entries are rejected while the free space is not greater than the reserved space, so reclaiming never runs out
+    /// of scratch space under heavy writes. The free and reserved space are exported with [`Statistics`].
+    ///
+    /// Only the large object disk cache supports reserved space.
+    ///
+    /// Default: `0`, no reserved space.
+    pub fn with_reserved_space(mut self, bytes: usize) -> Self {
+        self.reserved_space = bytes;
+        self
+    }
+
+    /// Set the budget of the bytes of the serialized entries held in the write buffers of the disk cache store.
+    ///
+    /// The entries written to the small object disk cache with a flush interval (see
+    /// [`SmallEngineOptions::with_flush_interval`]) are serialized when they are enqueued, and held until their sets
+    /// are written. With the budget, the enqueued entries exceeding the budget wait in the flusher until the
+    /// serialized entries are released, so a burst of large entries doesn't spike the memory usage. The waiting
+    /// entries are not visible to loads until they are serialized. An entry larger than the whole budget waits for
+    /// all serialized entries to be released.
+    ///
+    /// The budget is shared by all flushers. Once an entry waits, the flushers flush the entries they hold without
+    /// waiting for the flush interval, so an entry never waits for the flush interval of another flusher.
+    ///
+    /// The usage of the budget and the waiting duration are exported with the
+    /// `foyer_storage_sodc_serialize_buffer_usage_bytes` gauge and the `serialize_buffer_wait` op of the
+    /// `foyer_storage_inner_op_duration` histogram.
+    ///
+    /// Default: `None`, no budget.
+    pub fn with_serialize_buffer_budget(mut self, bytes: usize) -> Self {
+        self.serialize_buffer_budget = Some(bytes);
+        self
+    }
+
+    /// Set the hasher of the disk cache store, independent of the hasher of the in-memory cache.
+    ///
+    /// The disk hasher calculates the hashes that route the entries to the sets of the small object disk cache and
+    /// index the entries of the large object disk cache. Writes and loads always use the same disk hasher.
+    ///
+    /// Reusing the hasher of the in-memory cache saves hashing the key again on each write, but couples the disk
+    /// layout to the in-memory cache: a fast hasher chosen for the in-memory cache may distribute keys poorly among
+    /// the sets, and changing it invalidates the entries on disk. A dedicated disk hasher, e.g. a seeded hasher for
+    /// keys chosen by untrusted clients, decouples them at the cost of hashing each written key twice.
+    ///
+    /// The disk hasher must return the same hashes across runs to recover the entries, changing it makes the entries
+    /// on disk unreachable.
+    ///
+    /// Default: the hasher of the in-memory cache.
+    pub fn with_disk_hasher<H>(mut self, hasher: H) -> Self
+    where
+        H: HashBuilder + Debug,
+    {
+        self.disk_hasher = Some(Arc::new(hasher));
+        self
+    }
+
     /// Set the recover mode for the disk cache store.
     ///
     /// See more in [`RecoverMode`].
@@ -546,6 +1503,29 @@ where
         self
     }
 
+    /// Set the resident admission picker for the disk cache store.
+    ///
+    /// The resident admission picker is consulted after the admission picker for the entries populated from the disk
+    /// cache, with the hint if the entry is probably still resident, e.g. [`crate::RejectResidentPicker`] skips re-writing
+    /// them. Updates of existing keys are not affected.
+    ///
+    /// Default: No resident admission picker.
+    pub fn with_resident_admission_picker(mut self, picker: Arc<dyn ResidentAdmissionPicker>) -> Self {
+        self.resident_admission_picker = Some(picker);
+        self
+    }
+
+    /// Set the loader for the disk cache store.
+    ///
+    /// With a loader, [`Store::get`] loads the entry from the backing source on disk cache miss and inserts it into
+    /// the disk cache, which makes the disk cache store a read-through cache.
+    ///
+    /// Default: no loader.
+    pub fn with_loader(mut self, loader: Arc<dyn Loader<K, V>>) -> Self {
+        self.loader = Some(loader);
+        self
+    }
+
     /// Configure the dedicated runtime for the disk cache store.
     pub fn with_runtime_options(mut self, runtime_options: RuntimeOptions) -> Self {
         self.runtime_config = runtime_options;
@@ -553,7 +1533,51 @@ where
     }
 
     /// Build the disk cache store with the given configuration.
-    pub async fn build(self) -> Result<Store<K, V, S, P>> {
+    ///
+    /// Returns [`Error::InvalidConfig`] if the configuration cannot work, e.g. zero shards or a set size that doesn't
+    /// fit in a region.
+    pub async fn build(mut self) -> Result<Store<K, V, S, P>> {
+        if self.io_concurrency == Some(0) {
+            return Err(Error::InvalidConfig(
+                "io concurrency must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.io_retry.is_some_and(|policy| policy.max_attempts == 0) {
+            return Err(Error::InvalidConfig(
+                "io retry max attempts must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.reserved_space > 0 && matches!(self.engine, Engine::Small(_)) {
+            return Err(Error::InvalidConfig(
+                "only the large object disk cache supports reserved space".to_string(),
+            ));
+        }
+
+        if let Engine::Small(small) | Engine::Mixed { small, .. } = &mut self.engine {
+            small.align_set_size()?;
+        }
+
+        match &self.engine {
+            Engine::Large(large) => large.validate()?,
+            Engine::Small(small) => small.validate()?,
+            Engine::Mixed { large, small, .. } => {
+                large.validate()?;
+                small.validate()?;
+                // The estimated size includes both the key and the value.
+                if self.large_object_threshold > 0
+                    && SmallEngineOptions::min_set_size(0, self.large_object_threshold - 1) > small.max_set_size()
+                {
+                    return Err(Error::InvalidConfig(format!(
+                        "entries under the large object threshold {} don't fit in the small object disk cache set size {}",
+                        self.large_object_threshold,
+                        small.max_set_size()
+                    )));
+                }
+            }
+        }
+
         let memory = self.memory.clone();
         let metrics = self.metrics.clone();
         let mut admission_picker = self.admission_picker.clone();
@@ -599,9 +1623,12 @@ where
         };
         let runtime = Runtime::new(read_runtime, write_runtime, user_runtime_handle);
 
-        let engine = {
+        let events = EventBus::default();
+
+        let (engine, device) = {
             let metrics = metrics.clone();
             let runtime = runtime.clone();
+            let events = events.clone();
             // Use the user runtime to open engine.
             tokio::spawn(async move {
                 match self.device_options {
@@ -609,20 +1636,37 @@ where
                         tracing::warn!(
                             "[store builder]: No device config set. Use `NoneStore` which always returns `None` for queries."
                         );
-                        EngineEnum::open(EngineConfig::Noop).await
+                        EngineEnum::open(EngineConfig::Noop).await.map(|engine| (engine, None))
                     }
-                    DeviceOptions::DeviceConfig(options) => {
+                    DeviceOptions::DeviceConfig(mut options) => {
+                        if let Some(direct_io) = self.direct_io {
+                            options.set_direct_io(direct_io);
+                        }
                         let device = match Monitored::open(MonitoredConfig {
                             config: options,
                             metrics: metrics.clone(),
+                            io_concurrency: self.io_concurrency,
+                            io_retry: self.io_retry,
                         }, runtime.clone())
                         .await {
                             Ok(device) => device,
                             Err(e) =>return Err(e),
                         };
-                        match self.engine {
+                        let handle = device.clone();
+                        let reserved_regions = self.reserved_space.div_ceil(device.region_size());
+                        let validate_reserved_regions = |regions: usize, flushers: usize| {
+                            if reserved_regions > 0 && reserved_regions + flushers >= regions {
+                                return Err(Error::InvalidConfig(format!(
+                                    "reserved space {} ({reserved_regions} regions) leaves no region for the {flushers} flushers of the large object disk cache with {regions} regions",
+                                    self.reserved_space
+                                )));
+                            }
+                            Ok(())
+                        };
+                        let engine = match self.engine {
                             Engine::Large(large) => {
                                 let regions = 0..device.regions() as RegionId;
+                                validate_reserved_regions(regions.len(), large.flushers)?;
                                 EngineEnum::open(EngineConfig::Large(GenericLargeStorageConfig {
                                     device,
                                     regions,
@@ -634,12 +1678,14 @@ where
                                     flushers: large.flushers,
                                     reclaimers: large.reclaimers,
                                     clean_region_threshold: large.clean_region_threshold.unwrap_or(large.reclaimers),
+                                    reserved_regions,
                                     eviction_pickers: large.eviction_pickers,
                                     reinsertion_picker: large.reinsertion_picker,
                                     tombstone_log_config: large.tombstone_log_config,
                                     buffer_pool_size: large.buffer_pool_size,
                                     blob_index_size: large.blob_index_size,
                                     submit_queue_size_threshold: large.submit_queue_size_threshold.unwrap_or(large.buffer_pool_size * 2),
+                                    events,
                                     runtime,
                                     marker: PhantomData,
                                 }))
@@ -647,15 +1693,37 @@ where
                             }
                             Engine::Small(small) => {
                                 let regions = 0..device.regions() as RegionId;
+                                small.validate_with_device(device.region_size(), device.regions())?;
                                 EngineEnum::open(EngineConfig::Small(GenericSmallStorageConfig {
                                     set_size: small.set_size,
-                                    set_cache_capacity: small.set_cache_capacity,
+                                    set_size_classes: small.set_size_classes.clone(),
+                                    set_cache_capacity: small.set_cache_capacity(),
                                     set_cache_shards: small.set_cache_shards,
                                     device,
                                     regions,
                                     flush: self.flush,
                                     flushers: small.flushers,
                                     buffer_pool_size: small.buffer_pool_size,
+                                    flush_interval: small.flush_interval,
+                                    serialize_buffer_budget: self.serialize_buffer_budget,
+                                    apply_yield_interval: small.apply_yield_interval,
+                                    apply_concurrency: small.apply_concurrency,
+                                    checksum_on_write: small.checksum_on_write,
+                                    verify_on_read: small.verify_on_read,
+                                    set_layout: small.set_layout,
+                                    delete_mode: small.delete_mode,
+                                    bloom_filter_rebuild: small.bloom_filter_rebuild,
+                                    integrity_mode: small.integrity_mode,
+                                    secondary_hash: small.secondary_hash,
+                                    inserted_at: small.inserted_at,
+                                    chunk_size: small.chunk_size,
+                                    initial_sets: small.initial_sets,
+                                    resize_policy: small.resize_policy,
+                                    set_router: small.set_router,
+                                    index_checkpoint: small.index_checkpoint,
+                                    index_checkpoint_interval: small.index_checkpoint_interval,
+                                    bloom_filter_preload: small.bloom_filter_preload,
+                                    events,
                                     runtime,
                                     marker: PhantomData,
                                 }))
@@ -665,17 +1733,40 @@ where
                                 let small_region_count = std::cmp::max((device.regions() as f64 * ratio) as usize,1);
                                 let small_regions = 0..small_region_count as RegionId;
                                 let large_regions = small_region_count as RegionId..device.regions() as RegionId;
+                                small.validate_with_device(device.region_size(), small_region_count)?;
+                                validate_reserved_regions(large_regions.len(), large.flushers)?;
                                 EngineEnum::open(EngineConfig::Mixed(EitherConfig {
-                                    selector: SizeSelector::new(Engine::OBJECT_SIZE_THRESHOLD),
+                                    selector: SizeSelector::new(self.large_object_threshold),
                                     left: GenericSmallStorageConfig {
                                         set_size: small.set_size,
-                                        set_cache_capacity: small.set_cache_capacity,
+                                        set_size_classes: small.set_size_classes.clone(),
+                                        set_cache_capacity: small.set_cache_capacity(),
                                         set_cache_shards: small.set_cache_shards,
                                         device: device.clone(),
                                         regions: small_regions,
                                         flush: self.flush,
                                         flushers: small.flushers,
                                         buffer_pool_size: small.buffer_pool_size,
+                                        flush_interval: small.flush_interval,
+                                        serialize_buffer_budget: self.serialize_buffer_budget,
+                                        apply_yield_interval: small.apply_yield_interval,
+                                        apply_concurrency: small.apply_concurrency,
+                                        checksum_on_write: small.checksum_on_write,
+                                        verify_on_read: small.verify_on_read,
+                                        set_layout: small.set_layout,
+                                        delete_mode: small.delete_mode,
+                                        bloom_filter_rebuild: small.bloom_filter_rebuild,
+                                        integrity_mode: small.integrity_mode,
+                                        secondary_hash: small.secondary_hash,
+                                        inserted_at: small.inserted_at,
+                                        chunk_size: small.chunk_size,
+                                        initial_sets: small.initial_sets,
+                                        resize_policy: small.resize_policy,
+                                        set_router: small.set_router,
+                                        index_checkpoint: small.index_checkpoint,
+                                        index_checkpoint_interval: small.index_checkpoint_interval,
+                                        bloom_filter_preload: small.bloom_filter_preload,
+                                        events: events.clone(),
                                         runtime: runtime.clone(),
                                         marker: PhantomData,
                                     },
@@ -690,12 +1781,14 @@ where
                                         flushers: large.flushers,
                                         reclaimers: large.reclaimers,
                                         clean_region_threshold: large.clean_region_threshold.unwrap_or(large.reclaimers),
+                                        reserved_regions,
                                         eviction_pickers: large.eviction_pickers,
                                         reinsertion_picker: large.reinsertion_picker,
                                         tombstone_log_config: large.tombstone_log_config,
                                         buffer_pool_size: large.buffer_pool_size,
                                         blob_index_size: large.blob_index_size,
                                         submit_queue_size_threshold: large.submit_queue_size_threshold.unwrap_or(large.buffer_pool_size * 2),
+                                        events,
                                         runtime,
                                         marker: PhantomData,
                                     },
@@ -703,7 +1796,8 @@ where
                                 }))
                                 .await
                             }
-                        }
+                        };
+                        engine.map(|engine| (engine, Some(handle)))
                     }
                 }
             }).await.unwrap()?
@@ -732,13 +1826,20 @@ where
         let hasher = memory.hash_builder().clone();
         let inner = StoreInner {
             hasher,
+            disk_hasher: self.disk_hasher,
             engine,
+            device,
             admission_picker,
+            resident_admission_picker: self.resident_admission_picker,
             load_throttler,
+            read_timeout: self.read_timeout,
+            loader: self.loader,
+            inflight: Mutex::new(HashMap::new()),
             compression,
             runtime,
             statistics,
             metrics,
+            events,
 
             #[cfg(feature = "test_utils")]
             load_throttle_switch: LoadThrottleSwitch::default(),
@@ -887,6 +1988,9 @@ impl LargeEngineOptions {
     ///
     /// If no eviction picker picks a region, a region will be picked randomly.
     ///
+    /// The fifo picker reclaims the earliest sealed region first. To reclaim the least recently accessed region first,
+    /// replace it with [`crate::LruPicker`].
+    ///
     /// Default: [ invalid ratio picker { threshold = 0.8 }, fifo picker ]
     pub fn with_eviction_pickers(mut self, eviction_pickers: Vec<Box<dyn EvictionPicker>>) -> Self {
         self.eviction_pickers = eviction_pickers;
@@ -915,16 +2019,53 @@ impl LargeEngineOptions {
         self.tombstone_log_config = Some(tombstone_log_config);
         self
     }
+
+    fn validate(&self) -> Result<()> {
+        let check = |value: usize, name: &str| {
+            if value == 0 {
+                return Err(Error::InvalidConfig(format!(
+                    "large object disk cache {name} must be greater than 0"
+                )));
+            }
+            Ok(())
+        };
+        check(self.indexer_shards, "indexer shards")?;
+        check(self.recover_concurrency, "recover concurrency")?;
+        check(self.flushers, "flushers")?;
+        check(self.reclaimers, "reclaimers")?;
+        Ok(())
+    }
 }
 
 /// Small object disk cache engine default options.
 #[derive(Debug)]
 pub struct SmallEngineOptions {
     set_size: usize,
+    set_size_classes: Vec<SetSizeClass>,
+    set_alignment: SetAlignment,
     set_cache_capacity: usize,
+    set_cache_size: Option<usize>,
     set_cache_shards: usize,
     buffer_pool_size: usize,
+    flush_interval: Option<Duration>,
     flushers: usize,
+    apply_yield_interval: usize,
+    apply_concurrency: usize,
+    checksum_on_write: bool,
+    verify_on_read: bool,
+    set_layout: SetLayout,
+    delete_mode: SetDeleteMode,
+    bloom_filter_rebuild: SetBloomFilterRebuild,
+    integrity_mode: IntegrityMode,
+    secondary_hash: bool,
+    inserted_at: bool,
+    chunk_size: Option<usize>,
+    initial_sets: Option<usize>,
+    resize_policy: Option<SetResizePolicy>,
+    set_router: Option<Arc<dyn SetRouter>>,
+    index_checkpoint: Option<PathBuf>,
+    index_checkpoint_interval: Option<Duration>,
+    bloom_filter_preload: bool,
 }
 
 impl Default for SmallEngineOptions {
@@ -938,17 +2079,51 @@ impl SmallEngineOptions {
     /// Create small object disk cache engine default options.
     pub fn new() -> Self {
         Self {
-            set_size: 16 * 1024,    // 16 KiB
+            set_size: 16 * 1024, // 16 KiB
+            set_size_classes: vec![],
+            set_alignment: SetAlignment::Page,
             set_cache_capacity: 64, // 64 sets
+            set_cache_size: None,
             set_cache_shards: 4,
             flushers: 1,
             buffer_pool_size: 4 * 1024 * 1024, // 4 MiB
+            flush_interval: None,
+            apply_yield_interval: 0,
+            apply_concurrency: 0,
+            checksum_on_write: true,
+            verify_on_read: true,
+            set_layout: SetLayout::Interleaved,
+            delete_mode: SetDeleteMode::Compact,
+            bloom_filter_rebuild: SetBloomFilterRebuild::Eager,
+            integrity_mode: IntegrityMode::Set,
+            secondary_hash: false,
+            inserted_at: false,
+            chunk_size: None,
+            initial_sets: None,
+            resize_policy: None,
+            set_router: None,
+            index_checkpoint: None,
+            index_checkpoint_interval: None,
+            bloom_filter_preload: false,
         }
     }
 
+    /// Get the minimal set size that can hold an entry with the given max key and value lengths.
+    ///
+    /// The lengths are the serialized lengths. The result includes the set header and the entry header, and is 4K
+    /// aligned, so it can be passed to [`SmallEngineOptions::with_set_size`] directly.
+    pub fn min_set_size(max_key_len: usize, max_value_len: usize) -> usize {
+        bits::align_up(
+            PAGE,
+            SetStorage::SET_HEADER_SIZE + EntryHeader::ENTRY_HEADER_SIZE + max_key_len + max_value_len,
+        )
+    }
+
     /// Set the set size of the set-associated cache.
     ///
-    /// The set size will be 4K aligned.
+    /// The set size will be 4K aligned. Entries larger than the set are ignored, use
+    /// [`SmallEngineOptions::min_set_size`] to find a set size that fits the entries. The set size is rounded up on
+    /// build if it is not aligned to [`SmallEngineOptions::with_set_alignment`].
     ///
     /// Default: 16 KiB
     pub fn with_set_size(mut self, set_size: usize) -> Self {
@@ -957,6 +2132,48 @@ impl SmallEngineOptions {
         self
     }
 
+    /// Set the size classes of the sets, overrides [`SmallEngineOptions::with_set_size`].
+    ///
+    /// With a bimodal entry size distribution, small sets waste space on the large entries and large sets waste space
+    /// on the small ones. With the classes, e.g. 4 KiB and 64 KiB sets, each class has its own sets on its own part of
+    /// the regions, allocated in proportion to the [`SetSizeClass::ratio`]s, the first classes get at least one region
+    /// each and the last one gets the rest. The classes are sorted by their set sizes, which must be distinct.
+    ///
+    /// Routing rules:
+    ///
+    /// - An entry is written to the smallest class whose sets can hold it, by the serialized size of the entry. An
+    ///   entry too large for all classes is ignored.
+    /// - Writing an entry deletes the older entries of the same key from the other classes, so an entry is kept in one
+    ///   class only. Loads try the classes from the smallest one.
+    /// - The set of an entry in its class is picked as a single class does, a [`SetRouter`] is told the class with
+    ///   [`SetRouter::route_in_class`].
+    ///
+    /// Each class has its own flushers (see [`SmallEngineOptions::with_flushers`]) and set cache (see
+    /// [`SmallEngineOptions::with_set_cache_capacity`]), the flush buffer pool is shared by all flushers. With more
+    /// than one class, the sets cannot be resized, and the index checkpoint and the fill report are not supported.
+    /// Changing the classes invalidates the cached entries.
+    ///
+    /// Default: a single class with the set size of [`SmallEngineOptions::with_set_size`].
+    pub fn with_set_size_classes(mut self, classes: impl IntoIterator<Item = SetSizeClass>) -> Self {
+        self.set_size_classes = classes.into_iter().collect();
+        self.set_size_classes.sort_by_key(|class| class.set_size);
+        self
+    }
+
+    /// Set the alignment of the set size.
+    ///
+    /// Some devices perform best with I/O aligned to a power of two or to a larger block than 4 KiB. A set size that is
+    /// not aligned is rounded up on build with a warning, and the padding is used as set data capacity, see
+    /// [`SetAlignment`]. Together with direct I/O (see [`StoreBuilder::with_direct_io`]), every read and write of a
+    /// set is then aligned to the alignment on the device. A rounded set size takes effect as if it is set by
+    /// [`SmallEngineOptions::with_set_size`], e.g., changing it invalidates the cached entries.
+    ///
+    /// Default: [`SetAlignment::Page`].
+    pub fn with_set_alignment(mut self, set_alignment: SetAlignment) -> Self {
+        self.set_alignment = set_alignment;
+        self
+    }
+
     /// Set the capacity of the set cache.
     ///
     /// Count by set amount.
@@ -967,6 +2184,18 @@ impl SmallEngineOptions {
         self
     }
 
+    /// Set the capacity of the set cache in bytes.
+    ///
+    /// The set cache keeps the recently loaded sets, so loads of the entries in a hot set don't read the set from the
+    /// device again. The capacity is rounded down to whole sets, and overrides
+    /// [`SmallEngineOptions::with_set_cache_capacity`].
+    ///
+    /// Default: 64 sets.
+    pub fn with_set_cache_size(mut self, set_cache_size: usize) -> Self {
+        self.set_cache_size = Some(set_cache_size);
+        self
+    }
+
     /// Set the shards of the set cache.
     ///
     /// Default: 4
@@ -987,6 +2216,18 @@ impl SmallEngineOptions {
         self
     }
 
+    /// Set the interval to combine the writes before they are flushed to the sets.
+    ///
+    /// With the interval, each flusher buffers the writes until the interval elapses since the first buffered write,
+    /// the buffer is full (see [`SmallEngineOptions::with_buffer_pool_size`]), or [`Store::wait`] is called, so more
+    /// entries of the same set are written with one set write. Loads read the buffered writes before the sets.
+    ///
+    /// Default: `None`, a batch is flushed as soon as the last one is finished.
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = Some(flush_interval);
+        self
+    }
+
     /// Set the flusher count for the disk cache store.
     ///
     /// The flusher count limits how many regions can be concurrently written.
@@ -996,67 +2237,2225 @@ impl SmallEngineOptions {
         self.flushers = flushers;
         self
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use foyer_common::hasher::ModHasher;
-    use foyer_memory::CacheBuilder;
+    /// Set how many entries are scanned before yielding to the runtime when applying a batch to a set.
+    ///
+    /// Applying deletions to a large set with lots of small entries may block the runtime for a while. Yielding
+    /// periodically keeps the runtime responsive at the cost of some throughput.
+    ///
+    /// `0` means never yield.
+    ///
+    /// Default: `0`.
+    pub fn with_apply_yield_interval(mut self, apply_yield_interval: usize) -> Self {
+        self.apply_yield_interval = apply_yield_interval;
+        self
+    }
 
-    use super::*;
+    /// Set how many sets a flusher applies a batch to concurrently.
+    ///
+    /// The entries of each set are always applied in order, only different sets of a batch are applied concurrently.
+    /// Bounding the concurrency limits the in-flight set reads and writes of a flusher.
+    ///
+    /// `0` means all sets of a batch are applied concurrently.
+    ///
+    /// Default: `0`.
+    pub fn with_apply_concurrency(mut self, apply_concurrency: usize) -> Self {
+        self.apply_concurrency = apply_concurrency;
+        self
+    }
 
-    #[tokio::test]
-    async fn test_build_with_unaligned_buffer_pool_size() {
-        let dir = tempfile::tempdir().unwrap();
-        let metrics = Arc::new(Metrics::noop());
-        let memory: Cache<u64, u64> = CacheBuilder::new(10).build();
-        let _ = StoreBuilder::new(
-            "test",
-            memory,
-            metrics,
-            Engine::Large(
-                LargeEngineOptions::new()
-                    .with_flushers(3)
-                    .with_buffer_pool_size(128 * 1024 * 1024),
-            ),
-        )
-        .with_device_options(DirectFsDeviceOptions::new(dir.path()))
-        .build()
-        .await
-        .unwrap();
+    /// Set whether to calculate the set checksum when writing a set.
+    ///
+    /// Skipping the checksum saves the cost of checksumming the whole set on each write, for devices that guarantee
+    /// data integrity themselves. Sets written without a checksum cannot be verified on read, so disabling it requires
+    /// disabling [`SmallEngineOptions::with_verify_on_read`] as well, otherwise building the store fails.
+    ///
+    /// Default: `true`.
+    pub fn with_checksum_on_write(mut self, checksum_on_write: bool) -> Self {
+        self.checksum_on_write = checksum_on_write;
+        self
     }
 
-    #[tokio::test]
-    async fn test_entry_hash_collision() {
-        let dir = tempfile::tempdir().unwrap();
-        let metrics = Arc::new(Metrics::noop());
-        let memory: Cache<u128, String, ModHasher> =
-            CacheBuilder::new(10).with_hash_builder(ModHasher::default()).build();
+    /// Set whether to verify the set checksum when reading a set.
+    ///
+    /// A set that fails the verification is loaded as an empty set. The valid combinations with
+    /// [`SmallEngineOptions::with_checksum_on_write`] are:
+    ///
+    /// - checksum on write, verify on read: full integrity check.
+    /// - checksum on write, no verify on read: sets can still be verified if verifying is enabled later.
+    /// - no checksum on write, no verify on read: no checksum cost at all.
+    /// - no checksum on write, verify on read: invalid, every set would fail the verification.
+    ///
+    /// Note: sets written without a checksum are cleared on read if verifying is enabled after restarting.
+    ///
+    /// Default: `true`.
+    pub fn with_verify_on_read(mut self, verify_on_read: bool) -> Self {
+        self.verify_on_read = verify_on_read;
+        self
+    }
 
-        let e1 = memory.insert(1, "foo".to_string());
-        let e2 = memory.insert(1 + 1 + u64::MAX as u128, "bar".to_string());
+    /// Set the entry layout of the sets.
+    ///
+    /// [`SetLayout::Interleaved`] stores each key right after its value, [`SetLayout::Split`] packs the keys together
+    /// and the values separately, so a full key scan of a set only reads the key region. The layout is recorded in
+    /// each set, sets written with another layout are cleared on read, so changing it invalidates the cached entries.
+    ///
+    /// Default: [`SetLayout::Interleaved`].
+    pub fn with_set_layout(mut self, set_layout: SetLayout) -> Self {
+        self.set_layout = set_layout;
+        self
+    }
 
-        assert_eq!(memory.hash(e1.key()), memory.hash(e2.key()));
+    /// Set how entries are deleted from the sets.
+    ///
+    /// [`SetDeleteMode::Compact`] moves the following entries forward on each deletion. [`SetDeleteMode::Tombstone`]
+    /// only flips a bit in the entry header and leaves the space to be reclaimed when the set runs out of space or the
+    /// tombstones exceed the given ratio of the set.
+    ///
+    /// Default: [`SetDeleteMode::Compact`].
+    pub fn with_delete_mode(mut self, delete_mode: SetDeleteMode) -> Self {
+        self.delete_mode = delete_mode;
+        self
+    }
 
-        let store = StoreBuilder::new("test", memory, metrics, Engine::Large(LargeEngineOptions::default()))
-            .with_device_options(
-                DirectFsDeviceOptions::new(dir.path())
-                    .with_capacity(4 * 1024 * 1024)
-                    .with_file_size(1024 * 1024),
-            )
-            .build()
-            .await
-            .unwrap();
+    /// Set when the bloom filters of the sets are rebuilt on deletions.
+    ///
+    /// [`SetBloomFilterRebuild::Eager`] rebuilds the bloom filter of a set from the live entries on each deletion.
+    /// [`SetBloomFilterRebuild::Lazy`] leaves the deleted hashes in the bloom filter until enough of them pile up,
+    /// which makes deletions from large sets cheaper. Each deleted hash left behind raises the false positive
+    /// probability of the set as if the entry was still there, a lookup of it reads the set and finds nothing.
+    ///
+    /// Default: [`SetBloomFilterRebuild::Eager`].
+    pub fn with_bloom_filter_rebuild(mut self, bloom_filter_rebuild: SetBloomFilterRebuild) -> Self {
+        self.bloom_filter_rebuild = bloom_filter_rebuild;
+        self
+    }
 
-        store.enqueue(e1.piece(), true);
-        store.enqueue(e2.piece(), true);
-        store.wait().await;
+    /// Set the integrity checks of the small object disk cache on top of the set checksums.
+    ///
+    /// [`IntegrityMode::RegionFooter`] ends each region with a footer over the set checksums and the layout of the
+    /// region, see [`crate::RegionFooter`] for the format. The footers are verified when the store is opened, the sets
+    /// of a region whose footer is missing or doesn't match are invalidated, the other regions are kept. Each set
+    /// write rewrites the footer of its region as well, and a crash between them also fails the verification of the
+    /// region.
+    ///
+    /// Changing the mode changes how many sets a region holds, so it invalidates the cached entries. The footer
+    /// checksums the set checksums, so it requires [`SmallEngineOptions::with_checksum_on_write`].
+    ///
+    /// Default: [`IntegrityMode::Set`].
+    pub fn with_integrity_mode(mut self, integrity_mode: IntegrityMode) -> Self {
+        self.integrity_mode = integrity_mode;
+        self
+    }
 
-        let l1 = store.load(e1.key()).await.unwrap();
-        let l2 = store.load(e2.key()).await.unwrap();
+    /// Set whether each entry of the small object disk cache stores a secondary hash of its key.
+    ///
+    /// The secondary hash is a 32-bit xxHash of the encoded key, independent of the hash of the key. On load, an entry
+    /// with the hash is only taken if its key matches the secondary hash, otherwise it is skipped as corrupted and
+    /// the following entries with the hash are tried. So a key torn or corrupted on the device is caught even if the
+    /// sets are not verified on read. It takes 4 more bytes of each entry.
+    ///
+    /// Enabling or disabling the secondary hash invalidates the cached entries.
+    ///
+    /// Default: `false`.
+    pub fn with_secondary_hash(mut self, secondary_hash: bool) -> Self {
+        self.secondary_hash = secondary_hash;
+        self
+    }
 
-        assert!(matches!(l1, Load::Miss));
-        assert!(matches!(l2, Load::Entry { .. }));
-        assert_eq!(l2.entry().unwrap().1, "bar");
+    /// Set whether each entry of the small object disk cache stores its insertion time.
+    ///
+    /// The insertion time is the time the entry is first written to a set, in milliseconds since the unix epoch. It is
+    /// kept when the entry is moved to another set by resizing the sets, and reported by
+    /// [`ScanProgress::inserted_at`] for age-based analytics or per-entry expiration. It takes 8 more bytes of each
+    /// entry, e.g. about 8% more space for entries of 100 bytes.
+    ///
+    /// Enabling or disabling the insertion time invalidates the cached entries.
+    ///
+    /// Default: `false`.
+    pub fn with_entry_inserted_at(mut self, inserted_at: bool) -> Self {
+        self.inserted_at = inserted_at;
+        self
+    }
+
+    /// Enable striping the entries too large for the sets across multiple sets, in chunks of `chunk_size` bytes.
+    ///
+    /// An entry is too large if it doesn't fit in a set of the largest set size class, or its value exceeds the
+    /// `64 KiB - 1` limit of an entry. Without chunking, such entries are skipped. With chunking, the encoded value is
+    /// split into chunks of `chunk_size` bytes, only the last chunk may be shorter. Each chunk is written as an entry
+    /// with the key, under a hash derived from the hash of the entry and the chunk index, so the chunks are spread
+    /// across the sets. A manifest entry with the key, under another derived hash, takes the place of the entry:
+    ///
+    /// ```plain
+    /// | magic (4B) | chunk size (4B) | chunks (4B) | value len (8B) | value checksum (8B) |
+    /// ```
+    ///
+    /// Loads read the manifest and all chunks concurrently and reassemble the value, so an entry of `N` chunks takes
+    /// up to `N + 1` set reads.
+    ///
+    /// The chunks and the manifest are evicted with their sets independently. A value with any chunk evicted, or
+    /// mixed with the chunks of a newer value of the same key, fails the checksum and is reported as a miss. Deleting
+    /// or overwriting a chunked entry only removes the manifest, the chunks are left to be evicted.
+    ///
+    /// The chunks and the manifests are tagged in their entry headers, which limits the keys to `16 KiB - 1`. They are
+    /// skipped by [`Store::scan_with_options`], [`Store::warm_up`] and [`Store::iter_sorted`], so the chunked entries
+    /// are only read by their keys. Enabling or disabling chunking invalidates the cached entries.
+    ///
+    /// The chunk size must not be greater than `64 KiB - 1`, and a chunk entry, i.e. the chunk, the key and the entry
+    /// header, must fit in a set of the largest set size class.
+    ///
+    /// Default: `None`, entries too large for the sets are skipped.
+    pub fn with_chunking(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Set how many sets are in use when the small object disk cache is created, the meta set excluded.
+    ///
+    /// The sets in use can be grown up to all sets the device can hold with [`Store::resize_sets`] or the
+    /// [`SmallEngineOptions::with_resize_policy`], and shrunk back to the initial sets. Starting with fewer sets keeps
+    /// the sets dense while the cache warms up. Changing the initial sets invalidates the cached entries.
+    ///
+    /// Default: all sets the device can hold.
+    pub fn with_initial_sets(mut self, initial_sets: usize) -> Self {
+        self.initial_sets = Some(initial_sets);
+        self
+    }
+
+    /// Set the policy to grow the sets in use automatically.
+    ///
+    /// Only takes effect if not all sets are in use, see [`SmallEngineOptions::with_initial_sets`].
+    ///
+    /// Default: `None`, the sets are only resized with [`Store::resize_sets`].
+    pub fn with_resize_policy(mut self, resize_policy: SetResizePolicy) -> Self {
+        self.resize_policy = Some(resize_policy);
+        self
+    }
+
+    /// Set the router to route the entries to the sets.
+    ///
+    /// A custom router can co-locate related entries in the same set, see [`SetRouter`]. The sets cannot be resized
+    /// with a custom router, so it conflicts with [`SmallEngineOptions::with_initial_sets`] and
+    /// [`SmallEngineOptions::with_resize_policy`].
+    ///
+    /// Default: `None`, the entries are routed by the hashes of their keys modulo the sets.
+    pub fn with_set_router(mut self, set_router: Arc<dyn SetRouter>) -> Self {
+        self.set_router = Some(set_router);
+        self
+    }
+
+    /// Set the path of the index checkpoint.
+    ///
+    /// The in-memory index of the sets, i.e. the bloom filters of the sets, is lost on restart. With the checkpoint,
+    /// it is written to the file on [`Store::checkpoint_index`], periodically (see
+    /// [`SmallEngineOptions::with_index_checkpoint_interval`]), and on close, and restored from the file when the
+    /// store is built, so there is no need to scan the sets. A set updated after the checkpoint is detected by its
+    /// content checksum on its first read and falls back to its own bloom filter. Without a valid checkpoint, the sets
+    /// are scanned in the background.
+    ///
+    /// Default: `None`, the in-memory index is not recovered.
+    pub fn with_index_checkpoint(mut self, path: impl AsRef<Path>) -> Self {
+        self.index_checkpoint = Some(path.as_ref().into());
+        self
+    }
+
+    /// Set the interval to write the index checkpoint, requires [`SmallEngineOptions::with_index_checkpoint`].
+    ///
+    /// Entries written after the last checkpoint are missed after a crash until their sets are read again.
+    ///
+    /// Default: `None`, the index checkpoint is only written on [`Store::checkpoint_index`] and on close.
+    pub fn with_index_checkpoint_interval(mut self, interval: Duration) -> Self {
+        self.index_checkpoint_interval = Some(interval);
+        self
+    }
+
+    /// Set whether to preload the bloom filters of the sets on open.
+    ///
+    /// The bloom filters of the sets are kept in memory and updated on writes, so a miss is answered without any I/O,
+    /// but they are lost on restart. With the preload, they are rebuilt from the set headers when the store is built,
+    /// which reads the first page of each set in use and keeps 32 bytes of memory for each set. Ignored with
+    /// [`SmallEngineOptions::with_index_checkpoint`], which restores the bloom filters from the checkpoint instead.
+    ///
+    /// Default: `false`, the in-memory index is not recovered.
+    pub fn with_bloom_filter_preload(mut self, bloom_filter_preload: bool) -> Self {
+        self.bloom_filter_preload = bloom_filter_preload;
+        self
+    }
+
+    /// The options to read the sets written with these options outside of the store, see
+    /// [`read_set_entries`](crate::read_set_entries).
+    pub fn set_read_options(&self) -> SetReadOptions {
+        SetReadOptions::new()
+            .with_verify(self.verify_on_read)
+            .with_set_layout(self.set_layout)
+            .with_secondary_hash(self.secondary_hash)
+            .with_entry_inserted_at(self.inserted_at)
+            .with_chunking(self.chunk_size.is_some())
+    }
+
+    /// Size classes of the sets, a single class of the set size if none is set.
+    fn set_size_classes(&self) -> Vec<SetSizeClass> {
+        match self.set_size_classes.is_empty() {
+            true => vec![SetSizeClass::new(self.set_size, 1.0)],
+            false => self.set_size_classes.clone(),
+        }
+    }
+
+    /// Size of the sets of the largest set size class.
+    fn max_set_size(&self) -> usize {
+        self.set_size_classes().last().unwrap().set_size
+    }
+
+    /// Count of the sets the set cache of each set size class holds.
+    fn set_cache_capacity(&self) -> usize {
+        match self.set_cache_size {
+            Some(size) => size / self.max_set_size().max(1),
+            None => self.set_cache_capacity,
+        }
+    }
+
+    /// Round the set size up to the set alignment.
+    fn align_set_size(&mut self) -> Result<()> {
+        if let SetAlignment::Bytes(alignment) = self.set_alignment {
+            if !alignment.is_power_of_two() || alignment % PAGE != 0 {
+                return Err(Error::InvalidConfig(format!(
+                    "small object disk cache set alignment {alignment} must be a power of two multiple of {PAGE}"
+                )));
+            }
+        }
+        let set_alignment = self.set_alignment;
+        let align = |set_size: &mut usize| {
+            let aligned = set_alignment.align(*set_size);
+            if aligned != *set_size {
+                tracing::warn!(
+                    from = *set_size,
+                    to = aligned,
+                    "[store builder]: Set size is rounded up to the set alignment, the padding is used as set capacity."
+                );
+                *set_size = aligned;
+            }
+        };
+        align(&mut self.set_size);
+        self.set_size_classes
+            .iter_mut()
+            .for_each(|class| align(&mut class.set_size));
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.set_size <= SetStorage::SET_HEADER_SIZE {
+            return Err(Error::InvalidConfig(format!(
+                "small object disk cache set size {} must be greater than the set header size {}",
+                self.set_size,
+                SetStorage::SET_HEADER_SIZE
+            )));
+        }
+        for (i, class) in self.set_size_classes.iter().enumerate() {
+            if class.set_size <= SetStorage::SET_HEADER_SIZE || class.set_size % PAGE != 0 {
+                return Err(Error::InvalidConfig(format!(
+                    "small object disk cache set size class {} must be a multiple of {PAGE} greater than the set header size {}",
+                    class.set_size,
+                    SetStorage::SET_HEADER_SIZE
+                )));
+            }
+            if !(class.ratio.is_finite() && class.ratio > 0.0) {
+                return Err(Error::InvalidConfig(format!(
+                    "small object disk cache set size class {} ratio {} must be positive",
+                    class.set_size, class.ratio
+                )));
+            }
+            if i > 0 && self.set_size_classes[i - 1].set_size == class.set_size {
+                return Err(Error::InvalidConfig(format!(
+                    "small object disk cache set size classes must be distinct, duplicated: {}",
+                    class.set_size
+                )));
+            }
+        }
+        if self.set_size_classes.len() > 1
+            && (self.initial_sets.is_some() || self.resize_policy.is_some() || self.index_checkpoint.is_some())
+        {
+            return Err(Error::InvalidConfig(
+                "small object disk cache with multiple set size classes supports neither resizing the sets nor the index checkpoint"
+                    .to_string(),
+            ));
+        }
+        if self.set_cache_shards == 0 {
+            return Err(Error::InvalidConfig(
+                "small object disk cache set cache shards must be greater than 0".to_string(),
+            ));
+        }
+        if self.set_cache_capacity() < self.set_cache_shards {
+            return Err(Error::InvalidConfig(format!(
+                "small object disk cache set cache capacity {} must not be less than the set cache shards {}",
+                self.set_cache_capacity(),
+                self.set_cache_shards
+            )));
+        }
+        if self.flushers == 0 {
+            return Err(Error::InvalidConfig(
+                "small object disk cache flushers must be greater than 0".to_string(),
+            ));
+        }
+        if self.verify_on_read && !self.checksum_on_write {
+            return Err(Error::InvalidConfig(
+                "small object disk cache cannot verify on read with checksum on write disabled".to_string(),
+            ));
+        }
+        if let SetDeleteMode::Tombstone { ratio } = self.delete_mode {
+            if !(ratio > 0.0 && ratio <= 1.0) {
+                return Err(Error::InvalidConfig(format!(
+                    "small object disk cache tombstone ratio {ratio} must be in (0, 1]"
+                )));
+            }
+        }
+        if self.integrity_mode == IntegrityMode::RegionFooter && !self.checksum_on_write {
+            return Err(Error::InvalidConfig(
+                "small object disk cache region footer requires checksum on write".to_string(),
+            ));
+        }
+        if self.bloom_filter_rebuild == (SetBloomFilterRebuild::Lazy { interval: 0 }) {
+            return Err(Error::InvalidConfig(
+                "small object disk cache lazy bloom filter rebuild interval must be positive".to_string(),
+            ));
+        }
+        if let Some(resize_policy) = self.resize_policy.as_ref() {
+            resize_policy.verify()?;
+        }
+        if self.set_router.is_some() && (self.initial_sets.is_some() || self.resize_policy.is_some()) {
+            return Err(Error::InvalidConfig(
+                "small object disk cache sets cannot be resized with a custom set router".to_string(),
+            ));
+        }
+        if self.flush_interval.is_some_and(|interval| interval.is_zero()) {
+            return Err(Error::InvalidConfig(
+                "small object disk cache flush interval must be positive".to_string(),
+            ));
+        }
+        if let Some(interval) = self.index_checkpoint_interval {
+            if self.index_checkpoint.is_none() || interval.is_zero() {
+                return Err(Error::InvalidConfig(
+                    "small object disk cache index checkpoint interval requires the index checkpoint path and must be positive"
+                        .to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_with_device(&self, region_size: usize, regions: usize) -> Result<()> {
+        let classes = self.set_size_classes();
+        if let Some(chunk_size) = self.chunk_size {
+            // The same capacity as the flushers use, the optional fields of each entry excluded.
+            let fields = SetEntryFields {
+                secondary_hash: self.secondary_hash,
+                inserted_at: self.inserted_at,
+                chunked: true,
+            };
+            let capacity = classes.last().unwrap().capacity() - fields.size();
+            if chunk_size == 0
+                || chunk_size > EntryHeader::MAX_VALUE_LEN
+                || EntryHeader::ENTRY_HEADER_SIZE + chunk_size >= capacity
+            {
+                return Err(Error::InvalidConfig(format!(
+                    "small object disk cache chunk size {chunk_size} must be in [1, {}] and leave room for the key in a set of {capacity} bytes",
+                    EntryHeader::MAX_VALUE_LEN
+                )));
+            }
+        }
+        let class_regions = SetSizeClass::split(&classes, 0..regions as RegionId);
+        for (class, regions) in classes.iter().zip(class_regions) {
+            let set_size = class.set_size;
+            let regions = (regions.end - regions.start) as usize;
+            if region_size < set_size {
+                return Err(Error::InvalidConfig(format!(
+                    "small object disk cache set size {set_size} must not be greater than the region size {region_size}"
+                )));
+            }
+            // Set 0 is reserved for metadata, at least one more set is required for data.
+            let sets = region_size / set_size * regions;
+            if sets < 2 {
+                return Err(Error::InvalidConfig(format!(
+                    "small object disk cache requires at least 2 sets of {set_size} bytes, but only {sets} sets fit in {regions} regions"
+                )));
+            }
+            if let Some(initial_sets) = self.initial_sets {
+                if initial_sets == 0 || initial_sets > sets - 1 {
+                    return Err(Error::InvalidConfig(format!(
+                        "small object disk cache initial sets {initial_sets} must be in [1, {}]",
+                        sets - 1
+                    )));
+                }
+            }
+            if region_size % set_size != 0 {
+                tracing::warn!(
+                    region_size,
+                    set_size,
+                    "[store builder]: Region size is not a multiple of the set size, the remaining space is wasted."
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        hash::BuildHasher,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::SystemTime,
+    };
+
+    use foyer_common::{code::Code, hasher::ModHasher};
+    use foyer_memory::CacheBuilder;
+    use futures_util::{StreamExt, TryStreamExt};
+
+    use super::*;
+    use crate::SetId;
+
+    /// Routes the hashes with the same prefix to the same set and counts the routed hashes.
+    #[derive(Debug, Default)]
+    struct PrefixRouter {
+        routed: AtomicUsize,
+    }
+
+    impl SetRouter for PrefixRouter {
+        fn route(&self, hash: u64, sets: usize) -> SetId {
+            self.routed.fetch_add(1, Ordering::Relaxed);
+            (hash >> 8) % sets as SetId
+        }
+    }
+
+    #[tokio::test]
+    async fn test_build_with_unaligned_buffer_pool_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, u64> = CacheBuilder::new(10).build();
+        let _ = StoreBuilder::new(
+            "test",
+            memory,
+            metrics,
+            Engine::Large(
+                LargeEngineOptions::new()
+                    .with_flushers(3)
+                    .with_buffer_pool_size(128 * 1024 * 1024),
+            ),
+        )
+        .with_device_options(DirectFsDeviceOptions::new(dir.path()))
+        .build()
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn test_min_set_size() {
+        assert_eq!(SmallEngineOptions::min_set_size(8, 100), PAGE);
+        assert_eq!(
+            SmallEngineOptions::min_set_size(
+                8,
+                PAGE - SetStorage::SET_HEADER_SIZE - EntryHeader::ENTRY_HEADER_SIZE - 8
+            ),
+            PAGE
+        );
+        assert_eq!(SmallEngineOptions::min_set_size(8, PAGE), 2 * PAGE);
+    }
+
+    #[tokio::test]
+    async fn test_buffered_io() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, u64> = CacheBuilder::new(10).build();
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Large(LargeEngineOptions::new()),
+        )
+        .with_device_options(
+            DirectFsDeviceOptions::new(dir.path())
+                .with_capacity(4 * 1024 * 1024)
+                .with_file_size(1024 * 1024),
+        )
+        .with_direct_io(false)
+        .build()
+        .await
+        .unwrap();
+
+        store.enqueue(memory.insert(1, 1).piece(), true);
+        store.wait().await;
+        assert_eq!(store.load(&1).await.unwrap().entry().unwrap().1, 1);
+    }
+
+    #[tokio::test]
+    async fn test_build_with_invalid_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, u64, ModHasher> = CacheBuilder::new(10).with_hash_builder(ModHasher::default()).build();
+
+        let build = |engine: Engine| {
+            StoreBuilder::new("test", memory.clone(), metrics.clone(), engine)
+                .with_device_options(
+                    DirectFsDeviceOptions::new(dir.path())
+                        .with_capacity(4 * 1024 * 1024)
+                        .with_file_size(1024 * 1024),
+                )
+                .build()
+        };
+
+        for engine in [
+            Engine::Large(LargeEngineOptions::new().with_indexer_shards(0)),
+            Engine::Small(SmallEngineOptions::new().with_set_cache_shards(0)),
+            Engine::Small(
+                SmallEngineOptions::new()
+                    .with_set_cache_capacity(2)
+                    .with_set_cache_shards(4),
+            ),
+            Engine::Small(SmallEngineOptions::new().with_set_size(0)),
+            Engine::Small(SmallEngineOptions::new().with_set_cache_size(16 * 1024)),
+            Engine::Small(SmallEngineOptions::new().with_set_size(2 * 1024 * 1024)),
+            Engine::Small(SmallEngineOptions::new().with_checksum_on_write(false)),
+            Engine::Small(SmallEngineOptions::new().with_delete_mode(SetDeleteMode::Tombstone { ratio: 0.0 })),
+            Engine::Small(SmallEngineOptions::new().with_delete_mode(SetDeleteMode::Tombstone { ratio: 1.5 })),
+            Engine::Small(
+                SmallEngineOptions::new().with_bloom_filter_rebuild(SetBloomFilterRebuild::Lazy { interval: 0 }),
+            ),
+            Engine::Small(
+                SmallEngineOptions::new()
+                    .with_checksum_on_write(false)
+                    .with_verify_on_read(false)
+                    .with_integrity_mode(IntegrityMode::RegionFooter),
+            ),
+            Engine::Small(SmallEngineOptions::new().with_initial_sets(0)),
+            Engine::Small(SmallEngineOptions::new().with_index_checkpoint_interval(Duration::from_secs(1))),
+            Engine::Small(SmallEngineOptions::new().with_flush_interval(Duration::ZERO)),
+            Engine::Small(
+                SmallEngineOptions::new()
+                    .with_set_router(Arc::new(PrefixRouter::default()))
+                    .with_initial_sets(4),
+            ),
+            Engine::Small(SmallEngineOptions::new().with_initial_sets(1024 * 1024)),
+            Engine::Small(
+                SmallEngineOptions::new()
+                    .with_set_size_classes([SetSizeClass::new(4 * 1024, 1.0), SetSizeClass::new(4 * 1024, 1.0)]),
+            ),
+            Engine::Small(SmallEngineOptions::new().with_set_size_classes([SetSizeClass::new(4 * 1024, 0.0)])),
+            Engine::Small(
+                SmallEngineOptions::new()
+                    .with_set_size_classes([SetSizeClass::new(4 * 1024, 1.0), SetSizeClass::new(64 * 1024, 1.0)])
+                    .with_initial_sets(4),
+            ),
+            Engine::Small(SmallEngineOptions::new().with_set_size_classes([
+                SetSizeClass::new(4 * 1024, 1.0),
+                SetSizeClass::new(2 * 1024 * 1024, 1.0),
+            ])),
+            Engine::Small(SmallEngineOptions::new().with_resize_policy(SetResizePolicy {
+                growth: 0.0,
+                ..Default::default()
+            })),
+        ] {
+            let res = build(engine).await;
+            assert!(matches!(res, Err(Error::InvalidConfig(_))), "{res:?}");
+        }
+
+        let res = StoreBuilder::new("test", memory.clone(), metrics.clone(), Engine::mixed())
+            .with_device_options(
+                DirectFsDeviceOptions::new(dir.path())
+                    .with_capacity(4 * 1024 * 1024)
+                    .with_file_size(1024 * 1024),
+            )
+            .with_large_object_threshold(32 * 1024)
+            .build()
+            .await;
+        assert!(matches!(res, Err(Error::InvalidConfig(_))), "{res:?}");
+
+        let res = StoreBuilder::new("test", memory.clone(), metrics.clone(), Engine::small())
+            .with_memory_device(4 * 1024 * 1024)
+            .with_io_concurrency(0)
+            .build()
+            .await;
+        assert!(matches!(res, Err(Error::InvalidConfig(_))), "{res:?}");
+
+        let res = StoreBuilder::new("test", memory.clone(), metrics.clone(), Engine::small())
+            .with_memory_device(4 * 1024 * 1024)
+            .with_io_retry(RetryPolicy {
+                max_attempts: 0,
+                backoff: Duration::from_millis(1),
+            })
+            .build()
+            .await;
+        assert!(matches!(res, Err(Error::InvalidConfig(_))), "{res:?}");
+
+        let res = StoreBuilder::new("test", memory.clone(), metrics.clone(), Engine::small())
+            .with_memory_device(4 * 1024 * 1024)
+            .with_reserved_space(1024 * 1024)
+            .build()
+            .await;
+        assert!(matches!(res, Err(Error::InvalidConfig(_))), "{res:?}");
+
+        let res = StoreBuilder::new("test", memory.clone(), metrics.clone(), Engine::large())
+            .with_device_options(MemoryDeviceOptions::new(4 * 1024 * 1024).with_region_size(1024 * 1024))
+            .with_reserved_space(3 * 1024 * 1024)
+            .build()
+            .await;
+        assert!(matches!(res, Err(Error::InvalidConfig(_))), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn test_reserved_space() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Large(
+                LargeEngineOptions::new()
+                    .with_reclaimers(1)
+                    .with_clean_region_threshold(0),
+            ),
+        )
+        .with_device_options(MemoryDeviceOptions::new(512 * 1024).with_region_size(64 * 1024))
+        .with_reserved_space(100 * 1024)
+        .build()
+        .await
+        .unwrap();
+        let statistics = store.statistics();
+        assert_eq!(statistics.reserved_space(), 128 * 1024);
+        assert_eq!(statistics.free_space(), 512 * 1024);
+
+        for i in 0..64 {
+            store.enqueue(memory.insert(i, vec![i as u8; 16 * 1024]).piece(), true);
+            store.wait().await;
+        }
+
+        // Only the reserved regions are left clean, and the new entries are rejected.
+        assert_eq!(statistics.free_space(), statistics.reserved_space());
+        assert!(store.load(&0).await.unwrap().is_entry());
+        assert!(!store.load(&63).await.unwrap().is_entry());
+    }
+
+    #[tokio::test]
+    async fn test_set_alignment() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let build = |set_alignment: SetAlignment| {
+            StoreBuilder::new(
+                "test",
+                memory.clone(),
+                metrics.clone(),
+                Engine::Small(
+                    SmallEngineOptions::new()
+                        .with_set_size(12 * 1024)
+                        .with_set_alignment(set_alignment),
+                ),
+            )
+            .with_memory_device(4 * 1024 * 1024)
+            .build()
+        };
+        for (set_alignment, expected) in [
+            (SetAlignment::Page, 12 * 1024),
+            (SetAlignment::PowerOfTwo, 16 * 1024),
+            (SetAlignment::Bytes(8 * 1024), 16 * 1024),
+            (SetAlignment::Bytes(32 * 1024), 32 * 1024),
+        ] {
+            let store = build(set_alignment).await.unwrap();
+            let set_size = match &store.inner.engine {
+                EngineEnum::Small(small) => small.set_size(),
+                _ => unreachable!(),
+            };
+            assert_eq!(set_size, expected, "{set_alignment:?}");
+
+            // The padding is used as set capacity.
+            store.enqueue(memory.insert(1, vec![1; expected - 1024]).piece(), true);
+            store.wait().await;
+            assert!(store.load(&1).await.unwrap().is_entry(), "{set_alignment:?}");
+        }
+
+        for set_alignment in [SetAlignment::Bytes(12 * 1024), SetAlignment::Bytes(1024)] {
+            let res = build(set_alignment).await;
+            assert!(matches!(res, Err(Error::InvalidConfig(_))), "{res:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mixed_engine_large_object_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Mixed {
+                ratio: 0.5,
+                large: LargeEngineOptions::new(),
+                small: SmallEngineOptions::new().with_set_size(4 * 1024),
+            },
+        )
+        .with_device_options(
+            DirectFsDeviceOptions::new(dir.path())
+                .with_capacity(4 * 1024 * 1024)
+                .with_file_size(1024 * 1024),
+        )
+        .with_large_object_threshold(1024)
+        .build()
+        .await
+        .unwrap();
+
+        // Entries larger than the set are routed to the large object disk cache instead of being dropped.
+        let small = memory.insert(1, vec![1; 100]);
+        let large = memory.insert(2, vec![2; 20 * 1024]);
+        store.enqueue(small.piece(), true);
+        store.enqueue(large.piece(), true);
+        store.wait().await;
+
+        assert_eq!(
+            store.load(&1).await.unwrap().entry().map(|(k, v, _)| (k, v)),
+            Some((1, vec![1; 100]))
+        );
+        assert_eq!(
+            store.load(&2).await.unwrap().entry().map(|(k, v, _)| (k, v)),
+            Some((2, vec![2; 20 * 1024]))
+        );
+        assert_eq!(store.load(&3).await.unwrap().entry().map(|(k, v, _)| (k, v)), None);
+    }
+
+    #[tokio::test]
+    async fn test_locate() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Mixed {
+                ratio: 0.5,
+                large: LargeEngineOptions::new(),
+                small: SmallEngineOptions::new().with_set_size(4 * 1024),
+            },
+        )
+        .with_device_options(
+            DirectFsDeviceOptions::new(dir.path())
+                .with_capacity(4 * 1024 * 1024)
+                .with_file_size(1024 * 1024),
+        )
+        .with_large_object_threshold(1024)
+        .build()
+        .await
+        .unwrap();
+
+        store.enqueue(memory.insert(1, vec![1; 100]).piece(), true);
+        store.enqueue(memory.insert(2, vec![2; 20 * 1024]).piece(), true);
+        store.wait().await;
+
+        assert!(matches!(
+            store.locate(&1).await.unwrap(),
+            EntryLocation::Small { class: 0, set } if set > 0
+        ));
+        assert!(matches!(store.locate(&2).await.unwrap(), EntryLocation::Large { .. }));
+        assert_eq!(store.locate(&3).await.unwrap(), EntryLocation::Absent);
+
+        store.delete(&1);
+        store.wait().await;
+        assert_eq!(store.locate(&1).await.unwrap(), EntryLocation::Absent);
+    }
+
+    #[tokio::test]
+    async fn test_load_raw() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Mixed {
+                ratio: 0.5,
+                large: LargeEngineOptions::new(),
+                small: SmallEngineOptions::new().with_set_size(4 * 1024),
+            },
+        )
+        .with_device_options(
+            DirectFsDeviceOptions::new(dir.path())
+                .with_capacity(4 * 1024 * 1024)
+                .with_file_size(1024 * 1024),
+        )
+        .with_compression(Compression::Zstd)
+        .with_large_object_threshold(1024)
+        .build()
+        .await
+        .unwrap();
+
+        store.enqueue(memory.insert(1, vec![1; 100]).piece(), true);
+        store.enqueue(memory.insert(2, vec![2; 20 * 1024]).piece(), true);
+        store.wait().await;
+
+        // The small object disk cache never compresses the values.
+        let (key, raw, _) = store.load_raw(&1).await.unwrap().entry().unwrap();
+        assert_eq!(key, 1);
+        assert_eq!(raw.compression(), Compression::None);
+        assert_eq!(raw.decode::<Vec<u8>>().unwrap(), vec![1; 100]);
+
+        let (key, raw, _) = store.load_raw(&2).await.unwrap().entry().unwrap();
+        assert_eq!(key, 2);
+        assert_eq!(raw.compression(), Compression::Zstd);
+        assert!(raw.bytes().len() < 20 * 1024);
+        let mut encoded = vec![];
+        vec![2u8; 20 * 1024].encode(&mut encoded).unwrap();
+        assert_eq!(raw.decompress().unwrap(), encoded);
+
+        assert!(store.load_raw(&3).await.unwrap().is_miss());
+    }
+
+    #[tokio::test]
+    async fn test_warm_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(100).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Small(SmallEngineOptions::new().with_set_size(4 * 1024)),
+        )
+        .with_device_options(
+            DirectFsDeviceOptions::new(dir.path())
+                .with_capacity(4 * 1024 * 1024)
+                .with_file_size(1024 * 1024),
+        )
+        .build()
+        .await
+        .unwrap();
+
+        for i in 0..10 {
+            store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+        }
+        store.wait().await;
+        for i in 10..20 {
+            store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+        }
+        store.wait().await;
+
+        // The most recently appended entry of the most recently updated set is loaded first.
+        let warm: Cache<u64, Vec<u8>> = CacheBuilder::new(100).build();
+        assert_eq!(store.warm_up(&warm, 1).await.unwrap(), 1);
+        assert_eq!(warm.usage(), 1);
+        assert!((10..20).any(|i| warm.contains(&i)));
+
+        // Loading stops once the in-memory cache is full, present entries are skipped.
+        let warm: Cache<u64, Vec<u8>> = CacheBuilder::new(5).with_shards(1).build();
+        warm.insert(0, vec![0; 100]);
+        assert_eq!(store.warm_up(&warm, 100).await.unwrap(), 4);
+        assert_eq!(warm.usage(), 5);
+        let warm: Cache<u64, Vec<u8>> = CacheBuilder::new(100).build();
+        assert_eq!(store.warm_up(&warm, 100).await.unwrap(), 20);
+        for i in 0..20 {
+            assert_eq!(warm.get(&i).unwrap().value(), &vec![i as u8; 100]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_iter_sorted() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(100).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Small(SmallEngineOptions::new().with_set_size(4 * 1024)),
+        )
+        .with_device_options(
+            DirectFsDeviceOptions::new(dir.path())
+                .with_capacity(4 * 1024 * 1024)
+                .with_file_size(1024 * 1024),
+        )
+        .build()
+        .await
+        .unwrap();
+
+        let mut hashes = vec![];
+        for i in 0..50 {
+            let entry = memory.insert(i, vec![i as u8; 100]);
+            hashes.push(entry.hash());
+            store.enqueue(entry.piece(), true);
+        }
+        store.wait().await;
+
+        let entries = store.iter_sorted().await.unwrap().collect::<Vec<_>>();
+        hashes.sort();
+        assert_eq!(entries.iter().map(|(hash, _, _)| *hash).collect::<Vec<_>>(), hashes);
+        for (hash, key, value) in entries {
+            assert_eq!(memory.hash(&key), hash);
+            assert_eq!(value, vec![key as u8; 100]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_device() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Small(SmallEngineOptions::new().with_set_size(4 * 1024)),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .build()
+        .await
+        .unwrap();
+
+        for i in 0..16 {
+            store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+        }
+        store.wait().await;
+        for i in 0..16 {
+            assert_eq!(
+                store.load(&i).await.unwrap().entry().map(|(k, v, _)| (k, v)),
+                Some((i, vec![i as u8; 100]))
+            );
+        }
+
+        store.delete(&3);
+        store.wait().await;
+        assert_eq!(store.load(&3).await.unwrap().entry().map(|(k, v, _)| (k, v)), None);
+        assert!(store.load(&4).await.unwrap().entry().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_set_router() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>, ModHasher> =
+            CacheBuilder::new(10).with_hash_builder(ModHasher::default()).build();
+        let router = Arc::new(PrefixRouter::default());
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Small(
+                SmallEngineOptions::new()
+                    .with_set_size(4 * 1024)
+                    .with_set_router(router.clone()),
+            ),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .build()
+        .await
+        .unwrap();
+
+        // Keys with the same prefix are co-located in the same set.
+        let keys = (0..16).chain(256..272).collect::<Vec<u64>>();
+        for &i in keys.iter() {
+            store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+        }
+        store.wait().await;
+        let routed = router.routed.load(Ordering::Relaxed);
+        assert!(routed >= keys.len());
+
+        for &i in keys.iter() {
+            assert_eq!(
+                store.load(&i).await.unwrap().entry().map(|(k, v, _)| (k, v)),
+                Some((i, vec![i as u8; 100]))
+            );
+        }
+        assert!(router.routed.load(Ordering::Relaxed) >= routed + keys.len());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics.clone(),
+            Engine::Small(SmallEngineOptions::new().with_set_size(4 * 1024).with_initial_sets(4)),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .build()
+        .await
+        .unwrap();
+        let mut events = Box::pin(store.subscribe());
+        store.resize_sets(8).await.unwrap();
+        // Resizing to the sets in use is a no-op without events.
+        store.resize_sets(8).await.unwrap();
+        store.resize_sets(4).await.unwrap();
+        assert_eq!(events.next().await, Some(StoreEvent::ResizeStarted { from: 4, to: 8 }));
+        assert_eq!(events.next().await, Some(StoreEvent::ResizeFinished { sets: 8 }));
+        assert_eq!(events.next().await, Some(StoreEvent::ResizeStarted { from: 8, to: 4 }));
+        assert_eq!(events.next().await, Some(StoreEvent::ResizeFinished { sets: 4 }));
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Large(LargeEngineOptions::new().with_reclaimers(1)),
+        )
+        .with_device_options(MemoryDeviceOptions::new(256 * 1024).with_region_size(64 * 1024))
+        .build()
+        .await
+        .unwrap();
+        let mut events = Box::pin(store.subscribe());
+        for i in 0..16 {
+            store.enqueue(memory.insert(i, vec![i as u8; 16 * 1024]).piece(), true);
+            store.wait().await;
+        }
+
+        let StoreEvent::CompactionStarted { region } = events.next().await.unwrap() else {
+            panic!("the first event must be a compaction start");
+        };
+        assert!(matches!(
+            events.next().await,
+            Some(StoreEvent::CompactionFinished { region: r, reinserted: 0, dropped }) if r == region && dropped > 0
+        ));
+        assert_eq!(events.next().await, Some(StoreEvent::RegionReclaimed { region }));
+    }
+
+    #[tokio::test]
+    async fn test_fill_report() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Small(SmallEngineOptions::new().with_set_size(4 * 1024).with_initial_sets(4)),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .build()
+        .await
+        .unwrap();
+
+        let report = store.fill_report().await.unwrap();
+        assert_eq!(report, vec![(1, 0.0), (2, 0.0), (3, 0.0), (4, 0.0)]);
+
+        for i in 0..64 {
+            store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+        }
+        store.wait().await;
+
+        let report = store.fill_report().await.unwrap();
+        assert_eq!(report.iter().map(|(sid, _)| *sid).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert!(report.iter().all(|(_, fill)| *fill > 0.0 && *fill <= 1.0));
+        // The sets in the set cache are reported the same.
+        for i in 0..64 {
+            store.load(&i).await.unwrap();
+        }
+        assert_eq!(store.fill_report().await.unwrap(), report);
+
+        let metrics = Arc::new(Metrics::noop());
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Large(LargeEngineOptions::new()),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .build()
+        .await
+        .unwrap();
+        assert!(matches!(store.fill_report().await, Err(Error::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_resize_sets() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Small(SmallEngineOptions::new().with_set_size(4 * 1024).with_initial_sets(4)),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .build()
+        .await
+        .unwrap();
+        let sets = || match &store.inner.engine {
+            EngineEnum::Small(small) => small.sets(),
+            _ => unreachable!(),
+        };
+
+        for i in 0..64 {
+            store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+        }
+        store.wait().await;
+
+        let check = || async {
+            for i in 0..64 {
+                assert_eq!(
+                    store.load(&i).await.unwrap().entry().map(|(k, v, _)| (k, v)),
+                    Some((i, vec![i as u8; 100]))
+                );
+            }
+        };
+
+        assert_eq!(sets(), 4);
+        let (res, _) = tokio::join!(store.resize_sets(13), check());
+        res.unwrap();
+        assert_eq!(sets(), 13);
+        check().await;
+
+        // Entries written after resizing are routed by the new sets.
+        store.delete(&7);
+        store.enqueue(memory.insert(64, vec![64; 100]).piece(), true);
+        store.wait().await;
+        assert!(store.load(&7).await.unwrap().entry().is_none());
+        assert!(store.load(&64).await.unwrap().entry().is_some());
+        store.enqueue(memory.insert(7, vec![7; 100]).piece(), true);
+        store.wait().await;
+
+        let (res, _) = tokio::join!(store.resize_sets(4), check());
+        res.unwrap();
+        assert_eq!(sets(), 4);
+        check().await;
+
+        assert!(matches!(store.resize_sets(3).await, Err(Error::OutOfRange { .. })));
+        assert!(matches!(store.resize_sets(1024).await, Err(Error::OutOfRange { .. })));
+        store.resize_sets(1023).await.unwrap();
+        check().await;
+    }
+
+    #[tokio::test]
+    async fn test_resize_sets_with_policy() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Small(
+                SmallEngineOptions::new()
+                    .with_set_size(4 * 1024)
+                    .with_initial_sets(1)
+                    .with_resize_policy(SetResizePolicy {
+                        fill_ratio: 0.5,
+                        false_positive_rate: 1.0,
+                        growth: 1.0,
+                    }),
+            ),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .build()
+        .await
+        .unwrap();
+        let sets = || match &store.inner.engine {
+            EngineEnum::Small(small) => small.sets(),
+            _ => unreachable!(),
+        };
+
+        for i in 0..64 {
+            store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+            store.wait().await;
+        }
+        for _ in 0..100 {
+            if sets() > 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(sets() > 1);
+        assert!(store.load(&63).await.unwrap().entry().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_index_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = dir.path().join("index");
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>, ModHasher> =
+            CacheBuilder::new(10).with_hash_builder(ModHasher::default()).build();
+
+        let build = || {
+            StoreBuilder::new(
+                "test",
+                memory.clone(),
+                metrics.clone(),
+                Engine::Small(
+                    SmallEngineOptions::new()
+                        .with_set_size(4 * 1024)
+                        .with_index_checkpoint(&checkpoint),
+                ),
+            )
+            .with_device_options(
+                DirectFsDeviceOptions::new(dir.path().join("device"))
+                    .with_capacity(4 * 1024 * 1024)
+                    .with_file_size(1024 * 1024),
+            )
+            .build()
+        };
+        let get = |store: Store<u64, Vec<u8>, ModHasher, foyer_memory::CacheProperties>, key: u64| async move {
+            store.load(&key).await.unwrap().entry().map(|(_, v, _)| v)
+        };
+        // 4096 sets, 4095 data sets, the same set as `5` with the mod hasher.
+        const KEY: u64 = 4100;
+
+        let store = build().await.unwrap();
+        for i in 0..32 {
+            store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+        }
+        store.wait().await;
+        store.checkpoint_index().await.unwrap();
+
+        // Updated after the checkpoint, and not closed.
+        store.delete(&3);
+        store.enqueue(memory.insert(KEY, vec![42; 100]).piece(), true);
+        store.wait().await;
+        drop(store);
+
+        let store = build().await.unwrap();
+        for i in (0..32).filter(|&i| i != 3) {
+            assert_eq!(get(store.clone(), i).await, Some(vec![i as u8; 100]));
+        }
+        assert_eq!(get(store.clone(), 3).await, None);
+        // The stale set is verified on the first read above and falls back to its own bloom filter.
+        assert_eq!(get(store.clone(), KEY).await, Some(vec![42; 100]));
+        store.close().await.unwrap();
+
+        // Restored from the checkpoint written on close.
+        let store = build().await.unwrap();
+        assert_eq!(get(store.clone(), KEY).await, Some(vec![42; 100]));
+        drop(store);
+
+        // Without a valid checkpoint, the sets are scanned in the background.
+        std::fs::remove_file(&checkpoint).unwrap();
+        let store = build().await.unwrap();
+        for _ in 0..100 {
+            if get(store.clone(), 31).await.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(get(store.clone(), 31).await, Some(vec![31; 100]));
+        assert_eq!(get(store.clone(), KEY).await, Some(vec![42; 100]));
+
+        let store = StoreBuilder::new("test", memory.clone(), metrics.clone(), Engine::small())
+            .with_memory_device(4 * 1024 * 1024)
+            .build()
+            .await
+            .unwrap();
+        assert!(matches!(store.checkpoint_index().await, Err(Error::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = dir.path().join("index");
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let build = || {
+            StoreBuilder::new(
+                "test",
+                memory.clone(),
+                metrics.clone(),
+                Engine::Small(
+                    SmallEngineOptions::new()
+                        .with_set_size(4 * 1024)
+                        .with_flush_interval(Duration::from_secs(3600))
+                        .with_index_checkpoint(&checkpoint),
+                ),
+            )
+            .with_device_options(
+                DirectFsDeviceOptions::new(dir.path().join("device"))
+                    .with_capacity(4 * 1024 * 1024)
+                    .with_file_size(1024 * 1024),
+            )
+            .build()
+        };
+
+        let store = build().await.unwrap();
+        for i in 0..32 {
+            store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+        }
+        // The entries are still in the write buffers.
+        let clone = store.clone();
+        store.shutdown().await.unwrap();
+        assert!(checkpoint.exists());
+
+        // New entries are ignored after shutdown.
+        clone.enqueue(memory.insert(32, vec![32; 100]).piece(), true);
+        clone.wait().await;
+        assert!(clone.load(&32).await.unwrap().is_miss());
+        drop(clone);
+
+        // Restored from the checkpoint, the entries are found without scanning the sets.
+        let store = build().await.unwrap();
+        for i in 0..32 {
+            assert_eq!(
+                store.load(&i).await.unwrap().entry().map(|(_, v, _)| v),
+                Some(vec![i as u8; 100])
+            );
+        }
+        assert!(store.load(&32).await.unwrap().is_miss());
+    }
+
+    #[tokio::test]
+    async fn test_chunking() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let build = |chunk_size: usize| {
+            StoreBuilder::new(
+                "test",
+                memory.clone(),
+                metrics.clone(),
+                Engine::Small(
+                    SmallEngineOptions::new()
+                        .with_set_size(4 * 1024)
+                        .with_chunking(chunk_size),
+                ),
+            )
+            .with_memory_device(4 * 1024 * 1024)
+            .build()
+        };
+        assert!(matches!(build(0).await, Err(Error::InvalidConfig(_))));
+        assert!(matches!(build(4 * 1024).await, Err(Error::InvalidConfig(_))));
+
+        let store = build(1024).await.unwrap();
+        let value = (0..10 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        store.enqueue(memory.insert(1, value.clone()).piece(), true);
+        store.enqueue(memory.insert(2, vec![2; 100]).piece(), true);
+        store.wait().await;
+
+        // The value larger than a set is striped across the sets and reassembled.
+        assert!(store.may_contains(&1));
+        assert_eq!(
+            store.load(&1).await.unwrap().entry().map(|(_, v, _)| v),
+            Some(value.clone())
+        );
+        let (key, raw, _) = store.load_raw(&1).await.unwrap().entry().unwrap();
+        assert_eq!(key, 1);
+        assert_eq!(raw.decode::<Vec<u8>>().unwrap(), value);
+        let values = store.get_many(&[1, 2, 3]).await;
+        assert_eq!(values[0].as_ref().unwrap(), &Some(value));
+        assert_eq!(values[1].as_ref().unwrap(), &Some(vec![2; 100]));
+        assert_eq!(values[2].as_ref().unwrap(), &None);
+
+        // A small value replaces the chunked one, and the other way around.
+        store.enqueue(memory.insert(1, vec![1; 100]).piece(), true);
+        store.enqueue(memory.insert(2, vec![2; 8 * 1024]).piece(), true);
+        store.wait().await;
+        assert_eq!(
+            store.load(&1).await.unwrap().entry().map(|(_, v, _)| v),
+            Some(vec![1; 100])
+        );
+        assert_eq!(
+            store.load(&2).await.unwrap().entry().map(|(_, v, _)| v),
+            Some(vec![2; 8 * 1024])
+        );
+
+        store.delete(&2);
+        store.wait().await;
+        assert!(store.load(&2).await.unwrap().is_miss());
+    }
+
+    #[tokio::test]
+    async fn test_chunking_iterations() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(100).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Small(SmallEngineOptions::new().with_set_size(4 * 1024).with_chunking(1024)),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .build()
+        .await
+        .unwrap();
+
+        let mut hashes = vec![];
+        for i in 0..4 {
+            store.enqueue(memory.insert(i, vec![i as u8; 10 * 1024]).piece(), true);
+        }
+        for i in 4..20 {
+            let entry = memory.insert(i, vec![i as u8; 100]);
+            hashes.push(entry.hash());
+            store.enqueue(entry.piece(), true);
+        }
+        store.wait().await;
+        assert_eq!(store.get(&0).await.unwrap(), Some(vec![0; 10 * 1024]));
+
+        // The chunks and the manifests are never decoded as entries.
+        let progress = store
+            .scan_with_options(ScanOptions::new().with_entries(true))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let mut keys = progress
+            .into_iter()
+            .flat_map(|p| {
+                assert_eq!(p.inserted_at.len(), p.entries.len());
+                p.entries
+            })
+            .map(|(key, value)| {
+                assert_eq!(value, vec![key as u8; 100]);
+                key
+            })
+            .collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, (4..20).collect::<Vec<_>>());
+
+        let warm: Cache<u64, Vec<u8>> = CacheBuilder::new(100).build();
+        assert_eq!(store.warm_up(&warm, 100).await.unwrap(), 16);
+        for i in 4..20 {
+            assert_eq!(warm.get(&i).unwrap().value(), &vec![i as u8; 100]);
+        }
+
+        let entries = store.iter_sorted().await.unwrap().collect::<Vec<_>>();
+        hashes.sort();
+        assert_eq!(entries.iter().map(|(hash, _, _)| *hash).collect::<Vec<_>>(), hashes);
+    }
+
+    #[tokio::test]
+    async fn test_serialize_buffer_budget() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Small(
+                SmallEngineOptions::new()
+                    .with_set_size(4 * 1024)
+                    .with_flush_interval(Duration::from_secs(3600)),
+            ),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .with_serialize_buffer_budget(1024)
+        .build()
+        .await
+        .unwrap();
+
+        // Most of the entries exceed the budget and wait for the serialized entries to be flushed.
+        for i in 0..64 {
+            store.enqueue(memory.insert(i, vec![i as u8; 200]).piece(), true);
+        }
+        // Deletions never wait.
+        store.delete(&0);
+        store.wait().await;
+
+        assert!(store.load(&0).await.unwrap().is_miss());
+        for i in 1..64 {
+            assert_eq!(
+                store.load(&i).await.unwrap().entry().map(|(_, v, _)| v),
+                Some(vec![i as u8; 200])
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serialize_buffer_budget_shared() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>, ModHasher> =
+            CacheBuilder::new(10).with_hash_builder(ModHasher::default()).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Small(
+                SmallEngineOptions::new()
+                    .with_set_size(4 * 1024)
+                    .with_flushers(2)
+                    .with_flush_interval(Duration::from_secs(3600)),
+            ),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .with_serialize_buffer_budget(1024)
+        .build()
+        .await
+        .unwrap();
+
+        // The entry of the first flusher holds most of the budget until its batch is flushed.
+        store.enqueue(memory.insert(0, vec![0; 600]).piece(), true);
+        // The entry of the second flusher waits for the budget, the first flusher flushes its batch for it without
+        // waiting for the flush interval.
+        store.enqueue(memory.insert(1, vec![1; 600]).piece(), true);
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while !store.load(&1).await.unwrap().is_entry() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            store.load(&0).await.unwrap().entry().map(|(_, v, _)| v),
+            Some(vec![0; 600])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bloom_filter_preload() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>, ModHasher> =
+            CacheBuilder::new(10).with_hash_builder(ModHasher::default()).build();
+
+        let build = |preload: bool| {
+            StoreBuilder::new(
+                "test",
+                memory.clone(),
+                metrics.clone(),
+                Engine::Small(
+                    SmallEngineOptions::new()
+                        .with_set_size(4 * 1024)
+                        .with_bloom_filter_preload(preload),
+                ),
+            )
+            .with_device_options(
+                DirectFsDeviceOptions::new(dir.path())
+                    .with_capacity(4 * 1024 * 1024)
+                    .with_file_size(1024 * 1024),
+            )
+            .build()
+        };
+        let get = |store: Store<u64, Vec<u8>, ModHasher, foyer_memory::CacheProperties>, key: u64| async move {
+            store.load(&key).await.unwrap().entry().map(|(_, v, _)| v)
+        };
+
+        let store = build(false).await.unwrap();
+        assert!(!store.is_bloom_filter_preload_enabled());
+        for i in 0..32 {
+            store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+        }
+        store.wait().await;
+        store.close().await.unwrap();
+
+        // The bloom filters are lost without the preload.
+        let store = build(false).await.unwrap();
+        assert_eq!(get(store.clone(), 7).await, None);
+        drop(store);
+
+        let store = build(true).await.unwrap();
+        assert!(store.is_bloom_filter_preload_enabled());
+        for i in 0..32 {
+            assert_eq!(get(store.clone(), i).await, Some(vec![i as u8; 100]));
+        }
+        assert_eq!(get(store.clone(), 32).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_quiesce() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Large(LargeEngineOptions::new()),
+        )
+        .with_device_options(
+            DirectFsDeviceOptions::new(dir.path())
+                .with_capacity(4 * 1024 * 1024)
+                .with_file_size(1024 * 1024),
+        )
+        .build()
+        .await
+        .unwrap();
+
+        let e1 = memory.insert(1, vec![1; 1024]);
+        store.enqueue(e1.piece(), true);
+        let guard = store.quiesce().await.unwrap();
+        // Pending writes are finished before quiesced.
+        assert!(store.load(&1).await.unwrap().is_entry());
+
+        // New writes are blocked until the guard is dropped.
+        let written = store.statistics().disk_write_bytes();
+        let e2 = memory.insert(2, vec![2; 1024]);
+        store.enqueue(e2.piece(), true);
+        assert!(tokio::time::timeout(Duration::from_millis(100), store.wait())
+            .await
+            .is_err());
+        assert_eq!(store.statistics().disk_write_bytes(), written);
+        assert!(store.load(&1).await.unwrap().is_entry());
+
+        drop(guard);
+        store.wait().await;
+        assert!(store.statistics().disk_write_bytes() > written);
+        assert!(store.load(&2).await.unwrap().is_entry());
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new("test", memory.clone(), metrics, Engine::small())
+            .with_device_options(
+                DirectFsDeviceOptions::new(dir.path())
+                    .with_capacity(4 * 1024 * 1024)
+                    .with_file_size(1024 * 1024),
+            )
+            .with_read_timeout(Duration::from_millis(100))
+            .build()
+            .await
+            .unwrap();
+
+        let e1 = memory.insert(1, vec![1; 64]);
+        store.enqueue(e1.piece(), true);
+        store.wait().await;
+        assert!(store.load(&1).await.unwrap().is_entry());
+
+        // Block the update of the set while it holds the set lock, to make loading from the set slow.
+        let guard = store.quiesce().await.unwrap();
+        store.enqueue(e1.piece(), true);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let res = store.load(&1).await;
+        assert!(matches!(res, Err(Error::Timeout(_))), "{res:?}");
+
+        drop(guard);
+        store.wait().await;
+        assert!(store.load(&1).await.unwrap().is_entry());
+    }
+
+    #[tokio::test]
+    async fn test_entry_hash_collision() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u128, String, ModHasher> =
+            CacheBuilder::new(10).with_hash_builder(ModHasher::default()).build();
+
+        let e1 = memory.insert(1, "foo".to_string());
+        let e2 = memory.insert(1 + 1 + u64::MAX as u128, "bar".to_string());
+
+        assert_eq!(memory.hash(e1.key()), memory.hash(e2.key()));
+
+        let store = StoreBuilder::new("test", memory, metrics, Engine::Large(LargeEngineOptions::default()))
+            .with_device_options(
+                DirectFsDeviceOptions::new(dir.path())
+                    .with_capacity(4 * 1024 * 1024)
+                    .with_file_size(1024 * 1024),
+            )
+            .build()
+            .await
+            .unwrap();
+
+        store.enqueue(e1.piece(), true);
+        store.enqueue(e2.piece(), true);
+        store.wait().await;
+
+        let l1 = store.load(e1.key()).await.unwrap();
+        let l2 = store.load(e2.key()).await.unwrap();
+
+        assert!(matches!(l1, Load::Miss));
+        assert!(matches!(l2, Load::Entry { .. }));
+        assert_eq!(l2.entry().unwrap().1, "bar");
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_raw() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let build = |engine: Engine, path: &str| {
+            StoreBuilder::new("test", memory.clone(), metrics.clone(), engine)
+                .with_device_options(
+                    DirectFsDeviceOptions::new(dir.path().join(path))
+                        .with_capacity(4 * 1024 * 1024)
+                        .with_file_size(1024 * 1024),
+                )
+                .build()
+        };
+
+        let encode = |key: u64, value: &Vec<u8>| {
+            let mut k = vec![];
+            key.encode(&mut k).unwrap();
+            let mut v = vec![];
+            value.encode(&mut v).unwrap();
+            (Bytes::from(k), Bytes::from(v))
+        };
+
+        let store = build(Engine::Small(SmallEngineOptions::new()), "small").await.unwrap();
+        let value = vec![b'x'; 100];
+        let (k, v) = encode(42, &value);
+        assert!(store.enqueue_raw(memory.hash(&42u64), k, v, true));
+        store.wait().await;
+        let (key, v, _) = store.load(&42u64).await.unwrap().entry().unwrap();
+        assert_eq!(key, 42);
+        assert_eq!(v, value);
+
+        let store = build(Engine::Large(LargeEngineOptions::new()), "large").await.unwrap();
+        let (k, v) = encode(42, &value);
+        assert!(!store.enqueue_raw(memory.hash(&42u64), k, v, true));
+
+        // Raw entries are routed by the large object threshold, a raw entry the large object disk cache ignores keeps
+        // the entry written to it.
+        let store = build(
+            Engine::Mixed {
+                ratio: 0.5,
+                large: LargeEngineOptions::new(),
+                small: SmallEngineOptions::new().with_set_size(4 * 1024),
+            },
+            "mixed",
+        )
+        .await
+        .unwrap();
+        let (k, v) = encode(1, &value);
+        assert!(store.enqueue_raw(memory.hash(&1u64), k, v, true));
+        store.enqueue(memory.insert(2, vec![2; 20 * 1024]).piece(), true);
+        store.wait().await;
+        let (k, v) = encode(2, &vec![b'x'; 20 * 1024]);
+        assert!(!store.enqueue_raw(memory.hash(&2u64), k, v, true));
+        store.wait().await;
+        assert_eq!(store.get(&1).await.unwrap(), Some(value));
+        assert_eq!(store.get(&2).await.unwrap(), Some(vec![2; 20 * 1024]));
+    }
+
+    #[derive(Debug, Default)]
+    struct CountingLoader {
+        calls: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl Loader<u64, u64> for CountingLoader {
+        fn load(&self, key: &u64) -> futures_core::future::BoxFuture<'static, anyhow::Result<Option<u64>>> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let key = *key;
+            Box::pin(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                Ok((key % 2 == 0).then_some(key * 10))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_with_loader() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, u64> = CacheBuilder::new(10).build();
+        let loader = Arc::new(CountingLoader::default());
+        let calls = loader.calls.clone();
+
+        let store = StoreBuilder::new("test", memory, metrics, Engine::Large(LargeEngineOptions::new()))
+            .with_device_options(
+                DirectFsDeviceOptions::new(dir.path())
+                    .with_capacity(4 * 1024 * 1024)
+                    .with_file_size(1024 * 1024),
+            )
+            .with_loader(loader)
+            .build()
+            .await
+            .unwrap();
+
+        let gets = (0..8).map(|_| {
+            let store = store.clone();
+            tokio::spawn(async move { store.get(&42).await.unwrap() })
+        });
+        for res in futures_util::future::join_all(gets).await {
+            assert_eq!(res.unwrap(), Some(420));
+        }
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+        // The loaded entry is inserted into the disk cache.
+        store.wait().await;
+        assert_eq!(store.load(&42).await.unwrap().entry().unwrap().1, 420);
+        assert_eq!(store.get(&42).await.unwrap(), Some(420));
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+        assert_eq!(store.get(&43).await.unwrap(), None);
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 2);
+    }
+
+    /// Loader that records the max count of the concurrent loads.
+    #[derive(Debug, Default)]
+    struct ConcurrencyLoader {
+        loading: Arc<std::sync::atomic::AtomicUsize>,
+        max: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl Loader<u64, u64> for ConcurrencyLoader {
+        fn load(&self, _: &u64) -> futures_core::future::BoxFuture<'static, anyhow::Result<Option<u64>>> {
+            let loading = self.loading.clone();
+            let max = self.max.clone();
+            Box::pin(async move {
+                max.fetch_max(
+                    loading.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1,
+                    std::sync::atomic::Ordering::SeqCst,
+                );
+                tokio::task::yield_now().await;
+                loading.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(None)
+            })
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_get_with_loader_single_flight() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, u64> = CacheBuilder::new(10).build();
+        let loader = Arc::new(ConcurrencyLoader::default());
+        let max = loader.max.clone();
+
+        let store = StoreBuilder::new("test", memory, metrics, Engine::Large(LargeEngineOptions::new()))
+            .with_loader(loader)
+            .build()
+            .await
+            .unwrap();
+
+        // The missed key is never cached, so each wave of gets races a new leader against the completion of the
+        // previous one.
+        let gets = (0..8).map(|_| {
+            let store = store.clone();
+            tokio::spawn(async move {
+                for _ in 0..10000 {
+                    assert_eq!(store.get(&43).await.unwrap(), None);
+                }
+            })
+        });
+        for res in futures_util::future::join_all(gets).await {
+            res.unwrap();
+        }
+        assert_eq!(max.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_many() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, u64, ModHasher> = CacheBuilder::new(10).with_hash_builder(ModHasher::default()).build();
+        let loader = Arc::new(CountingLoader::default());
+        let calls = loader.calls.clone();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Small(
+                SmallEngineOptions::new()
+                    .with_set_size(4 * 1024)
+                    .with_set_router(Arc::new(PrefixRouter::default())),
+            ),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .with_loader(loader)
+        .build()
+        .await
+        .unwrap();
+
+        // Keys with the same prefix are co-located in the same set.
+        let cached = (0..16).chain(256..272).collect::<Vec<u64>>();
+        for &i in cached.iter() {
+            store.enqueue(memory.insert(i, i * 10 + 1).piece(), true);
+        }
+        store.wait().await;
+        assert!(store.get_many(&[]).await.is_empty());
+
+        // Each set is read at most once.
+        let reads = store.statistics().disk_read_ios();
+        let res = store.get_many(&cached).await;
+        assert!(store.statistics().disk_read_ios() - reads <= 2);
+        assert_eq!(res.len(), cached.len());
+
+        // Misses go to the loader, which loads even keys only.
+        let keys = cached.iter().copied().chain([1000, 1001, 5]).collect::<Vec<u64>>();
+        let res = store.get_many(&keys).await;
+        assert_eq!(res.len(), keys.len());
+        for (key, r) in keys.iter().zip(res) {
+            let expected = match key {
+                1000 => Some(10000),
+                1001 => None,
+                key => Some(key * 10 + 1),
+            };
+            assert_eq!(r.unwrap(), expected, "key: {key}");
+        }
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 2);
+
+        // Deleted keys are missed.
+        store.delete(&256);
+        store.wait().await;
+        let res = store.get_many(&[256, 257]).await;
+        assert_eq!(
+            res.into_iter().map(|r| r.unwrap()).collect::<Vec<_>>(),
+            vec![Some(2560), Some(2571)]
+        );
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 3);
+    }
+
+    /// Routes the hashes by the hashes and counts the routed hashes of each set size class.
+    #[derive(Debug, Default)]
+    struct ClassRouter {
+        routed: [AtomicUsize; 2],
+    }
+
+    impl SetRouter for ClassRouter {
+        fn route(&self, hash: u64, sets: usize) -> SetId {
+            hash % sets as SetId
+        }
+
+        fn route_in_class(&self, class: usize, hash: u64, sets: usize) -> SetId {
+            self.routed[class].fetch_add(1, Ordering::Relaxed);
+            self.route(hash, sets)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_size_classes() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+        let router = Arc::new(ClassRouter::default());
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Small(
+                SmallEngineOptions::new()
+                    .with_set_size_classes([SetSizeClass::new(64 * 1024, 1.0), SetSizeClass::new(4 * 1024, 1.0)])
+                    .with_set_router(router.clone()),
+            ),
+        )
+        .with_device_options(MemoryDeviceOptions::new(4 * 1024 * 1024).with_region_size(256 * 1024))
+        .build()
+        .await
+        .unwrap();
+        let EngineEnum::Small(small) = &store.inner.engine else {
+            unreachable!()
+        };
+        // 8 regions of 4 KiB sets and 8 regions of 64 KiB sets, the meta sets excluded.
+        assert_eq!(small.set_size(), 4 * 1024);
+        assert_eq!(small.sets(), 511 + 31);
+        assert!(store.resize_sets(511).await.is_err());
+        assert!(store.fill_report().await.is_err());
+
+        // Small entries go to the 4 KiB sets, and large ones to the 64 KiB sets.
+        let value = |i: u64| match i < 32 {
+            true => vec![i as u8; 100],
+            false => vec![i as u8; 16 * 1024],
+        };
+        for i in 0..40 {
+            store.enqueue(memory.insert(i, value(i)).piece(), true);
+        }
+        store.wait().await;
+        assert!(router.routed[0].load(Ordering::Relaxed) > 0);
+        assert!(router.routed[1].load(Ordering::Relaxed) > 0);
+        for i in 0..40 {
+            assert_eq!(store.load(&i).await.unwrap().entry().unwrap().1, value(i));
+        }
+
+        // Updates of another size move the entries to another class.
+        store.enqueue(memory.insert(0, vec![0; 16 * 1024]).piece(), true);
+        store.enqueue(memory.insert(32, vec![32; 100]).piece(), true);
+        store.delete(&1);
+        store.delete(&33);
+        store.wait().await;
+        let res = store.get_many(&[0, 1, 32, 33, 2, 34]).await;
+        assert_eq!(
+            res.into_iter().map(|r| r.unwrap()).collect::<Vec<_>>(),
+            vec![
+                Some(vec![0; 16 * 1024]),
+                None,
+                Some(vec![32; 100]),
+                None,
+                Some(value(2)),
+                Some(value(34))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics.clone(),
+            Engine::Small(
+                SmallEngineOptions::new()
+                    .with_set_size(4 * 1024)
+                    .with_delete_mode(SetDeleteMode::Tombstone { ratio: 0.5 })
+                    .with_flush_interval(Duration::from_secs(60)),
+            ),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .build()
+        .await
+        .unwrap();
+
+        for i in 0..4 {
+            store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+        }
+        // The buffered writes are flushed before removing.
+        assert!(store.remove(&1).await.unwrap());
+        assert!(!store.remove(&1).await.unwrap());
+        assert!(store.secure_remove(&2).await.unwrap());
+        assert!(!store.load(&1).await.unwrap().is_entry());
+        assert!(!store.load(&2).await.unwrap().is_entry());
+        assert!(store.load(&0).await.unwrap().is_entry());
+        assert!(store.load(&3).await.unwrap().is_entry());
+
+        let store = StoreBuilder::new("test", memory.clone(), metrics, Engine::large())
+            .with_memory_device(4 * 1024 * 1024)
+            .build()
+            .await
+            .unwrap();
+        assert!(matches!(store.remove(&0).await, Err(Error::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_entry_inserted_at() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics,
+            Engine::Small(
+                SmallEngineOptions::new()
+                    .with_set_size(4 * 1024)
+                    .with_entry_inserted_at(true),
+            ),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .build()
+        .await
+        .unwrap();
+
+        let before = SystemTime::now() - Duration::from_millis(1);
+        for i in 0..64 {
+            store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+        }
+        store.wait().await;
+        let after = SystemTime::now() + Duration::from_millis(1);
+
+        for i in 0..64 {
+            assert_eq!(store.get(&i).await.unwrap(), Some(vec![i as u8; 100]));
+        }
+        let progress = store
+            .scan_with_options(ScanOptions::new().with_entries(true))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(progress.iter().map(|p| p.entries.len()).sum::<usize>(), 64);
+        for p in progress {
+            assert_eq!(p.inserted_at.len(), p.entries.len());
+            assert!(p
+                .inserted_at
+                .iter()
+                .all(|t| t.is_some_and(|t| (before..after).contains(&t))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_with_progress() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        let store = StoreBuilder::new(
+            "test",
+            memory.clone(),
+            metrics.clone(),
+            Engine::Small(SmallEngineOptions::new().with_set_size(4 * 1024)),
+        )
+        .with_memory_device(4 * 1024 * 1024)
+        .build()
+        .await
+        .unwrap();
+        for i in 0..64 {
+            store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+        }
+        store.wait().await;
+
+        let progress = store.scan_with_progress().try_collect::<Vec<_>>().await.unwrap();
+        let last = progress.last().unwrap();
+        assert_eq!(progress.len(), last.sets_total);
+        assert_eq!(last.sets_done, last.sets_total);
+        assert_eq!(last.entries_seen, 64);
+        assert!(last.bytes_seen > 64 * 100);
+        assert!(progress.iter().all(|p| p.entries.is_empty()));
+
+        let mut entries = store
+            .scan_with_options(ScanOptions::new().with_entries(true).with_iops(100000))
+            .map_ok(|p| p.entries)
+            .try_concat()
+            .await
+            .unwrap();
+        entries.sort();
+        assert_eq!(entries, (0..64).map(|i| (i, vec![i as u8; 100])).collect::<Vec<_>>());
+        assert!(store
+            .scan_with_options(ScanOptions::new().with_entries(true))
+            .map_ok(|p| p.inserted_at)
+            .try_concat()
+            .await
+            .unwrap()
+            .iter()
+            .all(|t| t.is_none()));
+
+        // Resume from the middle of the scan.
+        let start = last.sets_total / 2;
+        let resumed = store
+            .scan_with_options(ScanOptions::new().with_start(start))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(resumed.len(), last.sets_total - start);
+        assert_eq!(resumed[0].sets_done, start + 1);
+        assert_eq!(
+            resumed.last().unwrap().entries_seen,
+            last.entries_seen - progress[start - 1].entries_seen
+        );
+
+        let store = StoreBuilder::new("test", memory.clone(), metrics, Engine::large())
+            .with_memory_device(4 * 1024 * 1024)
+            .build()
+            .await
+            .unwrap();
+        let res = store.scan_with_progress().collect::<Vec<_>>().await;
+        assert!(matches!(res.as_slice(), [Err(Error::InvalidConfig(_))]));
+    }
+
+    #[tokio::test]
+    async fn test_disk_hasher() {
+        let metrics = Arc::new(Metrics::noop());
+        let memory: Cache<u64, Vec<u8>> = CacheBuilder::new(10).build();
+
+        for engine in [
+            Engine::Small(SmallEngineOptions::new().with_set_size(4 * 1024)),
+            Engine::large(),
+        ] {
+            let store = StoreBuilder::new("test", memory.clone(), metrics.clone(), engine)
+                .with_memory_device(4 * 1024 * 1024)
+                .with_disk_hasher(ModHasher::default())
+                .build()
+                .await
+                .unwrap();
+            assert_eq!(store.hash(&7u64), ModHasher::default().hash_one(7u64));
+            assert_ne!(store.hash(&7u64), memory.hash_builder().hash_one(7u64));
+
+            for i in 0..16 {
+                store.enqueue(memory.insert(i, vec![i as u8; 100]).piece(), true);
+            }
+            store.wait().await;
+            for i in 0..16 {
+                assert!(store.may_contains(&i));
+                assert_eq!(store.get(&i).await.unwrap(), Some(vec![i as u8; 100]));
+            }
+            store.delete(&3);
+            store.wait().await;
+            assert!(store.get(&3).await.unwrap().is_none());
+        }
     }
 }
@@ -83,16 +83,31 @@ impl Recorder {
 
     /// Get the hash set of the remaining hash at the moment.
     pub fn remains(&self) -> HashSet<u64> {
-        let records = self.dump();
+        self.at_step(usize::MAX)
+    }
+
+    /// Replay the record entries as the sequence of state transitions.
+    ///
+    /// Each transition is the entry hash and whether the entry is resident after it (`true` for admission, `false`
+    /// for eviction).
+    pub fn replay(&self) -> Vec<(u64, bool)> {
+        self.dump()
+            .into_iter()
+            .map(|record| match record {
+                Record::Admit(hash) => (hash, true),
+                Record::Evict(hash) => (hash, false),
+            })
+            .collect()
+    }
+
+    /// Get the hash set of the resident hash after the first `n` record entries.
+    pub fn at_step(&self, n: usize) -> HashSet<u64> {
         let mut res = HashSet::default();
-        for record in records {
-            match record {
-                Record::Admit(key) => {
-                    res.insert(key);
-                }
-                Record::Evict(key) => {
-                    res.remove(&key);
-                }
+        for (hash, resident) in self.replay().into_iter().take(n) {
+            if resident {
+                res.insert(hash);
+            } else {
+                res.remove(&hash);
             }
         }
         res
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{borrow::Cow, fmt::Debug, sync::Arc};
+use std::{borrow::Cow, fmt::Debug, sync::Arc, time::Duration};
 
 #[cfg(feature = "tracing")]
 use foyer_common::tracing::TracingOptions;
@@ -22,7 +22,10 @@ use foyer_common::{
     metrics::Metrics,
 };
 use foyer_memory::{Cache, CacheBuilder, EvictionConfig, Weighter};
-use foyer_storage::{AdmissionPicker, Compression, DeviceOptions, Engine, RecoverMode, RuntimeOptions, StoreBuilder};
+use foyer_storage::{
+    AdmissionPicker, Compression, DeviceOptions, Engine, RecoverMode, ResidentAdmissionPicker, RetryPolicy,
+    RuntimeOptions, StoreBuilder,
+};
 use mixtrics::{metrics::BoxedRegistry, registry::noop::NoopMetricsRegistry};
 
 use super::cache::{HybridCacheOptions, HybridCachePipe};
@@ -34,6 +37,7 @@ pub struct HybridCacheBuilder<K, V> {
     options: HybridCacheOptions,
     event_listener: Option<Arc<dyn EventListener<Key = K, Value = V>>>,
     registry: BoxedRegistry,
+    metrics_labels: Vec<(String, String)>,
 }
 
 impl<K, V> Default for HybridCacheBuilder<K, V> {
@@ -50,6 +54,7 @@ impl<K, V> HybridCacheBuilder<K, V> {
             options: HybridCacheOptions::default(),
             event_listener: None,
             registry: Box::new(NoopMetricsRegistry),
+            metrics_labels: vec![],
         }
     }
 }
@@ -102,19 +107,37 @@ impl<K, V> HybridCacheBuilder<K, V> {
 
     /// Set metrics registry.
     ///
+    /// To export the metrics to Prometheus, use `mixtrics::registry::prometheus::PrometheusMetricsRegistry`. See the
+    /// `export_metrics_prometheus_hyper` example.
+    ///
     /// Default: [`NoopMetricsRegistry`].
     pub fn with_metrics_registry(mut self, registry: BoxedRegistry) -> HybridCacheBuilder<K, V> {
         self.registry = registry;
         self
     }
 
+    /// Set extra static labels attached to all metrics of the hybrid cache, besides the `name` label.
+    ///
+    /// e.g. `[("cache", "user-sessions"), ("tier", "ssd")]`. Hybrid caches that share a metrics registry must use the
+    /// same label keys.
+    ///
+    /// Default: No extra labels.
+    pub fn with_metrics_labels(mut self, labels: Vec<(String, String)>) -> Self {
+        self.metrics_labels = labels;
+        self
+    }
+
     /// Continue to modify the in-memory cache configurations.
     pub fn memory(self, capacity: usize) -> HybridCacheBuilderPhaseMemory<K, V, DefaultHasher>
     where
         K: StorageKey,
         V: StorageValue,
     {
-        let metrics = Arc::new(Metrics::new(self.name.clone(), &self.registry));
+        let metrics = Arc::new(Metrics::with_labels(
+            self.name.clone(),
+            self.metrics_labels,
+            &self.registry,
+        ));
         let mut builder = CacheBuilder::new(capacity)
             .with_name(self.name.clone())
             .with_metrics(metrics.clone());
@@ -175,6 +198,19 @@ where
         }
     }
 
+    /// Set how many entries to evict at least when the in-memory cache runs out of capacity.
+    ///
+    /// See [`CacheBuilder::with_eviction_batch`].
+    pub fn with_eviction_batch(self, eviction_batch: usize) -> Self {
+        let builder = self.builder.with_eviction_batch(eviction_batch);
+        HybridCacheBuilderPhaseMemory {
+            name: self.name,
+            options: self.options,
+            metrics: self.metrics,
+            builder,
+        }
+    }
+
     /// Set in-memory cache hash builder.
     pub fn with_hash_builder<OS>(self, hash_builder: OS) -> HybridCacheBuilderPhaseMemory<K, V, OS>
     where
@@ -245,6 +281,20 @@ where
         }
     }
 
+    /// Use a memory device with the given capacity in bytes for the disk cache store.
+    ///
+    /// The entries are kept in memory with the disk cache format and are lost once the hybrid cache is dropped.
+    pub fn with_memory_device(self, capacity: usize) -> Self {
+        let builder = self.builder.with_memory_device(capacity);
+        Self {
+            name: self.name,
+            options: self.options,
+            metrics: self.metrics,
+            memory: self.memory,
+            builder,
+        }
+    }
+
     /// Enable/disable `sync` after writes.
     ///
     /// Default: `false`.
@@ -259,6 +309,127 @@ where
         }
     }
 
+    /// Enable/disable direct I/O (`O_DIRECT`) of the device, overriding the option of the device options.
+    ///
+    /// The set size of the small object disk cache must be a multiple of 4 KiB with direct I/O. If the file system
+    /// doesn't support direct I/O, buffered I/O is used instead.
+    ///
+    /// Default: the option of the device options, which enables direct I/O by default.
+    pub fn with_direct_io(self, direct_io: bool) -> Self {
+        let builder = self.builder.with_direct_io(direct_io);
+        Self {
+            name: self.name,
+            options: self.options,
+            metrics: self.metrics,
+            memory: self.memory,
+            builder,
+        }
+    }
+
+    /// Set the threshold of the estimated serialized size for routing entries to the large object disk cache.
+    ///
+    /// Only takes effect with [`Engine::Mixed`]. Entries smaller than the threshold go to the small object disk cache,
+    /// others go to the large object disk cache, which is checked first on lookup.
+    ///
+    /// Default: [`Engine::OBJECT_SIZE_THRESHOLD`].
+    pub fn with_large_object_threshold(self, threshold: usize) -> Self {
+        let builder = self.builder.with_large_object_threshold(threshold);
+        Self {
+            name: self.name,
+            options: self.options,
+            metrics: self.metrics,
+            memory: self.memory,
+            builder,
+        }
+    }
+
+    /// Set the deadline of loading an entry from the disk cache store.
+    ///
+    /// A load that exceeds the deadline is taken as a disk cache miss instead of blocking the query. The disk read in
+    /// flight keeps running in the background and its result is discarded.
+    ///
+    /// Default: no deadline.
+    pub fn with_read_timeout(self, timeout: Duration) -> Self {
+        let builder = self.builder.with_read_timeout(timeout);
+        Self {
+            name: self.name,
+            options: self.options,
+            metrics: self.metrics,
+            memory: self.memory,
+            builder,
+        }
+    }
+
+    /// Set the max count of the concurrent operations of the disk cache device.
+    ///
+    /// Reads, writes and flushes share the budget, an operation waits in the queue once the limit is reached.
+    ///
+    /// Default: no limit.
+    pub fn with_io_concurrency(self, io_concurrency: usize) -> Self {
+        let builder = self.builder.with_io_concurrency(io_concurrency);
+        Self {
+            name: self.name,
+            options: self.options,
+            metrics: self.metrics,
+            memory: self.memory,
+            builder,
+        }
+    }
+
+    /// Set the retry policy of the disk cache device operations on transient errors.
+    ///
+    /// A failed operation is retried with exponential backoff on retryable errors only, see
+    /// [`RetryPolicy::is_retryable`].
+    ///
+    /// Default: no retry.
+    pub fn with_io_retry(self, policy: RetryPolicy) -> Self {
+        let builder = self.builder.with_io_retry(policy);
+        Self {
+            name: self.name,
+            options: self.options,
+            metrics: self.metrics,
+            memory: self.memory,
+            builder,
+        }
+    }
+
+    /// Set the space in bytes of the large object disk cache reserved as the headroom for reclaiming.
+    ///
+    /// New entries are rejected while the free space is not greater than the reserved space, only the entries
+    /// reinserted while reclaiming can use it.
+    ///
+    /// Default: `0`, no reserved space.
+    pub fn with_reserved_space(self, bytes: usize) -> Self {
+        let builder = self.builder.with_reserved_space(bytes);
+        Self {
+            name: self.name,
+            options: self.options,
+            metrics: self.metrics,
+            memory: self.memory,
+            builder,
+        }
+    }
+
+    /// Set the hasher of the disk cache store, independent of the hasher of the in-memory cache.
+    ///
+    /// The disk hasher routes and indexes the entries on disk, it must return the same hashes across runs to recover
+    /// the entries. A dedicated disk hasher hashes each written key twice.
+    ///
+    /// Default: the hasher of the in-memory cache.
+    pub fn with_disk_hasher<H>(self, hasher: H) -> Self
+    where
+        H: HashBuilder + Debug,
+    {
+        let builder = self.builder.with_disk_hasher(hasher);
+        Self {
+            name: self.name,
+            options: self.options,
+            metrics: self.metrics,
+            memory: self.memory,
+            builder,
+        }
+    }
+
     /// Set the recover mode for the disk cache store.
     ///
     /// See more in [`RecoverMode`].
@@ -291,6 +462,24 @@ where
         }
     }
 
+    /// Set the resident admission picker for the disk cache store.
+    ///
+    /// The resident admission picker is consulted for the entries populated from the disk cache, with the hint if the
+    /// entry is probably still resident, e.g. [`crate::RejectResidentPicker`] skips re-writing them. Updates of existing
+    /// keys are not affected.
+    ///
+    /// Default: No resident admission picker.
+    pub fn with_resident_admission_picker(self, picker: Arc<dyn ResidentAdmissionPicker>) -> Self {
+        let builder = self.builder.with_resident_admission_picker(picker);
+        Self {
+            name: self.name,
+            options: self.options,
+            metrics: self.metrics,
+            memory: self.memory,
+            builder,
+        }
+    }
+
     /// Set the compression algorithm of the disk cache store.
     ///
     /// Default: [`Compression::None`].
@@ -36,7 +36,7 @@ use foyer_common::{
     properties::{Hint, Location, Properties, Source},
 };
 use foyer_memory::{Cache, CacheEntry, Fetch, FetchContext, FetchState, Piece, Pipe};
-use foyer_storage::{IoThrottler, Load, Statistics, Store};
+use foyer_storage::{EntryLocation, IoThrottler, Load, Statistics, Store};
 use pin_project::pin_project;
 use serde::{Deserialize, Serialize};
 use tokio::sync::oneshot;
@@ -193,6 +193,8 @@ where
     S: HashBuilder + Debug,
 {
     store: Store<K, V, S, HybridCacheProperties>,
+    /// Throttler of [`Pipe::send_async`], shared by the calls to pace the evicted entries by the write throttle.
+    throttler: Arc<IoThrottler>,
 }
 
 impl<K, V, S> Debug for HybridCachePipe<K, V, S>
@@ -213,7 +215,9 @@ where
     S: HashBuilder + Debug,
 {
     pub fn new(store: Store<K, V, S, HybridCacheProperties>) -> Self {
-        Self { store }
+        let throttle = store.throttle();
+        let throttler = Arc::new(IoThrottler::new(throttle.write_throughput, throttle.write_iops));
+        Self { store, throttler }
     }
 }
 
@@ -259,6 +263,29 @@ where
             }
         })
     }
+
+    fn send_async(
+        &self,
+        pieces: Vec<Piece<Self::Key, Self::Value, HybridCacheProperties>>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let store = self.store.clone();
+        let throttler = self.throttler.clone();
+        Box::pin(async move {
+            for piece in pieces {
+                match piece.properties().location() {
+                    Location::InMem => continue,
+                    Location::Default | Location::OnDisk => {}
+                }
+                let bytes = store.entry_estimated_size(piece.key(), piece.value());
+                let ios = store.throttle().iops_counter.count(bytes);
+                let wait = throttler.consume(bytes as _, ios as _);
+                if !wait.is_zero() {
+                    tokio::time::sleep(wait).await
+                }
+                store.enqueue(piece, false);
+            }
+        })
+    }
 }
 
 /// A cached entry holder of the hybrid cache.
@@ -554,7 +581,7 @@ where
         #[cfg(not(feature = "tracing"))]
         let load = self.inner.storage.load(key);
 
-        let entry = match load.await? {
+        let entry = match load_or_miss(load.await)? {
             Load::Entry { key, value, populated } => {
                 record_hit();
                 Some(self.inner.memory.insert_with_properties(
@@ -601,7 +628,7 @@ where
             || {
                 let store = self.inner.storage.clone();
                 async move {
-                    match store.load(&key).await.map_err(anyhow::Error::from) {
+                    match load_or_miss(store.load(&key).await) {
                         Ok(Load::Entry {
                             key: _,
                             value,
@@ -699,6 +726,26 @@ where
         self.inner.memory.contains(key) || self.inner.storage.may_contains(key)
     }
 
+    /// Locate the entry with the given key in the tiers of the hybrid cache without deserializing it.
+    ///
+    /// Returns [`EntryLocation::Memory`] if the entry is in the in-memory cache, otherwise where it is in the disk
+    /// cache, see [`Store::locate`].
+    pub async fn locate<Q>(&self, key: &Q) -> anyhow::Result<EntryLocation>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        if self.inner.memory.contains(key) {
+            return Ok(EntryLocation::Memory);
+        }
+        Ok(self.inner.storage.locate(key).await?)
+    }
+
+    /// Load up to `n` of the most recently written entries from the disk cache to the in-memory cache, e.g. after a
+    /// restart. Returns the count of the loaded entries, see [`Store::warm_up`] for the selection.
+    pub async fn warm_up(&self, n: usize) -> anyhow::Result<usize> {
+        Ok(self.inner.storage.warm_up(&self.inner.memory, n).await?)
+    }
+
     /// Clear the hybrid cache.
     pub async fn clear(&self) -> anyhow::Result<()> {
         self.inner.memory.clear();
@@ -756,6 +803,19 @@ where
     }
 }
 
+/// Take a disk cache load that exceeds the read timeout as a miss, so a degraded device slows down the hybrid cache
+/// queries instead of failing them. See
+/// [`HybridCacheBuilderPhaseStorage::with_read_timeout`](crate::HybridCacheBuilderPhaseStorage::with_read_timeout).
+fn load_or_miss<K, V>(res: foyer_storage::Result<Load<K, V>>) -> anyhow::Result<Load<K, V>> {
+    match res {
+        Err(foyer_storage::Error::Timeout(timeout)) => {
+            tracing::warn!(?timeout, "[hybrid]: disk cache load timed out, take it as a miss");
+            Ok(Load::Miss)
+        }
+        res => res.map_err(anyhow::Error::from),
+    }
+}
+
 #[derive(Debug)]
 enum ObtainFetchError {
     Throttled,
@@ -891,7 +951,7 @@ where
                 let runtime = self.storage().runtime().clone();
 
                 async move {
-                    let throttled = match store.load(&key).await.map_err(anyhow::Error::from) {
+                    let throttled = match load_or_miss(store.load(&key).await) {
                         Ok(Load::Entry {
                             key: _,
                             value,
@@ -957,7 +1017,7 @@ where
 #[cfg(test)]
 mod tests {
 
-    use std::{path::Path, sync::Arc};
+    use std::{path::Path, sync::Arc, time::Duration};
 
     use foyer_common::hasher::ModHasher;
     use foyer_storage::test_utils::{Record, Recorder};
@@ -1102,6 +1162,8 @@ mod tests {
         assert!(hybrid.contains(&3));
         hybrid.remove(&3);
         assert!(!hybrid.contains(&3));
+        assert_eq!(hybrid.locate(&1).await.unwrap(), EntryLocation::Memory);
+        assert_eq!(hybrid.locate(&3).await.unwrap(), EntryLocation::Absent);
 
         assert!(hybrid.contains(&4));
         hybrid.clear().await.unwrap();
@@ -1137,6 +1199,44 @@ mod tests {
         assert_eq!(e5.value(), &vec![5; 7 * KB]);
     }
 
+    #[test_log::test(tokio::test)]
+    async fn test_insert_async_with_cache_location() {
+        let dir = tempfile::tempdir().unwrap();
+        let hybrid: HybridCache<u64, Vec<u8>> = HybridCacheBuilder::new()
+            .with_name("test")
+            .memory(1)
+            .with_shards(1)
+            .with_weighter(|_, _| 1)
+            .storage(Engine::large())
+            .with_device_options(
+                DirectFsDeviceOptions::new(dir.path())
+                    .with_capacity(16 * MB)
+                    .with_file_size(MB),
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let insert = |key: u64, location| {
+            hybrid.memory().insert_with_properties_async(
+                key,
+                vec![key as u8; 7 * KB],
+                HybridCacheProperties::default().with_location(location),
+            )
+        };
+        insert(1, Location::InMem).await;
+        insert(2, Location::Default).await;
+        insert(3, Location::Default).await;
+        hybrid.storage().wait().await;
+
+        // The evicted entry kept in memory only is not written to the disk cache.
+        assert!(hybrid.storage().load(&1).await.unwrap().is_miss());
+        assert_eq!(
+            hybrid.storage().load(&2).await.unwrap().entry().unwrap().1,
+            vec![2; 7 * KB]
+        );
+    }
+
     #[test_log::test(tokio::test)]
     async fn test_hybrid_fetch_with_cache_location() {
         // Test hybrid cache that write disk cache on eviction.
@@ -1435,4 +1535,37 @@ mod tests {
         let hybrid = open(&dir).await;
         assert_eq!(*hybrid.get(&1).await.unwrap().unwrap(), vec![1; 3 * KB]);
     }
+
+    #[test_log::test(tokio::test)]
+    async fn test_read_timeout_as_miss() {
+        let dir = tempfile::tempdir().unwrap();
+        let hybrid: HybridCache<u64, Vec<u8>, ModHasher> = HybridCacheBuilder::new()
+            .with_name("test")
+            .memory(4 * MB)
+            .with_hash_builder(ModHasher::default())
+            .storage(Engine::small())
+            .with_device_options(DirectFsDeviceOptions::new(dir).with_capacity(4 * MB).with_file_size(MB))
+            .with_read_timeout(Duration::from_millis(100))
+            .build()
+            .await
+            .unwrap();
+
+        let e1 = hybrid.memory().insert(1, vec![1; 64]);
+        hybrid.storage().enqueue(e1.piece(), true);
+        hybrid.storage().wait().await;
+        hybrid.memory().remove(&1);
+
+        // Block the update of the set while it holds the set lock, to make loading from the set slow.
+        let guard = hybrid.storage().quiesce().await.unwrap();
+        hybrid.storage().enqueue(e1.piece(), true);
+        drop(e1);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert!(hybrid.get(&1).await.unwrap().is_none());
+        assert!(hybrid.obtain(1).await.unwrap().is_none());
+        let e = hybrid.fetch(1, || async move { Ok(vec![2; 64]) }).await.unwrap();
+        assert_eq!(*e, vec![2; 64]);
+
+        drop(guard);
+    }
 }
@@ -14,6 +14,8 @@
 
 #[cfg(feature = "tracing")]
 pub use crate::common::tracing::TracingOptions;
+#[cfg(feature = "debug")]
+pub use crate::memory::EvictionDebug;
 pub use crate::{
     common::{
         buf::{BufExt, BufMutExt},
@@ -28,15 +30,19 @@ pub use crate::{
         writer::{HybridCacheStorageWriter, HybridCacheWriter},
     },
     memory::{
-        Cache, CacheBuilder, CacheEntry, CacheProperties, EvictionConfig, FetchState, FifoConfig, LfuConfig, LruConfig,
-        S3FifoConfig, Weighter,
+        Cache, CacheBuilder, CacheEntry, CacheEntryMeta, CacheProperties, EvictionConfig, FetchState, FifoConfig,
+        GdsfConfig, LfuConfig, LruConfig, S3FifoConfig, WeakCacheEntry, Weighter,
     },
     storage::{
-        AdmissionPicker, AdmitAllPicker, ChainedAdmissionPicker, ChainedAdmissionPickerBuilder, Compression, Dev,
-        DevConfig, DevExt, DirectFileDevice, DirectFileDeviceOptions, DirectFsDevice, DirectFsDeviceOptions, Engine,
-        EvictionInfo, EvictionPicker, FifoPicker, InvalidRatioPicker, IopsCounter, LargeEngineOptions, Load, Pick,
-        RecoverMode, Region, RegionStatistics, ReinsertionPicker, RejectAllPicker, Runtime, RuntimeOptions,
-        SmallEngineOptions, Statistics, Storage, Store, StoreBuilder, Throttle, TokioRuntimeOptions,
+        read_set_entries, region_offset, AdmissionPicker, AdmitAllPicker, ChainedAdmissionPicker,
+        ChainedAdmissionPickerBuilder, Compression, Dev, DevConfig, DevExt, DirectFileDevice, DirectFileDeviceOptions,
+        DirectFsDevice, DirectFsDeviceOptions, Engine, EntryLocation, EvictionInfo, EvictionPicker, FifoPicker,
+        IntegrityMode, InvalidRatioPicker, IopsCounter, LargeEngineOptions, Load, LruPicker, MemoryDevice,
+        MemoryDeviceOptions, Pick, QuiesceGuard, RawValue, RecoverMode, Region, RegionFooter, RegionStatistics,
+        ReinsertionPicker, RejectAllPicker, RejectResidentPicker, ResidentAdmissionPicker, RetryPolicy, Runtime,
+        RuntimeOptions, ScanOptions, ScanProgress, SetAlignment, SetBloomFilterRebuild, SetDeleteMode, SetId,
+        SetLayout, SetReadOptions, SetResizePolicy, SetRouter, SetSizeClass, SmallEngineOptions, Statistics,
+        StatisticsSnapshot, Storage, Store, StoreBuilder, StoreEvent, Throttle, TokioRuntimeOptions,
         TombstoneLogConfigBuilder,
     },
 };